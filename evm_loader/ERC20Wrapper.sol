@@ -15,12 +15,50 @@ contract ERC20Wrapper {
     uint256 mint_id;
     string str;
     
-    function setToken(uint256 _mint, string memory _str) public {
-        // TODO: Move to initialize
+    // Canonical wrappers are deployed by the CreateERC20Wrapper instruction of evm_loader,
+    // which passes the mint and the seed of balance accounts
+    constructor(uint256 _mint, string memory _str) public {
         mint_id = _mint;
         str = _str;
     }
     
+    // Name and symbol from Metaplex metadata of the mint, decimals from the mint itself
+    function tokenMetadata() private returns(string memory, string memory, uint8) {
+        bool status;
+        bytes memory result;
+        (status, result) = solana.call(abi.encodePacked(uint8(2), mint_id));
+        if (!status) {
+            revert();
+        }
+        return abi.decode(result, (string, string, uint8));
+    }
+
+    function name() public returns(string memory) {
+        (string memory _name,,) = tokenMetadata();
+        return _name;
+    }
+
+    function symbol() public returns(string memory) {
+        (, string memory _symbol,) = tokenMetadata();
+        return _symbol;
+    }
+
+    function decimals() public returns(uint8) {
+        (,, uint8 _decimals) = tokenMetadata();
+        return _decimals;
+    }
+
+    // Balance of the holder's associated token account, zero if there is none
+    function balanceOf(address who) public returns(uint256) {
+        bool status;
+        bytes memory result;
+        (status, result) = solana.call(abi.encodePacked(uint8(3), mint_id, uint256(who)));
+        if (!status) {
+            revert();
+        }
+        return abi.decode(result, (uint256));
+    }
+
     function packMeta(bool is_signer, bool is_writable, uint256 account) pure private returns(bytes memory) {
         return abi.encodePacked(false, is_signer, is_writable, account);
     }
@@ -93,7 +131,7 @@ contract ERC20Wrapper {
                     abi.encodePacked(
                         uint8(12),        // transferChecked
                         uint64(val),      // amount
-                        uint64(9)         // decimals
+                        decimals()        // decimals
                     )
                 );
                     
@@ -116,7 +154,7 @@ contract ERC20Wrapper {
                     abi.encodePacked(
                         uint8(12),        // transferChecked
                         uint64(val),      // amount
-                        uint8(9)          // decimals
+                        decimals()        // decimals
                     )
                 );
                     
@@ -0,0 +1,66 @@
+pragma solidity ^0.5.12;
+
+// ERC-721 interface to Metaplex NFTs: token id is the mint of the NFT.
+// Token accounts and metadata accounts of the NFT are passed to the transaction as Solana accounts.
+contract ERC721Wrapper {
+    uint256 constant token_id = 0x06ddf6e1d765a193d9cbe146ceeb79ac1cb485ed5f5b37913a8cf5857eff00a9; // hex representation of "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+    address constant solana = 0xfF00000000000000000000000000000000000000;
+
+    event Transfer(address indexed from, address indexed to, uint256 indexed tokenId);
+
+    function packMeta(bool is_signer, bool is_writable, bytes memory account) pure private returns(bytes memory) {
+        if(account.length != 32) {revert();}
+        return abi.encodePacked(false, is_signer, is_writable, account);
+    }
+    function packMeta(bool is_signer, bool is_writable, address account) pure private returns(bytes memory) {
+        return abi.encodePacked(true, is_signer, is_writable, uint256(account));
+    }
+
+    function tokenAccount(address holder, uint256 tokenId) private returns(bytes memory) {
+        bool status;
+        bytes memory result;
+        (status, result) = solana.call(abi.encodePacked(uint8(5), tokenId, uint256(holder)));
+        if (!status) {
+            revert();
+        }
+        return result;
+    }
+
+    function ownerOf(uint256 tokenId) public returns(address) {
+        bool status;
+        bytes memory result;
+        (status, result) = solana.call(abi.encodePacked(uint8(4), tokenId));
+        if (!status) {
+            revert();
+        }
+        return abi.decode(result, (address));
+    }
+
+    // The NFT moves between associated token accounts of the holders,
+    // the account of the recipient must exist
+    function transferFrom(address from, address to, uint256 tokenId) public {
+        if (from != msg.sender || ownerOf(tokenId) != from) {
+            revert();
+        }
+
+        bytes memory instruction_data = abi.encodePacked(
+                    uint8(0),    // external call
+                    token_id,    // token contract
+                    uint16(3),   // accountMeta count
+                        packMeta(false, true,  tokenAccount(from, tokenId)),
+                        packMeta(false, true,  tokenAccount(to, tokenId)),
+                        packMeta(true,  false, from),
+                    abi.encodePacked(
+                        uint8(3),         // transfer
+                        uint64(0x0100000000000000)  // amount 1, little endian
+                    )
+                );
+
+        bool status;
+        bytes memory result;
+        (status, result) = solana.call(instruction_data);
+        if (!status) {revert();}
+
+        emit Transfer(from, to, tokenId);
+    }
+}
@@ -1,23 +1,34 @@
-use evm::backend::Apply;
+use evm::{backend::Apply, ExitReason};
 use primitive_types::{H160, H256, U256};
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::{
+    instruction::AccountMeta,
     pubkey::Pubkey,
     account::Account,
-    commitment_config::CommitmentConfig
+    commitment_config::CommitmentConfig,
+    system_program,
+    sysvar::{rent::Rent, slot_hashes},
 };
 use serde_json::json;
+use log::*;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 use evm_loader::{
-    account_data::AccountData,
-    solana_backend::AccountStorage,
+    account_data::{AccountData, GasPrice, Config as ProgramConfig},
+    solana_backend::{AccountStorage, CHAIN_ID},
     solidity_account::SolidityAccount,
+    utils::{ether_to_program_address, gas_price_address, config_address},
+    block,
+    bloom::Bloom,
+    gas::GasSettlement,
+    executor_state::{FrameLog, BalanceChange, TransferLedger},
+    exit_status::{exit_kind, exit_status_info},
 };
 use std::borrow::BorrowMut;
 use std::cell::RefCell; 
 use std::rc::Rc;
-use crate::Config;
+use crate::{Config, cost_model::ComputeEstimate};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct AccountJSON {
@@ -27,93 +38,224 @@ struct AccountJSON {
     writable: bool,
     new: bool,
     code_size: Option<usize>,
+    /// Balance after the value transfers of the call
+    balance: String,
 }
 
+#[derive(Clone)]
 struct SolanaAccount {
     account: Account,
-    code_account: Option<Account>,
+    /// Data of the code account, lent to `SolidityAccount` by every query without copying
+    code_data: Option<RefCell<Vec<u8>>>,
     key: Pubkey,
     writable: bool,
     code_size: Option<usize>,
+    balance_change: BalanceChange,
 }
 
+#[derive(Clone)]
 struct SolanaNewAccount {
     key: Pubkey,
     writable: bool,
-    code_size: Option<usize>
+    code_size: Option<usize>,
+    balance_change: BalanceChange,
 }
 
 impl SolanaAccount {
     pub fn new(account: Account, key: Pubkey, code_account: Option<Account>) -> SolanaAccount {
-        eprintln!("SolanaAccount::new");
-        Self{account, key, writable: false, code_account, code_size: None}
+        trace!("SolanaAccount::new");
+        let code_data = code_account.map(|code_account| RefCell::new(code_account.data));
+        Self{account, key, writable: false, code_data, code_size: None, balance_change: BalanceChange::default()}
     }
 }
 
 impl SolanaNewAccount {
     pub fn new(key: Pubkey) -> SolanaNewAccount {
-        Self{key, writable: false, code_size: None}
+        Self{key, writable: false, code_size: None, balance_change: BalanceChange::default()}
     }
 }
 
+/// Cached accounts at the moment of `EmulatorAccountStorage::snapshot`.
+/// Loaded accounts are shared with the storage until one side changes them.
+struct AccountsSnapshot {
+    accounts: HashMap<H160, Rc<SolanaAccount>>,
+    new_accounts: HashMap<H160, SolanaNewAccount>,
+    missing_accounts: HashSet<H160>,
+    solana_accounts: HashSet<Pubkey>,
+}
+
 pub struct EmulatorAccountStorage<'a> {
-    accounts: RefCell<HashMap<H160, SolanaAccount>>,
+    accounts: RefCell<HashMap<H160, Rc<SolanaAccount>>>,
     new_accounts: RefCell<HashMap<H160, SolanaNewAccount>>,
+    missing_accounts: RefCell<HashSet<H160>>,
+    solana_accounts: RefCell<HashSet<Pubkey>>,
     config: &'a Config,
     contract_id: H160,
     caller_id: H160,
     block_number: u64,
     block_timestamp: i64,
+    epoch: u64,
+    lamports_per_gas: u64,
+    coinbase: H160,
+    chain_id: u64,
+    slot_hashes: RefCell<Option<Vec<u8>>>,
+    snapshots: RefCell<Vec<AccountsSnapshot>>,
 }
 
 impl<'a> EmulatorAccountStorage<'a> {
     pub fn new(config: &'a Config, contract_id: H160, caller_id: H160) -> EmulatorAccountStorage {
-        eprintln!("backend::new");
+        trace!("backend::new");
 
         let slot = match config.rpc_client.get_slot() {
             Ok(slot) => {
-                eprintln!("Got slot");
-                eprintln!("Slot {}", slot);
+                trace!("Got slot");
+                debug!("Slot {}", slot);
                 slot
             },
             Err(_) => {
-                eprintln!("Get slot error");
+                warn!("Get slot error");
                 0
             }
         };
     
         let timestamp = match config.rpc_client.get_block_time(slot) {
             Ok(timestamp) => {
-                eprintln!("Got timestamp");
-                eprintln!("timestamp {}", timestamp);
+                trace!("Got timestamp");
+                debug!("timestamp {}", timestamp);
                 timestamp
             },
             Err(_) => {
-                eprintln!("Get timestamp error");
+                warn!("Get timestamp error");
+                0
+            }
+        };
+
+        let epoch = match config.rpc_client.get_epoch_info() {
+            Ok(epoch_info) => epoch_info.epoch,
+            Err(_) => {
+                warn!("Get epoch info error");
                 0
             }
         };
 
+        // The oracle is passed to the program to charge the used gas
+        let mut solana_accounts = HashSet::new();
+        let (lamports_per_gas, coinbase) = match Self::get_gas_price_from_solana(config) {
+            Some(gas_price) => {
+                solana_accounts.insert(gas_price_address(&config.evm_loader).0);
+                (gas_price.lamports_per_gas, gas_price.coinbase)
+            },
+            None => (0, H160::default()),
+        };
+        // The config account sets the chain id of the transactions passing it
+        let chain_id = match Self::get_config_from_solana(config) {
+            Some(program_config) => {
+                solana_accounts.insert(config_address(&config.evm_loader).0);
+                program_config.chain_id
+            },
+            None => CHAIN_ID,
+        };
+
         Self {
             accounts: RefCell::new(HashMap::new()),
             new_accounts: RefCell::new(HashMap::new()),
+            missing_accounts: RefCell::new(HashSet::new()),
+            solana_accounts: RefCell::new(solana_accounts),
             config: config,
             contract_id: contract_id,
             caller_id: caller_id,
             block_number: slot,
             block_timestamp: timestamp,
+            epoch,
+            lamports_per_gas,
+            coinbase,
+            chain_id,
+            slot_hashes: RefCell::new(None),
+            snapshots: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Warp the block seen by the emulated execution, so time-locked contracts (vesting, auctions)
+    /// are tested without waiting for real slots. `BLOCKHASH` keeps using the real `SlotHashes`.
+    pub fn override_block(&mut self, number: Option<u64>, timestamp: Option<i64>) {
+        if let Some(number) = number {
+            debug!("Block number {} overridden with {}", self.block_number, number);
+            self.block_number = number;
+        }
+        if let Some(timestamp) = timestamp {
+            debug!("Block timestamp {} overridden with {}", self.block_timestamp, timestamp);
+            self.block_timestamp = timestamp;
+        }
+    }
+
+    /// `SlotHashes` sysvar is loaded on the first use and recorded as it must be supplied to the program
+    fn with_slot_hashes<F, U>(&self, f: F) -> U where F: FnOnce(&[u8]) -> U {
+        let mut slot_hashes = self.slot_hashes.borrow_mut();
+        let data = slot_hashes.get_or_insert_with(|| {
+            self.solana_accounts.borrow_mut().insert(slot_hashes::id());
+            match self.config.rpc_client.get_account_data(&slot_hashes::id()) {
+                Ok(data) => data,
+                Err(_) => {
+                    warn!("Get SlotHashes error");
+                    Vec::new()
+                }
+            }
+        });
+
+        f(data)
+    }
+
+    /// State of the gas price oracle, `None` if it is not created yet
+    pub fn get_gas_price_from_solana(config: &Config) -> Option<GasPrice> {
+        let key = gas_price_address(&config.evm_loader).0;
+        match config.rpc_client.get_account_with_commitment(&key, CommitmentConfig::recent()) {
+            Ok(response) => {
+                let account = response.value?;
+                match AccountData::unpack(&account.data) {
+                    Ok(AccountData::GasPrice(gas_price)) => Some(gas_price),
+                    _ => {
+                        warn!("Invalid gas price account {}", key);
+                        None
+                    }
+                }
+            },
+            Err(_) => {
+                warn!("Get gas price account error {}", key);
+                None
+            }
+        }
+    }
+
+    /// State of the program config account, `None` if it is not created yet
+    pub fn get_config_from_solana(config: &Config) -> Option<ProgramConfig> {
+        let key = config_address(&config.evm_loader).0;
+        match config.rpc_client.get_account_with_commitment(&key, CommitmentConfig::recent()) {
+            Ok(response) => {
+                let account = response.value?;
+                match AccountData::unpack(&account.data) {
+                    Ok(AccountData::Config(program_config)) => Some(program_config),
+                    _ => {
+                        warn!("Invalid config account {}", key);
+                        None
+                    }
+                }
+            },
+            Err(_) => {
+                warn!("Get config account error {}", key);
+                None
+            }
         }
     }
 
     pub fn get_account_from_solana(config: &'a Config, address: &H160) -> Option<(Account, Option<Account>)> {
-        let solana_address =  Pubkey::find_program_address(&[&address.to_fixed_bytes()], &config.evm_loader).0;
-        eprintln!("Not found account for 0x{} => {}", &hex::encode(&address.as_fixed_bytes()), &solana_address.to_string());
+        let solana_address =  ether_to_program_address(&config.evm_loader, address).0;
+        debug!("Not found account for 0x{} => {}", &hex::encode(&address.as_fixed_bytes()), &solana_address.to_string());
 
         match config.rpc_client.get_account_with_commitment(&solana_address, CommitmentConfig::recent()).unwrap().value {
             Some(acc) => {
-                eprintln!("Account found");
-                eprintln!("Account data len {}", acc.data.len());
-                eprintln!("Account owner {}", acc.owner.to_string());
+                trace!("Account found");
+                trace!("Account data len {}", acc.data.len());
+                trace!("Account owner {}", acc.owner.to_string());
 
                 let account_data = match AccountData::unpack(&acc.data) {
                     Ok(acc_data) => match acc_data {
@@ -124,20 +266,20 @@ impl<'a> EmulatorAccountStorage<'a> {
                 };
 
                 let code_account = if account_data.code_account == Pubkey::new_from_array([0u8; 32]) {
-                    eprintln!("code_account == Pubkey::new_from_array([0u8; 32])");
+                    trace!("code_account == Pubkey::new_from_array([0u8; 32])");
                     None
                 } else {
-                    eprintln!("code_account != Pubkey::new_from_array([0u8; 32])");
-                    eprintln!("account key:  {}", &solana_address.to_string());
-                    eprintln!("code account: {}", &account_data.code_account.to_string());
+                    trace!("code_account != Pubkey::new_from_array([0u8; 32])");
+                    trace!("account key:  {}", &solana_address.to_string());
+                    trace!("code account: {}", &account_data.code_account.to_string());
 
                     match config.rpc_client.get_account_with_commitment(&account_data.code_account, CommitmentConfig::recent()).unwrap().value {
                         Some(acc) => {
-                            eprintln!("Account found");
+                            trace!("Account found");
                             Some(acc)
                         },
                         None => {
-                            eprintln!("Account not found");
+                            warn!("Code account not found {}", &account_data.code_account);
                             None
                         }
                     }
@@ -146,32 +288,83 @@ impl<'a> EmulatorAccountStorage<'a> {
                 Some((acc, code_account))
             },
             None => {
-                eprintln!("Account not found {}", &address.to_string());
+                debug!("Account not found {}", &address.to_string());
 
                 None
             }
         }    
     }
 
-    fn create_acc_if_not_exists(&self, address: &H160) -> bool {
-        let mut accounts = self.accounts.borrow_mut(); 
-        let mut new_accounts = self.new_accounts.borrow_mut(); 
-        if accounts.get(address).is_none() {
-            let solana_address =  Pubkey::find_program_address(&[&address.to_fixed_bytes()], &self.config.evm_loader).0;
-
-            match Self::get_account_from_solana(&self.config, address) {
-                Some((acc, code_account)) => {
-                    accounts.insert(address.clone(), SolanaAccount::new(acc, solana_address, code_account));
-                    true
-                },
-                None => {
-                    eprintln!("Account not found {}", &address.to_string());
-                    new_accounts.insert(address.clone(), SolanaNewAccount::new(solana_address));
-                    false
-                }
+    /// Fetch the account into the cache on the first query, `false` if it doesn't exist
+    fn load_account(&self, address: &H160) -> bool {
+        if self.accounts.borrow().contains_key(address) {
+            return true;
+        }
+        if self.missing_accounts.borrow().contains(address) {
+            return false;
+        }
+
+        match Self::get_account_from_solana(&self.config, address) {
+            Some((acc, code_account)) => {
+                let solana_address = ether_to_program_address(&self.config.evm_loader, address).0;
+                self.accounts.borrow_mut().insert(*address, Rc::new(SolanaAccount::new(acc, solana_address, code_account)));
+                true
+            },
+            None => {
+                self.missing_accounts.borrow_mut().insert(*address);
+                false
             }
-        } else {
-            true
+        }
+    }
+
+    /// Record the account as used, the account which doesn't exist is recorded as new
+    fn create_acc_if_not_exists(&self, address: &H160) -> bool {
+        if self.load_account(address) {
+            return true;
+        }
+
+        debug!("Account not found {}", &address.to_string());
+        let solana_address = ether_to_program_address(&self.config.evm_loader, address).0;
+        self.new_accounts.borrow_mut().entry(*address).or_insert_with(|| SolanaNewAccount::new(solana_address));
+        false
+    }
+
+    /// Save the cached accounts, as `evm_snapshot` does, the id is passed to `revert`.
+    /// Accounts are copied on write, so the snapshot costs the copy of the maps only.
+    pub fn snapshot(&self) -> usize {
+        let mut snapshots = self.snapshots.borrow_mut();
+        snapshots.push(AccountsSnapshot {
+            accounts: self.accounts.borrow().clone(),
+            new_accounts: self.new_accounts.borrow().clone(),
+            missing_accounts: self.missing_accounts.borrow().clone(),
+            solana_accounts: self.solana_accounts.borrow().clone(),
+        });
+        snapshots.len() - 1
+    }
+
+    /// Restore the cached accounts saved by `snapshot`, as `evm_revert` does:
+    /// the snapshot and the later ones are dropped. `false` if there is no such snapshot.
+    pub fn revert(&self, id: usize) -> bool {
+        let mut snapshots = self.snapshots.borrow_mut();
+        if id >= snapshots.len() {
+            return false;
+        }
+
+        let snapshot = snapshots.swap_remove(id);
+        snapshots.truncate(id);
+        *self.accounts.borrow_mut() = snapshot.accounts;
+        *self.new_accounts.borrow_mut() = snapshot.new_accounts;
+        *self.missing_accounts.borrow_mut() = snapshot.missing_accounts;
+        *self.solana_accounts.borrow_mut() = snapshot.solana_accounts;
+        true
+    }
+
+    /// Register accounts touched by the execution, so they appear in the list of used accounts
+    pub fn add_touched_accounts<I>(&self, touched: I)
+            where I: IntoIterator<Item=H160>
+    {
+        for address in touched {
+            self.create_acc_if_not_exists(&address);
         }
     }
 
@@ -192,31 +385,80 @@ impl<'a> EmulatorAccountStorage<'a> {
         for apply in values {
             match apply {
                 Apply::Modify {address, basic, code, storage: _, reset_storage} => {
-                    if let Some(acc) = accounts.get_mut(&address) {
+                    if let Some(acc) = accounts.get_mut(&address).map(Rc::make_mut) {
                         *acc.writable.borrow_mut() = true;
                         *acc.code_size.borrow_mut() = code.map(|v| v.len());
                     } else if let Some(acc) = new_accounts.get_mut(&address) {
                         *acc.code_size.borrow_mut() = code.map(|v| v.len());
                         *acc.writable.borrow_mut() = true;
                     } else {
-                        eprintln!("Account not found {}", &address.to_string());
+                        warn!("Account not found {}", &address.to_string());
                     }
-                    eprintln!("Modify: {} {} {} {}", &address.to_string(), &basic.nonce.as_u64(), &basic.balance.as_u64(), &reset_storage.to_string());
+                    debug!("Modify: {} {} {} {}", &address.to_string(), &basic.nonce.as_u64(), &basic.balance.as_u64(), &reset_storage.to_string());
                 },
                 Apply::Delete {address: addr} => {
-                    eprintln!("Delete: {}", addr.to_string());
+                    debug!("Delete: {}", addr.to_string());
                 },
             }
         };
     }
 
-    pub fn get_used_accounts(&self, status: &String, result: &std::vec::Vec<u8>)
+    /// Settle balances with the transfer ledger of the call, as `ProgramAccountStorage::apply_with_transfers` does
+    pub fn apply_transfers(&self, transfers: &TransferLedger) {
+        let mut accounts = self.accounts.borrow_mut();
+        let mut new_accounts = self.new_accounts.borrow_mut();
+
+        for (address, change) in transfers.balance_changes() {
+            debug!("Balance of {}: +{} -{}", &address.to_string(), change.credit, change.debit);
+            if let Some(acc) = accounts.get_mut(&address).map(Rc::make_mut) {
+                acc.writable = true;
+                acc.balance_change = change;
+            } else if let Some(acc) = new_accounts.get_mut(&address) {
+                acc.writable = true;
+                acc.balance_change = change;
+            } else {
+                warn!("Account not found {}", &address.to_string());
+            }
+        }
+    }
+
+    /// Solana accounts used by the call besides the contract and the caller,
+    /// to be passed to the instruction which executes the call
+    pub fn used_account_metas(&self) -> Vec<AccountMeta> {
+        let mut metas = Vec::new();
+
+        for (address, acc) in self.accounts.borrow().iter() {
+            if *address == self.contract_id || *address == self.caller_id {
+                continue;
+            }
+            metas.push(AccountMeta {pubkey: acc.key, is_signer: false, is_writable: acc.writable});
+            let code_account = AccountData::unpack(&acc.account.data).unwrap().get_account().unwrap().code_account;
+            if code_account != Pubkey::new_from_array([0u8; 32]) {
+                metas.push(AccountMeta {pubkey: code_account, is_signer: false, is_writable: acc.writable});
+            }
+        }
+
+        for (address, acc) in self.new_accounts.borrow().iter() {
+            if *address == self.contract_id || *address == self.caller_id {
+                continue;
+            }
+            metas.push(AccountMeta {pubkey: acc.key, is_signer: false, is_writable: acc.writable});
+        }
+
+        for key in self.solana_accounts.borrow().iter() {
+            metas.push(AccountMeta::new_readonly(*key, false));
+        }
+
+        metas
+    }
+
+    pub fn get_used_accounts(&self, exit_reason: &ExitReason, result: &std::vec::Vec<u8>, logs: &[FrameLog], logs_bloom: &Bloom, gas_settlement: &GasSettlement, compute: &ComputeEstimate)
     {
         let mut arr = Vec::new();
 
         let accounts = self.accounts.borrow();
         for (address, acc) in accounts.iter() {
-            let solana_address = Pubkey::find_program_address(&[&address.to_fixed_bytes()], &self.config.evm_loader).0;
+            let solana_address = ether_to_program_address(&self.config.evm_loader, address).0;
 
             let contract_address = {
                 let addr = AccountData::unpack(&acc.account.data).unwrap().get_account().unwrap().code_account;
@@ -234,12 +476,13 @@ impl<'a> EmulatorAccountStorage<'a> {
                     account: solana_address.to_string(),
                     contract: contract_address.map(|v| v.to_string()),
                     code_size: acc.code_size,
+                    balance: balance_after(U256::from(acc.account.lamports), &acc.balance_change),
                 });
         }
 
         let new_accounts = self.new_accounts.borrow();
         for (address, acc) in new_accounts.iter() {
-            let solana_address = Pubkey::find_program_address(&[&address.to_fixed_bytes()], &self.config.evm_loader).0;
+            let solana_address = ether_to_program_address(&self.config.evm_loader, address).0;
             arr.push(AccountJSON{
                     address: "0x".to_string() + &hex::encode(&address.to_fixed_bytes()),
                     writable: acc.writable,
@@ -247,21 +490,48 @@ impl<'a> EmulatorAccountStorage<'a> {
                     account: solana_address.to_string(),
                     contract: None,
                     code_size: acc.code_size,
+                    balance: balance_after(U256::zero(), &acc.balance_change),
                 });
         }    
 
-        let js = json!({"accounts": arr, "result": &hex::encode(&result), "exit_status": &status}).to_string();
+        let solana_accounts: Vec<String> = self.solana_accounts.borrow().iter().map(|key| key.to_string()).collect();
+
+        // Logs in the order OnEvent emits them, tagged with the call frames
+        let logs: Vec<serde_json::Value> = logs.iter().map(|frame_log| json!({
+            "address": format!("0x{}", hex::encode(frame_log.log.address.as_bytes())),
+            "topics": frame_log.log.topics.iter().map(|topic| format!("0x{}", hex::encode(topic.as_bytes()))).collect::<Vec<_>>(),
+            "data": format!("0x{}", hex::encode(&frame_log.log.data)),
+            "logIndex": frame_log.index,
+            "depth": frame_log.depth,
+            "frame": frame_log.frame,
+        })).collect();
+
+        let (exit_code, exit_description) = exit_status_info(exit_reason);
+        let js = json!({"accounts": arr, "solana_accounts": solana_accounts, "result": &hex::encode(&result),
+            "exit_status": exit_kind(exit_reason), "exit_code": exit_code, "exit_reason": exit_description, "chain_id": format!("0x{:x}", self.chain_id),
+            "logs": logs, "logs_bloom": hex::encode(&logs_bloom[..]), "used_gas": gas_settlement.gas_used, "refunded_gas": gas_settlement.refunded,
+            "compute": compute.to_json()}).to_string();
 
         println!("{}", js);
     }
 }
 
+fn balance_after(balance: U256, change: &BalanceChange) -> String {
+    match change.apply_to(balance) {
+        Some(balance) => format!("0x{:x}", balance),
+        None => {
+            warn!("Balance {} can't cover {}", balance, change.debit);
+            format!("0x{:x}", balance)
+        }
+    }
+}
+
 impl<'a> AccountStorage for EmulatorAccountStorage<'a> {
     fn apply_to_account<U, D, F>(&self, address: &H160, d: D, f: F) -> U
     where F: FnOnce(&SolidityAccount) -> U,
           D: FnOnce() -> U
     {
-        self.create_acc_if_not_exists(address);
+        self.load_account(address);
         let accounts = self.accounts.borrow();
         match accounts.get(&address) {
             None => d(),
@@ -273,8 +543,8 @@ impl<'a> AccountStorage for EmulatorAccountStorage<'a> {
                     },
                     Err(_) => return d(),
                 };
-                if acc.code_account.is_some() {
-                    let mut code_data = acc.code_account.as_ref().unwrap().data.clone();
+                if let Some(code_data) = &acc.code_data {
+                    let mut code_data = code_data.borrow_mut();
                     let contract_data = match AccountData::unpack(&code_data) {
                         Ok(acc_data) => match acc_data {
                             AccountData::Contract(_) => acc_data,
@@ -282,11 +552,11 @@ impl<'a> AccountStorage for EmulatorAccountStorage<'a> {
                         },
                         Err(_) => return d(),
                     };
-                    let code_data: std::rc::Rc<std::cell::RefCell<&mut [u8]>> = Rc::new(RefCell::new(&mut code_data));
-                    let account = SolidityAccount::new(&acc.key, acc.account.lamports, account_data, Some((contract_data, code_data))).unwrap();
+                    let code_data: Rc<RefCell<&mut [u8]>> = Rc::new(RefCell::new(&mut code_data[..]));
+                    let account = SolidityAccount::new(&self.config.evm_loader, &acc.key, acc.account.lamports, account_data, Some((contract_data, code_data))).unwrap();
                     f(&account)
                 } else {
-                    let account = SolidityAccount::new(&acc.key, acc.account.lamports, account_data, None).unwrap();
+                    let account = SolidityAccount::new(&self.config.evm_loader, &acc.key, acc.account.lamports, account_data, None).unwrap();
                     f(&account)
                 }
             },
@@ -297,7 +567,65 @@ impl<'a> AccountStorage for EmulatorAccountStorage<'a> {
 
     fn origin(&self) -> H160 { self.caller_id }
 
+    fn program_id(&self) -> Pubkey { self.config.evm_loader }
+
     fn block_number(&self) -> U256 { self.block_number.into() }
 
     fn block_timestamp(&self) -> U256 { self.block_timestamp.into() }
+
+    fn epoch(&self) -> U256 { self.epoch.into() }
+
+    fn rent_exempt_minimum(&self, data_len: usize) -> u64 {
+        match self.config.rpc_client.get_minimum_balance_for_rent_exemption(data_len) {
+            Ok(lamports) => lamports,
+            Err(_) => {
+                warn!("Get rent exemption error");
+                Rent::default().minimum_balance(data_len)
+            }
+        }
+    }
+
+    fn gas_price(&self) -> U256 { self.lamports_per_gas.into() }
+
+    fn block_hash(&self, number: U256) -> H256 {
+        self.with_slot_hashes(|data| block::block_hash(data, self.block_number(), number))
+    }
+
+    fn block_coinbase(&self) -> H160 { self.coinbase }
+
+    fn chain_id(&self) -> U256 { self.chain_id.into() }
+
+    fn block_randomness(&self) -> U256 {
+        self.with_slot_hashes(block::prevrandao)
+    }
+
+    /// Largest token account of the NFT mint, recorded as it must be supplied to the program
+    fn find_nft_account(&self, mint: &Pubkey) -> Option<Pubkey> {
+        let largest = match self.config.rpc_client.get_token_largest_accounts(mint) {
+            Ok(largest) => largest,
+            Err(_) => {
+                warn!("Get token largest accounts error {}", mint);
+                return None;
+            }
+        };
+
+        let key = largest.into_iter()
+            .find(|balance| balance.amount.amount == "1")
+            .and_then(|balance| Pubkey::from_str(&balance.address).ok())?;
+        self.solana_accounts.borrow_mut().insert(key);
+        Some(key)
+    }
+
+    /// Fetch the account and record it, the transaction must supply it to the program.
+    /// Nonexistent account is reported as an empty system account, as the program sees it.
+    fn solana_account_data(&self, key: &Pubkey) -> Option<(Pubkey, Vec<u8>)> {
+        self.solana_accounts.borrow_mut().insert(*key);
+        match self.config.rpc_client.get_account_with_commitment(key, CommitmentConfig::recent()) {
+            Ok(response) => Some(response.value.map_or_else(|| (system_program::id(), Vec::new()), |acc| (acc.owner, acc.data))),
+            Err(_) => {
+                warn!("Get account error {}", key);
+                None
+            }
+        }
+    }
 }
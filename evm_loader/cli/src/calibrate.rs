@@ -0,0 +1,124 @@
+//! Calibration of the compute-unit cost model
+//!
+//! Each microbenchmark is init code looping over a snippet of one opcode class. It's executed by
+//! `Finalize` in `simulateTransaction`, as `verify` does, and in the emulator for the opcode counts.
+//! The class costs the units consumed above the empty init code, less the cost of the other opcodes
+//! in the loop, divided by the opcodes of the class. The stack class is measured first, it's the
+//! loop itself. The base is what the empty init code consumes besides its opcodes.
+
+use log::*;
+use std::collections::BTreeMap;
+use crate::{
+    Config, CommandResult, Error,
+    cost_model::{class_counts, CalibrationTable, OpcodeClass},
+    planner::plan_deployment,
+    verify::simulate_deployment,
+};
+
+/// Storage headroom of the code accounts of the benchmarks, they write one slot at most
+const BENCHMARK_STORAGE_HEADROOM: usize = 1024;
+
+/// Stack-neutral snippets of the classes and the loop iterations, within the compute budget.
+/// `Create` isn't measured, its accounts can't be passed to the simulated `Finalize`.
+fn benchmarks() -> Vec<(OpcodeClass, Vec<u8>, u16)> {
+    vec![
+        // The loop alone
+        (OpcodeClass::Stack, vec![], 100),
+        // DIV 0xff by 3
+        (OpcodeClass::Arithmetic, vec![0x60, 0x03, 0x60, 0xff, 0x04, 0x50], 50),
+        // MSTORE 1 at 0
+        (OpcodeClass::Memory, vec![0x60, 0x01, 0x60, 0x00, 0x52], 50),
+        // SHA3 of 32 bytes at 0
+        (OpcodeClass::Hash, vec![0x60, 0x20, 0x60, 0x00, 0x20, 0x50], 20),
+        // SLOAD slot 0
+        (OpcodeClass::StorageRead, vec![0x60, 0x00, 0x54, 0x50], 20),
+        // SSTORE 1 to slot 0
+        (OpcodeClass::StorageWrite, vec![0x60, 0x01, 0x60, 0x00, 0x55], 20),
+        // BALANCE of ADDRESS
+        (OpcodeClass::Account, vec![0x30, 0x31, 0x50], 20),
+        // LOG0 of empty data
+        (OpcodeClass::Log, vec![0x60, 0x00, 0x60, 0x00, 0xa0], 10),
+        // STATICCALL of ADDRESS, it has no code during creation
+        (OpcodeClass::Call, vec![0x60, 0x00, 0x80, 0x80, 0x80, 0x30, 0x5a, 0xfa, 0x50], 5),
+    ]
+}
+
+/// Init code executing the snippet in a loop of `iterations`
+fn loop_code(snippet: &[u8], iterations: u16) -> Vec<u8> {
+    let [hi, lo] = iterations.to_be_bytes();
+    // PUSH2 iterations, JUMPDEST at 3
+    let mut code = vec![0x61, hi, lo, 0x5b];
+    code.extend_from_slice(snippet);
+    // PUSH1 1, SWAP1, SUB, DUP1, PUSH1 3, JUMPI, POP, STOP
+    code.extend_from_slice(&[0x60, 0x01, 0x90, 0x03, 0x80, 0x60, 0x03, 0x57, 0x50, 0x00]);
+    code
+}
+
+/// Compute units consumed by the last invocation of the program in the logs
+fn consumed_units(logs: &[String], program: &str) -> Option<u64> {
+    let prefix = format!("Program {} consumed ", program);
+    let line = logs.iter().rev().find(|line| line.starts_with(&prefix))?;
+    line[prefix.len()..].split(' ').next()?.parse().ok()
+}
+
+/// Compute units and emulated opcode counts of the init code
+fn measure(config: &Config, code: &[u8]) -> Result<(u64, BTreeMap<OpcodeClass, u64>), Error> {
+    let plan = plan_deployment(config, code, false, BENCHMARK_STORAGE_HEADROOM)?;
+    if !plan.exit_reason.is_succeed() {
+        return Err(format!("Emulation failed: {:?}", plan.exit_reason).into());
+    }
+
+    let simulation = simulate_deployment(config, &plan, code)?;
+    if let Some(err) = simulation.err {
+        return Err(format!("Simulation failed: {}", err).into());
+    }
+    let logs = simulation.logs.unwrap_or_default();
+    let units = consumed_units(&logs, &config.evm_loader.to_string()).ok_or("No consumed compute units in the logs")?;
+
+    Ok((units, class_counts(&plan.profile)))
+}
+
+/// Measure the classes in simulated transactions and print the calibration table.
+/// Classes failed to measure keep the built-in estimates.
+pub fn command_calibrate(config: &Config) -> CommandResult {
+    let mut table = CalibrationTable::default();
+
+    let (empty_units, empty_counts) = measure(config, &[0x00])?;
+    debug!("Empty init code: {} units, {:?}", empty_units, empty_counts);
+
+    for (class, snippet, iterations) in benchmarks() {
+        let (units, counts) = match measure(config, &loop_code(&snippet, iterations)) {
+            Ok(measured) => measured,
+            Err(err) => {
+                warn!("Class {:?} is not calibrated: {}", class, err);
+                continue;
+            },
+        };
+
+        let delta = |counts: &BTreeMap<OpcodeClass, u64>, class: &OpcodeClass| {
+            counts.get(class).copied().unwrap_or(0).saturating_sub(empty_counts.get(class).copied().unwrap_or(0))
+        };
+        let class_count = delta(&counts, &class);
+        if class_count == 0 {
+            warn!("Class {:?} is not calibrated: no opcodes of the class executed", class);
+            continue;
+        }
+        let others: BTreeMap<OpcodeClass, u64> = counts.keys()
+            .filter(|other| **other != class)
+            .map(|other| (*other, delta(&counts, other)))
+            .collect();
+
+        let class_units = units.saturating_sub(empty_units).saturating_sub(table.opcodes_cost(&others));
+        let cost = class_units / class_count;
+        info!("Class {:?}: {} opcodes, {} units, {} units each", class, class_count, units, cost);
+        table.classes.insert(class, cost);
+    }
+
+    table.base = empty_units.saturating_sub(table.opcodes_cost(&empty_counts));
+    info!("Base: {} units", table.base);
+
+    println!("{}", serde_json::to_string(&table)?);
+
+    Ok(())
+}
+
@@ -0,0 +1,175 @@
+//! Compute-unit cost model of the program
+//!
+//! Opcodes are grouped into classes of similar cost in the program. The calibration table
+//! holds the compute units of one opcode of each class and of the instruction around them.
+//! The emulator applies it to the opcode profile of a call to predict whether the call fits
+//! in one transaction or is executed iteratively, and how many steps fit in one iteration.
+//! The `calibrate` command measures the table on the cluster, built-in estimates are used otherwise.
+
+use evm_loader::profile::Profile;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::BTreeMap, fs::File};
+use crate::Error;
+
+/// Compute units available to one instruction of the program
+pub const MAX_COMPUTE_UNITS: u64 = 200_000;
+
+/// Compute units kept out of the prediction, the model doesn't count memory expansion and data sizes
+const SAFETY_MARGIN: u64 = 20_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpcodeClass {
+    /// Stack, control flow, comparison, bitwise and context opcodes
+    Stack,
+    /// Multiplication, division, modulo and exponentiation of 256-bit words
+    Arithmetic,
+    /// Memory access and copies into memory
+    Memory,
+    Hash,
+    StorageRead,
+    StorageWrite,
+    /// Balance and code of accounts
+    Account,
+    Log,
+    Call,
+    /// Creation and destruction of contracts
+    Create,
+}
+
+impl OpcodeClass {
+    pub const ALL: [OpcodeClass; 10] = [
+        OpcodeClass::Stack, OpcodeClass::Arithmetic, OpcodeClass::Memory, OpcodeClass::Hash,
+        OpcodeClass::StorageRead, OpcodeClass::StorageWrite, OpcodeClass::Account,
+        OpcodeClass::Log, OpcodeClass::Call, OpcodeClass::Create,
+    ];
+
+    /// Estimated compute units of one opcode of the class, until the class is calibrated
+    fn default_cost(self) -> u64 {
+        match self {
+            OpcodeClass::Stack => 150,
+            OpcodeClass::Arithmetic => 600,
+            OpcodeClass::Memory => 400,
+            OpcodeClass::Hash => 3_000,
+            OpcodeClass::StorageRead => 2_500,
+            OpcodeClass::StorageWrite => 3_500,
+            OpcodeClass::Account => 2_000,
+            OpcodeClass::Log => 1_500,
+            OpcodeClass::Call => 12_000,
+            OpcodeClass::Create => 25_000,
+        }
+    }
+}
+
+/// Cost class of the opcode, undefined opcodes are in the stack class
+#[must_use]
+pub fn opcode_class(opcode: u8) -> OpcodeClass {
+    match opcode {
+        0x02 | 0x04..=0x0a => OpcodeClass::Arithmetic,
+        0x20 => OpcodeClass::Hash,
+        0x31 | 0x3b | 0x3c | 0x3f | 0x47 => OpcodeClass::Account,
+        0x37 | 0x39 | 0x3e | 0x51..=0x53 => OpcodeClass::Memory,
+        0x54 => OpcodeClass::StorageRead,
+        0x55 => OpcodeClass::StorageWrite,
+        0xa0..=0xa4 => OpcodeClass::Log,
+        0xf1 | 0xf2 | 0xf4 | 0xfa => OpcodeClass::Call,
+        0xf0 | 0xf5 | 0xff => OpcodeClass::Create,
+        _ => OpcodeClass::Stack,
+    }
+}
+
+/// Opcodes executed in the profile, by class
+#[must_use]
+pub fn class_counts(profile: &Profile) -> BTreeMap<OpcodeClass, u64> {
+    let mut counts = BTreeMap::new();
+    for (opcode, stats) in profile.opcodes() {
+        *counts.entry(opcode_class(opcode)).or_insert(0) += stats.count;
+    }
+    counts
+}
+
+/// Compute units of the instruction and of the opcode classes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationTable {
+    /// Compute units of the instruction without opcodes: accounts, decoding and commit of the state
+    pub base: u64,
+    /// Compute units of one opcode of the class
+    pub classes: BTreeMap<OpcodeClass, u64>,
+}
+
+impl Default for CalibrationTable {
+    fn default() -> Self {
+        Self {
+            base: 30_000,
+            classes: OpcodeClass::ALL.iter().map(|class| (*class, class.default_cost())).collect(),
+        }
+    }
+}
+
+impl CalibrationTable {
+    /// Load the table printed by `calibrate`, classes missing in the file keep the built-in estimates
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let file = File::open(path).map_err(|e| format!("Unable to open calibration table {}: {}", path, e))?;
+        let mut table: Self = serde_json::from_reader(file).map_err(|e| format!("Invalid calibration table {}: {}", path, e))?;
+        for class in OpcodeClass::ALL.iter() {
+            table.classes.entry(*class).or_insert_with(|| class.default_cost());
+        }
+        Ok(table)
+    }
+
+    #[must_use]
+    pub fn class_cost(&self, class: OpcodeClass) -> u64 {
+        self.classes.get(&class).copied().unwrap_or_else(|| class.default_cost())
+    }
+
+    /// Compute units of the opcodes counted by class
+    #[must_use]
+    pub fn opcodes_cost(&self, counts: &BTreeMap<OpcodeClass, u64>) -> u64 {
+        counts.iter().map(|(class, count)| count * self.class_cost(*class)).sum()
+    }
+
+    /// Predict the compute units of the profiled call
+    #[must_use]
+    pub fn estimate(&self, profile: &Profile) -> ComputeEstimate {
+        let counts = class_counts(profile);
+        let steps: u64 = counts.values().sum();
+        let opcodes_cost = self.opcodes_cost(&counts);
+        let units = self.base + opcodes_cost;
+
+        let budget = MAX_COMPUTE_UNITS.saturating_sub(SAFETY_MARGIN + self.base);
+        let steps_per_iteration = if opcodes_cost == 0 {
+            steps
+        } else {
+            let step_cost = (opcodes_cost + steps - 1) / steps;
+            budget / step_cost
+        };
+
+        ComputeEstimate {
+            units,
+            iterative: units + SAFETY_MARGIN > MAX_COMPUTE_UNITS,
+            steps_per_iteration,
+        }
+    }
+}
+
+/// Predicted compute units of a call
+pub struct ComputeEstimate {
+    /// Compute units of the call executed in one instruction
+    pub units: u64,
+    /// The call doesn't fit in one transaction, it's executed by `PartialCallFromRawEthereumTX` and `Continue`
+    pub iterative: bool,
+    /// Steps of one iteration within the budget, at the average cost of a step of the call
+    pub steps_per_iteration: u64,
+}
+
+impl ComputeEstimate {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "compute_units": self.units,
+            "max_compute_units": MAX_COMPUTE_UNITS,
+            "iterative": self.iterative,
+            "steps_per_iteration": self.steps_per_iteration,
+        })
+    }
+}
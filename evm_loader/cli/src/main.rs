@@ -1,35 +1,51 @@
 mod account_storage;
+mod calibrate;
+mod cost_model;
+mod planner;
+mod verify;
 use crate::account_storage::EmulatorAccountStorage;
+use crate::calibrate::command_calibrate;
+use crate::cost_model::{CalibrationTable, ComputeEstimate};
+use crate::planner::plan_deployment;
+use crate::verify::command_verify;
 
+use evm::ExitReason;
 use evm_loader::{
     instruction::EvmInstruction,
-    solana_backend::SolanaBackend,
-    account_data::{AccountData, Account, Contract},
+    solana_backend::{SolanaBackend, CHAIN_ID},
+    executor::Machine,
+    executor_state::{ExecutorState, ExecutorSubstate},
+    account_data::AccountData,
+    bloom,
+    gas::GasSettlement,
+    exit_status::{exit_kind, exit_status_info},
+    hamt::{Hamt, ProofNode},
+    profile::{opcode_name, OpcodeStats, Profile},
+    transaction::tx_hash,
+    version::ProgramVersion,
+    utils::{ether_to_program_address, ether_to_alias_address, solana_to_alias_address, gas_price_address, receipt_address, config_address},
 };
 
-use evm::{executor::StackExecutor, ExitReason};
 use bincode::serialize;
 use hex;
 use primitive_types::{H160, H256, U256};
 use solana_sdk::{
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
     clock::Slot,
     commitment_config::CommitmentConfig,
     instruction::{AccountMeta, Instruction},
-    loader_instruction::LoaderInstruction,
     message::Message,
     pubkey::Pubkey,
     signature::Signer,
     signers::Signers,
     transaction::Transaction,
     system_program,
-    system_instruction,
-    sysvar::{rent, clock},
+    sysvar::clock,
 };
 use serde_json::json;
 use std::{
-    cmp::min,
-    collections::HashMap,
-    io::{Read},
+    collections::{BTreeMap, HashMap},
+    io::{BufRead, Read},
     fs::File,
     env, str::FromStr,
     net::{SocketAddr, UdpSocket},
@@ -63,11 +79,11 @@ use solana_cli::{
 use solana_cli_output::display::new_spinner_progress_bar;
 use solana_transaction_status::TransactionConfirmationStatus;
 
-use sha3::{Keccak256, Digest};
 
 use log::*;
 
 const DATA_CHUNK_SIZE: usize = 229; // Keep program chunks under PACKET_DATA_SIZE
+const HOLDER_PART_SIZE: usize = 32*DATA_CHUNK_SIZE; // Part of the code uploaded to one holder account
 const NUM_TPU_LEADERS: u64 = 2;
 
 type Error = Box<dyn std::error::Error>;
@@ -81,54 +97,222 @@ pub struct Config {
     signer: Box<dyn Signer>,
 }
 
-fn command_emulate(config: &Config, contract_id: H160, caller_id: H160, data: Vec<u8>) -> CommandResult {
-    let account_storage = EmulatorAccountStorage::new(config, contract_id, caller_id);
+fn command_emulate(
+    config: &Config,
+    contract_id: H160,
+    caller_id: H160,
+    data: Vec<u8>,
+    access_list_mode: bool,
+    profile_mode: bool,
+    calibration: &CalibrationTable,
+    override_block: Option<u64>,
+    override_timestamp: Option<i64>,
+) -> CommandResult {
+    let mut account_storage = EmulatorAccountStorage::new(config, contract_id, caller_id);
+    account_storage.override_block(override_block, override_timestamp);
 
-    let (exit_reason, result, applies_logs) = {
+    let (exit_reason, result, applies_logs, gas_settlement, access_list, profile) = {
         let backend = SolanaBackend::new(&account_storage, None);
-        let config = evm::Config::istanbul();
-        let mut executor = StackExecutor::new(&backend, usize::max_value(), &config);
-    
-        let (exit_reason, result) = executor.transact_call(caller_id, contract_id, U256::zero(), data, usize::max_value());
-    
+        let executor_state = ExecutorState::new(ExecutorSubstate::new(), backend);
+        let mut executor = Machine::new(executor_state);
+        executor.enable_profile();
+
+        executor.call_begin(caller_id, contract_id, data, u64::max_value());
+        let exit_reason = executor.execute();
+        let result = executor.return_value();
+        let gas_settlement = executor.gas_settlement();
+        let profile = (executor.take_profile().unwrap_or_default(), executor.executed_steps());
+
         debug!("Call done");
-        
+
+        let executor_state = executor.into_state();
+        account_storage.add_touched_accounts(executor_state.touched_accounts());
+        let access_list = executor_state.access_list();
+
         if exit_reason.is_succeed() {
             debug!("Succeed execution");
-            let (applies, logs) = executor.deconstruct();
-            (exit_reason, result, Some((applies, logs)))
+            let (_, (applies, logs, transfers)) = executor_state.deconstruct();
+            (exit_reason, result, Some((applies, logs, transfers)), gas_settlement, access_list, profile)
         } else {
-            (exit_reason, result, None)
+            (exit_reason, result, None, gas_settlement, access_list, profile)
         }
     };
 
+    if access_list_mode {
+        print_access_list(&exit_reason, caller_id, &access_list, &gas_settlement);
+        return Ok(());
+    }
+
+    let (profile, steps) = profile;
+    let compute = calibration.estimate(&profile);
+    debug!("Predicted {} compute units", compute.units);
+
+    if profile_mode {
+        print_profile(&exit_reason, &profile, steps, &compute, &gas_settlement);
+        return Ok(());
+    }
+
     debug!("Call done");
-    let status = match exit_reason {
-        ExitReason::Succeed(_) => {
-            let (applies, _logs) = applies_logs.unwrap();
-    
-            account_storage.apply(applies);
+    let mut logs_bloom = [0u8; bloom::BLOOM_SIZE];
+    let mut logs = Vec::new();
+    if let Some((applies, frame_logs, transfers)) = applies_logs {
+        logs_bloom = bloom::logs_bloom(frame_logs.iter().map(|frame_log| &frame_log.log));
+        logs = frame_logs;
 
-            debug!("Applies done");
-            "succeed".to_string()
-        }
-        ExitReason::Error(_) => "error".to_string(),
-        ExitReason::Revert(_) => "revert".to_string(),
-        ExitReason::Fatal(_) => "fatal".to_string(),
-    };
+        account_storage.apply(applies);
+        account_storage.apply_transfers(&transfers);
 
-    info!("{}", &status);
+        debug!("Applies done");
+    }
+
+    let (exit_code, exit_description) = exit_status_info(&exit_reason);
+    info!("{} {:#x} {}", exit_kind(&exit_reason), exit_code, exit_description);
     info!("{}", &hex::encode(&result));
 
     if !exit_reason.is_succeed() {
         debug!("Not succeed execution");
     }
 
-    account_storage.get_used_accounts(&status, &result);
+    account_storage.get_used_accounts(&exit_reason, &result, &logs, &logs_bloom, &gas_settlement, &compute);
+
+    Ok(())
+}
+
+/// Emulate calls read from stdin, one JSON request per line, on the same state, as a development node does:
+/// `{"method": "emulate", "params": [sender, contract, data]}` executes the call,
+/// `{"method": "evm_snapshot"}` saves the state and returns the id of the snapshot,
+/// `{"method": "evm_revert", "params": [id]}` restores the state saved by the snapshot and drops it with the later ones.
+/// A response is printed for every request, the accounts used by the session are printed at the end of the input.
+fn command_emulate_session(config: &Config, contract_id: H160, caller_id: H160, calibration: &CalibrationTable) -> CommandResult {
+    let account_storage = EmulatorAccountStorage::new(config, contract_id, caller_id);
+
+    let (logs, gas_settlement, profile) = {
+        let backend = SolanaBackend::new(&account_storage, None);
+        let executor_state = ExecutorState::new(ExecutorSubstate::new(), backend);
+        let mut executor = Machine::new(executor_state);
+        executor.enable_profile();
+        let mut snapshots = Vec::new();
+
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request: serde_json::Value = serde_json::from_str(&line)?;
+            let params = &request["params"];
+            let response = match request["method"].as_str() {
+                Some("evm_snapshot") => {
+                    let id = account_storage.snapshot();
+                    snapshots.push(executor.snapshot());
+                    json!({"result": format!("0x{:x}", id)})
+                },
+                Some("evm_revert") => {
+                    let id = params[0].as_str()
+                        .and_then(|id| usize::from_str_radix(id.trim_start_matches("0x"), 16).ok())
+                        .filter(|id| *id < snapshots.len());
+                    match id {
+                        Some(id) if account_storage.revert(id) => {
+                            snapshots.truncate(id + 1);
+                            executor.revert_to_snapshot(snapshots.pop().unwrap());
+                            json!({"result": true})
+                        },
+                        _ => json!({"result": false}),
+                    }
+                },
+                Some("emulate") => {
+                    let address = |i: usize| params[i].as_str().and_then(|v| H160::from_str(v.trim_start_matches("0x")).ok());
+                    let data = params[2].as_str().and_then(|v| hex::decode(v.trim_start_matches("0x")).ok());
+                    match (address(0), address(1), data) {
+                        (Some(sender), Some(contract), Some(data)) => {
+                            let used_gas = executor.used_gas();
+                            executor.call_begin(sender, contract, data, u64::max_value());
+                            let exit_reason = executor.execute();
+                            let (exit_code, exit_description) = exit_status_info(&exit_reason);
+                            json!({"result": hex::encode(executor.return_value()),
+                                "exit_status": exit_kind(&exit_reason), "exit_code": exit_code, "exit_reason": exit_description,
+                                "used_gas": executor.used_gas() - used_gas})
+                        },
+                        _ => json!({"error": "Expected [sender, contract, data] params"}),
+                    }
+                },
+                _ => json!({"error": format!("Unknown method {}", request["method"])}),
+            };
+            println!("{}", response);
+        }
+
+        let gas_settlement = executor.gas_settlement();
+        let profile = executor.take_profile().unwrap_or_default();
+        let executor_state = executor.into_state();
+        account_storage.add_touched_accounts(executor_state.touched_accounts());
+        let (_, (applies, logs, transfers)) = executor_state.deconstruct();
+        account_storage.apply(applies);
+        account_storage.apply_transfers(&transfers);
+        (logs, gas_settlement, profile)
+    };
+
+    let logs_bloom = bloom::logs_bloom(logs.iter().map(|frame_log| &frame_log.log));
+    let compute = calibration.estimate(&profile);
+    let exit_reason = ExitReason::Succeed(evm::ExitSucceed::Stopped);
+    account_storage.get_used_accounts(&exit_reason, &Vec::new(), &logs, &logs_bloom, &gas_settlement, &compute);
 
     Ok(())
 }
 
+/// Print the EIP-2930 access list of the emulated call, as `eth_createAccessList` returns it.
+/// The sender is implied by the transaction, it's listed only with the storage keys.
+fn print_access_list(exit_reason: &ExitReason, caller_id: H160, access_list: &[(H160, Vec<H256>)], gas_settlement: &GasSettlement) {
+    let access_list: Vec<serde_json::Value> = access_list.iter()
+        .filter(|(address, keys)| *address != caller_id || !keys.is_empty())
+        .map(|(address, keys)| json!({
+            "address": format!("{:?}", address),
+            "storageKeys": keys.iter().map(|key| format!("{:?}", key)).collect::<Vec<_>>(),
+        }))
+        .collect();
+
+    let (exit_code, exit_description) = exit_status_info(exit_reason);
+    println!("{}", json!({
+        "accessList": access_list,
+        "gasUsed": format!("0x{:x}", gas_settlement.gas_used),
+        "exit_status": exit_kind(exit_reason), "exit_code": exit_code, "exit_reason": exit_description,
+    }).to_string());
+}
+
+/// Print opcode counts and modeled costs of the emulated call, by contract and in total,
+/// the most expensive opcodes first
+fn print_profile(exit_reason: &ExitReason, profile: &Profile, steps: u64, compute: &ComputeEstimate, gas_settlement: &GasSettlement) {
+    let opcodes_json = |opcodes: &BTreeMap<u8, OpcodeStats>| -> Vec<serde_json::Value> {
+        let mut opcodes: Vec<_> = opcodes.iter().collect();
+        opcodes.sort_by(|(_, a), (_, b)| b.cost.cmp(&a.cost).then(b.count.cmp(&a.count)));
+        opcodes.into_iter().map(|(opcode, stats)| json!({
+            "opcode": opcode_name(*opcode).map_or_else(|| format!("0x{:02x}", opcode), str::to_string),
+            "count": stats.count,
+            "cost": stats.cost,
+        })).collect()
+    };
+
+    let mut contracts: Vec<_> = profile.contracts.iter()
+        .map(|(address, opcodes)| (address, profile.contract_total(address), opcodes))
+        .collect();
+    contracts.sort_by(|(_, a, _), (_, b, _)| b.cost.cmp(&a.cost));
+    let contracts: Vec<serde_json::Value> = contracts.into_iter().map(|(address, total, opcodes)| json!({
+        "address": format!("{:?}", address),
+        "count": total.count,
+        "cost": total.cost,
+        "opcodes": opcodes_json(opcodes),
+    })).collect();
+
+    let (exit_code, exit_description) = exit_status_info(exit_reason);
+    println!("{}", json!({
+        "contracts": contracts,
+        "opcodes": opcodes_json(&profile.opcodes()),
+        "steps": steps,
+        "compute": compute.to_json(),
+        "used_gas": gas_settlement.gas_used,
+        "exit_status": exit_kind(exit_reason), "exit_code": exit_code, "exit_reason": exit_description,
+    }).to_string());
+}
+
 fn command_create_program_address (
     config: &Config,
     seed: &str,
@@ -152,16 +336,20 @@ fn command_create_ether_account (
     lamports: u64,
     space: u64
 ) -> CommandResult {
-    let (solana_address, nonce) = Pubkey::find_program_address(&[ether_address.as_bytes()], &config.evm_loader);
+    let (solana_address, nonce) = ether_to_program_address(&config.evm_loader, ether_address);
+    let (ether_alias, _) = ether_to_alias_address(&config.evm_loader, ether_address);
+    let (solana_alias, _) = solana_to_alias_address(&config.evm_loader, &solana_address);
     debug!("Create ethereum account {} <- {} {}", solana_address, hex::encode(ether_address), nonce);
 
     let instruction = Instruction::new(
             config.evm_loader,
-            &EvmInstruction::CreateAccount {lamports, space, ether: *ether_address, nonce},
+            &EvmInstruction::CreateAccount {lamports, space, ether: *ether_address, nonce, hashed_keys: false},
             vec![
                 AccountMeta::new(config.signer.pubkey(), true),
                 AccountMeta::new(solana_address, false),
-                AccountMeta::new_readonly(system_program::id(), false)
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(ether_alias, false),
+                AccountMeta::new(solana_alias, false),
             ]);
 
     let finalize_message = Message::new(&[instruction], Some(&config.signer.pubkey()));
@@ -330,126 +518,44 @@ fn send_and_confirm_transactions_with_spinner<T: Signers>(
 fn command_deploy(
     config: &Config,
     program_location: &str,
+    hashed_keys: bool,
+    storage_headroom: usize,
+    plan_only: bool,
 ) -> CommandResult {
-
-    let ACCOUNT_HEADER_SIZE = 1+Account::SIZE;
-    let CONTRACT_HEADER_SIZE = 1+Contract::SIZE;
-
     let program_data = read_program_data(program_location)?;
-    let program_code_len = CONTRACT_HEADER_SIZE + program_data.len() + 2*1024;
-    let minimum_balance_for_account = config.rpc_client.get_minimum_balance_for_rent_exemption(ACCOUNT_HEADER_SIZE)?;
-    let minimum_balance_for_code = config.rpc_client.get_minimum_balance_for_rent_exemption(program_code_len)?;
-
-    let creator = &config.signer;
     let signers = [&*config.signer];
 
-    let creator_ether: H160 = H256::from_slice(Keccak256::digest(&creator.pubkey().to_bytes()).as_slice()).into();
-    debug!("Creator: ether {}, solana {}", creator_ether, creator.pubkey());
-
-    let (program_id, ether, nonce) = {
-        let code_hash = Keccak256::digest(&program_data);
-        let mut hasher = Keccak256::new();
-        hasher.input(&[0xff]);
-        hasher.input(&creator_ether.as_bytes());
-        hasher.input(&[0u8; 32]);
-        hasher.input(&code_hash.as_slice());
-        let ether: H160 = H256::from_slice(hasher.result().as_slice()).into();
-        let seeds = [ether.as_bytes()];
-        let (address, nonce) = Pubkey::find_program_address(&seeds[..], &config.evm_loader);
-        debug!("Creator: {}, code_hash: {}", &hex::encode(&creator.pubkey().to_bytes()), &hex::encode(code_hash.as_slice()));
-        (address, ether, nonce)
-    };
-
-    debug!("Create account: {} with {} {}", program_id, ether, nonce);  
-
-    let (program_code, program_seed) = {
-        let seed = bs58::encode(&ether.to_fixed_bytes()).into_string();
-        debug!("Code account seed {} and len {}", &seed, &seed.len());
-        let address = Pubkey::create_with_seed(&creator.pubkey(), &seed, &config.evm_loader).unwrap();
-        (address, seed)
-    };
-
-    debug!("Create code account: {}", &program_code.to_string());
-
-    let make_create_account_instruction = |acc: &Pubkey, ether: &H160, nonce: u8, balance: u64| {
-        Instruction::new(
-            config.evm_loader,
-            &(2u32, balance, 0 as u64, ether.as_fixed_bytes(), nonce),
-            vec![AccountMeta::new(creator.pubkey(), true),
-                 AccountMeta::new(*acc, false),
-                 AccountMeta::new(program_code, false),
-                 AccountMeta::new_readonly(system_program::id(), false),]
-        )
-    };
-
-    let make_write_instruction = |offset: u32, bytes: Vec<u8>| -> Instruction {
-        Instruction::new(
-            config.evm_loader,
-            &LoaderInstruction::Write {offset, bytes},
-            vec![AccountMeta::new(program_code, false),
-                 AccountMeta::new(creator.pubkey(), true)]
-        )
-    };
-
-    let make_finalize_instruction = || -> Instruction {
-        Instruction::new(
-            config.evm_loader,
-            &LoaderInstruction::Finalize,
-            vec![AccountMeta::new(program_id, false),
-                 AccountMeta::new(program_code, false),
-                //  AccountMeta::new(caller_id, false),
-                 AccountMeta::new(creator.pubkey(), true),
-                 AccountMeta::new(clock::id(), false),
-                 AccountMeta::new(rent::id(), false),
-                 AccountMeta::new(config.evm_loader, false),
-                ]
-        )
-    };
-
+    let plan = plan_deployment(config, &program_data, hashed_keys, storage_headroom)?;
+    if plan_only {
+        println!("{}", plan.to_json().to_string());
+        return Ok(());
+    }
+    if !plan.exit_reason.is_succeed() {
+        let (exit_code, exit_description) = exit_status_info(&plan.exit_reason);
+        return Err(format!("Init code failed in emulation: {:#x} {}", exit_code, exit_description).into());
+    }
 
     // Check program account to see if partial initialization has occurred
-    let initial_instructions = if let Some(account) = config.rpc_client
-        .get_account_with_commitment(&program_id, config.rpc_client.commitment())?
-        .value
+    if config.rpc_client
+        .get_account_with_commitment(&plan.program_id, config.rpc_client.commitment())?
+        .value.is_some()
     {
         return Err(format!("Account already exist").into());
-    } else {
-        let mut instructions = Vec::new();
-        // if let Some(account) = config.rpc_client.get_account_with_commitment(&caller_id, config.commitment)?.value {
-        //     // TODO Check caller account
-        // } else {
-        //     instructions.push(make_create_account_instruction(&caller_id, &caller_ether, caller_nonce, minimum_balance_for_account, 0));
-        // }
-        instructions.push(system_instruction::create_account_with_seed(&creator.pubkey(), &program_code, &creator.pubkey(), &program_seed, minimum_balance_for_code, program_code_len as u64, &config.evm_loader));
-        instructions.push(make_create_account_instruction(&program_id, &ether, nonce, minimum_balance_for_account));
-        instructions
-    };
-    let balance_needed = minimum_balance_for_account + minimum_balance_for_code;
-    debug!("Minimum balance: {}", balance_needed);
+    }
+    debug!("Minimum balance: {}", plan.rent);
 
-    //debug!("Initialize instructions: {:x?}", initial_instructions);  
+    let payer = config.signer.pubkey();
+    let initial_message = Message::new(&plan.initial, Some(&payer));
+    let holder_messages: Vec<Message> = plan.create_holders.iter().map(|instruction| Message::new(&[instruction.clone()], Some(&payer))).collect();
+    let holder_write_messages: Vec<Message> = plan.holder_writes.iter().map(|instruction| Message::new(&[instruction.clone()], Some(&payer))).collect();
+    let append_messages: Vec<Message> = plan.appends.iter().map(|instruction| Message::new(&[instruction.clone()], Some(&payer))).collect();
+    let finalize_message = Message::new(&[plan.finalize.clone()], Some(&payer));
 
-    let initial_message = Message::new(&initial_instructions, Some(&config.signer.pubkey()));
     let mut messages: Vec<&Message> = Vec::new();
     messages.push(&initial_message);
-
-    let mut write_messages = vec![];
-
-    let mut code_len = Vec::new();
-    code_len.extend_from_slice(&(program_data.len() as u64).to_le_bytes());
-    let message = Message::new(&[make_write_instruction(0u32, code_len)], Some(&creator.pubkey()));
-    write_messages.push(message);
-
-    // Write code
-    for (chunk, i) in program_data.chunks(DATA_CHUNK_SIZE).zip(0..) {
-        let message = Message::new(&[make_write_instruction((8+i*DATA_CHUNK_SIZE) as u32, chunk.to_vec())], Some(&creator.pubkey()));
-        write_messages.push(message);
-    }
-    let mut write_message_refs = vec![];
-    for message in write_messages.iter() {write_message_refs.push(message);}
-    messages.append(&mut write_message_refs);
-
-    let finalize_message = Message::new(&[make_finalize_instruction()], Some(&creator.pubkey()));
+    messages.extend(holder_messages.iter());
+    messages.extend(holder_write_messages.iter());
+    messages.extend(append_messages.iter());
     messages.push(&finalize_message);
 
     let (blockhash, fee_calculator, _) = config.rpc_client
@@ -459,12 +565,34 @@ fn command_deploy(
     check_account_for_spend_multiple_fees_with_commitment(
         &config.rpc_client,
         &config.signer.pubkey(),
-        balance_needed,
+        plan.rent,
         &fee_calculator,
         &messages,
         config.rpc_client.commitment(),
     )?;
 
+    let send_in_parallel = |messages: Vec<Message>| -> CommandResult {
+        let (blockhash, _, last_valid_slot) = config.rpc_client
+            .get_recent_blockhash_with_commitment(config.rpc_client.commitment())?
+            .value;
+
+        let mut transactions = vec![];
+        for message in messages.into_iter() {
+            let mut tx = Transaction::new_unsigned(message);
+            tx.try_sign(&signers, blockhash)?;
+            transactions.push(tx);
+        }
+
+        send_and_confirm_transactions_with_spinner(
+            config.rpc_client.clone(),
+            &config.websocket_url,
+            transactions,
+            &signers,
+            config.rpc_client.commitment(),
+            last_valid_slot,
+        )
+    };
+
     {  // Send initialize message
         debug!("Creating or modifying program account");
         let mut initial_transaction = Transaction::new_unsigned(initial_message);
@@ -476,30 +604,37 @@ fn command_deploy(
         )?;
     }
 
-    {  // Send write message
-        let (blockhash, _, last_valid_slot) = config.rpc_client
-            .get_recent_blockhash_with_commitment(config.rpc_client.commitment())?
-            .value;
-    
-        let mut write_transactions = vec![];
-        for message in write_messages.into_iter() {
+    {  // Create holder accounts and write parts of the code to them in parallel
+        debug!("Creating {} holder accounts", holder_messages.len());
+        send_in_parallel(holder_messages).map_err(|err| {
+            format!("Creating holder accounts failed: {}", err)
+        })?;
+
+        debug!("Writing program data to holder accounts");
+        send_in_parallel(holder_write_messages).map_err(|err| {
+            format!("Data writes to holder accounts failed: {}", err)
+        })?;
+        debug!("Writing program data to holder accounts done");
+    }
+
+    {  // Send append messages one by one, the program accepts only contiguous writes to the code account
+        debug!("Appending program data from holder accounts");
+        for message in append_messages.into_iter() {
+            let (blockhash, _, _) = config.rpc_client
+                .get_recent_blockhash_with_commitment(config.rpc_client.commitment())?
+                .value;
             let mut tx = Transaction::new_unsigned(message);
             tx.try_sign(&signers, blockhash)?;
-            write_transactions.push(tx);
+
+            config.rpc_client.send_and_confirm_transaction_with_spinner_and_config(
+                &tx,
+                config.rpc_client.commitment(),
+                RpcSendTransactionConfig::default(),
+            ).map_err(|err| {
+                format!("Data writes to program account failed: {}", err)
+            })?;
         }
-    
-        debug!("Writing program data");
-        send_and_confirm_transactions_with_spinner(
-            config.rpc_client.clone(),
-            &config.websocket_url,
-            write_transactions,
-            &signers,
-            config.rpc_client.commitment(),
-            last_valid_slot,
-        ).map_err(|err| {
-            format!("Data writes to program account failed: {}", err)
-        })?;
-        debug!("Writing program data done");
+        debug!("Appending program data done");
     }
 
     { // Send finalize message
@@ -524,9 +659,9 @@ fn command_deploy(
     }
 
     println!("{}", json!({
-        "programId": format!("{}", program_id),
-        "codeId": format!("{}", program_code),
-        "ethereum": format!("{:?}", ether),
+        "programId": format!("{}", plan.program_id),
+        "codeId": format!("{}", plan.program_code),
+        "ethereum": format!("{:?}", plan.ether),
     }).to_string());
     Ok(())
 }
@@ -537,7 +672,7 @@ fn command_get_ether_account_data (
 ) -> CommandResult {
     match EmulatorAccountStorage::get_account_from_solana(&config, ether_address) {
         Some((acc, code_account)) => {
-            let solana_address =  Pubkey::find_program_address(&[&ether_address.to_fixed_bytes()], &config.evm_loader).0;
+            let (solana_address, _) = ether_to_program_address(&config.evm_loader, ether_address);
             let account_data = AccountData::unpack(&acc.data).unwrap();
             let account_data = AccountData::get_account(&account_data).unwrap();
 
@@ -560,6 +695,10 @@ fn command_get_ether_account_data (
                 println!("Contract fields");
                 println!("    owner: {}", &code_data.owner);
                 println!("    code_size: {}", &code_data.code_size);
+                println!("    storage_root: {:?}", &code_data.storage_root);
+                println!("    deployer: {:?}", &code_data.deployer);
+                println!("    immutable: {}", &code_data.immutable);
+                println!("    hashed_keys: {}", &code_data.hashed_keys);
                 println!("    code as hex:");
     
                 let code_size = code_data.code_size;
@@ -580,10 +719,274 @@ fn command_get_ether_account_data (
 
         },
         None => {
-            eprintln!("Account not found {}", &ether_address.to_string());
+            error!("Account not found {}", &ether_address.to_string());
+        }
+    }
+
+    Ok(())
+}
+
+fn command_verify_storage(config: &Config, ether_address: &H160) -> CommandResult {
+    let code_account = match EmulatorAccountStorage::get_account_from_solana(&config, ether_address) {
+        Some((_, Some(code_account))) => code_account,
+        Some((_, None)) => return Err(format!("Account {} is not a contract", ether_address).into()),
+        None => return Err(format!("Account not found {}", ether_address).into()),
+    };
+
+    let mut data = code_account.data;
+    let code_data = AccountData::unpack(&data)?;
+    let header = AccountData::size(&code_data);
+    let contract = AccountData::get_contract(&code_data)?;
+    let hamt = Hamt::new(&mut data[header+contract.code_size as usize..], false)?;
+
+    let usage = hamt.verify().map_err(|e| format!("Storage of {} is corrupted: {}", ether_address, e))?;
+    let root = hamt.root_hash();
+
+    println!("{}", json!({
+        "address": format!("{:?}", ether_address),
+        "items": usage.items,
+        "used": usage.used,
+        "free": usage.free,
+        "storageHash": format!("{:?}", root),
+        "storageHashMatches": root == contract.storage_root,
+    }).to_string());
+
+    Ok(())
+}
+
+fn command_get_storage_proof(config: &Config, ether_address: &H160, index: U256) -> CommandResult {
+    let code_account = match EmulatorAccountStorage::get_account_from_solana(&config, ether_address) {
+        Some((_, Some(code_account))) => code_account,
+        Some((_, None)) => return Err(format!("Account {} is not a contract", ether_address).into()),
+        None => return Err(format!("Account not found {}", ether_address).into()),
+    };
+
+    let mut data = code_account.data;
+    let code_data = AccountData::unpack(&data)?;
+    let header = AccountData::size(&code_data);
+    let contract = AccountData::get_contract(&code_data)?;
+    let hamt = Hamt::new(&mut data[header+contract.code_size as usize..], false)?;
+
+    let proof = hamt.proof(contract.storage_key(index));
+    let nodes: Vec<_> = proof.nodes.iter().map(|node| match node {
+        ProofNode::Root(slots) => json!({
+            "slots": slots.iter().map(|slot| format!("{:?}", slot)).collect::<Vec<_>>(),
+        }),
+        ProofNode::Array{tags, children} => json!({
+            "tags": format!("0x{:08x}", tags),
+            "children": children.iter().map(|child| format!("{:?}", child)).collect::<Vec<_>>(),
+        }),
+    }).collect();
+    let value = match proof.item {
+        Some((key, value)) if key == contract.storage_key(index) => value,
+        _ => U256::zero(),
+    };
+
+    println!("{}", json!({
+        "address": format!("{:?}", ether_address),
+        "storageHash": format!("{:?}", contract.storage_root),
+        "key": format!("0x{:x}", index),
+        "value": format!("0x{:x}", value),
+        "proof": nodes,
+        "item": proof.item.map(|(key, value)| json!({
+            "key": format!("0x{:x}", key),
+            "value": format!("0x{:x}", value),
+        })),
+    }).to_string());
+
+    Ok(())
+}
+
+fn command_gas_price(config: &Config) -> CommandResult {
+    let lamports_per_gas = EmulatorAccountStorage::get_gas_price_from_solana(config)
+        .map_or(0, |gas_price| gas_price.lamports_per_gas);
+
+    // 1 lamport of the Ether account balance is 1 wei
+    println!("{}", json!({
+        "gas_price": format!("0x{:x}", lamports_per_gas),
+    }).to_string());
+
+    Ok(())
+}
+
+fn command_chain_id(config: &Config) -> CommandResult {
+    // Transactions are accepted only with the chain id of the config account
+    let chain_id = EmulatorAccountStorage::get_config_from_solana(config)
+        .map_or(CHAIN_ID, |program_config| program_config.chain_id);
+    println!("{}", json!({
+        "chain_id": format!("0x{:x}", chain_id),
+        "net_version": chain_id.to_string(),
+    }).to_string());
+
+    Ok(())
+}
+
+/// Version and features of the program the emulator is built with,
+/// the proxy compares them with `GetVersion` of the deployed program
+fn command_version() -> CommandResult {
+    let version = ProgramVersion::current();
+    println!("{}", json!({
+        "version": version.to_string(),
+        "features": format!("0x{:x}", version.features),
+        "precompiles": format!("0x{:x}", version.precompiles),
+    }).to_string());
+
+    Ok(())
+}
+
+/// State of the RPC node and the program, the proxy reports it as `eth_syncing` and to load balancers.
+/// The node is syncing while the finalized slot lags the processed one by more than `max_slot_lag`.
+fn command_health(config: &Config, max_slot_lag: u64) -> CommandResult {
+    let node_version = match config.rpc_client.get_version() {
+        Ok(version) => version.solana_core,
+        Err(err) => {
+            println!("{}", json!({"healthy": false, "rpc": {"reachable": false, "error": err.to_string()}}).to_string());
+            return Err("RPC node is unreachable".into());
         }
+    };
+
+    let program_account = config.rpc_client.get_account_with_commitment(&config.evm_loader, CommitmentConfig::recent())?.value;
+    let program = match program_account {
+        Some(account) => {
+            // Upgradeable programs report the slot of the last deployment as the version
+            let deployment_slot = match bincode::deserialize(&account.data) {
+                Ok(UpgradeableLoaderState::Program {programdata_address}) if account.owner == bpf_loader_upgradeable::id() => {
+                    config.rpc_client.get_account_with_commitment(&programdata_address, CommitmentConfig::recent())?.value
+                        .and_then(|programdata| match bincode::deserialize(&programdata.data) {
+                            Ok(UpgradeableLoaderState::ProgramData {slot, ..}) => Some(slot),
+                            _ => None,
+                        })
+                },
+                _ => None,
+            };
+            json!({"present": true, "executable": account.executable, "loader": format!("{}", account.owner), "deploymentSlot": deployment_slot})
+        },
+        None => json!({"present": false, "executable": false}),
+    };
+    let program_executable = program["executable"] == true;
+
+    let (config_key, _) = config_address(&config.evm_loader);
+    let config_account = config.rpc_client.get_account_with_commitment(&config_key, CommitmentConfig::recent())?.value;
+    let program_config = match config_account.as_ref().map(|account| AccountData::unpack(&account.data)) {
+        Some(Ok(AccountData::Config(program_config))) => json!({
+            "account": format!("{}", config_key),
+            "admin": format!("{}", program_config.admin),
+            "operatorsOnly": program_config.operators_only,
+            "nativeMint": format!("{}", program_config.native_mint),
+            "nativeDecimals": program_config.native_decimals,
+            "chainId": format!("0x{:x}", program_config.chain_id),
+            "allowLegacyTx": program_config.allow_legacy_tx,
+        }),
+        Some(_) => return Err(format!("Invalid config account {}", config_key).into()),
+        None => serde_json::Value::Null,
+    };
+    let lamports_per_gas = EmulatorAccountStorage::get_gas_price_from_solana(config)
+        .map_or(0, |gas_price| gas_price.lamports_per_gas);
+
+    let processed_slot = config.rpc_client.get_slot_with_commitment(CommitmentConfig::processed())?;
+    let finalized_slot = config.rpc_client.get_slot_with_commitment(CommitmentConfig::finalized())?;
+    let slot_lag = processed_slot.saturating_sub(finalized_slot);
+    let syncing = if slot_lag > max_slot_lag {
+        json!({"startingBlock": format!("0x{:x}", finalized_slot), "currentBlock": format!("0x{:x}", finalized_slot), "highestBlock": format!("0x{:x}", processed_slot)})
+    } else {
+        json!(false)
+    };
+
+    let healthy = program_executable && slot_lag <= max_slot_lag;
+    println!("{}", json!({
+        "healthy": healthy,
+        "rpc": {"reachable": true, "version": node_version},
+        "program": program,
+        "config": program_config,
+        "gas_price": format!("0x{:x}", lamports_per_gas),
+        "slot": {"processed": processed_slot, "finalized": finalized_slot, "lag": slot_lag},
+        "syncing": syncing,
+    }).to_string());
+
+    if healthy {
+        Ok(())
+    } else {
+        Err("Unhealthy".into())
     }
+}
+
+fn command_tx_hash(config: &Config, signed_trx: &[u8]) -> CommandResult {
+    // The program keys receipts and events by the same hash
+    let hash = tx_hash(signed_trx);
+    let (receipt_key, _) = receipt_address(&config.evm_loader, &hash);
+
+    println!("{}", json!({
+        "transactionHash": format!("{:?}", hash),
+        "receipt": format!("{}", receipt_key),
+    }).to_string());
+
+    Ok(())
+}
+
+fn command_get_receipt(config: &Config, tx_hash: &H256) -> CommandResult {
+    let (receipt_key, _) = receipt_address(&config.evm_loader, tx_hash);
+    let account = config.rpc_client.get_account_with_commitment(&receipt_key, CommitmentConfig::recent())?.value;
+    let receipt = match account.as_ref().map(|account| AccountData::unpack(&account.data)) {
+        Some(Ok(AccountData::Receipt(receipt))) => receipt,
+        Some(_) => return Err(format!("Invalid receipt account {}", receipt_key).into()),
+        None => {
+            println!("{}", json!({"account": format!("{}", receipt_key), "receipt": null}).to_string());
+            return Ok(());
+        }
+    };
+
+    println!("{}", json!({
+        "account": format!("{}", receipt_key),
+        "receipt": {
+            "transactionHash": format!("{:?}", receipt.tx_hash),
+            "status": format!("0x{:x}", receipt.status),
+            "gasUsed": format!("0x{:x}", receipt.gas_used),
+            "logsCount": receipt.logs_count,
+            "logsBloom": format!("0x{}", hex::encode(&receipt.logs_bloom[..])),
+            "blockNumber": format!("0x{:x}", receipt.slot),
+        },
+    }).to_string());
+
+    Ok(())
+}
+
+fn command_update_gas_price(config: &Config, lamports_per_gas: u64, coinbase: H160) -> CommandResult {
+    let (gas_price_key, _) = gas_price_address(&config.evm_loader);
+
+    let mut data = vec![21u8];
+    data.extend_from_slice(&lamports_per_gas.to_le_bytes());
+    data.extend_from_slice(coinbase.as_bytes());
+    let instruction = Instruction {
+        program_id: config.evm_loader,
+        accounts: vec![
+            AccountMeta::new(gas_price_key, false),
+            AccountMeta::new(config.signer.pubkey(), true),
+            AccountMeta::new_readonly(clock::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    };
+
+    let message = Message::new(&[instruction], Some(&config.signer.pubkey()));
+    let (blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+
+    check_account_for_fee(
+        &config.rpc_client,
+        &config.signer.pubkey(),
+        &fee_calculator,
+        &message)?;
+
+    let mut tx = Transaction::new_unsigned(message);
+    tx.try_sign(&[&*config.signer], blockhash)?;
+    debug!("signed: {:x?}", tx);
+
+    config.rpc_client.send_and_confirm_transaction_with_spinner(&tx)?;
 
+    println!("{}", json!({
+        "account": format!("{}", gas_price_key),
+        "lamports_per_gas": lamports_per_gas,
+        "coinbase": format!("{:?}", coinbase),
+    }).to_string());
     Ok(())
 }
 
@@ -609,6 +1012,34 @@ fn is_valid_h160<T>(string: T) -> Result<(), String> where T: AsRef<str>,
         .map_err(|e| e.to_string())
 }
 
+// Return H256 for an argument
+fn h256_of(matches: &ArgMatches<'_>, name: &str) -> Option<H256> {
+    matches.value_of(name).map(|value| {
+        H256::from_str(&make_clean_hex(value)).unwrap()
+    })
+}
+
+// Return an error if string cannot be parsed as a H256 hash
+fn is_valid_h256<T>(string: T) -> Result<(), String> where T: AsRef<str>,
+{
+    H256::from_str(&make_clean_hex(string.as_ref())).map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Return U256 for an argument
+fn u256_of(matches: &ArgMatches<'_>, name: &str) -> Option<U256> {
+    matches.value_of(name).map(|value| {
+        U256::from_str(&make_clean_hex(value)).unwrap()
+    })
+}
+
+// Return an error if string cannot be parsed as a U256 number
+fn is_valid_u256<T>(string: T) -> Result<(), String> where T: AsRef<str>,
+{
+    U256::from_str(&make_clean_hex(string.as_ref())).map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
 // Return hexdata for an argument
 fn hexdata_of(matches: &ArgMatches<'_>, name: &str) -> Option<Vec<u8>> {
     matches.value_of(name).map(|value| {
@@ -721,6 +1152,73 @@ fn main() {
                         .validator(is_valid_hexdata)
                         .help("Transaction data")
                 )
+                .arg(
+                    Arg::with_name("access_list")
+                        .long("access-list")
+                        .takes_value(false)
+                        .help("Print the EIP-2930 access list of the call instead of the used accounts"),
+                )
+                .arg(
+                    Arg::with_name("profile")
+                        .long("profile")
+                        .takes_value(false)
+                        .conflicts_with("access_list")
+                        .help("Print opcode counts and modeled costs per contract instead of the used accounts"),
+                )
+                .arg(
+                    Arg::with_name("calibration")
+                        .long("calibration")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Calibration table printed by `calibrate` for the compute units prediction, built-in estimates otherwise"),
+                )
+                .arg(
+                    Arg::with_name("override_block")
+                        .long("override-block")
+                        .value_name("NUMBER")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Block number seen by the call instead of the current slot"),
+                )
+                .arg(
+                    Arg::with_name("override_timestamp")
+                        .long("override-timestamp")
+                        .value_name("SECONDS")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Block timestamp seen by the call instead of the time of the current slot"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("emulate-session")
+                .about("Emulate calls read from stdin on the same state, with evm_snapshot and evm_revert requests")
+                .arg(
+                    Arg::with_name("sender")
+                        .value_name("SENDER")
+                        .takes_value(true)
+                        .index(1)
+                        .required(true)
+                        .validator(is_valid_h160)
+                        .help("The origin of the calls")
+                )
+                .arg(
+                    Arg::with_name("contract")
+                        .value_name("CONTRACT")
+                        .takes_value(true)
+                        .index(2)
+                        .required(true)
+                        .validator(is_valid_h160)
+                        .help("The contract called by the session")
+                )
+                .arg(
+                    Arg::with_name("calibration")
+                        .long("calibration")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Calibration table printed by `calibrate` for the compute units prediction, built-in estimates otherwise"),
+                )
         )
         .subcommand(
             SubCommand::with_name("create-ether-account")
@@ -775,6 +1273,106 @@ fn main() {
                         .required(true)
                         .help("/path/to/program.o"),
                 )
+                .arg(
+                    Arg::with_name("hashed_keys")
+                        .long("hashed-keys")
+                        .takes_value(false)
+                        .help("Hash the storage keys of the contract, for contracts with sequential slots"),
+                )
+                .arg(
+                    Arg::with_name("storage_headroom")
+                        .long("storage-headroom")
+                        .value_name("BYTES")
+                        .takes_value(true)
+                        .default_value("2048")
+                        .required(false)
+                        .help("Space of the code account for storage writes after the constructor"),
+                )
+                .arg(
+                    Arg::with_name("plan")
+                        .long("plan")
+                        .takes_value(false)
+                        .help("Print the deployment plan sized from the emulation of the init code, without sending transactions"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("gas-price")
+                .about("Get gas price in wei from the gas price oracle, as eth_gasPrice returns it")
+        )
+        .subcommand(
+            SubCommand::with_name("chain-id")
+                .about("Get chain id of the program, as eth_chainId and net_version return it")
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Run canonical init codes in the emulator and in simulated transactions of the program and compare the results")
+        )
+        .subcommand(
+            SubCommand::with_name("calibrate")
+                .about("Measure compute units of the opcode classes in simulated transactions of the program and print the calibration table")
+        )
+        .subcommand(
+            SubCommand::with_name("version")
+                .about("Get version, feature and precompile bits of the program the emulator is built with")
+        )
+        .subcommand(
+            SubCommand::with_name("health")
+                .about("Check the RPC node, the program and its config, the finalized slot lag is reported as eth_syncing")
+                .arg(
+                    Arg::with_name("max_slot_lag")
+                        .long("max-slot-lag")
+                        .value_name("SLOTS")
+                        .takes_value(true)
+                        .default_value("150")
+                        .required(false)
+                        .help("Lag of the finalized slot behind the processed one the node is healthy with"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("update-gas-price")
+                .about("Set lamports per gas in the gas price oracle, signed by the operator")
+                .arg(
+                    Arg::with_name("lamports_per_gas")
+                        .index(1)
+                        .value_name("LAMPORTS_PER_GAS")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lamports charged per unit of gas"),
+                )
+                .arg(
+                    Arg::with_name("coinbase")
+                        .long("coinbase")
+                        .value_name("ETHER")
+                        .takes_value(true)
+                        .validator(is_valid_h160)
+                        .help("EVM address of the operator returned as block.coinbase, zero by default"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("tx-hash")
+                .about("Get hash of the signed Ethereum transaction and address of its receipt")
+                .arg(
+                    Arg::with_name("signed_trx")
+                        .index(1)
+                        .value_name("SIGNED_TRX")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_valid_hexdata)
+                        .help("Raw RLP of the signed transaction, as eth_sendRawTransaction takes it"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("get-receipt")
+                .about("Get the receipt of the Ethereum transaction written by the program")
+                .arg(
+                    Arg::with_name("tx_hash")
+                        .index(1)
+                        .value_name("TX_HASH")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_valid_h256)
+                        .help("Ethereum transaction hash"),
+                )
         )
         .subcommand(
             SubCommand::with_name("get-ether-account-data")
@@ -789,6 +1387,41 @@ fn main() {
                         .help("Ethereum address"),
                 )
         )
+        .subcommand(
+            SubCommand::with_name("verify-storage")
+                .about("Check the integrity of the contract storage")
+                .arg(
+                    Arg::with_name("ether")
+                        .index(1)
+                        .value_name("ether")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_valid_h160)
+                        .help("Ethereum address of the contract"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("get-storage-proof")
+                .about("Get the Merkle proof of the contract storage value")
+                .arg(
+                    Arg::with_name("ether")
+                        .index(1)
+                        .value_name("ether")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_valid_h160)
+                        .help("Ethereum address of the contract"),
+                )
+                .arg(
+                    Arg::with_name("index")
+                        .index(2)
+                        .value_name("INDEX")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_valid_u256)
+                        .help("Storage index, hex"),
+                )
+        )
         .get_matches();
 
         stderrlog::new()
@@ -851,8 +1484,40 @@ fn main() {
                 let contract = h160_of(&arg_matches, "contract").unwrap();
                 let sender = h160_of(&arg_matches, "sender").unwrap();
                 let data = hexdata_of(&arg_matches, "data").unwrap();
+                let access_list = arg_matches.is_present("access_list");
+                let profile = arg_matches.is_present("profile");
+                let calibration = match arg_matches.value_of("calibration") {
+                    Some(path) => CalibrationTable::load(path).unwrap_or_else(|e| {
+                        error!("{}", e);
+                        exit(1);
+                    }),
+                    None => CalibrationTable::default(),
+                };
+                let override_block = if arg_matches.is_present("override_block") {
+                    Some(value_t_or_exit!(arg_matches, "override_block", u64))
+                } else {
+                    None
+                };
+                let override_timestamp = if arg_matches.is_present("override_timestamp") {
+                    Some(value_t_or_exit!(arg_matches, "override_timestamp", i64))
+                } else {
+                    None
+                };
+
+                command_emulate(&config, contract, sender, data, access_list, profile, &calibration, override_block, override_timestamp)
+            }
+            ("emulate-session", Some(arg_matches)) => {
+                let contract = h160_of(&arg_matches, "contract").unwrap();
+                let sender = h160_of(&arg_matches, "sender").unwrap();
+                let calibration = match arg_matches.value_of("calibration") {
+                    Some(path) => CalibrationTable::load(path).unwrap_or_else(|e| {
+                        error!("{}", e);
+                        exit(1);
+                    }),
+                    None => CalibrationTable::default(),
+                };
 
-                command_emulate(&config, contract, sender, data)
+                command_emulate_session(&config, contract, sender, &calibration)
             }
             ("create-program-address", Some(arg_matches)) => {
                 let seed = arg_matches.value_of("seed").unwrap().to_string();
@@ -868,14 +1533,64 @@ fn main() {
             }
             ("deploy", Some(arg_matches)) => {
                 let program_location = arg_matches.value_of("program_location").unwrap().to_string();
+                let hashed_keys = arg_matches.is_present("hashed_keys");
+                let storage_headroom = value_t_or_exit!(arg_matches, "storage_headroom", usize);
+                let plan_only = arg_matches.is_present("plan");
+
+                command_deploy(&config, &program_location, hashed_keys, storage_headroom, plan_only)
+            }
+            ("gas-price", Some(_)) => {
+                command_gas_price(&config)
+            }
+            ("chain-id", Some(_)) => {
+                command_chain_id(&config)
+            }
+            ("verify", Some(_)) => {
+                command_verify(&config)
+            }
+            ("calibrate", Some(_)) => {
+                command_calibrate(&config)
+            }
+            ("version", Some(_)) => {
+                command_version()
+            }
+            ("health", Some(arg_matches)) => {
+                let max_slot_lag = value_t_or_exit!(arg_matches, "max_slot_lag", u64);
+
+                command_health(&config, max_slot_lag)
+            }
+            ("update-gas-price", Some(arg_matches)) => {
+                let lamports_per_gas = value_t_or_exit!(arg_matches, "lamports_per_gas", u64);
+                let coinbase = h160_of(&arg_matches, "coinbase").unwrap_or_default();
+
+                command_update_gas_price(&config, lamports_per_gas, coinbase)
+            }
+            ("tx-hash", Some(arg_matches)) => {
+                let signed_trx = hexdata_of(&arg_matches, "signed_trx").unwrap();
 
-                command_deploy(&config, &program_location)
+                command_tx_hash(&config, &signed_trx)
+            }
+            ("get-receipt", Some(arg_matches)) => {
+                let tx_hash = h256_of(&arg_matches, "tx_hash").unwrap();
+
+                command_get_receipt(&config, &tx_hash)
             }
             ("get-ether-account-data", Some(arg_matches)) => {
                 let ether = h160_of(&arg_matches, "ether").unwrap();
 
                 command_get_ether_account_data(&config, &ether)
             }
+            ("verify-storage", Some(arg_matches)) => {
+                let ether = h160_of(&arg_matches, "ether").unwrap();
+
+                command_verify_storage(&config, &ether)
+            }
+            ("get-storage-proof", Some(arg_matches)) => {
+                let ether = h160_of(&arg_matches, "ether").unwrap();
+                let index = u256_of(&arg_matches, "index").unwrap();
+
+                command_get_storage_proof(&config, &ether, index)
+            }
             _ => unreachable!(),
         };
         match result {
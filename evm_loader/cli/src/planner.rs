@@ -0,0 +1,278 @@
+//! Deployment planner
+//!
+//! Emulates the init code of the contract to size the code account exactly: the deployed code,
+//! the storage HAMT allocated by the constructor writes and the headroom for later writes.
+//! The plan holds the instructions of the whole deployment in the order they are sent.
+//! Failed emulation is part of the plan, the deployment is not sent then.
+
+use evm::{backend::Apply, CreateScheme, ExitReason};
+use primitive_types::{H160, H256, U256};
+use serde_json::json;
+use sha3::{Keccak256, Digest};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    loader_instruction::LoaderInstruction,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    signature::Signer,
+    system_instruction,
+    system_program,
+    sysvar::{rent, clock},
+};
+use evm_loader::{
+    account_data::{Account, Contract},
+    executor::Machine,
+    executor_state::{ExecutorState, ExecutorSubstate},
+    exit_status::{exit_kind, exit_status_info},
+    hamt::Hamt,
+    instruction::write_from_holder,
+    profile::Profile,
+    solana_backend::SolanaBackend,
+    utils::{ether_to_program_address, ether_to_seeded_address},
+};
+use log::*;
+use std::cmp::{max, min};
+use crate::{Config, Error, account_storage::EmulatorAccountStorage, DATA_CHUNK_SIZE, HOLDER_PART_SIZE};
+
+/// Accounts, rent and instructions of a deployment
+pub struct DeploymentPlan {
+    /// Ether account of the contract
+    pub program_id: Pubkey,
+    /// Code account of the contract
+    pub program_code: Pubkey,
+    pub ether: H160,
+    /// Exit reason of the emulated init code
+    pub exit_reason: ExitReason,
+    /// Result of the emulated init code, as `Finalize` passes it back: empty on success, revert data otherwise
+    pub result: Vec<u8>,
+    /// EVM steps executed by the init code
+    pub steps: u64,
+    /// Ether accounts touched by the init code
+    pub touched_accounts: usize,
+    /// Opcode profile of the init code
+    pub profile: Profile,
+    /// Length of the code returned by the init code
+    pub code_size: usize,
+    /// Storage slots written by the constructor
+    pub storage_items: usize,
+    /// Bytes of the storage HAMT allocated by the constructor writes
+    pub storage_len: usize,
+    /// Space of the code account
+    pub code_account_len: usize,
+    /// Rent of the created accounts, the rent of holders returns to the deployer by `WriteFromHolder`
+    pub rent: u64,
+    pub used_gas: u64,
+    /// Creation of the Ether account and the code account, sent in one transaction
+    pub initial: Vec<Instruction>,
+    /// Creation of the holder accounts, one transaction each, sent in parallel
+    pub create_holders: Vec<Instruction>,
+    /// Writes of the init code to the holder accounts, one transaction each, sent in parallel
+    pub holder_writes: Vec<Instruction>,
+    /// Writes to the code account, one transaction each, sent in order
+    pub appends: Vec<Instruction>,
+    /// Execution of the init code, with the accounts used by the constructor
+    pub finalize: Instruction,
+}
+
+impl DeploymentPlan {
+    /// Number of transactions of the deployment
+    pub fn transaction_count(&self) -> usize {
+        1 + self.create_holders.len() + self.holder_writes.len() + self.appends.len() + 1
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let (exit_code, exit_description) = exit_status_info(&self.exit_reason);
+        json!({
+            "exit_status": exit_kind(&self.exit_reason), "exit_code": exit_code, "exit_reason": exit_description,
+            "programId": format!("{}", self.program_id),
+            "codeId": format!("{}", self.program_code),
+            "ethereum": format!("{:?}", self.ether),
+            "codeSize": self.code_size,
+            "storageItems": self.storage_items,
+            "storageLen": self.storage_len,
+            "codeAccountLen": self.code_account_len,
+            "rent": self.rent,
+            "usedGas": self.used_gas,
+            "holders": self.create_holders.len(),
+            "transactions": self.transaction_count(),
+        })
+    }
+}
+
+/// Bytes of the storage HAMT holding the items, as the program allocates them
+fn storage_len(items: &[(U256, U256)]) -> Result<usize, ProgramError> {
+    let mut data = vec![0u8; 4*1024];
+    loop {
+        let result = Hamt::new(&mut data, true).and_then(|mut hamt| {
+            for (key, value) in items {
+                hamt.insert(*key, *value)?;
+            }
+            Ok(hamt.allocated_len())
+        });
+        match result {
+            Err(ProgramError::AccountDataTooSmall) => {
+                let len = data.len() * 2;
+                data.resize(len, 0);
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Plan the deployment of the init code by the signer of the config, as `Finalize` executes it with `CREATE2`
+pub fn plan_deployment(config: &Config, program_data: &[u8], hashed_keys: bool, storage_headroom: usize) -> Result<DeploymentPlan, Error> {
+    let creator = config.signer.pubkey();
+    let creator_ether: H160 = H256::from_slice(Keccak256::digest(&creator.to_bytes()).as_slice()).into();
+    debug!("Creator: ether {}, solana {}", creator_ether, creator);
+
+    let code_hash = H256::from_slice(Keccak256::digest(program_data).as_slice());
+    let ether: H160 = {
+        let mut hasher = Keccak256::new();
+        hasher.input(&[0xff]);
+        hasher.input(&creator_ether.as_bytes());
+        hasher.input(&[0u8; 32]);
+        hasher.input(&code_hash.as_bytes());
+        H256::from_slice(hasher.result().as_slice()).into()
+    };
+    let (program_id, nonce) = ether_to_program_address(&config.evm_loader, &ether);
+    let (program_code, program_seed) = ether_to_seeded_address(&creator, &ether, &config.evm_loader)?;
+    debug!("Create account: {} with {} {}, code account {}", program_id, ether, nonce, program_code);
+
+    let account_storage = EmulatorAccountStorage::new(config, ether, creator_ether);
+    let (exit_reason, result, steps, profile, touched_accounts, applies, transfers, used_gas) = {
+        let backend = SolanaBackend::new(&account_storage, None);
+        let mut executor = Machine::new(ExecutorState::new(ExecutorSubstate::new(), backend));
+        executor.enable_profile();
+
+        let scheme = CreateScheme::Create2 { caller: creator_ether, code_hash, salt: H256::zero() };
+        let exit_reason = match executor.create_begin(creator_ether, program_data.to_vec(), U256::zero(), scheme, u64::max_value()) {
+            Ok(()) => executor.execute(),
+            Err(reason) => reason,
+        };
+        let result = executor.return_value();
+        let steps = executor.executed_steps();
+        let profile = executor.take_profile().unwrap_or_default();
+        let used_gas = executor.gas_settlement().gas_used;
+
+        let executor_state = executor.into_state();
+        let touched_accounts = executor_state.touched_accounts();
+        let touched_count = touched_accounts.len();
+        account_storage.add_touched_accounts(touched_accounts);
+        if exit_reason.is_succeed() {
+            let (_, (applies, _, transfers)) = executor_state.deconstruct();
+            (exit_reason, result, steps, profile, touched_count, applies, Some(transfers), used_gas)
+        } else {
+            (exit_reason, result, steps, profile, touched_count, Vec::new(), None, used_gas)
+        }
+    };
+
+    let contract = Contract {
+        owner: program_id,
+        code_size: 0,
+        storage_root: H256::zero(),
+        deployer: creator_ether,
+        immutable: false,
+        hashed_keys,
+        uploaded_len: 0,
+    };
+    let mut code_size = 0;
+    let mut storage = Vec::new();
+    for apply in applies.iter() {
+        if let Apply::Modify {address, code, storage: items, ..} = apply {
+            if *address == ether {
+                code_size = code.as_ref().map_or(0, Vec::len);
+                storage = items.iter().map(|(index, value)| (contract.storage_key(*index), *value)).collect();
+            }
+        }
+    }
+    account_storage.apply(applies);
+    if let Some(transfers) = transfers {
+        account_storage.apply_transfers(&transfers);
+    }
+
+    let storage_len = if storage.is_empty() { 0 } else { storage_len(&storage)? };
+    let contract_header_len = 1 + Contract::SIZE;
+    let code_account_len = contract_header_len + max(8 + program_data.len(), code_size + storage_len + storage_headroom);
+    debug!("Code size {}, {} storage items in {} bytes, code account {} bytes", code_size, storage.len(), storage_len, code_account_len);
+
+    let account_rent = config.rpc_client.get_minimum_balance_for_rent_exemption(1 + Account::SIZE)?;
+    let code_account_rent = config.rpc_client.get_minimum_balance_for_rent_exemption(code_account_len)?;
+    let mut rent = account_rent + code_account_rent;
+
+    let make_write_instruction = |account: &Pubkey, offset: u32, bytes: Vec<u8>| -> Instruction {
+        Instruction::new(
+            config.evm_loader,
+            &LoaderInstruction::Write {offset, bytes},
+            vec![AccountMeta::new(*account, false),
+                 AccountMeta::new(creator, true)]
+        )
+    };
+
+    let initial = vec![
+        system_instruction::create_account_with_seed(&creator, &program_code, &creator, &program_seed, code_account_rent, code_account_len as u64, &config.evm_loader),
+        Instruction::new(
+            config.evm_loader,
+            &(2u32, account_rent, 0 as u64, ether.as_fixed_bytes(), nonce, hashed_keys),
+            vec![AccountMeta::new(creator, true),
+                 AccountMeta::new(program_id, false),
+                 AccountMeta::new(program_code, false),
+                 AccountMeta::new_readonly(system_program::id(), false),]
+        ),
+    ];
+
+    // Parts of the init code are uploaded to independent holder accounts in parallel,
+    // then appended to the code account in order
+    let mut create_holders = Vec::new();
+    let mut holder_writes = Vec::new();
+    let mut appends = vec![make_write_instruction(&program_code, 0u32, (program_data.len() as u64).to_le_bytes().to_vec())];
+    for (part, i) in program_data.chunks(HOLDER_PART_SIZE).zip(0..) {
+        let seed = format!("{}.{}", &program_seed[..min(program_seed.len(), 24)], i);
+        let holder = Pubkey::create_with_seed(&creator, &seed, &config.evm_loader)?;
+        debug!("Holder account {} with seed {} for {} bytes", holder, seed, part.len());
+
+        let holder_len = 1 + 8 + part.len();
+        let holder_rent = config.rpc_client.get_minimum_balance_for_rent_exemption(holder_len)?;
+        rent += holder_rent;
+        create_holders.push(system_instruction::create_account_with_seed(&creator, &holder, &creator, &seed, holder_rent, holder_len as u64, &config.evm_loader));
+
+        holder_writes.push(make_write_instruction(&holder, 0u32, (part.len() as u64).to_le_bytes().to_vec()));
+        for (chunk, i) in part.chunks(DATA_CHUNK_SIZE).zip(0..) {
+            holder_writes.push(make_write_instruction(&holder, (8+i*DATA_CHUNK_SIZE) as u32, chunk.to_vec()));
+        }
+
+        appends.push(write_from_holder(&config.evm_loader, &program_code, &holder, &creator, &seed));
+    }
+
+    let mut finalize_accounts = vec![
+        AccountMeta::new(program_id, false),
+        AccountMeta::new(program_code, false),
+        AccountMeta::new(creator, true),
+        AccountMeta::new(clock::id(), false),
+        AccountMeta::new(rent::id(), false),
+        AccountMeta::new(config.evm_loader, false),
+    ];
+    finalize_accounts.extend(account_storage.used_account_metas());
+    let finalize = Instruction::new(config.evm_loader, &LoaderInstruction::Finalize, finalize_accounts);
+
+    Ok(DeploymentPlan {
+        program_id,
+        program_code,
+        ether,
+        exit_reason,
+        result,
+        steps,
+        touched_accounts,
+        profile,
+        code_size,
+        storage_items: storage.len(),
+        storage_len,
+        code_account_len,
+        rent,
+        used_gas,
+        initial,
+        create_holders,
+        holder_writes,
+        appends,
+        finalize,
+    })
+}
@@ -0,0 +1,140 @@
+//! Consistency self-test of the emulator and the program
+//!
+//! Canonical init codes are executed by `Finalize` in the emulator and in `simulateTransaction`
+//! of the whole deployment in one transaction, nothing is committed to the chain.
+//! The program logs the exit status, the result and the telemetry of the execution,
+//! they must match the emulation. The init codes revert with the values they compute,
+//! so state reads and context values appear in the result.
+
+use evm_loader::exit_status::exit_status_info;
+use serde_json::json;
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    loader_instruction::LoaderInstruction,
+    message::Message,
+    signature::Signer,
+    transaction::Transaction,
+};
+use log::*;
+use crate::{Config, CommandResult, Error, planner::{plan_deployment, DeploymentPlan}};
+
+/// Storage headroom of the code accounts of the cases, the cases write one slot at most
+const CASE_STORAGE_HEADROOM: usize = 1024;
+
+/// Canonical init codes and what they check
+fn cases() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        // SSTORE 42 to slot 0, SLOAD it back and revert with it
+        ("storage", vec![0x60, 0x2a, 0x60, 0x00, 0x55, 0x60, 0x00, 0x54, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xfd]),
+        // Revert with keccak256 of 42
+        ("keccak", vec![0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0x20, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xfd]),
+        // Revert with CALLER, ADDRESS, CHAINID and SELFBALANCE
+        ("context", vec![0x33, 0x60, 0x00, 0x52, 0x30, 0x60, 0x20, 0x52, 0x46, 0x60, 0x40, 0x52, 0x47, 0x60, 0x60, 0x52, 0x60, 0x80, 0x60, 0x00, 0xfd]),
+        // STATICCALL ecrecover with zero input, revert with its output and the success flag
+        ("ecrecover", vec![0x60, 0x20, 0x60, 0x00, 0x60, 0x80, 0x60, 0x00, 0x60, 0x01, 0x5a, 0xfa, 0x60, 0x20, 0x52, 0x60, 0x40, 0x60, 0x00, 0xfd]),
+        // LOG1 and return 32 bytes of code, succeeds with empty result
+        ("log", vec![0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x01, 0x60, 0x20, 0x60, 0x00, 0xa1, 0x60, 0x20, 0x60, 0x00, 0xf3]),
+        // Invalid opcode
+        ("invalid", vec![0xfe]),
+    ]
+}
+
+/// Exit status, result and telemetry logged by the program in `invoke_on_return`
+#[derive(Debug, PartialEq)]
+struct LoggedExecution {
+    exit_status: u8,
+    result: String,
+    steps: u64,
+    touched_accounts: u64,
+}
+
+/// Value of the `name: value` field of the logged telemetry
+fn telemetry_field(line: &str, name: &str) -> Option<u64> {
+    let start = line.find(&format!("{}: ", name))? + name.len() + 2;
+    line[start..].split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+}
+
+fn parse_program_logs(logs: &[String]) -> Option<LoggedExecution> {
+    const PREFIX: &str = "Program log: ";
+    let pos = logs.iter().rposition(|line| line.starts_with("Program log: Exit status 0x"))?;
+
+    let status = logs[pos].trim_start_matches("Program log: Exit status 0x").split(':').next()?;
+    let result = logs.get(pos + 1)?.strip_prefix(PREFIX)?;
+    let telemetry = logs.get(pos + 2)?.strip_prefix(PREFIX)?;
+
+    Some(LoggedExecution {
+        exit_status: u8::from_str_radix(status, 16).ok()?,
+        result: result.to_string(),
+        steps: telemetry_field(telemetry, "steps")?,
+        touched_accounts: telemetry_field(telemetry, "touched_accounts")?,
+    })
+}
+
+/// Simulate the deployment of the init code of the plan in one transaction:
+/// creation of the accounts, the write of the whole code and `Finalize`
+pub fn simulate_deployment(config: &Config, plan: &DeploymentPlan, code: &[u8]) -> Result<RpcSimulateTransactionResult, Error> {
+    let payer = config.signer.pubkey();
+    let mut upload = (code.len() as u64).to_le_bytes().to_vec();
+    upload.extend_from_slice(code);
+    let mut instructions = plan.initial.clone();
+    instructions.push(Instruction::new(
+        config.evm_loader,
+        &LoaderInstruction::Write {offset: 0, bytes: upload},
+        vec![AccountMeta::new(plan.program_code, false),
+             AccountMeta::new(payer, true)]
+    ));
+    instructions.push(plan.finalize.clone());
+
+    let (blockhash, _) = config.rpc_client.get_recent_blockhash()?;
+    let mut tx = Transaction::new_unsigned(Message::new(&instructions, Some(&payer)));
+    tx.try_sign(&[&*config.signer], blockhash)?;
+    Ok(config.rpc_client.simulate_transaction(&tx)?.value)
+}
+
+/// Run the canonical cases in the emulator and in the simulated program and report the differences
+pub fn command_verify(config: &Config) -> CommandResult {
+    let mut reports = Vec::new();
+    let mut diverged = 0;
+
+    for (name, code) in cases() {
+        debug!("Case {}", name);
+        let plan = plan_deployment(config, &code, false, CASE_STORAGE_HEADROOM)?;
+        let (exit_status, _) = exit_status_info(&plan.exit_reason);
+        let emulated = LoggedExecution {
+            exit_status,
+            result: hex::encode(&plan.result),
+            steps: plan.steps,
+            touched_accounts: plan.touched_accounts as u64,
+        };
+
+        let simulation = simulate_deployment(config, &plan, &code)?;
+        let logs = simulation.logs.unwrap_or_default();
+        let executed = parse_program_logs(&logs);
+
+        let matched = simulation.err.is_none() && executed.as_ref() == Some(&emulated);
+        if !matched {
+            diverged += 1;
+            warn!("Case {} diverged: emulator {:?}, program {:?} {:?}", name, emulated, executed, simulation.err);
+        }
+
+        reports.push(json!({
+            "case": name,
+            "match": matched,
+            "emulator": {"exit_status": format!("{:#x}", emulated.exit_status), "result": emulated.result,
+                "steps": emulated.steps, "touched_accounts": emulated.touched_accounts},
+            "program": executed.map(|executed| json!({"exit_status": format!("{:#x}", executed.exit_status), "result": executed.result,
+                "steps": executed.steps, "touched_accounts": executed.touched_accounts})),
+            "error": simulation.err.map(|err| err.to_string()),
+        }));
+    }
+
+    println!("{}", json!({"cases": reports, "diverged": diverged}).to_string());
+
+    if diverged == 0 {
+        Ok(())
+    } else {
+        Err(format!("{} cases diverged between the emulator and the program", diverged).into())
+    }
+}
+
@@ -0,0 +1,37 @@
+//! AccountData pack and unpack
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use evm_loader::account_data::{AccountData, Account, Contract};
+use primitive_types::{H160, H256};
+use solana_program::pubkey::Pubkey;
+
+fn bench_account_data(c: &mut Criterion) {
+    let account = AccountData::Account(Account {
+        ether: H160::repeat_byte(1),
+        nonce: 255,
+        trx_count: 10,
+        signer: Pubkey::new_unique(),
+        code_account: Pubkey::new_unique(),
+        blocked: Some(Pubkey::new_unique()),
+    });
+    let contract = AccountData::Contract(Contract {
+        owner: Pubkey::new_unique(),
+        code_size: 0x6000,
+        storage_root: H256::repeat_byte(2),
+        deployer: H160::repeat_byte(3),
+        immutable: false,
+        hashed_keys: false,
+        uploaded_len: 0x6008,
+    });
+
+    for (name, data) in [("account", account), ("contract", contract)].iter() {
+        let mut packed = vec![0u8; data.size()];
+        c.bench_function(&format!("pack_{}", name), |b| b.iter(|| black_box(data).pack(&mut packed).unwrap()));
+
+        data.pack(&mut packed).unwrap();
+        c.bench_function(&format!("unpack_{}", name), |b| b.iter(|| AccountData::unpack(black_box(&packed)).unwrap()));
+    }
+}
+
+criterion_group!(benches, bench_account_data);
+criterion_main!(benches);
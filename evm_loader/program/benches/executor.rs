@@ -0,0 +1,66 @@
+//! `Machine::step` throughput on a loop of arithmetic, memory, SHA3 and SSTORE
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use evm_loader::{
+    executor::Machine,
+    executor_state::{ExecutorState, ExecutorSubstate, StackState},
+    solana_backend::{AccountStorage, SolanaBackend, CHAIN_ID},
+    solidity_account::SolidityAccount,
+};
+use primitive_types::{H160, U256};
+use solana_program::pubkey::Pubkey;
+
+/// Storage without Solana accounts, the contract code is set in the executor substate
+struct EmptyStorage;
+
+impl AccountStorage for EmptyStorage {
+    fn apply_to_account<U, D, F>(&self, _address: &H160, d: D, _f: F) -> U
+    where F: FnOnce(&SolidityAccount) -> U,
+          D: FnOnce() -> U
+    {
+        d()
+    }
+
+    fn contract(&self) -> H160 { H160::repeat_byte(2) }
+    fn origin(&self) -> H160 { H160::repeat_byte(1) }
+    fn block_number(&self) -> U256 { U256::one() }
+    fn block_timestamp(&self) -> U256 { U256::one() }
+    fn chain_id(&self) -> U256 { U256::from(CHAIN_ID) }
+    fn program_id(&self) -> Pubkey { Pubkey::default() }
+}
+
+/// Count down from 1024, each iteration stores `keccak(counter)` at `counter`:
+/// `JUMPDEST PUSH1 1 SWAP1 SUB DUP1 PUSH1 0 MSTORE PUSH1 32 PUSH1 0 SHA3 DUP2 SSTORE DUP1 PUSH1 3 JUMPI`
+const LOOP_CODE: &[u8] = &[
+    0x61, 0x04, 0x00,
+    0x5b, 0x60, 0x01, 0x90, 0x03, 0x80, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0x20, 0x81, 0x55, 0x80, 0x60, 0x03, 0x57,
+    0x00,
+];
+
+fn machine(storage: &EmptyStorage) -> Machine<SolanaBackend<EmptyStorage>> {
+    let backend = SolanaBackend::new(storage, None);
+    let mut state = ExecutorState::new(ExecutorSubstate::new(), backend);
+    state.set_code(storage.contract(), LOOP_CODE.to_vec());
+
+    let mut machine = Machine::new(state);
+    machine.call_begin(storage.origin(), storage.contract(), Vec::new(), u64::max_value());
+    machine
+}
+
+fn bench_step(c: &mut Criterion) {
+    let storage = EmptyStorage;
+
+    let mut counter = machine(&storage);
+    assert!(counter.execute().is_succeed());
+    let steps = counter.executed_steps();
+
+    let mut group = c.benchmark_group("machine_step");
+    group.throughput(Throughput::Elements(steps));
+    group.bench_function("loop", |b| {
+        b.iter_batched(|| machine(&storage), |mut machine| machine.execute(), BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_step);
+criterion_main!(benches);
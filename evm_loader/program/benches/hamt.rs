@@ -0,0 +1,60 @@
+//! Hamt insert and find at various fill levels
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use evm_loader::hamt::Hamt;
+use primitive_types::U256;
+
+const DATA_SIZE: usize = 4 * 1024 * 1024;
+const FILL_LEVELS: [usize; 3] = [100, 1_000, 10_000];
+
+/// Spread keys like Solidity mappings do: keccak of the slot
+fn key(i: usize) -> U256 {
+    U256::from_big_endian(&solana_program::keccak::hash(&i.to_le_bytes()).to_bytes())
+}
+
+fn filled(items: usize) -> Vec<u8> {
+    let mut data = vec![0u8; DATA_SIZE];
+    let mut hamt = Hamt::new(&mut data, true).unwrap();
+    for i in 0..items {
+        hamt.insert(key(i), U256::from(i)).unwrap();
+    }
+    data
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hamt_insert");
+    for &items in FILL_LEVELS.iter() {
+        let data = filled(items);
+        group.bench_with_input(BenchmarkId::from_parameter(items), &items, |b, &items| {
+            b.iter_batched_ref(
+                || data.clone(),
+                |data| {
+                    let mut hamt = Hamt::new(data, false).unwrap();
+                    hamt.insert(key(items), U256::one()).unwrap();
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_find(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hamt_find");
+    for &items in FILL_LEVELS.iter() {
+        let mut data = filled(items);
+        let hamt = Hamt::new(&mut data, false).unwrap();
+        let keys: Vec<U256> = (0..items).map(key).collect();
+        group.bench_with_input(BenchmarkId::from_parameter(items), &keys, |b, keys| {
+            let mut i = 0;
+            b.iter(|| {
+                i = (i + 1) % keys.len();
+                hamt.find(keys[i])
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_insert, bench_find);
+criterion_main!(benches);
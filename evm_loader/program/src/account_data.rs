@@ -1,5 +1,9 @@
 use arrayref::{array_ref, array_refs, array_mut_ref, mut_array_refs};
-use primitive_types::H160;
+use primitive_types::{H160, H256, U256};
+use crate::{
+    bloom::Bloom,
+    utils::{keccak256_h256, u256_to_h256},
+};
 use solana_program::{
     program_error::ProgramError,
     pubkey::Pubkey,
@@ -8,6 +12,7 @@ use solana_program::{
 #[derive(Debug,Clone)]
 pub struct Account {
     pub ether: H160,
+    /// Bump seed of the program address `[ether, nonce]`
     pub nonce: u8,
     pub trx_count: u64,
     pub signer: Pubkey,
@@ -19,22 +24,98 @@ pub struct Account {
 pub struct Contract {
     pub owner: Pubkey,
     pub code_size: u32,
+    /// Merkle commitment of the storage, see `Hamt::root_hash`
+    pub storage_root: H256,
+    /// Origin of the transaction which deployed the code, allowed to update it
+    pub deployer: H160,
+    /// Code updates are disabled permanently
+    pub immutable: bool,
+    /// Storage keys are hashed with keccak256 before they are used in the HAMT,
+    /// which spreads sequential slots over the tree
+    pub hashed_keys: bool,
+    /// Bytes written by `Write` before `Finalize`, every write continues the previous ones
+    pub uploaded_len: u32,
 }
 
 #[derive(Debug,Clone)]
 pub struct Storage {
     pub caller: H160,
     pub nonce: u64,
+    /// Hash of the transaction holding the lock, see `transaction::tx_hash`
+    pub tx_hash: H256,
+    /// Operator which started the transaction, it may cancel the transaction as well as the caller
+    pub operator: Pubkey,
     pub accounts_len: usize,
     pub executor_data_size: usize,
     pub evm_data_size: usize
 }
 
+/// Registry record linking an Ether address and its Solana account
+#[derive(Debug,Clone)]
+pub struct Alias {
+    pub ether: H160,
+    pub account: Pubkey,
+}
+
+/// Gas price oracle: conversion of gas to lamports, updated by the operator
+#[derive(Debug,Clone)]
+pub struct GasPrice {
+    pub operator: Pubkey,
+    pub lamports_per_gas: u64,
+    /// Slot of the last update
+    pub slot: u64,
+    /// EVM address registered by the operator, returned as `block.coinbase`
+    pub coinbase: H160,
+}
+
+/// Receipt of the Ethereum transaction, written by `CallFromRawEthereumTX` if the receipt account is passed
+#[derive(Debug,Clone)]
+pub struct Receipt {
+    pub tx_hash: H256,
+    /// Exit status as reported by `OnReturn`
+    pub status: u8,
+    pub gas_used: u64,
+    pub logs_count: u64,
+    pub logs_bloom: Bloom,
+    /// Slot of the transaction, the block number
+    pub slot: u64,
+}
+
+/// Program configuration, `program_address(["config"])`, created by the first `UpdateConfig`
+#[derive(Debug,Clone)]
+pub struct Config {
+    /// Signer of the first update, manages the configuration and the operators
+    pub admin: Pubkey,
+    /// Only registered operators may submit Call-type instructions,
+    /// enforced with `operator-allow-list` feature
+    pub operators_only: bool,
+    /// Mint of the native currency: balances of foreign accounts are their token accounts
+    /// of this mint instead of lamports. Default key if lamports are the native currency.
+    pub native_mint: Pubkey,
+    /// Decimals of the native mint, amounts are scaled to 18 decimals
+    pub native_decimals: u8,
+    /// Chain id accepted in transactions (EIP-155)
+    pub chain_id: u64,
+    /// Transactions signed without chain id (pre-EIP-155) are accepted
+    pub allow_legacy_tx: bool,
+}
+
+/// Registered operator, `program_address(["operator", key])`
+#[derive(Debug,Clone)]
+pub struct Operator {
+    pub key: Pubkey,
+}
+
 #[derive(Debug,Clone)]
 pub enum AccountData {
     Account(Account),
     Contract(Contract),
     Storage(Storage),
+    Alias(Alias),
+    GasPrice(GasPrice),
+    Receipt(Receipt),
+    Config(Config),
+    Operator(Operator),
     Empty
 }
 
@@ -43,6 +124,11 @@ impl AccountData {
     const ACCOUNT_TAG: u8 = 1;
     const CONTRACT_TAG: u8 = 2;
     const STORAGE_TAG: u8 = 3;
+    const ALIAS_TAG: u8 = 4;
+    const GAS_PRICE_TAG: u8 = 5;
+    const RECEIPT_TAG: u8 = 6;
+    const CONFIG_TAG: u8 = 7;
+    const OPERATOR_TAG: u8 = 8;
 
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         let (&tag, rest) = input.split_first().ok_or(ProgramError::InvalidAccountData)?;
@@ -51,6 +137,11 @@ impl AccountData {
             AccountData::ACCOUNT_TAG => AccountData::Account( Account::unpack(rest) ),
             AccountData::CONTRACT_TAG => AccountData::Contract( Contract::unpack(rest) ),
             AccountData::STORAGE_TAG => AccountData::Storage( Storage::unpack(rest) ),
+            AccountData::ALIAS_TAG => AccountData::Alias( Alias::unpack(rest) ),
+            AccountData::GAS_PRICE_TAG => AccountData::GasPrice( GasPrice::unpack(rest) ),
+            AccountData::RECEIPT_TAG => AccountData::Receipt( Receipt::unpack(rest) ),
+            AccountData::CONFIG_TAG => AccountData::Config( Config::unpack(rest) ),
+            AccountData::OPERATOR_TAG => AccountData::Operator( Operator::unpack(rest) ),
 
             _ => return Err(ProgramError::InvalidAccountData),
         })
@@ -82,6 +173,36 @@ impl AccountData {
                 dst[0] = AccountData::STORAGE_TAG;
                 Storage::pack(acc, &mut dst[1..])
             },
+            AccountData::Alias(acc) => {
+                if dst[0] != AccountData::ALIAS_TAG && dst[0] != AccountData::EMPTY_TAG { return Err(ProgramError::InvalidAccountData); }
+                if dst.len() < self.size() { return Err(ProgramError::AccountDataTooSmall); }
+                dst[0] = AccountData::ALIAS_TAG;
+                Alias::pack(acc, &mut dst[1..])
+            },
+            AccountData::GasPrice(acc) => {
+                if dst[0] != AccountData::GAS_PRICE_TAG && dst[0] != AccountData::EMPTY_TAG { return Err(ProgramError::InvalidAccountData); }
+                if dst.len() < self.size() { return Err(ProgramError::AccountDataTooSmall); }
+                dst[0] = AccountData::GAS_PRICE_TAG;
+                GasPrice::pack(acc, &mut dst[1..])
+            },
+            AccountData::Receipt(acc) => {
+                if dst[0] != AccountData::RECEIPT_TAG && dst[0] != AccountData::EMPTY_TAG { return Err(ProgramError::InvalidAccountData); }
+                if dst.len() < self.size() { return Err(ProgramError::AccountDataTooSmall); }
+                dst[0] = AccountData::RECEIPT_TAG;
+                Receipt::pack(acc, &mut dst[1..])
+            },
+            AccountData::Config(acc) => {
+                if dst[0] != AccountData::CONFIG_TAG && dst[0] != AccountData::EMPTY_TAG { return Err(ProgramError::InvalidAccountData); }
+                if dst.len() < self.size() { return Err(ProgramError::AccountDataTooSmall); }
+                dst[0] = AccountData::CONFIG_TAG;
+                Config::pack(acc, &mut dst[1..])
+            },
+            AccountData::Operator(acc) => {
+                if dst[0] != AccountData::OPERATOR_TAG && dst[0] != AccountData::EMPTY_TAG { return Err(ProgramError::InvalidAccountData); }
+                if dst.len() < self.size() { return Err(ProgramError::AccountDataTooSmall); }
+                dst[0] = AccountData::OPERATOR_TAG;
+                Operator::pack(acc, &mut dst[1..])
+            },
 
             _ => return Err(ProgramError::InvalidAccountData),
         })
@@ -92,6 +213,11 @@ impl AccountData {
             AccountData::Account(acc) => acc.size() + 1,
             AccountData::Contract(acc) => acc.size() + 1,
             AccountData::Storage(acc) => acc.size() + 1,
+            AccountData::Alias(acc) => acc.size() + 1,
+            AccountData::GasPrice(acc) => acc.size() + 1,
+            AccountData::Receipt(acc) => acc.size() + 1,
+            AccountData::Config(acc) => acc.size() + 1,
+            AccountData::Operator(acc) => acc.size() + 1,
             _ => return 1,
         }
     }
@@ -137,6 +263,55 @@ impl AccountData {
             _ => return Err(ProgramError::InvalidAccountData),
         }
     }
+
+    pub fn get_alias(&self) -> Result<&Alias, ProgramError>  {
+        match self {
+            AccountData::Alias(ref acc) => Ok(acc),
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    pub fn get_gas_price(&self) -> Result<&GasPrice, ProgramError>  {
+        match self {
+            AccountData::GasPrice(ref acc) => Ok(acc),
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    pub fn get_mut_gas_price(&mut self) -> Result<&mut GasPrice, ProgramError>  {
+        match self {
+            AccountData::GasPrice(ref mut acc) => Ok(acc),
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    pub fn get_receipt(&self) -> Result<&Receipt, ProgramError>  {
+        match self {
+            AccountData::Receipt(ref acc) => Ok(acc),
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    pub fn get_config(&self) -> Result<&Config, ProgramError>  {
+        match self {
+            AccountData::Config(ref acc) => Ok(acc),
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    pub fn get_mut_config(&mut self) -> Result<&mut Config, ProgramError>  {
+        match self {
+            AccountData::Config(ref mut acc) => Ok(acc),
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    pub fn get_operator(&self) -> Result<&Operator, ProgramError>  {
+        match self {
+            AccountData::Operator(ref acc) => Ok(acc),
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+    }
 }
 
 impl Account {
@@ -181,42 +356,63 @@ impl Account {
 }
 
 impl Contract {
-    pub const SIZE: usize = 32+4;
+    pub const SIZE: usize = 32+4+32+20+1+1+4;
 
     pub fn unpack(input: &[u8]) -> Self {
         let data = array_ref![input, 0, Contract::SIZE];
-        let (owner, code_size) = array_refs![data, 32, 4];
+        let (owner, code_size, storage_root, deployer, immutable, hashed_keys, uploaded_len) = array_refs![data, 32, 4, 32, 20, 1, 1, 4];
 
         Contract {
             owner: Pubkey::new_from_array(*owner),
             code_size: u32::from_le_bytes(*code_size),
+            storage_root: H256::from_slice(&*storage_root),
+            deployer: H160::from_slice(&*deployer),
+            immutable: immutable[0] != 0,
+            hashed_keys: hashed_keys[0] != 0,
+            uploaded_len: u32::from_le_bytes(*uploaded_len),
         }
     }
 
     pub fn pack(acc: &Contract, dst: &mut [u8]) -> usize {
         let data = array_mut_ref![dst, 0, Contract::SIZE];
-        let (owner_dst, code_size_dst) = 
-                mut_array_refs![data, 32, 4];
+        let (owner_dst, code_size_dst, storage_root_dst, deployer_dst, immutable_dst, hashed_keys_dst, uploaded_len_dst) = 
+                mut_array_refs![data, 32, 4, 32, 20, 1, 1, 4];
         owner_dst.copy_from_slice(acc.owner.as_ref());
         *code_size_dst = acc.code_size.to_le_bytes();
+        storage_root_dst.copy_from_slice(acc.storage_root.as_bytes());
+        deployer_dst.copy_from_slice(acc.deployer.as_bytes());
+        immutable_dst[0] = acc.immutable as u8;
+        hashed_keys_dst[0] = acc.hashed_keys as u8;
+        *uploaded_len_dst = acc.uploaded_len.to_le_bytes();
         Contract::SIZE
     }
 
     pub fn size(&self) -> usize {
         Contract::SIZE
     }
+
+    /// Key of the storage index in the HAMT
+    pub fn storage_key(&self, index: U256) -> U256 {
+        if self.hashed_keys {
+            U256::from_big_endian(keccak256_h256(&u256_to_h256(index).0).as_bytes())
+        } else {
+            index
+        }
+    }
 }
 
 impl Storage {
-    const SIZE: usize = 20+8+8+8+8;
+    const SIZE: usize = 20+8+32+32+8+8+8;
 
     pub fn unpack(src: &[u8]) -> Self {
         let data = array_ref![src, 0, Storage::SIZE];
-        let (caller, nonce, accounts_len, executor_data_size, evm_data_size) = array_refs![data, 20, 8, 8, 8, 8];
+        let (caller, nonce, tx_hash, operator, accounts_len, executor_data_size, evm_data_size) = array_refs![data, 20, 8, 32, 32, 8, 8, 8];
         
         Self {
             caller: H160::from(*caller),
             nonce: u64::from_le_bytes(*nonce),
+            tx_hash: H256::from(*tx_hash),
+            operator: Pubkey::new_from_array(*operator),
             accounts_len: usize::from_le_bytes(*accounts_len),
             executor_data_size: usize::from_le_bytes(*executor_data_size),
             evm_data_size: usize::from_le_bytes(*evm_data_size),
@@ -225,9 +421,11 @@ impl Storage {
 
     pub fn pack(&self, dst: &mut [u8]) -> usize {
         let data = array_mut_ref![dst, 0, Storage::SIZE];
-        let (caller, nonce, accounts_len, executor_data_size, evm_data_size) = mut_array_refs![data, 20, 8, 8, 8, 8];
+        let (caller, nonce, tx_hash, operator, accounts_len, executor_data_size, evm_data_size) = mut_array_refs![data, 20, 8, 32, 32, 8, 8, 8];
         *caller = self.caller.to_fixed_bytes();
         *nonce = self.nonce.to_le_bytes();
+        *tx_hash = self.tx_hash.to_fixed_bytes();
+        operator.copy_from_slice(self.operator.as_ref());
         *accounts_len = self.accounts_len.to_le_bytes();
         *executor_data_size = self.executor_data_size.to_le_bytes();
         *evm_data_size = self.evm_data_size.to_le_bytes();
@@ -239,3 +437,159 @@ impl Storage {
         Storage::SIZE
     }
 }
+
+impl Alias {
+    pub const SIZE: usize = 20+32;
+
+    pub fn unpack(src: &[u8]) -> Self {
+        let data = array_ref![src, 0, Alias::SIZE];
+        let (ether, account) = array_refs![data, 20, 32];
+
+        Self {
+            ether: H160::from(*ether),
+            account: Pubkey::new_from_array(*account),
+        }
+    }
+
+    pub fn pack(&self, dst: &mut [u8]) -> usize {
+        let data = array_mut_ref![dst, 0, Alias::SIZE];
+        let (ether, account) = mut_array_refs![data, 20, 32];
+        *ether = self.ether.to_fixed_bytes();
+        account.copy_from_slice(self.account.as_ref());
+
+        Alias::SIZE
+    }
+
+    pub fn size(&self) -> usize {
+        Alias::SIZE
+    }
+}
+
+impl GasPrice {
+    pub const SIZE: usize = 32+8+8+20;
+
+    pub fn unpack(src: &[u8]) -> Self {
+        let data = array_ref![src, 0, GasPrice::SIZE];
+        let (operator, lamports_per_gas, slot, coinbase) = array_refs![data, 32, 8, 8, 20];
+
+        Self {
+            operator: Pubkey::new_from_array(*operator),
+            lamports_per_gas: u64::from_le_bytes(*lamports_per_gas),
+            slot: u64::from_le_bytes(*slot),
+            coinbase: H160::from_slice(&*coinbase),
+        }
+    }
+
+    pub fn pack(&self, dst: &mut [u8]) -> usize {
+        let data = array_mut_ref![dst, 0, GasPrice::SIZE];
+        let (operator, lamports_per_gas, slot, coinbase) = mut_array_refs![data, 32, 8, 8, 20];
+        operator.copy_from_slice(self.operator.as_ref());
+        *lamports_per_gas = self.lamports_per_gas.to_le_bytes();
+        *slot = self.slot.to_le_bytes();
+        coinbase.copy_from_slice(self.coinbase.as_bytes());
+
+        GasPrice::SIZE
+    }
+
+    pub fn size(&self) -> usize {
+        GasPrice::SIZE
+    }
+}
+
+impl Receipt {
+    pub const SIZE: usize = 32+1+8+8+256+8;
+
+    pub fn unpack(src: &[u8]) -> Self {
+        let data = array_ref![src, 0, Receipt::SIZE];
+        let (tx_hash, status, gas_used, logs_count, logs_bloom, slot) = array_refs![data, 32, 1, 8, 8, 256, 8];
+
+        Self {
+            tx_hash: H256::from(*tx_hash),
+            status: status[0],
+            gas_used: u64::from_le_bytes(*gas_used),
+            logs_count: u64::from_le_bytes(*logs_count),
+            logs_bloom: *logs_bloom,
+            slot: u64::from_le_bytes(*slot),
+        }
+    }
+
+    pub fn pack(&self, dst: &mut [u8]) -> usize {
+        let data = array_mut_ref![dst, 0, Receipt::SIZE];
+        let (tx_hash, status, gas_used, logs_count, logs_bloom, slot) = mut_array_refs![data, 32, 1, 8, 8, 256, 8];
+        *tx_hash = self.tx_hash.to_fixed_bytes();
+        status[0] = self.status;
+        *gas_used = self.gas_used.to_le_bytes();
+        *logs_count = self.logs_count.to_le_bytes();
+        *logs_bloom = self.logs_bloom;
+        *slot = self.slot.to_le_bytes();
+
+        Receipt::SIZE
+    }
+
+    pub fn size(&self) -> usize {
+        Receipt::SIZE
+    }
+}
+
+impl Config {
+    pub const SIZE: usize = 32+1+32+1+8+1;
+
+    pub fn unpack(src: &[u8]) -> Self {
+        let data = array_ref![src, 0, Config::SIZE];
+        let (admin, operators_only, native_mint, native_decimals, chain_id, allow_legacy_tx) = array_refs![data, 32, 1, 32, 1, 8, 1];
+
+        Self {
+            admin: Pubkey::new_from_array(*admin),
+            operators_only: operators_only[0] != 0,
+            native_mint: Pubkey::new_from_array(*native_mint),
+            native_decimals: native_decimals[0],
+            chain_id: u64::from_le_bytes(*chain_id),
+            allow_legacy_tx: allow_legacy_tx[0] != 0,
+        }
+    }
+
+    pub fn pack(&self, dst: &mut [u8]) -> usize {
+        let data = array_mut_ref![dst, 0, Config::SIZE];
+        let (admin, operators_only, native_mint, native_decimals, chain_id, allow_legacy_tx) = mut_array_refs![data, 32, 1, 32, 1, 8, 1];
+        admin.copy_from_slice(self.admin.as_ref());
+        operators_only[0] = self.operators_only as u8;
+        native_mint.copy_from_slice(self.native_mint.as_ref());
+        native_decimals[0] = self.native_decimals;
+        *chain_id = self.chain_id.to_le_bytes();
+        allow_legacy_tx[0] = self.allow_legacy_tx as u8;
+
+        Config::SIZE
+    }
+
+    /// Mint and decimals of the native currency, `None` if it is lamports
+    pub fn native_mint(&self) -> Option<(Pubkey, u8)> {
+        if self.native_mint == Pubkey::default() {
+            return None;
+        }
+        Some((self.native_mint, self.native_decimals))
+    }
+
+    pub fn size(&self) -> usize {
+        Config::SIZE
+    }
+}
+
+impl Operator {
+    pub const SIZE: usize = 32;
+
+    pub fn unpack(src: &[u8]) -> Self {
+        let key = array_ref![src, 0, Operator::SIZE];
+        Self { key: Pubkey::new_from_array(*key) }
+    }
+
+    pub fn pack(&self, dst: &mut [u8]) -> usize {
+        let key = array_mut_ref![dst, 0, Operator::SIZE];
+        key.copy_from_slice(self.key.as_ref());
+
+        Operator::SIZE
+    }
+
+    pub fn size(&self) -> usize {
+        Operator::SIZE
+    }
+}
@@ -1,28 +1,121 @@
 use crate::{
-    account_data::AccountData,
-    solana_backend::{AccountStorage, SolanaBackend},
+    account_data::{AccountData, Account},
+    solana_backend::{AccountStorage, SolanaBackend, CHAIN_ID},
     solidity_account::SolidityAccount,
-    utils::keccak256_h256,
+    utils::{keccak256_h256, ether_to_program_address, gas_price_address, config_address},
+    error::EvmLoaderError,
+    erc20_wrapper,
+    block,
+    executor::validate_code,
+    gas::GasSettlement,
+    executor_state::TransferLedger,
+    processor::Cpi,
 };
 use evm::backend::Apply;
 use primitive_types::{H160, H256, U256};
 use solana_program::{
     account_info::{AccountInfo, next_account_info},
+    msg,
     pubkey::Pubkey,
     program_error::ProgramError,
-    sysvar::{clock, clock::Clock, Sysvar},
+    system_instruction, system_program,
+    sysvar::{clock, clock::Clock, rent::Rent, slot_hashes, Sysvar},
 };
 use std::{
-    cell::RefCell,
+    cell::{Ref, RefCell},
+    collections::HashMap,
+    convert::TryInto,
 };
 
+/// Program account passed to the instruction, `SolidityAccount` is parsed on the first access.
+/// Accounts which the execution doesn't touch cost only the read of the Ether address.
+struct LazyAccount<'a> {
+    info: &'a AccountInfo<'a>,
+    code_info: Option<&'a AccountInfo<'a>>,
+    account: RefCell<Option<SolidityAccount<'a>>>,
+}
+
+impl<'a> LazyAccount<'a> {
+    fn new(info: &'a AccountInfo<'a>, code_info: Option<&'a AccountInfo<'a>>) -> Self {
+        Self { info, code_info, account: RefCell::new(None) }
+    }
+
+    fn foreign(info: &'a AccountInfo<'a>, balance: U256) -> Self {
+        let account = SolidityAccount::foreign(info.key, balance);
+        Self { info, code_info: None, account: RefCell::new(Some(account)) }
+    }
+
+    /// Foreign accounts are constructed parsed, the data of other accounts is not read
+    fn is_foreign(&self) -> bool {
+        self.account.borrow().as_ref().map_or(false, SolidityAccount::is_foreign)
+    }
+
+    fn parse(&self, program_id: &Pubkey) -> Result<SolidityAccount<'a>, ProgramError> {
+        evm_trace!("Parse account {}", self.info.key);
+        let account_data = AccountData::unpack(&self.info.data.borrow())?;
+
+        let code_data = match self.code_info {
+            Some(code_info) => {
+                let code_data = code_info.data.clone();
+                let code_acc = AccountData::unpack(&code_data.borrow())?;
+                code_acc.get_contract()?;
+                Some((code_acc, code_data))
+            },
+            None => None,
+        };
+
+        SolidityAccount::new(program_id, self.info.key, self.info.lamports(), account_data, code_data)
+    }
+
+    /// Parse the account if it is not parsed yet.
+    /// The account was checked to be an Ether account owned by the program, failures mean corrupted data.
+    fn get(&self, program_id: &Pubkey) -> Result<Ref<SolidityAccount<'a>>, ProgramError> {
+        if self.account.borrow().is_none() {
+            let account = self.parse(program_id)?;
+            *self.account.borrow_mut() = Some(account);
+        }
+
+        Ok(Ref::map(self.account.borrow(), |account| account.as_ref().unwrap()))
+    }
+
+    fn get_mut(&mut self, program_id: &Pubkey) -> Result<&mut SolidityAccount<'a>, ProgramError> {
+        if self.account.get_mut().is_none() {
+            let account = self.parse(program_id)?;
+            *self.account.get_mut() = Some(account);
+        }
+
+        Ok(self.account.get_mut().as_mut().unwrap())
+    }
+}
+
 pub struct ProgramAccountStorage<'a> {
-    accounts: Vec<SolidityAccount<'a>>,
-    aliases: RefCell<Vec<(H160, usize)>>,
+    accounts: Vec<LazyAccount<'a>>,
+    aliases: RefCell<HashMap<H160, usize>>,
     clock_account: &'a AccountInfo<'a>,
-    account_metas: Vec<&'a AccountInfo<'a>>,
+    slot_hashes_account: Option<&'a AccountInfo<'a>>,
     contract_id: H160,
     caller_id: H160,
+    program_id: Pubkey,
+    payer: Option<&'a AccountInfo<'a>>,
+    system_program: Option<&'a AccountInfo<'a>>,
+    missing_accounts: Vec<&'a AccountInfo<'a>>,
+    /// Failure to parse an account accessed by the execution, reported by `check_touched_accounts`
+    parse_error: RefCell<Option<ProgramError>>,
+    paid_rent: u64,
+    /// The payer pays the rent and the gas, they aren't charged from the caller
+    sponsored: bool,
+    lamports_per_gas: Option<u64>,
+    coinbase: H160,
+    chain_id: u64,
+    allow_legacy_tx: bool,
+}
+
+/// Ether account header of the program owned account, `None` for other account data
+fn read_ether_account(account_info: &AccountInfo) -> Result<Option<Account>, ProgramError> {
+    match AccountData::unpack(&account_info.data.borrow())? {
+        AccountData::Account(account) => Ok(Some(account)),
+        _ => Ok(None),
+    }
 }
 
 impl<'a> ProgramAccountStorage<'a> {
@@ -34,63 +127,89 @@ impl<'a> ProgramAccountStorage<'a> {
     /// 1. contract code info
     /// 2. caller or caller account info(for ether account)
     /// 3. ... other accounts
+    ///
+    /// Uninitialized writable accounts from the list can be created in `apply`,
+    /// if the system program is passed. They are funded by the payer:
+    /// the last writable signer owned by the system program, or set with `set_payer`.
+    ///
+    /// An account passed twice is read once. Different accounts with the same Ether address
+    /// are rejected with `AccountAliasConflict`, only program owned accounts resolve Ether addresses.
+    /// Only the contract and the caller are parsed here, other accounts are parsed on the first access.
+    /// The caller which is not an Ether account is a foreign account: only its balance is visible to the contracts.
+    /// If the config account sets the native mint, token accounts of the mint are foreign accounts of their owners,
+    /// with the token amount scaled to 18 decimals as the balance.
+    /// The config account also sets the chain id and whether legacy transactions are accepted,
+    /// `CHAIN_ID` without legacy transactions applies if it is not passed.
+    ///
+    /// The gas price oracle can be passed among other accounts to charge the used gas,
+    /// `SlotHashes` sysvar is required for `blockhash` and `prevrandao`.
     pub fn new(program_id: &Pubkey, account_infos: &'a [AccountInfo<'a>]) -> Result<Self, ProgramError> {
-        debug_print!("account_storage::new");
+        evm_debug!("account_storage::new");
 
         let account_info_iter = &mut account_infos.iter();
 
-        let mut accounts = Vec::with_capacity(account_infos.len());
-        let mut aliases = Vec::with_capacity(account_infos.len());
-        let mut account_metas = Vec::with_capacity(account_infos.len());
+        let mut accounts: Vec<LazyAccount<'a>> = Vec::with_capacity(account_infos.len());
+        let mut aliases = HashMap::with_capacity(account_infos.len());
 
         let mut clock_account = None;
+        let mut slot_hashes_account = None;
+        let mut system_program_account = None;
+        let mut payer = None;
+        let mut missing_accounts = Vec::new();
+        let mut lamports_per_gas = None;
+        let mut coinbase = H160::default();
+        let mut native_mint = None;
+        let mut chain_id = CHAIN_ID;
+        let mut allow_legacy_tx = false;
+        let mut token_accounts = Vec::new();
+
+        let mut push_account = |ether: H160, account: LazyAccount<'a>| -> Result<(), ProgramError> {
+            if let Some(&pos) = aliases.get(&ether) {
+                let other: &LazyAccount = &accounts[pos];
+                if other.info.key == account.info.key {
+                    evm_debug!("Account {} is passed twice", account.info.key);
+                    return Ok(());
+                }
+                debug_print!("Accounts {} and {} have the same address {}", other.info.key, account.info.key, &ether.to_string());
+                return Err(EvmLoaderError::AccountAliasConflict.into());
+            }
 
-        let mut push_account = |sol_account: SolidityAccount<'a>, account_info: &'a AccountInfo<'a>| {
-            aliases.push((sol_account.get_ether(), accounts.len()));
-            accounts.push(sol_account);
-            account_metas.push(account_info);
+            aliases.insert(ether, accounts.len());
+            accounts.push(account);
+            Ok(())
         };
 
-        let construct_contract_account = |account_info: &'a AccountInfo<'a>, code_info: &'a AccountInfo<'a>,| -> Result<SolidityAccount<'a>, ProgramError>
-        {
-            let account_data = AccountData::unpack(&account_info.data.borrow())?;
-            let account = account_data.get_account()?;
-    
+        let contract_account = |account_info: &'a AccountInfo<'a>, account: &Account, code_info: &'a AccountInfo<'a>| -> Result<LazyAccount<'a>, ProgramError> {
             if *code_info.key != account.code_account {
                 return Err(ProgramError::InvalidAccountData)
             }
-    
-            let code_data = code_info.data.clone();
-            let code_acc = AccountData::unpack(&code_data.borrow())?;
-            code_acc.get_contract()?;
-    
-            Ok( SolidityAccount::new(account_info.key, (*account_info.lamports.borrow()).clone(), account_data, Some((code_acc, code_data)))? )
+
+            Ok(LazyAccount::new(account_info, Some(code_info)))
         };
 
         let contract_id = {
             let program_info = next_account_info(account_info_iter)?;
             let program_code = next_account_info(account_info_iter)?;
 
-            let contract_acc = construct_contract_account(program_info, program_code)?;
-            let contract_id = contract_acc.get_ether();
-            push_account(contract_acc, program_info);
+            let account = read_ether_account(program_info)?.ok_or(ProgramError::InvalidAccountData)?;
+            let mut contract_acc = contract_account(program_info, &account, program_code)?;
+            contract_acc.get_mut(program_id)?;
+            push_account(account.ether, contract_acc)?;
 
-            contract_id
+            account.ether
         };
 
         let caller_id = {
             let caller_info = next_account_info(account_info_iter)?;
 
             let caller_id: H160 = if caller_info.owner == program_id {
-                let account_data = AccountData::unpack(&caller_info.data.borrow())?;
-                account_data.get_account()?;
-
-                let caller_acc = SolidityAccount::new(caller_info.key, (*caller_info.lamports.borrow()).clone(), account_data, None)?;
+                let account = read_ether_account(caller_info)?.ok_or(ProgramError::InvalidAccountData)?;
 
-                let caller_id = caller_acc.get_ether();
-                push_account(caller_acc, caller_info);
+                let mut caller_acc = LazyAccount::new(caller_info, None);
+                caller_acc.get_mut(program_id)?;
+                push_account(account.ether, caller_acc)?;
 
-                caller_id
+                account.ether
             } else {
                 if !caller_info.is_signer {
                     debug_print!("Caller mast be signer");
@@ -99,7 +218,10 @@ impl<'a> ProgramAccountStorage<'a> {
                     return Err(ProgramError::InvalidArgument);
                 }
 
-                keccak256_h256(&caller_info.key.to_bytes()).into()
+                let caller_id = keccak256_h256(&caller_info.key.to_bytes()).into();
+                push_account(caller_id, LazyAccount::foreign(caller_info, U256::from(caller_info.lamports())))?;
+
+                caller_id
             };
 
             caller_id
@@ -109,25 +231,50 @@ impl<'a> ProgramAccountStorage<'a> {
             if account_info.owner == program_id {
                 let account_data = AccountData::unpack(&account_info.data.borrow())?;
                 let account = match account_data {
-                    AccountData::Account(ref acc) => acc,
+                    AccountData::Account(acc) => acc,
+                    AccountData::GasPrice(ref gas_price) if *account_info.key == gas_price_address(program_id).0 => {
+                        evm_debug!("Gas price account {}", account_info.key);
+                        lamports_per_gas = Some(gas_price.lamports_per_gas);
+                        coinbase = gas_price.coinbase;
+                        continue;
+                    },
+                    AccountData::Config(ref config) if *account_info.key == config_address(program_id).0 => {
+                        native_mint = config.native_mint();
+                        chain_id = config.chain_id;
+                        allow_legacy_tx = config.allow_legacy_tx;
+                        continue;
+                    },
                     _ => { continue; },
                 };
 
-                let sol_account = if account.code_account == Pubkey::new_from_array([0u8; 32]) {
-                    debug_print!("Common account");
+                let lazy_account = if account.code_account == Pubkey::new_from_array([0u8; 32]) {
+                    evm_debug!("Common account");
 
-                    SolidityAccount::new(account_info.key, (*account_info.lamports.borrow()).clone(), account_data, None)?
+                    LazyAccount::new(account_info, None)
                 } else {
-                    debug_print!("Contract account");
+                    evm_debug!("Contract account");
                     let code_info = next_account_info(account_info_iter)?;
 
-                    construct_contract_account(account_info, code_info)?
+                    contract_account(account_info, &account, code_info)?
                 };
 
-                push_account(sol_account, account_info);
+                push_account(account.ether, lazy_account)?;
+            } else if erc20_wrapper::spl_token::check_id(account_info.owner) {
+                token_accounts.push(account_info);
             } else if clock::check_id(account_info.key) {
-                debug_print!("Clock account {}", account_info.key);
+                evm_debug!("Clock account {}", account_info.key);
                 clock_account = Some(account_info);
+            } else if slot_hashes::check_id(account_info.key) {
+                evm_debug!("SlotHashes account {}", account_info.key);
+                slot_hashes_account = Some(account_info);
+            } else if system_program::check_id(account_info.key) {
+                system_program_account = Some(account_info);
+            } else if system_program::check_id(account_info.owner) && account_info.is_writable && account_info.is_signer {
+                evm_debug!("Payer account {}", account_info.key);
+                payer = Some(account_info);
+            } else if system_program::check_id(account_info.owner) && account_info.data_is_empty() && account_info.is_writable {
+                evm_debug!("Missing account {}", account_info.key);
+                missing_accounts.push(account_info);
             }
         }
 
@@ -135,84 +282,394 @@ impl<'a> ProgramAccountStorage<'a> {
             return Err(ProgramError::NotEnoughAccountKeys);
         }
 
-        debug_print!("Accounts was read");
-        aliases.sort_by_key(|v| v.0);
+        // Token accounts of the native mint are the foreign accounts of their owners,
+        // they replace the lamports of the owner passed as the caller
+        if let Some((mint, decimals)) = native_mint {
+            for token_info in token_accounts {
+                let (owner, amount) = {
+                    let data = token_info.data.borrow();
+                    match (erc20_wrapper::token_account_owner(&data, &mint), erc20_wrapper::token_account_amount(&data, &mint)) {
+                        (Some(owner), Some(amount)) => (owner, amount),
+                        _ => continue,
+                    }
+                };
+                let balance = erc20_wrapper::native_balance(amount, decimals).ok_or(ProgramError::InvalidAccountData)?;
+                let ether: H160 = keccak256_h256(&owner.to_bytes()).into();
+                evm_debug!("Native token account {} of {}", token_info.key, &ether.to_string());
+
+                match aliases.get(&ether) {
+                    Some(&pos) if accounts[pos].is_foreign() && *accounts[pos].info.key == owner => {
+                        accounts[pos] = LazyAccount::foreign(token_info, balance);
+                    },
+                    Some(&pos) if accounts[pos].info.key == token_info.key => {},
+                    Some(_) => {
+                        debug_print!("Token account {} conflicts with the account of {}", token_info.key, &ether.to_string());
+                        return Err(EvmLoaderError::AccountAliasConflict.into());
+                    },
+                    None => {
+                        aliases.insert(ether, accounts.len());
+                        accounts.push(LazyAccount::foreign(token_info, balance));
+                    },
+                }
+            }
+        }
+
+        evm_debug!("Accounts was read");
 
         Ok(Self {
             accounts: accounts,
             aliases: RefCell::new(aliases),
             clock_account: clock_account.unwrap(),
-            account_metas: account_metas,
+            slot_hashes_account,
             contract_id: contract_id,
             caller_id: caller_id,
+            program_id: *program_id,
+            payer,
+            system_program: system_program_account,
+            missing_accounts,
+            parse_error: RefCell::new(None),
+            paid_rent: 0,
+            sponsored: false,
+            lamports_per_gas,
+            coinbase,
+            chain_id,
+            allow_legacy_tx,
         })
     }
 
-    pub fn get_contract_account(&self) -> Option<&SolidityAccount<'a>> {
+    /// Transactions signed without chain id are accepted, as the config account sets
+    pub fn allow_legacy_tx(&self) -> bool {
+        self.allow_legacy_tx
+    }
+
+    /// Set account which funds accounts created in `apply`, if it was not passed explicitly
+    /// Payer for the accounts created by the program
+    pub fn payer(&self) -> Option<&'a AccountInfo<'a>> {
+        self.payer
+    }
+
+    pub fn set_payer(&mut self, payer: &'a AccountInfo<'a>) {
+        if self.payer.is_none() {
+            self.payer = Some(payer);
+        }
+    }
+
+    /// Charge rent paid for created accounts back from the caller Ether account to the payer
+    fn charge_paid_rent(&mut self) -> Result<(), ProgramError> {
+        if self.paid_rent == 0 {
+            return Ok(());
+        }
+
+        debug_print!("Charge {} lamports of rent", &self.paid_rent);
+        self.charge_caller(self.paid_rent)?;
+        self.paid_rent = 0;
+
+        Ok(())
+    }
+
+    /// Check that the caller Ether account can prepay the gas limit at the price of the gas price oracle.
+    /// Limits overflowing the lamports are not checked, as for the callers which are not Ether accounts.
+    pub fn check_gas_prepaid(&self, gas_limit: u64) -> Result<(), ProgramError> {
+        let settlement = GasSettlement { gas_limit, ..GasSettlement::default() };
+        let prepaid = match self.lamports_per_gas.and_then(|lamports_per_gas| settlement.prepaid_lamports(lamports_per_gas)) {
+            Some(prepaid) => prepaid,
+            None => return Ok(()),
+        };
+
+        if let Some(pos) = self.find_ether_account(&self.caller_id) {
+            if self.accounts[pos].info.lamports() < prepaid {
+                debug_print!("Caller can't prepay {} gas: {} lamports required", gas_limit, prepaid);
+                return Err(ProgramError::InsufficientFunds);
+            }
+        }
+        Ok(())
+    }
+
+    /// Settle the gas at the price of the gas price oracle: the used gas less the refund is moved
+    /// from the caller Ether account to the payer, the unused part of the prepaid gas stays with the caller.
+    /// Nothing is charged when the oracle is not passed.
+    pub fn charge_gas(&mut self, settlement: &GasSettlement) -> Result<(), ProgramError> {
+        let lamports_per_gas = match self.lamports_per_gas {
+            Some(lamports_per_gas) => lamports_per_gas,
+            None => return Ok(()),
+        };
+
+        let fee = settlement.charged_lamports(lamports_per_gas).ok_or(ProgramError::InsufficientFunds)?;
+        if fee == 0 {
+            return Ok(());
+        }
+
+        debug_print!("Charge {} lamports for {} gas, {} refunded, {} unused", &fee, settlement.gas_used, settlement.refunded, settlement.unused());
+        self.charge_caller(fee)
+    }
+
+    /// The payer pays the rent and the gas of the call instead of the caller, see `CallFromMetaTransaction`
+    pub fn sponsor(&mut self) {
+        self.sponsored = true;
+    }
+
+    fn charge_caller(&self, lamports: u64) -> Result<(), ProgramError> {
+        if self.sponsored {
+            debug_print!("Sponsored call, {} lamports are paid by the payer", &lamports);
+            return Ok(());
+        }
+
+        self.move_to_payer(lamports)
+    }
+
+    /// Move the fee signed by the caller of the sponsored call to the payer
+    pub fn reimburse_payer(&self, fee: u64) -> Result<(), ProgramError> {
+        if fee == 0 {
+            return Ok(());
+        }
+
+        self.move_to_payer(fee)
+    }
+
+    fn move_to_payer(&self, lamports: u64) -> Result<(), ProgramError> {
+        let payer = self.payer.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let caller_info = match self.find_ether_account(&self.caller_id) {
+            Some(pos) => self.accounts[pos].info,
+            None => {
+                debug_print!("Caller is solana user, lamports are not charged");
+                return Ok(());
+            }
+        };
+
+        if caller_info.key == payer.key {
+            return Ok(());
+        }
+
+        debug_print!("Move {} lamports from {} to {}", &lamports, caller_info.key, payer.key);
+        let caller_lamports = caller_info.lamports().checked_sub(lamports).ok_or(ProgramError::InsufficientFunds)?;
+        **caller_info.lamports.borrow_mut() = caller_lamports;
+        **payer.lamports.borrow_mut() += lamports;
+
+        Ok(())
+    }
+
+    pub fn get_contract_account(&self) -> Result<Option<Ref<SolidityAccount<'a>>>, ProgramError> {
         self.get_account(&self.contract_id)
     }
 
-    pub fn get_caller_account(&self) -> Option<&SolidityAccount<'a>> {
-        self.get_account(&self.caller_id)
+    /// Ether account of the caller, `None` if the caller is a Solana user
+    pub fn get_caller_account(&self) -> Result<Option<Ref<SolidityAccount<'a>>>, ProgramError> {
+        match self.find_ether_account(&self.caller_id) {
+            Some(pos) => self.accounts[pos].get(&self.program_id).map(Some),
+            None => Ok(None),
+        }
     }
 
     fn find_account(&self, address: &H160) -> Option<usize> {
-        let aliases = self.aliases.borrow();
-        match aliases.binary_search_by_key(&address, |v| &v.0) {
-            Ok(pos) => {
-                debug_print!("Found account for {} on position {}", &address.to_string(), &pos.to_string());
-                Some(aliases[pos].1)
+        match self.aliases.borrow().get(address) {
+            Some(&pos) => {
+                evm_trace!("Found account for {} on position {}", &address.to_string(), &pos.to_string());
+                Some(pos)
             }
-            Err(_) => {
-                debug_print!("Not found account for {}", &address.to_string());
+            None => {
+                evm_trace!("Not found account for {}", &address.to_string());
                 None
             }
         }
     }
 
-    fn get_account(&self, address: &H160) -> Option<&SolidityAccount<'a>> {
-        if let Some(pos) = self.find_account(address) {
-            Some(&self.accounts[pos])
-        } else {
-            None
+    fn find_ether_account(&self, address: &H160) -> Option<usize> {
+        self.find_account(address).filter(|&pos| !self.accounts[pos].is_foreign())
+    }
+
+    fn get_account(&self, address: &H160) -> Result<Option<Ref<SolidityAccount<'a>>>, ProgramError> {
+        match self.find_account(address) {
+            Some(pos) => self.accounts[pos].get(&self.program_id).map(Some),
+            None => Ok(None),
         }
     }
 
-    pub fn apply<A, I>(&mut self, values: A, delete_empty: bool) -> Result<(), ProgramError>
+    pub fn apply<A, I, C>(&mut self, cpi: &C, values: A) -> Result<(), ProgramError>
     where
         A: IntoIterator<Item = Apply<I>>,
         I: IntoIterator<Item = (U256, U256)>,
+        C: Cpi,
+    {
+        self.apply_values(cpi, values, true)?;
+
+        self.charge_paid_rent()
+    }
+
+    /// Apply state changes, then settle lamports with the net balance changes of the transfer ledger.
+    /// Balances of the applies are ignored, transfers are the only source of lamport changes.
+    pub fn apply_with_transfers<A, I, C>(&mut self, cpi: &C, values: A, transfers: &TransferLedger) -> Result<(), ProgramError>
+    where
+        A: IntoIterator<Item = Apply<I>>,
+        I: IntoIterator<Item = (U256, U256)>,
+        C: Cpi,
+    {
+        self.apply_values(cpi, values, false)?;
+        self.apply_transfers(transfers)?;
+
+        self.charge_paid_rent()
+    }
+
+    fn apply_values<A, I, C>(&mut self, cpi: &C, values: A, set_balance: bool) -> Result<(), ProgramError>
+    where
+        A: IntoIterator<Item = Apply<I>>,
+        I: IntoIterator<Item = (U256, U256)>,
+        C: Cpi,
     {
         let system_account = SolanaBackend::<ProgramAccountStorage>::system_account();
         let system_account_ecrecover = SolanaBackend::<ProgramAccountStorage>::system_account_ecrecover();
+        let erc20_wrapper_factory = erc20_wrapper::factory_address();
 
         for apply in values {
             match apply {
                 Apply::Modify {address, basic, code, storage, reset_storage} => {
-                    if (address == system_account) || (address == system_account_ecrecover) {
+                    if (address == system_account) || (address == system_account_ecrecover) || (address == erc20_wrapper_factory) {
                         continue;
                     }
-                    if let Some(pos) = self.find_account(&address) {
-                        let account = &mut self.accounts[pos];
-                        let account_info = &self.account_metas[pos];
-                        account.update(&account_info, address, basic.nonce, basic.balance.as_u64(), &code, storage, reset_storage)?;
+                    // The limits are checked here as well, rust-evm doesn't check 0xEF prefix
+                    if let Some(ref code) = code {
+                        if let Err(err) = validate_code(code) {
+                            debug_print!("Invalid code of {}: {:?}", &address, err);
+                            return Err(EvmLoaderError::InvalidCode.into());
+                        }
+                    }
+                    if let Some(pos) = self.find_ether_account(&address) {
+                        let account_info = self.accounts[pos].info;
+                        let account = self.accounts[pos].get_mut(&self.program_id)?;
+                        let lamports = if set_balance { Some(basic.balance.as_u64()) } else { None };
+                        account.update(&account_info, address, basic.nonce, lamports, &code, self.caller_id, storage, reset_storage)?;
                     }
-                    else if address == self.caller_id {
-                        debug_print!("This is solana user, because {:?} == {:?}.", address, self.caller_id);
+                    else if code.is_none() && !reset_storage && self.can_create_account(&address) {
+                        let (pos, rent) = self.create_account(cpi, &address)?;
+                        let account_info = self.accounts[pos].info;
+                        let account = self.accounts[pos].get_mut(&self.program_id)?;
+                        let lamports = if set_balance {
+                            Some(basic.balance.as_u64().checked_add(rent).ok_or(ProgramError::InvalidArgument)?)
+                        } else {
+                            None
+                        };
+                        account.update(&account_info, address, basic.nonce, lamports, &code, self.caller_id, storage, reset_storage)?;
+                    }
+                    else if self.find_account(&address).is_some() {
+                        debug_print!("Foreign account {:?}, only the balance is settled.", address);
                     }
                     else {
                         debug_print!("Apply can't be done. Not found account for address = {:?}.", address);
-                        return Err(ProgramError::NotEnoughAccountKeys);
+                        return Err(self.missing_account(&address));
                     }
                 }
-                Apply::Delete { address: _ } => {}
+                Apply::Delete { address } => {
+                    // Self-destruct doesn't close the Solana accounts, the Ether account is kept as it is
+                    debug_print!("Delete of {:?} is not applied", address);
+                }
             }
         }
 
-        //for log in logs {};
+        Ok(())
+    }
+
+    fn apply_transfers(&mut self, transfers: &TransferLedger) -> Result<(), ProgramError> {
+        for (address, change) in transfers.balance_changes() {
+            evm_debug!("Balance of {}: +{} -{}", &address.to_string(), change.credit, change.debit);
+            let pos = self.find_account(&address).ok_or_else(|| self.missing_account(&address))?;
+            let account_info = self.accounts[pos].info;
+            if self.accounts[pos].is_foreign() && erc20_wrapper::spl_token::check_id(account_info.owner) {
+                debug_print!("Native tokens of foreign account {} can't be transferred", account_info.key);
+                return Err(ProgramError::InvalidArgument);
+            }
+            if self.accounts[pos].is_foreign() && change.debit > change.credit {
+                debug_print!("Lamports of foreign account {} can't be debited", account_info.key);
+                return Err(ProgramError::InsufficientFunds);
+            }
+
+            let lamports = change.apply_to(U256::from(account_info.lamports())).ok_or(ProgramError::InsufficientFunds)?;
+            let lamports: u64 = lamports.try_into().map_err(|_| ProgramError::InvalidArgument)?;
+            **account_info.lamports.borrow_mut() = lamports;
+        }
 
         Ok(())
     }
+
+    fn find_missing_account(&self, solana_address: &Pubkey) -> Option<&'a AccountInfo<'a>> {
+        self.missing_accounts.iter().find(|info| info.key == solana_address).copied()
+    }
+
+    fn can_create_account(&self, address: &H160) -> bool {
+        if self.payer.is_none() || self.system_program.is_none() {
+            return false;
+        }
+
+        let (solana_address, _) = ether_to_program_address(&self.program_id, address);
+        self.find_missing_account(&solana_address).is_some()
+    }
+
+    /// Log the Ether address that has no corresponding Solana account.
+    /// Custom error code can't hold the address, clients take it from the `Missing account:` log line.
+    fn missing_account(&self, address: &H160) -> ProgramError {
+        let (solana_address, _) = ether_to_program_address(&self.program_id, address);
+        msg!("Missing account: 0x{} {}", hex::encode(address.as_bytes()), solana_address); // see MISSING_ACCOUNT_LOG_PREFIX
+        EvmLoaderError::AccountMissing.into()
+    }
+
+    /// Check that every address touched by the execution has its account supplied to the instruction,
+    /// and that the accounts accessed by the execution were parsed
+    pub fn check_touched_accounts<I>(&self, touched: I) -> Result<(), ProgramError>
+    where I: IntoIterator<Item=H160>
+    {
+        if let Some(err) = self.parse_error.borrow_mut().take() {
+            return Err(err);
+        }
+
+        for address in touched {
+            if self.find_account(&address).is_none() && !self.can_create_account(&address) {
+                return Err(self.missing_account(&address));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create account for the Ether address through CPI to the system program.
+    /// Returns position of the new account and lamports paid for rent exemption.
+    /// The account has no signer: the payer only funds it, it doesn't own the Ether address.
+    fn create_account<C: Cpi>(&mut self, cpi: &C, address: &H160) -> Result<(usize, u64), ProgramError> {
+        let payer = self.payer.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let system_program = self.system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        let (solana_address, nonce) = ether_to_program_address(&self.program_id, address);
+        let account_info = self.find_missing_account(&solana_address).ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        let account_data = AccountData::Account( Account {
+            ether: *address,
+            nonce,
+            trx_count: 0u64,
+            signer: Pubkey::default(),
+            code_account: Pubkey::new_from_array([0u8; 32]),
+            blocked: None,
+        } );
+        let space = account_data.size();
+        let rent = Rent::get()?.minimum_balance(space);
+
+        debug_print!("Create account {} for {} with {} lamports", &solana_address, &address.to_string(), &rent);
+        let program_seeds = [address.as_bytes(), &[nonce]];
+        cpi.invoke_signed(
+            &system_instruction::create_account(payer.key, &solana_address, rent, space as u64, &self.program_id),
+            &[payer.clone(), account_info.clone(), system_program.clone()],
+            &[&program_seeds[..]]
+        )?;
+
+        account_data.pack(&mut account_info.data.borrow_mut())?;
+
+        let sol_account = SolidityAccount::new(&self.program_id, account_info.key, rent, account_data, None)?;
+
+        let pos = self.accounts.len();
+        self.accounts.push(LazyAccount { info: account_info, code_info: None, account: RefCell::new(Some(sol_account)) });
+        self.missing_accounts.retain(|info| info.key != &solana_address);
+        self.paid_rent += rent;
+
+        self.aliases.borrow_mut().insert(*address, pos);
+
+        Ok((pos, rent))
+    }
 }
 
 impl<'a> AccountStorage for ProgramAccountStorage<'a> {    
@@ -220,11 +677,20 @@ impl<'a> AccountStorage for ProgramAccountStorage<'a> {
     where F: FnOnce(&SolidityAccount) -> U,
           D: FnOnce() -> U
     {
-        self.get_account(address).map_or_else(d, f)
+        match self.get_account(address) {
+            Ok(Some(account)) => f(&account),
+            Ok(None) => d(),
+            Err(err) => {
+                debug_print!("Invalid account {}: {:?}", &address.to_string(), err);
+                *self.parse_error.borrow_mut() = Some(err);
+                d()
+            }
+        }
     }
 
     fn contract(&self) -> H160 { self.contract_id }
     fn origin(&self) -> H160 { self.caller_id }
+    fn program_id(&self) -> Pubkey { self.program_id }
 
     fn block_number(&self) -> U256 {
         let clock = &Clock::from_account_info(self.clock_account).unwrap();
@@ -235,4 +701,35 @@ impl<'a> AccountStorage for ProgramAccountStorage<'a> {
         let clock = &Clock::from_account_info(self.clock_account).unwrap();
         clock.unix_timestamp.into()
     }
+
+    fn epoch(&self) -> U256 {
+        let clock = &Clock::from_account_info(self.clock_account).unwrap();
+        clock.epoch.into()
+    }
+
+    fn rent_exempt_minimum(&self, data_len: usize) -> u64 {
+        Rent::get().unwrap_or_default().minimum_balance(data_len)
+    }
+
+    fn block_hash(&self, number: U256) -> H256 {
+        match self.slot_hashes_account {
+            Some(slot_hashes_account) => block::block_hash(&slot_hashes_account.data.borrow(), self.block_number(), number),
+            None => {
+                debug_print!("SlotHashes account is not passed, block hash is zero");
+                H256::default()
+            }
+        }
+    }
+
+    fn block_coinbase(&self) -> H160 { self.coinbase }
+
+    fn chain_id(&self) -> U256 { self.chain_id.into() }
+
+    fn block_randomness(&self) -> U256 {
+        self.slot_hashes_account.map_or_else(U256::zero, |slot_hashes_account| block::prevrandao(&slot_hashes_account.data.borrow()))
+    }
+
+    fn gas_price(&self) -> U256 {
+        self.lamports_per_gas.unwrap_or_default().into()
+    }
 }
@@ -0,0 +1,111 @@
+//! EVM blocks over Solana slots
+//!
+//! Every slot is a block: `block.number` is the slot and `block.timestamp` is the
+//! unix timestamp of the slot clock. `blockhash(n)` is the bank hash of slot `n`
+//! taken from the `SlotHashes` sysvar, which the program and the emulator read in the
+//! same format. As in Ethereum, the hash of the current block, of future blocks and of
+//! blocks older than `BLOCK_HASH_HISTORY` is zero; skipped slots have zero hash too.
+//! `block.difficulty` (`prevrandao`) is the hash of the most recent slot in `SlotHashes`.
+
+use primitive_types::{H256, U256};
+
+/// Number of the most recent blocks with available hash
+pub const BLOCK_HASH_HISTORY: u64 = 256;
+
+/// Size of the `(slot, hash)` entry of `SlotHashes` sysvar data
+const SLOT_HASH_SIZE: usize = 8 + 32;
+
+/// Hash of the block `number` seen from the block `current`
+pub fn block_hash(slot_hashes: &[u8], current: U256, number: U256) -> H256 {
+    if number >= current || current - number > U256::from(BLOCK_HASH_HISTORY) {
+        return H256::default();
+    }
+
+    find_slot_hash(slot_hashes, number.as_u64()).map_or_else(H256::default, H256::from_slice)
+}
+
+/// `prevrandao` of the current block: hash of the most recent slot, zero if there is none
+pub fn prevrandao(slot_hashes: &[u8]) -> U256 {
+    match slot_hashes.get(8 + 8..8 + SLOT_HASH_SIZE) {
+        Some(hash) if slot_hashes_len(slot_hashes) > 0 => U256::from_big_endian(hash),
+        _ => U256::zero(),
+    }
+}
+
+fn slot_hashes_len(data: &[u8]) -> usize {
+    data.get(..8).map_or(0, |len| u64::from_le_bytes([len[0], len[1], len[2], len[3], len[4], len[5], len[6], len[7]]) as usize)
+}
+
+/// Bank hash of the slot in `SlotHashes` sysvar data: `u64` length followed by
+/// `(slot: u64, hash: [u8; 32])` entries ordered by slot descending
+fn find_slot_hash(data: &[u8], slot: u64) -> Option<&[u8]> {
+    let len = slot_hashes_len(data);
+    let entries = data.get(8..)?;
+    let len = len.min(entries.len() / SLOT_HASH_SIZE);
+
+    let entry_slot = |i: usize| {
+        let slot = &entries[i * SLOT_HASH_SIZE..i * SLOT_HASH_SIZE + 8];
+        u64::from_le_bytes([slot[0], slot[1], slot[2], slot[3], slot[4], slot[5], slot[6], slot[7]])
+    };
+
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let middle = (low + high) / 2;
+        let middle_slot = entry_slot(middle);
+        if middle_slot == slot {
+            let hash = middle * SLOT_HASH_SIZE + 8;
+            return Some(&entries[hash..hash + 32]);
+        }
+        if middle_slot > slot {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot_hashes(slots: &[u64]) -> Vec<u8> {
+        let mut data = (slots.len() as u64).to_le_bytes().to_vec();
+        for slot in slots {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[*slot as u8; 32]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_block_hash() {
+        let data = slot_hashes(&[109, 108, 106, 105, 101]);
+        let current = U256::from(110);
+
+        assert_eq!(block_hash(&data, current, U256::from(109)), H256::repeat_byte(109));
+        assert_eq!(block_hash(&data, current, U256::from(106)), H256::repeat_byte(106));
+        assert_eq!(block_hash(&data, current, U256::from(101)), H256::repeat_byte(101));
+        assert_eq!(block_hash(&data, current, U256::from(107)), H256::default());
+        assert_eq!(block_hash(&data, current, U256::from(110)), H256::default());
+        assert_eq!(block_hash(&data, U256::from(400), U256::from(109)), H256::default());
+    }
+
+    #[test]
+    fn test_prevrandao() {
+        let data = slot_hashes(&[109, 108]);
+        assert_eq!(prevrandao(&data), U256::from_big_endian(&[109; 32]));
+        assert_eq!(prevrandao(&slot_hashes(&[])), U256::zero());
+        assert_eq!(prevrandao(&[]), U256::zero());
+    }
+
+    #[test]
+    fn test_block_hash_truncated_data() {
+        let mut data = slot_hashes(&[109, 108]);
+        data.truncate(8 + SLOT_HASH_SIZE + 10);
+
+        assert_eq!(block_hash(&data, U256::from(110), U256::from(109)), H256::repeat_byte(109));
+        assert_eq!(block_hash(&data, U256::from(110), U256::from(108)), H256::default());
+        assert_eq!(block_hash(&[], U256::from(110), U256::from(109)), H256::default());
+    }
+}
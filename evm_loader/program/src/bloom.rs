@@ -0,0 +1,81 @@
+//! Ethereum logs bloom
+//!
+//! 2048-bit filter of the addresses and topics of the transaction logs, as in
+//! the `logsBloom` field of the receipt: every item sets three bits selected by
+//! the low 11 bits of the first three byte pairs of its keccak256 hash.
+
+use evm::backend::Log;
+use crate::utils::keccak256_h256;
+
+/// Size of the logs bloom in bytes
+pub const BLOOM_SIZE: usize = 256;
+
+/// Logs bloom, big-endian as serialized in the receipt
+pub type Bloom = [u8; BLOOM_SIZE];
+
+/// Add the address and the topics of the log to the bloom
+pub fn accrue_log(bloom: &mut Bloom, log: &Log) {
+    accrue(bloom, log.address.as_bytes());
+    for topic in &log.topics {
+        accrue(bloom, topic.as_bytes());
+    }
+}
+
+/// Bloom of the logs
+pub fn logs_bloom<'l, I>(logs: I) -> Bloom where I: IntoIterator<Item = &'l Log> {
+    let mut bloom = [0u8; BLOOM_SIZE];
+    for log in logs {
+        accrue_log(&mut bloom, log);
+    }
+    bloom
+}
+
+/// Add the item to the bloom
+pub fn accrue(bloom: &mut Bloom, item: &[u8]) {
+    let hash = keccak256_h256(item);
+    for i in 0..3 {
+        let bit = (usize::from(hash[2 * i]) << 8 | usize::from(hash[2 * i + 1])) & 2047;
+        bloom[BLOOM_SIZE - 1 - bit / 8] |= 1 << (bit % 8);
+    }
+}
+
+/// Check if the item may be in the bloom, false positives are possible
+pub fn contains(bloom: &Bloom, item: &[u8]) -> bool {
+    let mut item_bloom = [0u8; BLOOM_SIZE];
+    accrue(&mut item_bloom, item);
+    bloom.iter().zip(item_bloom.iter()).all(|(b, i)| b & i == *i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitive_types::{H160, H256};
+
+    #[test]
+    fn test_accrue() {
+        let mut bloom = [0u8; BLOOM_SIZE];
+        accrue(&mut bloom, &[]);
+        // keccak256("") = c5d2 4601 86f7 ...: bits 0x5d2, 0x601, 0x6f7
+        let mut expected = [0u8; BLOOM_SIZE];
+        for bit in &[0x5d2usize, 0x601, 0x6f7] {
+            expected[BLOOM_SIZE - 1 - bit / 8] |= 1 << (bit % 8);
+        }
+        assert_eq!(&bloom[..], &expected[..]);
+    }
+
+    #[test]
+    fn test_logs_bloom() {
+        let log = Log {
+            address: H160::repeat_byte(0x11),
+            topics: vec![H256::repeat_byte(0x22), H256::repeat_byte(0x33)],
+            data: vec![1, 2, 3],
+        };
+        let bloom = logs_bloom(std::iter::once(&log));
+
+        assert!(contains(&bloom, log.address.as_bytes()));
+        assert!(contains(&bloom, log.topics[0].as_bytes()));
+        assert!(contains(&bloom, log.topics[1].as_bytes()));
+        assert!(bloom.iter().map(|b| b.count_ones()).sum::<u32>() <= 9);
+        assert_eq!(&logs_bloom(std::iter::empty())[..], &[0u8; BLOOM_SIZE][..]);
+    }
+}
@@ -0,0 +1,302 @@
+//! Helpers for programs which invoke evm_loader through CPI
+//!
+//! Depend on the crate with `features = ["cpi"]` to exclude the entrypoint.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::clock,
+};
+use crate::instruction::BatchCall;
+use crate::return_data::{get_return_data, parse_transaction_result, EtherAccountInfo, Telemetry};
+use crate::version::ProgramVersion;
+use primitive_types::{H160, U256};
+pub use crate::utils::{ether_to_program_address, ether_to_seeded_address};
+
+/// Status byte of `OnReturn` instruction and return data for successful execution
+pub fn is_succeed_status(status: u8) -> bool {
+    matches!(status, 0x11 | 0x12 | 0x13)
+}
+
+/// Accounts required by `Call` instruction
+pub struct CallAccounts {
+    /// Contract account (Ether account)
+    pub contract: Pubkey,
+    /// Contract code account
+    pub contract_code: Pubkey,
+    /// Caller account: Ether account or signer itself
+    pub caller: Pubkey,
+    /// Signer for caller, `None` when the caller is not an Ether account
+    pub signer: Option<Pubkey>,
+    /// Other Ether accounts touched by the call
+    pub other: Vec<AccountMeta>,
+    /// Payer for Ether accounts created by the call
+    pub payer: Option<Pubkey>,
+}
+
+impl CallAccounts {
+    /// Account list in the order expected by `Call` instruction
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        let mut accounts = Vec::with_capacity(7 + self.other.len());
+        accounts.push(AccountMeta::new(self.contract, false));
+        accounts.push(AccountMeta::new(self.contract_code, false));
+        match self.signer {
+            Some(signer) => {
+                accounts.push(AccountMeta::new(self.caller, false));
+                accounts.push(AccountMeta::new_readonly(signer, true));
+            },
+            None => accounts.push(AccountMeta::new(self.caller, true)),
+        }
+        accounts.push(AccountMeta::new_readonly(clock::id(), false));
+        accounts.extend_from_slice(&self.other);
+        if let Some(payer) = self.payer {
+            accounts.push(AccountMeta::new(payer, true));
+            accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+        }
+        accounts
+    }
+}
+
+/// Creates a `Call` instruction.
+pub fn call(
+    evm_loader_id: &Pubkey,
+    accounts: &CallAccounts,
+    call_data: &[u8],
+) -> Instruction {
+    let mut data = Vec::with_capacity(1 + call_data.len());
+    data.push(3u8);
+    data.extend_from_slice(call_data);
+
+    Instruction {
+        program_id: *evm_loader_id,
+        accounts: accounts.to_account_metas(),
+        data,
+    }
+}
+
+/// Creates a `CallBatch` instruction, contract accounts of all calls are expected in `accounts.other`.
+pub fn call_batch(
+    evm_loader_id: &Pubkey,
+    accounts: &CallAccounts,
+    calls: &[BatchCall],
+) -> Instruction {
+    let mut data = vec![20u8];
+    for call in calls {
+        call.pack(&mut data);
+    }
+
+    Instruction {
+        program_id: *evm_loader_id,
+        accounts: accounts.to_account_metas(),
+        data,
+    }
+}
+
+/// Invoke `Call` instruction and read its result from return data.
+///
+/// Only available when evm_loader is built with `return-data` feature,
+/// otherwise the result is passed in `OnReturn` instruction which is not visible to the caller.
+pub fn invoke_call(
+    evm_loader_id: &Pubkey,
+    accounts: &CallAccounts,
+    call_data: &[u8],
+    account_infos: &[AccountInfo],
+    signers_seeds: &[&[&[u8]]],
+) -> Result<(u8, Vec<u8>), ProgramError> {
+    let instruction = call(evm_loader_id, accounts, call_data);
+    invoke_signed(&instruction, account_infos, signers_seeds)?;
+
+    let (program_id, data) = get_return_data().ok_or(ProgramError::InvalidAccountData)?;
+    if program_id != *evm_loader_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let (status, result, _) = parse_transaction_result(&data).ok_or(ProgramError::InvalidAccountData)?;
+    Ok((status, result.to_vec()))
+}
+
+/// Creates a `GetAccountInfo` instruction, `code_account` is required for contracts.
+pub fn get_account_info(
+    evm_loader_id: &Pubkey,
+    account: &Pubkey,
+    code_account: Option<&Pubkey>,
+    ether: &H160,
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(*account, false)];
+    if let Some(code_account) = code_account {
+        accounts.push(AccountMeta::new_readonly(*code_account, false));
+    }
+
+    let mut data = Vec::with_capacity(21);
+    data.push(18u8);
+    data.extend_from_slice(ether.as_bytes());
+
+    Instruction {
+        program_id: *evm_loader_id,
+        accounts,
+        data,
+    }
+}
+
+/// Invoke `GetAccountInfo` instruction and read the account state from return data.
+///
+/// Only available when evm_loader is built with `return-data` feature.
+pub fn invoke_get_account_info(
+    evm_loader_id: &Pubkey,
+    account: &Pubkey,
+    code_account: Option<&Pubkey>,
+    ether: &H160,
+    account_infos: &[AccountInfo],
+) -> Result<EtherAccountInfo, ProgramError> {
+    let instruction = get_account_info(evm_loader_id, account, code_account, ether);
+    invoke_signed(&instruction, account_infos, &[])?;
+
+    let (program_id, data) = get_return_data().ok_or(ProgramError::InvalidAccountData)?;
+    if program_id != *evm_loader_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let (status, result, _) = parse_transaction_result(&data).ok_or(ProgramError::InvalidAccountData)?;
+    check_status(status)?;
+    EtherAccountInfo::unpack(result).ok_or(ProgramError::InvalidAccountData)
+}
+
+/// Creates a `GetStorageAt` instruction.
+pub fn get_storage_at(
+    evm_loader_id: &Pubkey,
+    contract: &Pubkey,
+    contract_code: &Pubkey,
+    address: &H160,
+    index: &U256,
+) -> Instruction {
+    let mut data = Vec::with_capacity(53);
+    data.push(19u8);
+    data.extend_from_slice(address.as_bytes());
+    let mut index_bytes = [0u8; 32];
+    index.to_big_endian(&mut index_bytes);
+    data.extend_from_slice(&index_bytes);
+
+    Instruction {
+        program_id: *evm_loader_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*contract, false),
+            AccountMeta::new_readonly(*contract_code, false),
+        ],
+        data,
+    }
+}
+
+/// Creates a `GetVersion` instruction.
+pub fn get_version(evm_loader_id: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *evm_loader_id,
+        accounts: vec![],
+        data: vec![30u8],
+    }
+}
+
+/// Invoke `GetVersion` instruction and read the version of evm_loader from return data.
+///
+/// Only available when evm_loader is built with `return-data` feature.
+pub fn invoke_get_version(evm_loader_id: &Pubkey, account_infos: &[AccountInfo]) -> Result<ProgramVersion, ProgramError> {
+    invoke_signed(&get_version(evm_loader_id), account_infos, &[])?;
+
+    let (program_id, data) = get_return_data().ok_or(ProgramError::InvalidAccountData)?;
+    if program_id != *evm_loader_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let (status, result, _) = parse_transaction_result(&data).ok_or(ProgramError::InvalidAccountData)?;
+    check_status(status)?;
+    ProgramVersion::unpack(result).ok_or(ProgramError::InvalidAccountData)
+}
+
+/// Parse `OnReturn` instruction data into status, result and telemetry
+pub fn parse_on_return(data: &[u8]) -> Option<(u8, &[u8], Telemetry)> {
+    match data.split_first() {
+        Some((6, rest)) => parse_transaction_result(rest),
+        _ => None,
+    }
+}
+
+/// Check status of the call, returning `Custom(status)` error for unsuccessful execution
+pub fn check_status(status: u8) -> ProgramResult {
+    if is_succeed_status(status) {
+        Ok(())
+    } else {
+        Err(ProgramError::Custom(status as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::{on_return, EvmInstruction};
+
+    #[test]
+    fn test_parse_on_return() {
+        let program_id = Pubkey::new_unique();
+        let telemetry = Telemetry { heap_high_water: 1024, steps: 10, touched_accounts: 2 };
+        let ix = on_return(&program_id, 0x12, &vec![1, 2, 3], &telemetry).unwrap();
+
+        assert_eq!(parse_on_return(&ix.data), Some((0x12, &[1u8, 2, 3][..], telemetry)));
+        assert_eq!(parse_on_return(&[7, 0x12]), None);
+        assert_eq!(parse_on_return(&[6]), None);
+    }
+
+    #[test]
+    fn test_get_storage_at_data() {
+        let program_id = Pubkey::new_unique();
+        let address = H160::repeat_byte(1);
+        let ix = get_storage_at(&program_id, &Pubkey::new_unique(), &Pubkey::new_unique(), &address, &U256::from(0x0102));
+
+        assert_eq!(
+            EvmInstruction::unpack(&ix.data),
+            Ok(EvmInstruction::GetStorageAt {address, index: U256::from(0x0102)})
+        );
+        assert_eq!(&ix.data[51..], &[1, 2]);
+        assert_eq!(EvmInstruction::unpack(&ix.data[..52]), Err(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn test_call_batch_data() {
+        let accounts = CallAccounts {
+            contract: Pubkey::new_unique(),
+            contract_code: Pubkey::new_unique(),
+            caller: Pubkey::new_unique(),
+            signer: None,
+            other: vec![],
+            payer: None,
+        };
+        let calls = vec![
+            BatchCall {to: H160::repeat_byte(1), value: U256::zero(), data: &[1, 2, 3]},
+            BatchCall {to: H160::repeat_byte(2), value: U256::from(5), data: &[]},
+        ];
+        let ix = call_batch(&Pubkey::new_unique(), &accounts, &calls);
+
+        assert_eq!(EvmInstruction::unpack(&ix.data), Ok(EvmInstruction::CallBatch {calls}));
+        assert_eq!(EvmInstruction::unpack(&ix.data[..ix.data.len() - 1]), Err(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn test_call_accounts_order() {
+        let accounts = CallAccounts {
+            contract: Pubkey::new_unique(),
+            contract_code: Pubkey::new_unique(),
+            caller: Pubkey::new_unique(),
+            signer: None,
+            other: vec![],
+            payer: None,
+        };
+        let metas = accounts.to_account_metas();
+
+        assert_eq!(metas.len(), 4);
+        assert!(metas[2].is_signer);
+        assert_eq!(metas[3].pubkey, clock::id());
+    }
+}
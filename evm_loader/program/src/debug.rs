@@ -13,3 +13,25 @@ macro_rules! debug_print {
 macro_rules! debug_print {
     ($( $args:expr ),*) => {}
 }
+
+/// Details of the accounts and calls, compiled only with `verbose-logs` feature
+#[cfg(feature = "verbose-logs")]
+macro_rules! evm_debug {
+    ($( $args:expr ),*) => { debug_print!( $( $args ),* ) }
+}
+
+#[cfg(not(feature = "verbose-logs"))]
+macro_rules! evm_debug {
+    ($( $args:expr ),*) => {}
+}
+
+/// Logging of every account lookup and storage access, compiled only with `trace-logs` feature
+#[cfg(feature = "trace-logs")]
+macro_rules! evm_trace {
+    ($( $args:expr ),*) => { debug_print!( $( $args ),* ) }
+}
+
+#[cfg(not(feature = "trace-logs"))]
+macro_rules! evm_trace {
+    ($( $args:expr ),*) => {}
+}
@@ -0,0 +1,126 @@
+//! EIP-712 typed structured data hashing
+//!
+//! Wallets sign `keccak256(0x19 0x01 ‖ domainSeparator ‖ hashStruct(message))`.
+//! Program and client build the domain separator and the struct hashes with these functions,
+//! so recovered signers of permits and meta-transactions match the wallet.
+
+use primitive_types::{H160, H256, U256};
+use crate::utils::{keccak256_h256, keccak256_h256_v, u256_to_h256};
+
+/// Signing domain, only the present fields are part of `EIP712Domain`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Domain<'a> {
+    pub name: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub chain_id: Option<U256>,
+    pub verifying_contract: Option<H160>,
+    pub salt: Option<H256>,
+}
+
+impl<'a> Domain<'a> {
+    /// Encoded type of the domain: `EIP712Domain(string name,...)` with the present fields
+    pub fn encode_type(&self) -> String {
+        let mut fields = Vec::new();
+        if self.name.is_some() { fields.push("string name"); }
+        if self.version.is_some() { fields.push("string version"); }
+        if self.chain_id.is_some() { fields.push("uint256 chainId"); }
+        if self.verifying_contract.is_some() { fields.push("address verifyingContract"); }
+        if self.salt.is_some() { fields.push("bytes32 salt"); }
+
+        format!("EIP712Domain({})", fields.join(","))
+    }
+
+    /// `hashStruct(domain)`
+    pub fn separator(&self) -> H256 {
+        let mut values = Vec::new();
+        if let Some(name) = self.name { values.push(encode_string(name)); }
+        if let Some(version) = self.version { values.push(encode_string(version)); }
+        if let Some(chain_id) = self.chain_id { values.push(encode_uint(chain_id)); }
+        if let Some(verifying_contract) = self.verifying_contract { values.push(encode_address(verifying_contract)); }
+        if let Some(salt) = self.salt { values.push(salt); }
+
+        struct_hash(&type_hash(&self.encode_type()), &values)
+    }
+}
+
+/// `typeHash`: keccak256 of the encoded type, e.g. `Mail(Person from,Person to,string contents)Person(string name,address wallet)`
+pub fn type_hash(encoded_type: &str) -> H256 {
+    keccak256_h256(encoded_type.as_bytes())
+}
+
+/// `hashStruct(s) = keccak256(typeHash ‖ encodeData(s))`, `values` are encoded members in the type order
+pub fn struct_hash(type_hash: &H256, values: &[H256]) -> H256 {
+    let mut data: Vec<&[u8]> = Vec::with_capacity(values.len() + 1);
+    data.push(type_hash.as_bytes());
+    data.extend(values.iter().map(H256::as_bytes));
+
+    keccak256_h256_v(&data)
+}
+
+/// Hash signed by the wallet: `keccak256(0x19 0x01 ‖ domainSeparator ‖ hashStruct(message))`
+pub fn typed_data_hash(domain_separator: &H256, struct_hash: &H256) -> H256 {
+    keccak256_h256_v(&[&[0x19, 0x01], domain_separator.as_bytes(), struct_hash.as_bytes()])
+}
+
+/// Encoded `address` member
+pub fn encode_address(value: H160) -> H256 {
+    H256::from(value)
+}
+
+/// Encoded `uint256` member, smaller unsigned integers are encoded the same way
+pub fn encode_uint(value: U256) -> H256 {
+    u256_to_h256(value)
+}
+
+/// Encoded `bool` member
+pub fn encode_bool(value: bool) -> H256 {
+    encode_uint(if value { U256::one() } else { U256::zero() })
+}
+
+/// Encoded dynamic `bytes` member
+pub fn encode_bytes(value: &[u8]) -> H256 {
+    keccak256_h256(value)
+}
+
+/// Encoded `string` member
+pub fn encode_string(value: &str) -> H256 {
+    encode_bytes(value.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    // Example of the EIP-712 specification
+    #[test]
+    fn test_mail_example() {
+        let domain = Domain {
+            name: Some("Ether Mail"),
+            version: Some("1"),
+            chain_id: Some(U256::one()),
+            verifying_contract: Some(H160::from_str("CcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC").unwrap()),
+            salt: None,
+        };
+        assert_eq!(domain.encode_type(), "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)");
+        let domain_separator = domain.separator();
+        assert_eq!(domain_separator, H256::from_str("f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f").unwrap());
+
+        let person_type = type_hash("Person(string name,address wallet)");
+        let person = |name: &str, wallet: &str| struct_hash(&person_type, &[
+            encode_string(name),
+            encode_address(H160::from_str(wallet).unwrap()),
+        ]);
+
+        let mail_type = type_hash("Mail(Person from,Person to,string contents)Person(string name,address wallet)");
+        let mail = struct_hash(&mail_type, &[
+            person("Cow", "CD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"),
+            person("Bob", "bBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"),
+            encode_string("Hello, Bob!"),
+        ]);
+        assert_eq!(mail, H256::from_str("c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e").unwrap());
+
+        assert_eq!(typed_data_hash(&domain_separator, &mail),
+            H256::from_str("be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2").unwrap());
+    }
+}
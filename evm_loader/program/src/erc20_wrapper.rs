@@ -0,0 +1,355 @@
+//! Factory of canonical ERC20 wrappers for SPL mints
+//!
+//! The wrapper of a mint is created with CREATE2 by the virtual factory address,
+//! with the mint as salt and `ERC20Wrapper.sol` bytecode followed by constructor arguments as init code.
+
+use primitive_types::{H160, H256, U256};
+use solana_program::pubkey::Pubkey;
+use crate::{
+    account_data::AccountData,
+    utils::{keccak256_h256, keccak256_h256_v},
+};
+
+/// SPL Token program
+pub mod spl_token {
+    use solana_program::{instruction::{AccountMeta, Instruction}, pubkey::Pubkey};
+
+    solana_program::declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+    /// Mint of wrapped SOL
+    pub mod native_mint {
+        solana_program::declare_id!("So11111111111111111111111111111111111111112");
+    }
+
+    /// `CloseAccount` instruction: all lamports of the account go to the destination
+    pub fn close_account(account: &Pubkey, destination: &Pubkey, owner: &Pubkey) -> Instruction {
+        Instruction {
+            program_id: id(),
+            accounts: vec![
+                AccountMeta::new(*account, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*owner, true),
+            ],
+            data: vec![9],
+        }
+    }
+
+    /// `Transfer` instruction: move the amount between token accounts of the same mint
+    pub fn transfer(source: &Pubkey, destination: &Pubkey, owner: &Pubkey, amount: u64) -> Instruction {
+        let mut data = vec![3];
+        data.extend_from_slice(&amount.to_le_bytes());
+        Instruction {
+            program_id: id(),
+            accounts: vec![
+                AccountMeta::new(*source, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*owner, true),
+            ],
+            data,
+        }
+    }
+
+    /// `SyncNative` instruction: set amount of the wrapped SOL account from its lamports
+    pub fn sync_native(account: &Pubkey) -> Instruction {
+        Instruction {
+            program_id: id(),
+            accounts: vec![AccountMeta::new(*account, false)],
+            data: vec![17],
+        }
+    }
+}
+
+/// SPL Associated Token Account program
+pub mod spl_associated_token_account {
+    solana_program::declare_id!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+}
+
+/// Metaplex Token Metadata program
+pub mod metaplex_metadata {
+    solana_program::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
+/// Size of SPL Token mint data
+const MINT_SIZE: usize = 82;
+/// Offset of `decimals` in SPL Token mint data
+const MINT_DECIMALS_OFFSET: usize = 44;
+/// Size of SPL Token account data
+const TOKEN_ACCOUNT_SIZE: usize = 165;
+/// Offset of `amount` in SPL Token account data: mint and owner precede it
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 32 + 32;
+/// Offset of the name in Metaplex metadata: key, update authority and mint precede it
+const METADATA_NAME_OFFSET: usize = 1 + 32 + 32;
+
+/// Seed of the registry record of the mint wrapper
+const REGISTRY_SEED: &[u8] = b"erc20_wrapper";
+
+/// Ether address deploying the wrappers. It has no account, its nonce is never stored.
+pub fn factory_address() -> H160 {
+    keccak256_h256(b"ERC20WrapperFactory").into()
+}
+
+/// Seed of the holders balance accounts: hex of the first 8 bytes of the mint
+pub fn balance_seed(mint: &Pubkey) -> String {
+    hex::encode(&mint.as_ref()[..8])
+}
+
+/// ABI encoded arguments of the wrapper constructor: `(uint256 mint, string seed)`
+pub fn constructor_args(mint: &Pubkey) -> Vec<u8> {
+    let seed = abi_encode_string(&balance_seed(mint));
+
+    let mut args = Vec::with_capacity(2 * 32 + seed.len());
+    args.extend_from_slice(mint.as_ref());
+    args.extend_from_slice(&u256_bytes(U256::from(64)));
+    args.extend(seed);
+    args
+}
+
+/// Init code of the wrapper: template bytecode followed by constructor arguments
+pub fn init_code(template: &[u8], mint: &Pubkey) -> Vec<u8> {
+    let mut code = template.to_vec();
+    code.extend(constructor_args(mint));
+    code
+}
+
+/// Salt of the CREATE2 deployment of the mint wrapper
+pub fn salt(mint: &Pubkey) -> H256 {
+    H256::from_slice(mint.as_ref())
+}
+
+/// Address of the mint wrapper: `keccak256(0xff ++ factory ++ mint ++ keccak256(init_code))[12..]`
+pub fn wrapper_address(template: &[u8], mint: &Pubkey) -> H160 {
+    let code_hash = keccak256_h256(&init_code(template, mint));
+    keccak256_h256_v(&[&[0xff], factory_address().as_bytes(), salt(mint).as_bytes(), code_hash.as_bytes()]).into()
+}
+
+/// Address and bump seed of the record pairing the mint with its wrapper: `program_address(["erc20_wrapper", mint])`
+pub fn registry_address(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REGISTRY_SEED, mint.as_ref()], program_id)
+}
+
+/// Seeds of the registry record without the bump seed
+pub fn registry_seeds(mint: &Pubkey) -> [&[u8]; 2] {
+    [REGISTRY_SEED, mint.as_ref()]
+}
+
+/// Wrapper address stored in the registry record, if the record pairs it with the mint
+pub fn registry_wrapper(registry_data: &[u8], mint: &Pubkey) -> Option<H160> {
+    match AccountData::unpack(registry_data).ok()? {
+        AccountData::Alias(alias) if alias.account == *mint => Some(alias.ether),
+        _ => None,
+    }
+}
+
+/// Address of the Metaplex metadata account of the mint: `program_address(["metadata", metaplex, mint])`
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    let program_id = metaplex_metadata::id();
+    Pubkey::find_program_address(&[b"metadata", program_id.as_ref(), mint.as_ref()], &program_id).0
+}
+
+/// Associated token account of the wallet for the mint
+pub fn associated_token_address(wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+    let token_program = spl_token::id();
+    Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &spl_associated_token_account::id()
+    ).0
+}
+
+/// Amount of the SPL Token account, if it holds tokens of the mint
+pub fn token_account_amount(account_data: &[u8], mint: &Pubkey) -> Option<u64> {
+    if account_data.len() != TOKEN_ACCOUNT_SIZE || &account_data[..32] != mint.as_ref() {
+        return None;
+    }
+    let amount = &account_data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8];
+    Some(u64::from_le_bytes([amount[0], amount[1], amount[2], amount[3], amount[4], amount[5], amount[6], amount[7]]))
+}
+
+/// Owner of the SPL Token account, if it holds tokens of the mint
+pub fn token_account_owner(account_data: &[u8], mint: &Pubkey) -> Option<Pubkey> {
+    token_account_amount(account_data, mint)?;
+    Some(Pubkey::new(&account_data[32..64]))
+}
+
+/// Token amount scaled to 18 decimals of the EVM native currency, `None` if the mint has more decimals
+pub fn native_balance(amount: u64, decimals: u8) -> Option<U256> {
+    let scale = 18usize.checked_sub(decimals as usize)?;
+    U256::from(amount).checked_mul(U256::exp10(scale))
+}
+
+/// Owner of the SPL Token account holding the only token of the NFT mint
+pub fn nft_holder(account_data: &[u8], mint: &Pubkey) -> Option<Pubkey> {
+    if token_account_amount(account_data, mint)? != 1 {
+        return None;
+    }
+    Some(Pubkey::new(&account_data[32..64]))
+}
+
+/// ABI encoded `uint256` balance
+pub fn encode_balance(amount: u64) -> Vec<u8> {
+    u256_bytes(U256::from(amount)).to_vec()
+}
+
+/// Decimals of the SPL Token mint
+pub fn mint_decimals(mint_data: &[u8]) -> Option<u8> {
+    if mint_data.len() != MINT_SIZE {
+        return None;
+    }
+    Some(mint_data[MINT_DECIMALS_OFFSET])
+}
+
+/// Name and symbol from Metaplex metadata of the mint, with trailing zero padding removed
+pub fn metadata_name_symbol(mint: &Pubkey, metadata: &[u8]) -> Option<(String, String)> {
+    let metadata_mint = metadata.get(METADATA_NAME_OFFSET - 32..METADATA_NAME_OFFSET)?;
+    if metadata_mint != mint.as_ref() {
+        return None;
+    }
+
+    let (name, rest) = read_borsh_string(&metadata[METADATA_NAME_OFFSET..])?;
+    let (symbol, _) = read_borsh_string(rest)?;
+    Some((name, symbol))
+}
+
+fn read_borsh_string(data: &[u8]) -> Option<(String, &[u8])> {
+    let len = data.get(..4)?;
+    let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize;
+    let value = data.get(4..4 + len)?;
+    let value = std::str::from_utf8(value).ok()?.trim_end_matches('\0').to_string();
+    Some((value, &data[4 + len..]))
+}
+
+/// ABI encoded result of the token metadata call: `(string name, string symbol, uint8 decimals)`
+pub fn encode_token_metadata(name: &str, symbol: &str, decimals: u8) -> Vec<u8> {
+    let name = abi_encode_string(name);
+    let symbol = abi_encode_string(symbol);
+
+    let mut result = Vec::with_capacity(3 * 32 + name.len() + symbol.len());
+    result.extend_from_slice(&u256_bytes(U256::from(3 * 32)));
+    result.extend_from_slice(&u256_bytes(U256::from(3 * 32 + name.len())));
+    result.extend_from_slice(&u256_bytes(U256::from(decimals)));
+    result.extend(name);
+    result.extend(symbol);
+    result
+}
+
+fn abi_encode_string(value: &str) -> Vec<u8> {
+    let padded_len = (value.len() + 31) / 32 * 32;
+    let mut encoded = Vec::with_capacity(32 + padded_len);
+    encoded.extend_from_slice(&u256_bytes(U256::from(value.len())));
+    encoded.extend_from_slice(value.as_bytes());
+    encoded.resize(32 + padded_len, 0);
+    encoded
+}
+
+fn u256_bytes(value: U256) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_data::Alias;
+
+    #[test]
+    fn test_constructor_args() {
+        let mint = Pubkey::new_from_array([7u8; 32]);
+        let args = constructor_args(&mint);
+
+        assert_eq!(args.len(), 4 * 32);
+        assert_eq!(&args[..32], mint.as_ref());
+        assert_eq!(args[63], 0x40);
+        assert_eq!(args[95], 16);
+        assert_eq!(&args[96..112], b"0707070707070707");
+        assert!(args[112..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_metadata_name_symbol() {
+        let mint = Pubkey::new_from_array([3u8; 32]);
+        let mut metadata = vec![4u8];
+        metadata.extend_from_slice(&[0u8; 32]);
+        metadata.extend_from_slice(mint.as_ref());
+        for (value, len) in [("Wrapped SOL", 32), ("SOL", 10)].iter() {
+            metadata.extend_from_slice(&(*len as u32).to_le_bytes());
+            let mut padded = value.as_bytes().to_vec();
+            padded.resize(*len, 0);
+            metadata.extend(padded);
+        }
+
+        assert_eq!(metadata_name_symbol(&mint, &metadata), Some(("Wrapped SOL".to_string(), "SOL".to_string())));
+        assert_eq!(metadata_name_symbol(&Pubkey::new_from_array([5u8; 32]), &metadata), None);
+    }
+
+    #[test]
+    fn test_encode_token_metadata() {
+        let result = encode_token_metadata("Token", "TKN", 9);
+
+        assert_eq!(result.len(), 7 * 32);
+        assert_eq!(result[31], 0x60);
+        assert_eq!(result[63], 0xa0);
+        assert_eq!(result[95], 9);
+        assert_eq!(result[127], 5);
+        assert_eq!(&result[128..133], b"Token");
+        assert_eq!(result[191], 3);
+        assert_eq!(&result[192..195], b"TKN");
+    }
+
+    #[test]
+    fn test_token_account_amount() {
+        let mint = Pubkey::new_from_array([6u8; 32]);
+        let mut data = vec![0u8; TOKEN_ACCOUNT_SIZE];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[64..72].copy_from_slice(&1_500u64.to_le_bytes());
+
+        assert_eq!(token_account_amount(&data, &mint), Some(1_500));
+        assert_eq!(token_account_amount(&data, &Pubkey::new_from_array([8u8; 32])), None);
+        assert_eq!(token_account_amount(&data[..82], &mint), None);
+    }
+
+    #[test]
+    fn test_nft_holder() {
+        let mint = Pubkey::new_from_array([6u8; 32]);
+        let owner = Pubkey::new_from_array([9u8; 32]);
+        let mut data = vec![0u8; TOKEN_ACCOUNT_SIZE];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+
+        assert_eq!(nft_holder(&data, &mint), None);
+        data[64..72].copy_from_slice(&1u64.to_le_bytes());
+        assert_eq!(nft_holder(&data, &mint), Some(owner));
+    }
+
+    #[test]
+    fn test_native_balance() {
+        assert_eq!(native_balance(1_500, 9), Some(U256::from(1_500u64) * U256::exp10(9)));
+        assert_eq!(native_balance(7, 18), Some(U256::from(7)));
+        assert_eq!(native_balance(u64::max_value(), 0), Some(U256::from(u64::max_value()) * U256::exp10(18)));
+        assert_eq!(native_balance(1, 19), None);
+    }
+
+    #[test]
+    fn test_wrapper_address_depends_on_mint() {
+        let template = [0x60u8, 0x80, 0x60, 0x40];
+        let first = Pubkey::new_from_array([1u8; 32]);
+        let second = Pubkey::new_from_array([2u8; 32]);
+
+        assert_eq!(wrapper_address(&template, &first), wrapper_address(&template, &first));
+        assert_ne!(wrapper_address(&template, &first), wrapper_address(&template, &second));
+    }
+
+    #[test]
+    fn test_registry_wrapper() {
+        let template = [0x60u8, 0x80, 0x60, 0x40];
+        let mint = Pubkey::new_from_array([5u8; 32]);
+        let wrapper = wrapper_address(&template, &mint);
+
+        let record = AccountData::Alias( Alias {ether: wrapper, account: mint} );
+        let mut data = vec![0u8; record.size()];
+        record.pack(&mut data).unwrap();
+
+        assert_eq!(registry_wrapper(&data, &mint), Some(wrapper));
+        assert_eq!(registry_wrapper(&data, &Pubkey::new_from_array([6u8; 32])), None);
+        assert_eq!(registry_wrapper(&[0u8; 8], &mint), None);
+    }
+}
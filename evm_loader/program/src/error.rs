@@ -0,0 +1,93 @@
+//! Error types
+
+use num_derive::FromPrimitive;
+use primitive_types::H160;
+use solana_program::{decode_error::DecodeError, program_error::ProgramError};
+use thiserror::Error;
+
+/// Prefix of the log line naming the Ether address reported by `AccountMissing`
+pub const MISSING_ACCOUNT_LOG_PREFIX: &str = "Program log: Missing account: 0x";
+
+/// Errors that may be returned by the EVM loader program.
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum EvmLoaderError {
+    /// Lamports balance below rent-exempt threshold.
+    #[error("Lamports balance below rent-exempt threshold")]
+    NotRentExempt,
+
+    /// Iterative execution data was saved by an incompatible program version.
+    #[error("Unsupported version of iterative execution data")]
+    StorageVersionMismatch,
+
+    /// Iterative execution data doesn't match its checksum.
+    #[error("Iterative execution data is corrupted")]
+    StorageCorrupted,
+
+    /// Iterative execution data can't be serialized or deserialized.
+    #[error("Iterative execution data serialization failed")]
+    StorageSerialization,
+
+    /// Account is locked by another iterative transaction.
+    #[error("Account is blocked by another transaction")]
+    AccountBlocked,
+
+    /// Execution touched an Ether account not supplied to the instruction.
+    /// The address is logged with `MISSING_ACCOUNT_LOG_PREFIX`.
+    #[error("Account required by the execution is not supplied")]
+    AccountMissing,
+
+    /// Contract is re-entered through a Solana program called by it.
+    #[error("Contract is re-entered during the execution")]
+    Reentrancy,
+
+    /// Deployed code exceeds the size limit or starts with 0xEF.
+    #[error("Invalid contract code")]
+    InvalidCode,
+
+    /// Contract account doesn't match the `to` address of the transaction.
+    #[error("Contract account doesn't match the transaction destination")]
+    ContractMismatch,
+
+    /// Caller account doesn't match the sender of the transaction.
+    #[error("Caller account doesn't match the transaction sender")]
+    CallerMismatch,
+
+    /// Different accounts are passed for the same Ether address.
+    #[error("Different accounts have the same Ether address")]
+    AccountAliasConflict,
+}
+impl From<EvmLoaderError> for ProgramError {
+    fn from(e: EvmLoaderError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+impl<T> DecodeError<T> for EvmLoaderError {
+    fn type_of() -> &'static str {
+        "EvmLoaderError"
+    }
+}
+
+/// Extract the Ether address reported by `AccountMissing` from the transaction logs
+pub fn parse_missing_account<S: AsRef<str>>(logs: &[S]) -> Option<H160> {
+    logs.iter().find_map(|log| {
+        let rest = log.as_ref().strip_prefix(MISSING_ACCOUNT_LOG_PREFIX)?;
+        let address = hex::decode(rest.get(..40)?).ok()?;
+        Some(H160::from_slice(&address))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_missing_account() {
+        let address = H160::repeat_byte(0xab);
+        let logs = vec![
+            "Program log: Call done".to_string(),
+            format!("{}{} 11111111111111111111111111111111", MISSING_ACCOUNT_LOG_PREFIX, hex::encode(address.as_bytes())),
+        ];
+        assert_eq!(parse_missing_account(&logs), Some(address));
+        assert_eq!(parse_missing_account(&logs[..1]), None);
+    }
+}
@@ -2,8 +2,11 @@ use std::convert::Infallible;
 use evm_runtime::{save_return_value, save_created_address, Control};
 
 use primitive_types::{H160, H256, U256};
-use evm::{Capture, ExitError, ExitReason, ExitSucceed, ExitFatal, Handler, backend::Backend, Resolve, Code};
-use crate::executor_state::{ StackState, ExecutorState, ExecutorMetadata };
+use evm::{Capture, ExitError, ExitReason, ExitSucceed, ExitFatal, ExitRevert, Handler, backend::Backend, Resolve, Code};
+use crate::executor_state::{ StackState, ExecutorState, ExecutorMetadata, ExecutorSnapshot, FrameLog };
+use crate::gas::{GasSettlement, SSTORE_CLEARS_REFUND};
+use crate::multicall;
+use crate::profile::Profile;
 use crate::storage_account::StorageAccount;
 use crate::utils::{keccak256_h256, keccak256_h256_v};
 use std::mem;
@@ -20,22 +23,137 @@ macro_rules! try_or_fail {
     }
 }
 
+/// Maximum size of the deployed contract code (EIP-170)
+pub const MAX_CODE_SIZE: usize = 0x6000;
+
+/// Check the code returned by the contract creation: size limit (EIP-170)
+/// and no code starting with the reserved 0xEF byte (EIP-3541)
+pub fn validate_code(code: &[u8]) -> Result<(), ExitError> {
+    if code.len() > MAX_CODE_SIZE {
+        return Err(ExitError::CreateContractLimit);
+    }
+    if code.first() == Some(&0xEF) {
+        return Err(ExitError::Other("code starts with 0xEF (EIP-3541)".into()));
+    }
+
+    Ok(())
+}
+
+/// Maximum depth of the call stack, each frame keeps its own stack and memory on the BPF heap
+pub const MAX_CALL_DEPTH: usize = 64;
+
+/// Maximum memory of one call frame, expansion beyond it fails with an error
+pub const MAX_MEMORY_SIZE: usize = 128 * 1024;
+
+/// Maximum size of the data returned by a call or contract creation
+pub const MAX_RETURN_DATA_SIZE: usize = 32 * 1024;
+
+/// EVM configuration with the limits fitting the BPF heap, used by all executors
+pub const fn evm_config() -> evm::Config {
+    evm::Config {
+        call_stack_limit: MAX_CALL_DEPTH,
+        memory_limit: MAX_MEMORY_SIZE,
+        ..evm::Config::istanbul()
+    }
+}
+
+static EVM_CONFIG: evm::Config = evm_config();
+
+/// Check that memory touched by the opcode fits `MAX_MEMORY_SIZE`.
+/// Memory is expanded by the runtime, so the limit should be checked before the opcode is executed.
+fn check_memory_limit(opcode: evm::Opcode, stack: &evm::Stack) -> Result<(), ExitError> {
+    let arg = |n: usize| stack.peek(n).map(|value| U256::from_big_endian(value.as_bytes()));
+    let fixed = |n: usize, len: u64| arg(n).map(|offset| (offset, U256::from(len)));
+    let range = |n: usize, m: usize| -> Result<(U256, U256), ExitError> { Ok((arg(n)?, arg(m)?)) };
+
+    let ranges = match opcode {
+        evm::Opcode::MLOAD | evm::Opcode::MSTORE => vec![fixed(0, 32)?],
+        evm::Opcode::MSTORE8 => vec![fixed(0, 1)?],
+        evm::Opcode::SHA3 | evm::Opcode::RETURN | evm::Opcode::REVERT |
+        evm::Opcode::LOG0 | evm::Opcode::LOG1 | evm::Opcode::LOG2 | evm::Opcode::LOG3 | evm::Opcode::LOG4 => vec![range(0, 1)?],
+        evm::Opcode::CALLDATACOPY | evm::Opcode::CODECOPY | evm::Opcode::RETURNDATACOPY => vec![range(0, 2)?],
+        evm::Opcode::EXTCODECOPY => vec![range(1, 3)?],
+        evm::Opcode::CREATE | evm::Opcode::CREATE2 => vec![range(1, 2)?],
+        evm::Opcode::CALL | evm::Opcode::CALLCODE => vec![range(3, 4)?, range(5, 6)?],
+        evm::Opcode::DELEGATECALL | evm::Opcode::STATICCALL => vec![range(2, 3)?, range(4, 5)?],
+        _ => return Ok(()),
+    };
+
+    for (offset, len) in ranges {
+        if len.is_zero() {
+            continue;
+        }
+        match offset.checked_add(len) {
+            Some(end) if end <= U256::from(MAX_MEMORY_SIZE) => (),
+            _ => return Err(ExitError::Other("memory limit exceeded".into())),
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that data returned by RETURN or REVERT fits `MAX_RETURN_DATA_SIZE`
+fn check_return_data_limit(opcode: evm::Opcode, stack: &evm::Stack) -> Result<(), ExitError> {
+    if opcode != evm::Opcode::RETURN && opcode != evm::Opcode::REVERT {
+        return Ok(());
+    }
+
+    let len = U256::from_big_endian(stack.peek(1)?.as_bytes());
+    if len > U256::from(MAX_RETURN_DATA_SIZE) {
+        return Err(ExitError::Other("return data limit exceeded".into()));
+    }
+
+    Ok(())
+}
+
 fn l64(gas: u64) -> u64 {
     gas - gas / 64
 }
 
+/// Base cost of the opcode (Istanbul), without memory expansion and dynamic parts
+fn opcode_cost(opcode: evm::Opcode) -> u64 {
+    match opcode {
+        evm::Opcode::STOP | evm::Opcode::RETURN | evm::Opcode::REVERT | evm::Opcode::JUMPDEST => 1,
+        evm::Opcode::SHA3 => 30,
+        evm::Opcode::BALANCE | evm::Opcode::EXTCODESIZE | evm::Opcode::EXTCODECOPY | evm::Opcode::EXTCODEHASH => 700,
+        evm::Opcode::SLOAD => 800,
+        evm::Opcode::SSTORE => 5000,
+        evm::Opcode::LOG0 | evm::Opcode::LOG1 | evm::Opcode::LOG2 | evm::Opcode::LOG3 | evm::Opcode::LOG4 => 375,
+        evm::Opcode::CALL | evm::Opcode::CALLCODE | evm::Opcode::DELEGATECALL | evm::Opcode::STATICCALL => 700,
+        evm::Opcode::CREATE | evm::Opcode::CREATE2 => 32000,
+        evm::Opcode::SUICIDE => 5000,
+        _ => 3,
+    }
+}
+
+/// Pending call of the sub-runtime.
+/// Code is loaded from `code_address`, while storage, balance and `ADDRESS` opcode
+/// refer to `context.address`. They differ for DELEGATECALL and CALLCODE.
+#[derive(serde::Serialize, serde::Deserialize)]
 struct CallInterrupt {
     code_address : H160,
     input : Vec<u8>,
+    is_static: bool,
+    /// Value transfer (source, target, value), applied when sub-runtime starts
+    transfer: Option<(H160, H160, U256)>,
     context: evm::Context,
 }
 
+#[derive(serde::Serialize, serde::Deserialize)]
 struct CreateInterrupt {
     init_code: Vec<u8>,
     context: evm::Context,
     address: H160
 }
 
+/// Interrupt which is trapped but its sub-runtime is not started yet.
+/// It is saved with runtimes, so execution can pause between them.
+#[derive(serde::Serialize, serde::Deserialize)]
+enum PendingInterrupt {
+    Call(CallInterrupt),
+    Create(CreateInterrupt),
+}
+
 enum RuntimeApply{
     Continue,
     Call(CallInterrupt),
@@ -46,6 +164,21 @@ enum RuntimeApply{
 struct Executor<'config, B: Backend> {
     state: ExecutorState<B>,
     config: &'config evm::Config,
+    /// Per-opcode profile, enabled by the emulator only
+    profile: Option<Profile>,
+}
+
+impl<'config, B: Backend> Executor<'config, B> {
+    /// Refund of SSTORE clearing a non-zero slot, the stack is `[index, value]`
+    fn record_sstore_refund(&mut self, address: H160, stack: &evm::Stack) {
+        let (index, value) = match (stack.peek(0), stack.peek(1)) {
+            (Ok(index), Ok(value)) => (index, value),
+            _ => return,
+        };
+        if value.is_zero() && !self.state.storage(address, U256::from_big_endian(index.as_bytes())).is_zero() {
+            self.state.metadata_mut().record_refund(SSTORE_CLEARS_REFUND);
+        }
+    }
 }
 
 impl<'config, B: Backend> Handler for Executor<'config, B> {
@@ -87,7 +220,7 @@ impl<'config, B: Backend> Handler for Executor<'config, B> {
     }
 
     fn gas_left(&self) -> U256 {
-        U256::one() // U256::from(self.state.metadata().gasometer.gas())
+        U256::from(self.state.metadata().gas_left())
     }
 
     fn gas_price(&self) -> U256 {
@@ -238,7 +371,10 @@ impl<'config, B: Backend> Handler for Executor<'config, B> {
             }
         }
 
-        let hook_res = self.state.call_inner(code_address, transfer, input.clone(), target_gas, is_static, true, true);
+        // Multicall is executed by the machine, it is not a backend precompile
+        let hook_res = if code_address == multicall::address() { None } else {
+            self.state.call_inner(code_address, transfer, input.clone(), target_gas, is_static, true, true)
+        };
         if hook_res.is_some() {
             match hook_res.as_ref().unwrap() {
                 Capture::Exit((reason, _return_data)) => {
@@ -250,7 +386,17 @@ impl<'config, B: Backend> Handler for Executor<'config, B> {
             }
         }
 
-        Capture::Trap(CallInterrupt{code_address, input, context})
+        let transfer = match transfer {
+            Some(transfer) => {
+                if self.balance(transfer.source) < transfer.value {
+                    return Capture::Exit((ExitError::OutOfFund.into(), Vec::new()));
+                }
+                Some((transfer.source, transfer.target, transfer.value))
+            },
+            None => None,
+        };
+
+        Capture::Trap(CallInterrupt{code_address, input, is_static, transfer, context})
     }
 
     fn pre_validate(
@@ -259,6 +405,16 @@ impl<'config, B: Backend> Handler for Executor<'config, B> {
         opcode: evm::Opcode,
         stack: &evm::Stack,
     ) -> Result<(), ExitError> {
+        check_memory_limit(opcode, stack)?;
+        check_return_data_limit(opcode, stack)?;
+        self.state.metadata_mut().record_gas(opcode_cost(opcode))?;
+        if let Some(profile) = &mut self.profile {
+            profile.record(context.address, opcode.0, opcode_cost(opcode));
+        }
+        if opcode == evm::Opcode::SSTORE {
+            self.record_sstore_refund(context.address, stack);
+        }
+
         // if let Some(cost) = gasometer::static_opcode_cost(opcode) {
         //     self.state.metadata_mut().gasometer.record_cost(cost)?;
         // } else {
@@ -283,32 +439,51 @@ pub enum CreateReason {
 
 type runtime_info<'config> = (evm::Runtime<'config>, CreateReason);
 
+/// Multicall in progress. It is active while no runtime is pushed above `depth`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct MulticallFrame {
+    depth: usize,
+    is_static: bool,
+    aggregate: multicall::Aggregate,
+}
+
 pub struct Machine<'config, B: Backend> {
     executor: Executor<'config, B>,
-    runtime: Vec<runtime_info<'config>>
+    runtime: Vec<runtime_info<'config>>,
+    pending: Option<PendingInterrupt>,
+    multicall: Vec<MulticallFrame>,
+    /// Result of the multicall started by `call_begin`, no runtime is left to hold it
+    multicall_result: Option<Vec<u8>>,
+    /// Steps executed since the machine was created or restored
+    steps: u64,
 }
 
 impl<'config, B: Backend> Machine<'config, B> {
 
     pub fn new(state: ExecutorState<B>) -> Self {
-        let executor = Executor { state, config: evm::Config::default() };
-        Self{ executor, runtime: Vec::new() }
+        let executor = Executor { state, config: &EVM_CONFIG, profile: None };
+        Self{ executor, runtime: Vec::new(), pending: None, multicall: Vec::new(), multicall_result: None, steps: 0 }
+    }
+
+    /// Size of the storage account required to save the machine
+    pub fn required_size(&self, storage: &StorageAccount) -> Result<usize, ProgramError> {
+        storage.required_size(&(&self.runtime, &self.pending, &self.multicall), self.executor.state.substate())
     }
 
-    pub fn save_into(&self, storage: &mut StorageAccount) {
-        storage.serialize(&self.runtime, self.executor.state.substate()).unwrap();
+    pub fn save_into(&self, storage: &mut StorageAccount) -> ProgramResult {
+        storage.serialize(&(&self.runtime, &self.pending, &self.multicall), self.executor.state.substate())
     }
 
-    pub fn restore(storage: &StorageAccount, backend: B) -> Self {
-        let (runtime, substate) = storage.deserialize().unwrap();
+    pub fn restore(storage: &StorageAccount, backend: B) -> Result<Self, ProgramError> {
+        let ((runtime, pending, multicall), substate) = storage.deserialize()?;
 
         let state = ExecutorState::new(substate, backend);
 
-        let executor = Executor { state, config: evm::Config::default() };
+        let executor = Executor { state, config: &EVM_CONFIG, profile: None };
 
-        let mut s = Self{ executor, runtime };
+        let mut s = Self{ executor, runtime, pending, multicall, multicall_result: None, steps: 0 };
         s.finalize_restore();
-        s
+        Ok(s)
     }
 
     fn finalize_restore(&mut self) {
@@ -317,51 +492,67 @@ impl<'config, B: Backend> Machine<'config, B> {
         }
     }
 
+    /// Begin the call of the contract without value, see `call_begin_with_value`
     pub fn call_begin(&mut self, caller: H160, code_address: H160, input: Vec<u8>, gas_limit: u64) {
-        self.executor.state.inc_nonce(caller);
-
+        // The transfer of zero value can't fail
+        let _ = self.call_begin_with_value(caller, code_address, input, U256::zero(), gas_limit);
+    }
 
-        // let after_gas = if take_l64 && self.config.call_l64_after_gas {
-        //     if self.config.estimate {
-        //         let initial_after_gas = self.state.metadata().gasometer.gas();
-        //         let diff = initial_after_gas - l64(initial_after_gas);
-        //         try_or_fail!(self.state.metadata_mut().gasometer.record_cost(diff));
-        //         self.state.metadata().gasometer.gas()
-        //     } else {
-        //         l64(self.state.metadata().gasometer.gas())
-        //     }
-        // } else {
-        //     self.state.metadata().gasometer.gas()
-        // };
+    /// Begin the transaction calling the contract, transferring `value` to it. The call is executed by `execute`
+    /// or `execute_n_steps`. Further calls of the transaction are begun by `call_next_with_value`,
+    /// they are executed one after another on the same machine, state changes of all of them are deconstructed together.
+    /// The exit reason is returned if the call fails before the execution, the nonce stays incremented.
+    pub fn call_begin_with_value(&mut self, caller: H160, code_address: H160, input: Vec<u8>, value: U256, gas_limit: u64) -> Result<(), ExitReason> {
+        self.executor.state.inc_nonce(caller);
+        self.call_next_with_value(caller, code_address, input, value, gas_limit)
+    }
 
-        // let mut gas_limit = min(gas_limit, after_gas);
+    /// Begin the next call of the transaction begun by `call_begin_with_value`,
+    /// the nonce of the caller is incremented once per transaction
+    pub fn call_next_with_value(&mut self, caller: H160, code_address: H160, input: Vec<u8>, value: U256, gas_limit: u64) -> Result<(), ExitReason> {
+        self.drop_finished();
 
-        // try_or_fail!(
-        //     self.state.metadata_mut().gasometer.record_cost(gas_limit)
-        // );
+        if code_address == multicall::address() {
+            if !value.is_zero() {
+                return Err(ExitError::Other("multicall is not payable".into()).into());
+            }
+            let context = evm::Context{address: code_address, caller: caller, apparent_value: U256::zero()};
+            self.pending = Some(PendingInterrupt::Call(CallInterrupt{code_address, input, is_static: false, transfer: None, context}));
+            return Ok(());
+        }
 
+        self.executor.state.metadata_mut().set_gas_limit(gas_limit);
         self.executor.state.enter(gas_limit, false);
         self.executor.state.touch(code_address);
 
+        if let Err(err) = self.executor.state.transfer(evm::Transfer { source: caller, target: code_address, value }) {
+            debug_print!("call_begin() transfer error {:?}", err);
+            self.executor.state.exit_discard().unwrap();
+            return Err(err.into());
+        }
+
         let code = self.executor.code(code_address);
-        let context = evm::Context{address: code_address, caller: caller, apparent_value: U256::zero()};
+        let context = evm::Context{address: code_address, caller: caller, apparent_value: value};
 
         let runtime = evm::Runtime::new(code, input, context, &self.executor.config);
 
         self.runtime.push((runtime, CreateReason::Call));
+        Ok(())
     }
 
-    pub fn create_begin(&mut self, caller: H160, code: Vec<u8>, gas_limit: u64) -> ProgramResult {
-
-        let scheme = evm::CreateScheme::Legacy {
-            caller: caller,
-        };
-        self.executor.state.enter(u64::max_value(), false);
+    /// Begin the deployment of `init_code` at the address of the `scheme`, transferring `value` to it.
+    /// The deployment is executed by `execute` or `execute_n_steps` like a call, it's saved and restored the same way.
+    /// The exit reason is returned if the deployment fails before the execution.
+    pub fn create_begin(&mut self, caller: H160, init_code: Vec<u8>, value: U256, scheme: evm::CreateScheme, gas_limit: u64) -> Result<(), ExitReason> {
+        self.drop_finished();
+        self.executor.state.metadata_mut().set_gas_limit(gas_limit);
+        self.executor.state.enter(gas_limit, false);
 
-        match self.executor.create(caller, scheme, U256::zero(),code, None ){
-            Capture::Exit((reason, address, return_data)) => {
-                debug_print!("create_begin() error ");
-                return Err(ProgramError::InvalidInstructionData);
+        match self.executor.create(caller, scheme, value, init_code, None) {
+            Capture::Exit((reason, _, _)) => {
+                debug_print!("create_begin() error {:?}", reason);
+                self.executor.state.exit_discard().unwrap();
+                return Err(reason);
             },
             Capture::Trap(info) => {
                 self.executor.state.touch(info.address);
@@ -370,7 +561,13 @@ impl<'config, B: Backend> Machine<'config, B> {
                     self.executor.state.inc_nonce(info.address);
                 }
 
-                let mut instance = evm::Runtime::new(
+                if let Err(err) = self.executor.state.transfer(evm::Transfer { source: caller, target: info.address, value }) {
+                    debug_print!("create_begin() transfer error {:?}", err);
+                    self.executor.state.exit_discard().unwrap();
+                    return Err(err.into());
+                }
+
+                let instance = evm::Runtime::new(
                     Code::Vec { code: info.init_code },
                     Vec::new(),
                     info.context,
@@ -382,6 +579,11 @@ impl<'config, B: Backend> Machine<'config, B> {
         Ok(())
     }
 
+    /// Drop the runtime left by the finished call, it holds only the return value
+    fn drop_finished(&mut self) {
+        self.runtime.clear();
+        self.multicall_result = None;
+    }
 
     pub fn step_opcode(&mut self) -> RuntimeApply {
         if let Some(runtime) = self.runtime.last_mut() {
@@ -414,25 +616,30 @@ impl<'config, B: Backend> Machine<'config, B> {
         }
     }
 
-    pub fn step(&mut self) -> Result<(), ExitReason> {
-
-        match self.step_opcode(){
-            RuntimeApply::Continue => {return Ok(())},
-            RuntimeApply::Call(info) => {
+    fn begin_pending(&mut self, pending: PendingInterrupt) -> Result<(), ExitReason> {
+        match pending {
+            PendingInterrupt::Call(info) if info.code_address == multicall::address() => {
+                return self.multicall_begin(info);
+            },
+            PendingInterrupt::Call(info) => {
                 let code = self.executor.code(info.code_address);
-                self.executor.state.enter(u64::max_value(), false);
-                self.executor.state.touch(info.code_address);
+                self.executor.state.enter(u64::max_value(), info.is_static);
+                self.executor.state.touch(info.context.address);
 
-                let mut instance = evm::Runtime::new(
+                if let Some((source, target, value)) = info.transfer {
+                    // Balance is checked when the call is trapped
+                    self.executor.state.transfer(evm::Transfer { source, target, value }).unwrap();
+                }
+
+                let instance = evm::Runtime::new(
                     code,
                     info.input,
                     info.context,
                     &self.executor.config
                 );
                 self.runtime.push((instance, CreateReason::Call));
-                return Ok(())
             },
-            RuntimeApply::Create(info) => {
+            PendingInterrupt::Create(info) => {
                 self.executor.state.enter(u64::max_value(), false);
                 self.executor.state.touch(info.address);
                 self.executor.state.reset_storage(info.address);
@@ -440,13 +647,124 @@ impl<'config, B: Backend> Machine<'config, B> {
                     self.executor.state.inc_nonce(info.address);
                 }
 
-                let mut instance = evm::Runtime::new(
+                let instance = evm::Runtime::new(
                     Code::Vec{ code: info.init_code },
                     Vec::new(),
                     info.context,
                     &self.executor.config
                 );
                 self.runtime.push((instance, CreateReason::Create(info.address)));
+            },
+        }
+        Ok(())
+    }
+
+    fn multicall_active(&self, depth: usize) -> bool {
+        self.multicall.last().map_or(false, |frame| frame.depth == depth)
+    }
+
+    fn multicall_begin(&mut self, info: CallInterrupt) -> Result<(), ExitReason> {
+        let value = info.transfer.map_or(U256::zero(), |(_, _, value)| value);
+        let request = match multicall::Request::decode(&info.input) {
+            Some(request) if value.is_zero() => request,
+            _ => {
+                debug_print!("multicall: invalid request");
+                return self.multicall_exit(ExitReason::Revert(ExitRevert::Reverted), Vec::new());
+            }
+        };
+
+        match request {
+            multicall::Request::Query(query) => {
+                let output = query.execute(&self.executor);
+                self.multicall_exit(ExitReason::Succeed(ExitSucceed::Returned), output)
+            },
+            multicall::Request::Aggregate(aggregate) => {
+                self.executor.state.enter(u64::max_value(), info.is_static);
+                self.multicall.push(MulticallFrame{ depth: self.runtime.len(), is_static: info.is_static, aggregate });
+                Ok(())
+            },
+        }
+    }
+
+    /// Start the next call of the active multicall or finish it
+    fn multicall_continue(&mut self) -> Result<(), ExitReason> {
+        let frame = self.multicall.last().unwrap();   // checked by multicall_active()
+        if let Some((address, input)) = frame.aggregate.next_call() {
+            let is_static = frame.is_static;
+            let context = evm::Context{address, caller: multicall::address(), apparent_value: U256::zero()};
+            return match self.executor.call(address, None, input, None, is_static, context) {
+                Capture::Exit((reason, return_data)) => self.multicall_record(reason, return_data),
+                Capture::Trap(interrupt) => self.begin_pending(PendingInterrupt::Call(interrupt)),
+            };
+        }
+
+        let frame = self.multicall.pop().unwrap();
+        self.executor.state.exit_commit().unwrap();
+        let block_number = self.executor.block_number();
+        let output = frame.aggregate.encode_output(block_number, self.executor.block_hash(block_number));
+        self.multicall_exit(ExitReason::Succeed(ExitSucceed::Returned), output)
+    }
+
+    /// Save the result of the call made by the active multicall
+    fn multicall_record(&mut self, reason: ExitReason, return_data: Vec<u8>) -> Result<(), ExitReason> {
+        let return_data = match reason {
+            ExitReason::Succeed(_) | ExitReason::Revert(_) => return_data,
+            ExitReason::Error(_) => Vec::new(),
+            ExitReason::Fatal(_) => return Err(reason),
+        };
+
+        let frame = self.multicall.last_mut().unwrap();
+        let success = reason.is_succeed();
+        if !success && frame.aggregate.require_success {
+            let frame = self.multicall.pop().unwrap();
+            self.executor.state.exit_revert().unwrap();
+            return self.multicall_exit(ExitReason::Revert(ExitRevert::Reverted), frame.aggregate.failure_reason());
+        }
+
+        frame.aggregate.results.push((success, return_data));
+        Ok(())
+    }
+
+    /// Pass the result of the finished multicall to its caller
+    fn multicall_exit(&mut self, reason: ExitReason, output: Vec<u8>) -> Result<(), ExitReason> {
+        if self.multicall_active(self.runtime.len()) {
+            return self.multicall_record(reason, output);
+        }
+
+        if let Some(runtime) = self.runtime.last_mut() {
+            match save_return_value(runtime.0.borrow_mut(), reason, output, &self.executor) {
+                Control::Continue => Ok(()),
+                Control::Exit(e) => Err(e.into()),
+                _ => {
+                    debug_print!("multicall_exit: save_return_value: NotSupported");
+                    Err(ExitReason::Fatal(ExitFatal::NotSupported))
+                }
+            }
+        } else {
+            self.multicall_result = Some(output);
+            Err(reason)
+        }
+    }
+
+    pub fn step(&mut self) -> Result<(), ExitReason> {
+        self.steps += 1;
+
+        if let Some(pending) = self.pending.take() {
+            return self.begin_pending(pending);
+        }
+
+        if self.multicall_active(self.runtime.len()) {
+            return self.multicall_continue();
+        }
+
+        match self.step_opcode(){
+            RuntimeApply::Continue => {return Ok(())},
+            RuntimeApply::Call(info) => {
+                self.pending = Some(PendingInterrupt::Call(info));
+                return Ok(())
+            },
+            RuntimeApply::Create(info) => {
+                self.pending = Some(PendingInterrupt::Create(info));
                 return Ok(())
             },
             RuntimeApply::Exit(exit_reason) => {
@@ -454,7 +772,7 @@ impl<'config, B: Backend> Machine<'config, B> {
                 match &exit_reason {
                     ExitReason::Succeed(res) => {
                         exit_success = true;
-                        debug_print!(" step_opcode: ExitReason::Succeed(res)");
+                        evm_trace!(" step_opcode: ExitReason::Succeed(res)");
                         // self.executor.state.exit_commit().unwrap();
                     },
                     ExitReason::Revert(_) => {
@@ -489,6 +807,11 @@ impl<'config, B: Backend> Machine<'config, B> {
                             self.executor.state.exit_commit().unwrap();
                         }
 
+                        if self.multicall_active(self.runtime.len() - 1) {
+                            self.runtime.pop();
+                            return self.multicall_record(exit_reason, return_value);
+                        }
+
                         if (self.runtime.len() > 1){
                             self.runtime.pop();
                             if let Some(runtime) = self.runtime.last_mut(){
@@ -522,13 +845,11 @@ impl<'config, B: Backend> Machine<'config, B> {
                         let mut actual_address:Option<H160> = None;
 
                         if exit_success {
-                            if let Some(limit) = self.executor.config.create_contract_limit {
-                                if return_value.len() > limit {
-                                    debug_print!("runtime.step: Err((ExitError::CreateContractLimit.into()))");
-                                    self.executor.state.exit_discard().unwrap();
-                                    actual_reason =  ExitReason::Error(ExitError::CreateContractLimit);
-                                    commit = false;
-                                }
+                            if let Err(err) = validate_code(&return_value) {
+                                debug_print!("runtime.step: Err({:?})", err);
+                                self.executor.state.exit_discard().unwrap();
+                                actual_reason = ExitReason::Error(err);
+                                commit = false;
                             }
                             if commit{
                                 self.executor.state.exit_commit().unwrap();
@@ -587,13 +908,16 @@ impl<'config, B: Backend> Machine<'config, B> {
         for i in 0..n {
             self.step()?;
         }
-        debug_print!(" execute_n_steps OK returned ");
+        evm_debug!(" execute_n_steps OK returned ");
 
         Ok(())
     }
 
     #[must_use]
     pub fn return_value(&self) -> Vec<u8> {
+        if let Some(result) = &self.multicall_result {
+            return result.clone();
+        }
 
         if let Some(runtime) = self.runtime.last() {
             let implementation = Some(runtime.1);
@@ -610,7 +934,91 @@ impl<'config, B: Backend> Machine<'config, B> {
         Vec::new()
     }
 
+    /// Logs committed so far, to be emitted before the execution is paused
+    pub fn drain_committed_logs(&mut self) -> Vec<FrameLog> {
+        self.executor.state.drain_committed_logs()
+    }
+
+    /// Count opcodes and their modeled cost per contract from now on
+    pub fn enable_profile(&mut self) {
+        self.executor.profile.get_or_insert_with(Profile::default);
+    }
+
+    /// Profile collected since `enable_profile`, `None` if it is not enabled
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.executor.profile.take()
+    }
+
+    /// Steps executed since the machine was created or restored
+    pub fn executed_steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Gas used by the transaction, accumulated across iterations
+    pub fn used_gas(&self) -> u64 {
+        self.executor.state.metadata().used_gas()
+    }
+
+    /// Gas charged for the transaction, valid when the execution is complete
+    pub fn gas_settlement(&self) -> GasSettlement {
+        let metadata = self.executor.state.metadata();
+        GasSettlement::new(metadata.gas_limit(), metadata.used_gas(), metadata.refund())
+    }
+
+    /// Addresses read or written by the execution
+    pub fn touched_accounts(&self) -> std::collections::BTreeSet<H160> {
+        self.executor.state.touched_accounts()
+    }
+
+    /// State changed by the calls executed so far, `revert_to_snapshot` restores it
+    #[must_use]
+    pub fn snapshot(&self) -> ExecutorSnapshot {
+        self.executor.state.snapshot()
+    }
+
+    /// Discard the state changes of the calls executed after `snapshot`
+    pub fn revert_to_snapshot(&mut self, snapshot: ExecutorSnapshot) {
+        self.drop_finished();
+        self.executor.state.revert_to_snapshot(snapshot);
+    }
+
     pub fn into_state(self) -> ExecutorState<B> {
         self.executor.state
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::u256_to_h256;
+
+    #[test]
+    fn test_validate_code() {
+        assert_eq!(validate_code(&[]), Ok(()));
+        assert_eq!(validate_code(&[0x60, 0xEF]), Ok(()));
+        assert_eq!(validate_code(&vec![0u8; MAX_CODE_SIZE]), Ok(()));
+        assert_eq!(validate_code(&vec![0u8; MAX_CODE_SIZE + 1]), Err(ExitError::CreateContractLimit));
+        assert!(matches!(validate_code(&[0xEF, 0x00]), Err(ExitError::Other(_))));
+    }
+
+    fn stack_of(args: &[u64]) -> evm::Stack {
+        let mut stack = evm::Stack::new(1024);
+        for arg in args.iter().rev() {
+            stack.push(u256_to_h256(U256::from(*arg))).unwrap();
+        }
+        stack
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let limit = MAX_MEMORY_SIZE as u64;
+        assert_eq!(check_memory_limit(evm::Opcode::MSTORE, &stack_of(&[limit - 32, 0])), Ok(()));
+        assert!(check_memory_limit(evm::Opcode::MSTORE, &stack_of(&[limit - 31, 0])).is_err());
+        assert_eq!(check_memory_limit(evm::Opcode::CALLDATACOPY, &stack_of(&[u64::max_value(), 0, 0])), Ok(()));
+        assert!(check_memory_limit(evm::Opcode::CALL, &stack_of(&[0, 0, 0, 0, 0, limit, 1])).is_err());
+        assert_eq!(check_memory_limit(evm::Opcode::ADD, &stack_of(&[u64::max_value(), u64::max_value()])), Ok(()));
+
+        let limit = MAX_RETURN_DATA_SIZE as u64;
+        assert_eq!(check_return_data_limit(evm::Opcode::RETURN, &stack_of(&[0, limit])), Ok(()));
+        assert!(check_return_data_limit(evm::Opcode::REVERT, &stack_of(&[0, limit + 1])).is_err());
+    }
+}
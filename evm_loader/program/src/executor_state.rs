@@ -1,5 +1,6 @@
 use std::{
     boxed::Box,
+    cell::RefCell,
     collections::{BTreeMap, BTreeSet},
     vec::Vec
 };
@@ -8,7 +9,7 @@ use evm::backend::{Apply, Backend, Basic, Log};
 use evm::{ExitError, Transfer, Code};
 use primitive_types::{H160, H256, U256};
 use serde::{Serialize, Deserialize};
-use crate::utils::{keccak256_h256, keccak256_h256_v};
+use crate::utils::{keccak256_h256, keccak256_h256_v, u256_to_h256};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct ExecutorAccount {
@@ -17,9 +18,61 @@ struct ExecutorAccount {
     pub reset: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Value transfer registered in the substate, applied to Solana balances on deconstruct
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ExecutorTransfer {
+    source: H160,
+    target: H160,
+    value: U256,
+}
+
+/// Net change of the balance made by the value transfers of the transaction
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BalanceChange {
+    /// Value received
+    pub credit: U256,
+    /// Value sent
+    pub debit: U256,
+}
+
+impl BalanceChange {
+    /// Balance after the change, `None` if the balance with the credit can't cover the debit
+    pub fn apply_to(&self, balance: U256) -> Option<U256> {
+        balance.checked_add(self.credit)?.checked_sub(self.debit)
+    }
+}
+
+/// Value transfers of the committed frames in execution order, returned by `deconstruct`.
+/// Program and emulator settle balances from the ledger, so reported balances match on-chain results.
+#[derive(Default)]
+pub struct TransferLedger {
+    transfers: Vec<Transfer>,
+}
+
+impl TransferLedger {
+    pub fn transfers(&self) -> &[Transfer] {
+        &self.transfers
+    }
+
+    /// Net balance change of every address which sent or received value
+    pub fn balance_changes(&self) -> BTreeMap<H160, BalanceChange> {
+        let mut changes = BTreeMap::<H160, BalanceChange>::new();
+        for transfer in self.transfers.iter().filter(|transfer| !transfer.value.is_zero()) {
+            let source = changes.entry(transfer.source).or_default();
+            source.debit = source.debit.saturating_add(transfer.value);
+            let target = changes.entry(transfer.target).or_default();
+            target.credit = target.credit.saturating_add(transfer.value);
+        }
+        changes
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ExecutorMetadata {
-    // gasometer: Gasometer<'config>,
+    gas_limit: u64,
+    used_gas: u64,
+    /// Refund of the cleared storage, kept only by committed frames
+    refund: u64,
     is_static: bool,
     depth: Option<usize>
 }
@@ -27,38 +80,38 @@ pub struct ExecutorMetadata {
 impl ExecutorMetadata {
     pub fn new() -> Self {
         Self {
-            // gasometer: Gasometer::new(gas_limit, config),
+            gas_limit: u64::max_value(),
+            used_gas: 0,
+            refund: 0,
             is_static: false,
             depth: None
         }
     }
 
     pub fn swallow_commit(&mut self, other: Self) -> Result<(), ExitError> {
-        // self.gasometer.record_stipend(other.gasometer.gas())?;
-        // self.gasometer
-        //     .record_refund(other.gasometer.refunded_gas())?;
-
-        // if let Some(runtime) = self.runtime.borrow_mut().as_ref() {
-        //     let return_value = other.borrow().runtime().unwrap().machine().return_value();
-        //     runtime.set_return_data(return_value);
-        // }
-
-        Ok(())
+        self.refund = self.refund.saturating_add(other.refund);
+        self.record_gas(other.used_gas)
     }
 
     pub fn swallow_revert(&mut self, other: Self) -> Result<(), ExitError> {
-        // self.gasometer.record_stipend(other.gasometer.gas())?;
-
-        Ok(())
+        self.record_gas(other.used_gas)
     }
 
-    pub fn swallow_discard(&mut self, _other: Self) -> Result<(), ExitError> {
-        Ok(())
+    pub fn swallow_discard(&mut self, other: Self) -> Result<(), ExitError> {
+        // Failed frame consumes all gas given to it
+        self.record_gas(std::cmp::min(other.gas_limit, self.gas_left()))
     }
 
     pub fn spit_child(&self, gas_limit: u64, is_static: bool) -> Self {
+        // The transaction frame gets all the gas, a call gets all but one 64th of the remaining gas (EIP-150)
+        let available = match self.depth {
+            None => self.gas_left(),
+            Some(_) => self.gas_left() - self.gas_left() / 64,
+        };
         Self {
-            // gasometer: Gasometer::new(gas_limit, self.gasometer.config()),
+            gas_limit: std::cmp::min(gas_limit, available),
+            used_gas: 0,
+            refund: 0,
             is_static: is_static || self.is_static,
             depth: match self.depth {
                 None => Some(0),
@@ -67,13 +120,44 @@ impl ExecutorMetadata {
         }
     }
 
-    // pub fn gasometer(&self) -> &Gasometer {
-    //     &self.gasometer
-    // }
+    /// Charge gas in the current frame.
+    /// Accumulated value is saved with the substate between iterations.
+    pub fn record_gas(&mut self, cost: u64) -> Result<(), ExitError> {
+        let used_gas = self.used_gas.saturating_add(cost);
+        if used_gas > self.gas_limit {
+            self.used_gas = self.gas_limit;
+            return Err(ExitError::OutOfGas);
+        }
+
+        self.used_gas = used_gas;
+        Ok(())
+    }
+
+    /// Limit the gas of the transaction, set on the root before the transaction frame is entered
+    pub fn set_gas_limit(&mut self, gas_limit: u64) {
+        self.gas_limit = gas_limit;
+    }
+
+    pub fn gas_limit(&self) -> u64 {
+        self.gas_limit
+    }
+
+    /// Record the refund in the current frame
+    pub fn record_refund(&mut self, refund: u64) {
+        self.refund = self.refund.saturating_add(refund);
+    }
+
+    pub fn refund(&self) -> u64 {
+        self.refund
+    }
+
+    pub fn gas_left(&self) -> u64 {
+        self.gas_limit - self.used_gas
+    }
 
-    // pub fn gasometer_mut(&mut self) -> &mut Gasometer {
-    //     &mut self.gasometer
-    // }
+    pub fn used_gas(&self) -> u64 {
+        self.used_gas
+    }
 
     pub fn is_static(&self) -> bool {
         self.is_static
@@ -84,14 +168,37 @@ impl ExecutorMetadata {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Log of the transaction with its position, emitted by `OnEvent`.
+/// Logs are kept by the frame which emitted them until it exits: commit passes them to the parent
+/// after the parent's own earlier logs, revert and discard drop them with the frame.
+/// So the logs taken from the root are in the emission order of the surviving frames.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FrameLog {
+    pub log: Log,
+    /// Depth of the call frame which emitted the log, 0 for the transaction frame
+    pub depth: usize,
+    /// Number of the call frame which emitted the log in the order the frames are entered,
+    /// 0 for the transaction frame
+    pub frame: u32,
+    /// Index of the log in the transaction, assigned when the log is taken from the root substate
+    pub index: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ExecutorSubstate {
     metadata: ExecutorMetadata,
     parent: Option<Box<ExecutorSubstate>>,
-    logs: Vec<Log>,
+    logs: Vec<FrameLog>,
+    /// Number of logs taken from the root substate
+    taken_logs: u32,
+    /// Number of this frame, see `FrameLog::frame`
+    frame: u32,
+    /// Number of frames entered in the transaction, passed back to the parent on exit
+    entered_frames: u32,
     accounts: BTreeMap<H160, ExecutorAccount>,
     storages: BTreeMap<(H160, U256), U256>,
     deletes: BTreeSet<H160>,
+    transfers: Vec<ExecutorTransfer>,
 }
 
 impl ExecutorSubstate {
@@ -100,9 +207,13 @@ impl ExecutorSubstate {
             metadata: ExecutorMetadata::new(),
             parent: None,
             logs: Vec::new(),
+            taken_logs: 0,
+            frame: 0,
+            entered_frames: 0,
             accounts: BTreeMap::new(),
             storages: BTreeMap::new(),
             deletes: BTreeSet::new(),
+            transfers: Vec::new(),
         }
     }
 
@@ -120,7 +231,7 @@ impl ExecutorSubstate {
     pub fn deconstruct<B: Backend>(
         mut self,
         backend: &B,
-    ) -> (Vec::<Apply<BTreeMap<U256, U256>>>, Vec<Log>) {
+    ) -> (Vec::<Apply<BTreeMap<U256, U256>>>, Vec<FrameLog>, TransferLedger) {
         assert!(self.parent.is_none());
 
         let mut applies = Vec::<Apply<BTreeMap<U256, U256>>>::new();
@@ -166,7 +277,32 @@ impl ExecutorSubstate {
             applies.push(Apply::Delete { address });
         }
 
-        (applies, self.logs)
+        let transfers = self.transfers.into_iter()
+            .map(|t| Transfer { source: t.source, target: t.target, value: t.value })
+            .collect();
+
+        let logs = self.take_logs();
+        (applies, logs, TransferLedger { transfers })
+    }
+
+    /// Take logs which are committed into the root substate and can't be reverted anymore.
+    /// Logs of the frames still in execution are kept, so the order is preserved
+    /// when the rest is drained on the next call.
+    pub fn drain_committed_logs(&mut self) -> Vec<FrameLog> {
+        match self.parent {
+            Some(ref mut parent) => parent.drain_committed_logs(),
+            None => self.take_logs(),
+        }
+    }
+
+    /// Take logs of the root substate, numbering them in the transaction
+    fn take_logs(&mut self) -> Vec<FrameLog> {
+        let mut logs = mem::take(&mut self.logs);
+        for log in &mut logs {
+            log.index = self.taken_logs;
+            self.taken_logs = self.taken_logs.saturating_add(1);
+        }
+        logs
     }
 
     pub fn enter(&mut self, gas_limit: u64, is_static: bool) {
@@ -174,9 +310,13 @@ impl ExecutorSubstate {
             metadata: self.metadata.spit_child(gas_limit, is_static),
             parent: None,
             logs: Vec::new(),
+            taken_logs: 0,
+            frame: self.entered_frames,
+            entered_frames: self.entered_frames.saturating_add(1),
             accounts: BTreeMap::new(),
             storages: BTreeMap::new(),
             deletes: BTreeSet::new(),
+            transfers: Vec::new(),
         };
         mem::swap(&mut entering, self);
 
@@ -187,6 +327,7 @@ impl ExecutorSubstate {
         let mut exited = *self.parent.take().expect("Cannot commit on root substate");
         mem::swap(&mut exited, self);
 
+        self.entered_frames = exited.entered_frames;
         self.metadata.swallow_commit(exited.metadata)?;
         self.logs.append(&mut exited.logs);
 
@@ -215,6 +356,7 @@ impl ExecutorSubstate {
         self.accounts.append(&mut exited.accounts);
         self.storages.append(&mut exited.storages);
         self.deletes.append(&mut exited.deletes);
+        self.transfers.append(&mut exited.transfers);
 
         for (address) in &resets {
             if self.accounts.contains_key(address){
@@ -229,6 +371,8 @@ impl ExecutorSubstate {
         let mut exited = *self.parent.take().expect("Cannot discard on root substate");
         mem::swap(&mut exited, self);
 
+        // Logs of the reverted frame are dropped with it
+        self.entered_frames = exited.entered_frames;
         self.metadata.swallow_revert(exited.metadata)?;
 
         Ok(())
@@ -238,6 +382,7 @@ impl ExecutorSubstate {
         let mut exited = *self.parent.take().expect("Cannot discard on root substate");
         mem::swap(&mut exited, self);
 
+        self.entered_frames = exited.entered_frames;
         self.metadata.swallow_discard(exited.metadata)?;
 
         Ok(())
@@ -376,10 +521,11 @@ impl ExecutorSubstate {
     }
 
     pub fn log(&mut self, address: H160, topics: Vec<H256>, data: Vec<u8>) {
-        self.logs.push(Log {
-            address,
-            topics,
-            data,
+        self.logs.push(FrameLog {
+            log: Log { address, topics, data },
+            depth: self.metadata.depth().unwrap_or(0),
+            frame: self.frame,
+            index: 0,
         });
     }
 
@@ -409,6 +555,12 @@ impl ExecutorSubstate {
             target.basic.balance = target.basic.balance.saturating_add(transfer.value);
         }
 
+        self.transfers.push(ExecutorTransfer {
+            source: transfer.source,
+            target: transfer.target,
+            value: transfer.value,
+        });
+
         Ok(())
     }
 
@@ -467,9 +619,19 @@ pub trait StackState : Backend {
     fn touch(&mut self, address: H160);
 }
 
+/// State of the executor taken by `ExecutorState::snapshot`
+#[derive(Clone, Debug)]
+pub struct ExecutorSnapshot {
+    substate: ExecutorSubstate,
+    touched: BTreeSet<H160>,
+    touched_storage: BTreeSet<(H160, U256)>,
+}
+
 pub struct ExecutorState<B: Backend> {
     backend: B,
     substate: ExecutorSubstate,
+    touched: RefCell<BTreeSet<H160>>,
+    touched_storage: RefCell<BTreeSet<(H160, U256)>>,
 }
 
 impl<B: Backend> Backend for ExecutorState<B> {
@@ -502,34 +664,40 @@ impl<B: Backend> Backend for ExecutorState<B> {
     }
 
     fn exists(&self, address: H160) -> bool {
+        self.mark_touched(address);
         self.substate.known_account(address).is_some() || self.backend.exists(address)
     }
 
     fn basic(&self, address: H160) -> Basic {
+        self.mark_touched(address);
         self.substate
             .known_basic(address)
             .unwrap_or_else(|| self.backend.basic(address))
     }
 
     fn code(&self, address: H160) -> Code {
+        self.mark_touched(address);
         self.substate
             .known_code(address)
             .unwrap_or_else(|| self.backend.code(address))
     }
 
     fn code_hash(&self, address: H160) -> H256 {
+        self.mark_touched(address);
         self.substate.known_code(address)
             .map(|code| keccak256_h256(&code))
             .unwrap_or(self.backend.code_hash(address))
     }
 
     fn code_size(&self, address: H160) -> usize {
+        self.mark_touched(address);
          self.substate.known_code(address)
             .map(|code| code.len())
             .unwrap_or(self.backend.code_size(address))
     }
 
     fn storage(&self, address: H160, key: U256) -> U256 {
+        self.mark_touched_storage(address, key);
         self.substate
             .known_storage(address, key)
             .unwrap_or_else(|| self.backend.storage(address, key))
@@ -586,6 +754,7 @@ impl<B: Backend> StackState for ExecutorState<B> {
     }
 
     fn is_empty(&self, address: H160) -> bool {
+        self.mark_touched(address);
         if let Some(known_empty) = self.substate.known_empty(address) {
             return known_empty;
         }
@@ -600,18 +769,22 @@ impl<B: Backend> StackState for ExecutorState<B> {
     }
 
     fn inc_nonce(&mut self, address: H160) {
+        self.mark_touched(address);
         self.substate.inc_nonce(address, &self.backend);
     }
 
     fn set_storage(&mut self, address: H160, key: U256, value: U256) {
+        self.mark_touched_storage(address, key);
         self.substate.set_storage(address, key, value)
     }
 
     fn reset_storage(&mut self, address: H160) {
+        self.mark_touched(address);
         self.substate.reset_storage(address, &self.backend);
     }
 
     fn original_storage(&self, address: H160, key: U256) -> Option<U256> {
+        self.mark_touched_storage(address, key);
         if let Some(value) = self.substate.known_original_storage(address, key) {
             return Some(value);
         }
@@ -620,26 +793,33 @@ impl<B: Backend> StackState for ExecutorState<B> {
     }
 
     fn log(&mut self, address: H160, topics: Vec<H256>, data: Vec<u8>) {
+        self.mark_touched(address);
         self.substate.log(address, topics, data);
     }
 
     fn set_deleted(&mut self, address: H160) {
+        self.mark_touched(address);
         self.substate.set_deleted(address)
     }
 
     fn set_code(&mut self, address: H160, code: Vec<u8>) {
+        self.mark_touched(address);
         self.substate.set_code(address, code, &self.backend)
     }
 
     fn transfer(&mut self, transfer: Transfer) -> Result<(), ExitError> {
+        self.mark_touched(transfer.source);
+        self.mark_touched(transfer.target);
         self.substate.transfer(transfer, &self.backend)
     }
 
     fn reset_balance(&mut self, address: H160) {
+        self.mark_touched(address);
         self.substate.reset_balance(address, &self.backend)
     }
 
     fn touch(&mut self, address: H160) {
+        self.mark_touched(address);
         self.substate.touch(address, &self.backend)
     }
 }
@@ -649,26 +829,270 @@ impl<B: Backend> ExecutorState<B> {
         Self {
             backend,
             substate,
+            touched: RefCell::new(BTreeSet::new()),
+            touched_storage: RefCell::new(BTreeSet::new()),
         }
     }
 
+    fn mark_touched(&self, address: H160) {
+        self.touched.borrow_mut().insert(address);
+    }
+
+    fn mark_touched_storage(&self, address: H160, key: U256) {
+        self.mark_touched(address);
+        self.touched_storage.borrow_mut().insert((address, key));
+    }
+
+    /// Every address read or written since the state was created
+    pub fn touched_accounts(&self) -> BTreeSet<H160> {
+        self.touched.borrow().clone()
+    }
+
+    /// EIP-2930 access list: every touched address with the storage keys read or written,
+    /// ordered by address and key
+    pub fn access_list(&self) -> Vec<(H160, Vec<H256>)> {
+        let touched_storage = self.touched_storage.borrow();
+        self.touched.borrow().iter()
+            .map(|&address| {
+                let keys = touched_storage.range((address, U256::zero())..=(address, U256::max_value()))
+                    .map(|&(_, key)| u256_to_h256(key))
+                    .collect();
+                (address, keys)
+            })
+            .collect()
+    }
+
     pub fn substate(&self) -> &ExecutorSubstate {
         &self.substate
     }
 
+    /// Copy of the substate and the touched accounts, `revert_to_snapshot` restores them
+    #[must_use]
+    pub fn snapshot(&self) -> ExecutorSnapshot {
+        ExecutorSnapshot {
+            substate: self.substate.clone(),
+            touched: self.touched.borrow().clone(),
+            touched_storage: self.touched_storage.borrow().clone(),
+        }
+    }
+
+    /// Restore the state taken by `snapshot`, changes made after it are discarded
+    pub fn revert_to_snapshot(&mut self, snapshot: ExecutorSnapshot) {
+        self.substate = snapshot.substate;
+        *self.touched.borrow_mut() = snapshot.touched;
+        *self.touched_storage.borrow_mut() = snapshot.touched_storage;
+    }
+
     #[must_use]
     pub fn deconstruct(
         self,
-    ) -> (B, (Vec::<Apply<BTreeMap<U256, U256>>>, Vec<Log>)) {
-        let (applies, logs) = self.substate.deconstruct(&self.backend);
-        (self.backend, (applies, logs))
+    ) -> (B, (Vec::<Apply<BTreeMap<U256, U256>>>, Vec<FrameLog>, TransferLedger)) {
+        let (applies, logs, transfers) = self.substate.deconstruct(&self.backend);
+        (self.backend, (applies, logs, transfers))
     }
 
     pub fn withdraw(&mut self, address: H160, value: U256) -> Result<(), ExitError> {
+        self.mark_touched(address);
         self.substate.withdraw(address, value, &self.backend)
     }
 
+    pub fn drain_committed_logs(&mut self) -> Vec<FrameLog> {
+        self.substate.drain_committed_logs()
+    }
+
     pub fn deposit(&mut self, address: H160, value: U256) {
+        self.mark_touched(address);
         self.substate.deposit(address, value, &self.backend)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Backend without accounts
+    struct EmptyBackend;
+
+    impl Backend for EmptyBackend {
+        fn gas_price(&self) -> U256 { U256::zero() }
+        fn origin(&self) -> H160 { H160::zero() }
+        fn block_hash(&self, _number: U256) -> H256 { H256::zero() }
+        fn block_number(&self) -> U256 { U256::zero() }
+        fn block_coinbase(&self) -> H160 { H160::zero() }
+        fn block_timestamp(&self) -> U256 { U256::zero() }
+        fn block_difficulty(&self) -> U256 { U256::zero() }
+        fn block_gas_limit(&self) -> U256 { U256::zero() }
+        fn chain_id(&self) -> U256 { U256::zero() }
+        fn exists(&self, _address: H160) -> bool { false }
+        fn basic(&self, _address: H160) -> Basic { Basic { balance: U256::zero(), nonce: U256::zero() } }
+        fn code(&self, _address: H160) -> Code { Code::Vec { code: Vec::new() } }
+        fn code_hash(&self, _address: H160) -> H256 { keccak256_h256(&[]) }
+        fn code_size(&self, _address: H160) -> usize { 0 }
+        fn storage(&self, _address: H160, _key: U256) -> U256 { U256::zero() }
+        fn create(&self, _scheme: &evm::CreateScheme, _address: &H160) {}
+        fn call_inner(&self,
+            _code_address: H160,
+            _transfer: Option<evm::Transfer>,
+            _input: Vec<u8>,
+            _target_gas: Option<usize>,
+            _is_static: bool,
+            _take_l64: bool,
+            _take_stipend: bool,
+        ) -> Option<evm::Capture<(evm::ExitReason, Vec<u8>), std::convert::Infallible>> {
+            None
+        }
+        fn keccak256_h256(&self, data: &[u8]) -> H256 { keccak256_h256(data) }
+        fn keccak256_h256_v(&self, data: &[&[u8]]) -> H256 { keccak256_h256_v(data) }
+    }
+
+    #[test]
+    fn test_revert_to_snapshot() {
+        let contract = H160::repeat_byte(1);
+        let caller = H160::repeat_byte(2);
+        let mut state = ExecutorState::new(ExecutorSubstate::new(), EmptyBackend);
+        state.set_storage(contract, U256::one(), U256::from(1));
+        let snapshot = state.snapshot();
+
+        state.set_storage(contract, U256::one(), U256::from(2));
+        state.inc_nonce(caller);
+        assert!(state.touched_accounts().contains(&caller));
+        assert_eq!(state.storage(contract, U256::one()), U256::from(2));
+
+        state.revert_to_snapshot(snapshot.clone());
+        assert!(!state.touched_accounts().contains(&caller));
+        assert_eq!(state.storage(contract, U256::one()), U256::from(1));
+        assert_eq!(state.basic(caller).nonce, U256::zero());
+
+        // The snapshot stays valid after the revert
+        state.set_storage(contract, U256::one(), U256::from(3));
+        state.revert_to_snapshot(snapshot);
+        assert_eq!(state.storage(contract, U256::one()), U256::from(1));
+    }
+
+    #[test]
+    fn test_gas_accumulates_in_parent() {
+        let mut substate = ExecutorSubstate::new();
+        substate.enter(1_000, false);
+        substate.metadata_mut().record_gas(300).unwrap();
+        substate.enter(1_000, false);
+        substate.metadata_mut().record_gas(200).unwrap();
+
+        substate.exit_commit().unwrap();
+        assert_eq!(substate.metadata().used_gas(), 500);
+
+        substate.exit_revert().unwrap();
+        assert_eq!(substate.metadata().used_gas(), 500);
+    }
+
+    #[test]
+    fn test_gas_survives_pause_and_resume() {
+        let mut substate = ExecutorSubstate::new();
+        substate.enter(50_000, false);
+        substate.metadata_mut().record_gas(20_000).unwrap();
+
+        let data = bincode::serialize(&substate).unwrap();
+        let mut substate: ExecutorSubstate = bincode::deserialize(&data).unwrap();
+        assert_eq!(substate.metadata().used_gas(), 20_000);
+
+        substate.metadata_mut().record_gas(20_000).unwrap();
+        assert_eq!(substate.metadata_mut().record_gas(20_000), Err(ExitError::OutOfGas));
+        assert_eq!(substate.metadata().gas_left(), 0);
+    }
+
+    #[test]
+    fn test_refund_kept_by_commit_only() {
+        let mut substate = ExecutorSubstate::new();
+        substate.metadata_mut().set_gas_limit(100_000);
+        substate.enter(u64::max_value(), false);
+        assert_eq!(substate.metadata().gas_left(), 100_000);
+
+        substate.enter(10_000, false);
+        substate.metadata_mut().record_refund(4_800);
+        substate.exit_revert().unwrap();
+        assert_eq!(substate.metadata().refund(), 0);
+
+        substate.enter(10_000, false);
+        substate.metadata_mut().record_refund(4_800);
+        substate.exit_commit().unwrap();
+        substate.exit_commit().unwrap();
+        assert_eq!(substate.metadata().refund(), 4_800);
+    }
+
+    #[test]
+    fn test_logs_are_tagged_with_depth_and_index() {
+        let address = H160::repeat_byte(1);
+        let mut substate = ExecutorSubstate::new();
+        substate.enter(10_000, false);
+        substate.log(address, Vec::new(), vec![0]);
+        substate.enter(1_000, false);
+        substate.log(address, Vec::new(), vec![1]);
+        substate.exit_commit().unwrap();
+        substate.exit_commit().unwrap();
+
+        let logs = substate.drain_committed_logs();
+        assert_eq!(logs.iter().map(|l| (l.depth, l.index)).collect::<Vec<_>>(), vec![(0, 0), (1, 1)]);
+
+        substate.enter(10_000, false);
+        substate.log(address, Vec::new(), vec![2]);
+        substate.exit_commit().unwrap();
+        let logs = substate.drain_committed_logs();
+        assert_eq!(logs[0].index, 2);
+        assert_eq!(logs[0].log.data, vec![2]);
+    }
+
+    #[test]
+    fn test_reverted_frame_logs_are_dropped() {
+        let address = H160::repeat_byte(1);
+        let mut substate = ExecutorSubstate::new();
+        substate.enter(10_000, false);
+        substate.log(address, Vec::new(), vec![0]);
+
+        substate.enter(1_000, false);
+        substate.log(address, Vec::new(), vec![1]);
+        substate.enter(1_000, false);
+        substate.log(address, Vec::new(), vec![2]);
+        substate.exit_commit().unwrap();
+        substate.exit_revert().unwrap();
+
+        substate.enter(1_000, false);
+        substate.log(address, Vec::new(), vec![3]);
+        substate.exit_discard().unwrap();
+
+        substate.enter(1_000, false);
+        substate.log(address, Vec::new(), vec![4]);
+        substate.exit_commit().unwrap();
+        substate.log(address, Vec::new(), vec![5]);
+        substate.exit_commit().unwrap();
+
+        let logs = substate.drain_committed_logs();
+        let tags = logs.iter().map(|l| (l.log.data[0], l.depth, l.frame, l.index)).collect::<Vec<_>>();
+        assert_eq!(tags, vec![(0, 0, 0, 0), (4, 1, 4, 1), (5, 0, 0, 2)]);
+    }
+
+    #[test]
+    fn test_balance_changes() {
+        let (a, b, c) = (H160::repeat_byte(1), H160::repeat_byte(2), H160::repeat_byte(3));
+        let ledger = TransferLedger { transfers: vec![
+            Transfer { source: a, target: b, value: U256::from(100) },
+            Transfer { source: b, target: c, value: U256::from(30) },
+            Transfer { source: c, target: a, value: U256::zero() },
+        ] };
+
+        let changes = ledger.balance_changes();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[&a], BalanceChange { credit: U256::zero(), debit: U256::from(100) });
+        assert_eq!(changes[&b].apply_to(U256::zero()), Some(U256::from(70)));
+        assert_eq!(changes[&c].apply_to(U256::from(5)), Some(U256::from(35)));
+        assert_eq!(changes[&a].apply_to(U256::from(99)), None);
+    }
+
+    #[test]
+    fn test_discard_consumes_child_gas() {
+        let mut substate = ExecutorSubstate::new();
+        substate.enter(10_000, false);
+        substate.enter(3_000, false);
+        substate.metadata_mut().record_gas(100).unwrap();
+
+        substate.exit_discard().unwrap();
+        assert_eq!(substate.metadata().used_gas(), 3_000);
+    }
+}
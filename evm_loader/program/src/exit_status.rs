@@ -0,0 +1,73 @@
+//! Stable codes of the EVM exit reasons
+//!
+//! The code is the status of `OnReturn` and of the receipt, the emulator reports it too,
+//! so clients classify failures the same way for executed and emulated transactions.
+
+use evm::{ExitReason, ExitSucceed, ExitError, ExitRevert, ExitFatal};
+
+/// Exit code and description of the exit reason
+pub fn exit_status_info(exit_reason: &ExitReason) -> (u8, &'static str) {
+    match exit_reason {
+        ExitReason::Succeed(success_code) => match success_code {
+            ExitSucceed::Stopped => (0x11, "Machine encountered an explict stop."),
+            ExitSucceed::Returned => (0x12, "Machine encountered an explict return."),
+            ExitSucceed::Suicided => (0x13, "Machine encountered an explict suicide."),
+        },
+        ExitReason::Error(error_code) => match error_code {
+            ExitError::StackUnderflow => (0xe1, "Trying to pop from an empty stack."),
+            ExitError::StackOverflow => (0xe2, "Trying to push into a stack over stack limit."),
+            ExitError::InvalidJump => (0xe3, "Jump destination is invalid."),
+            ExitError::InvalidRange => (0xe4, "An opcode accesses memory region, but the region is invalid."),
+            ExitError::DesignatedInvalid => (0xe5, "Encountered the designated invalid opcode."),
+            ExitError::CallTooDeep => (0xe6, "Call stack is too deep (runtime)."),
+            ExitError::CreateCollision => (0xe7, "Create opcode encountered collision (runtime)."),
+            ExitError::CreateContractLimit => (0xe8, "Create init code exceeds limit (runtime)."),
+            ExitError::OutOfOffset => (0xe9, "An opcode accesses external information, but the request is off offset limit (runtime)."),
+            ExitError::OutOfGas => (0xea, "Execution runs out of gas (runtime)."),
+            ExitError::OutOfFund => (0xeb, "Not enough fund to start the execution (runtime)."),
+            ExitError::PCUnderflow => (0xec, "PC underflowed (unused)."),
+            ExitError::CreateEmpty => (0xed, "Attempt to create an empty account (runtime, unused)."),
+            ExitError::Other(_) => (0xee, "Other normal errors."),
+        },
+        ExitReason::Revert(revert_code) => match revert_code {
+            ExitRevert::Reverted => (0xd0, "Machine encountered an explict revert."),
+        },
+        ExitReason::Fatal(fatal_code) => match fatal_code {
+            ExitFatal::NotSupported => (0xf1, "The operation is not supported."),
+            ExitFatal::UnhandledInterrupt => (0xf2, "The trap (interrupt) is unhandled."),
+            ExitFatal::CallErrorAsFatal(_) => (0xf3, "The environment explictly set call errors as fatal error."),
+            ExitFatal::Other(_) => (0xf4, "Other fatal errors."),
+        },
+    }
+}
+
+/// Exit code of the exit reason: `0x1*` succeed, `0xd0` revert, `0xe*` error, `0xf*` fatal
+pub fn exit_status(exit_reason: &ExitReason) -> u8 {
+    exit_status_info(exit_reason).0
+}
+
+/// Kind of the exit reason: "succeed", "revert", "error" or "fatal"
+pub fn exit_kind(exit_reason: &ExitReason) -> &'static str {
+    match exit_reason {
+        ExitReason::Succeed(_) => "succeed",
+        ExitReason::Error(_) => "error",
+        ExitReason::Revert(_) => "revert",
+        ExitReason::Fatal(_) => "fatal",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_exit_status() {
+        assert_eq!(exit_status(&ExitReason::Succeed(ExitSucceed::Returned)), 0x12);
+        assert_eq!(exit_status(&ExitReason::Revert(ExitRevert::Reverted)), 0xd0);
+        assert_eq!(exit_status(&ExitReason::Error(ExitError::OutOfGas)), 0xea);
+        assert_eq!(exit_status(&ExitReason::Error(ExitError::Other(Cow::from("custom")))), 0xee);
+        assert_eq!(exit_status(&ExitReason::Fatal(ExitFatal::NotSupported)), 0xf1);
+        assert_eq!(exit_kind(&ExitReason::Fatal(ExitFatal::NotSupported)), "fatal");
+    }
+}
@@ -0,0 +1,100 @@
+//! Settlement of the transaction gas
+//!
+//! The sender prepays `gas_limit` at the gas price, refunds of the cleared storage are
+//! capped by a fifth of the used gas (EIP-3529), and the unused part of the prepaid gas
+//! is credited back. Program and emulator share the arithmetic, so estimates match charges.
+
+use primitive_types::U256;
+
+/// Refund for setting a non-zero storage slot to zero (EIP-3529)
+pub const SSTORE_CLEARS_REFUND: u64 = 4_800;
+/// Refunds are capped by `used_gas / MAX_REFUND_QUOTIENT` (EIP-3529)
+pub const MAX_REFUND_QUOTIENT: u64 = 5;
+
+/// Gas charged on top of the used gas when the iterative execution is cancelled
+pub const CANCEL_PENALTY: u64 = 5_000;
+
+/// Gas limit of the transaction, saturated to `u64`
+pub fn gas_limit(trx_gas_limit: U256) -> u64 {
+    if trx_gas_limit > U256::from(u64::max_value()) {
+        u64::max_value()
+    } else {
+        trx_gas_limit.as_u64()
+    }
+}
+
+/// Gas charged from the sender at the end of the transaction
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GasSettlement {
+    /// Gas prepaid by the sender
+    pub gas_limit: u64,
+    /// Gas charged: used by the execution less the refund
+    pub gas_used: u64,
+    /// Refund applied, after the cap
+    pub refunded: u64,
+}
+
+impl GasSettlement {
+    pub fn new(gas_limit: u64, used_gas: u64, refund: u64) -> Self {
+        let refunded = refund.min(used_gas / MAX_REFUND_QUOTIENT);
+        Self { gas_limit, gas_used: used_gas - refunded, refunded }
+    }
+
+    /// Settlement of the cancelled execution: the penalty is charged as used gas, within the gas limit
+    pub fn with_penalty(&self, penalty: u64) -> Self {
+        let gas_used = self.gas_used.saturating_add(penalty).min(self.gas_limit);
+        Self { gas_used, ..*self }
+    }
+
+    /// Prepaid gas credited back to the sender
+    pub fn unused(&self) -> u64 {
+        self.gas_limit.saturating_sub(self.gas_used)
+    }
+
+    /// Lamports the sender must hold to prepay the gas limit, `None` on overflow
+    pub fn prepaid_lamports(&self, lamports_per_gas: u64) -> Option<u64> {
+        self.gas_limit.checked_mul(lamports_per_gas)
+    }
+
+    /// Lamports charged for the gas used, `None` on overflow
+    pub fn charged_lamports(&self, lamports_per_gas: u64) -> Option<u64> {
+        self.gas_used.checked_mul(lamports_per_gas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refund_is_capped() {
+        let settlement = GasSettlement::new(100_000, 50_000, SSTORE_CLEARS_REFUND);
+        assert_eq!(settlement.refunded, SSTORE_CLEARS_REFUND);
+        assert_eq!(settlement.gas_used, 50_000 - SSTORE_CLEARS_REFUND);
+        assert_eq!(settlement.unused(), 100_000 - 50_000 + SSTORE_CLEARS_REFUND);
+
+        let settlement = GasSettlement::new(100_000, 20_000, 3 * SSTORE_CLEARS_REFUND);
+        assert_eq!(settlement.refunded, 4_000);
+        assert_eq!(settlement.gas_used, 16_000);
+        assert_eq!(settlement.charged_lamports(10), Some(160_000));
+        assert_eq!(settlement.prepaid_lamports(10), Some(1_000_000));
+    }
+
+    #[test]
+    fn test_penalty_within_gas_limit() {
+        let settlement = GasSettlement::new(100_000, 50_000, 0).with_penalty(CANCEL_PENALTY);
+        assert_eq!(settlement.gas_used, 50_000 + CANCEL_PENALTY);
+        assert_eq!(settlement.unused(), 100_000 - 50_000 - CANCEL_PENALTY);
+
+        let settlement = GasSettlement::new(52_000, 50_000, 0).with_penalty(CANCEL_PENALTY);
+        assert_eq!(settlement.gas_used, 52_000);
+        assert_eq!(settlement.unused(), 0);
+    }
+
+    #[test]
+    fn test_gas_limit() {
+        assert_eq!(gas_limit(U256::from(21_000)), 21_000);
+        assert_eq!(gas_limit(U256::max_value()), u64::max_value());
+        assert_eq!(GasSettlement::new(u64::max_value(), 1, 0).prepaid_lamports(2), None);
+    }
+}
@@ -1,7 +1,9 @@
-use primitive_types::U256;
+use primitive_types::{H256, U256};
 use arrayref::{array_ref, array_mut_ref, mut_array_refs};
 use std::mem::size_of;
+use std::collections::BTreeMap;
 use solana_program::program_error::ProgramError;
+use crate::utils::{keccak256_h256, keccak256_h256_v, u256_to_h256};
 
 /*
 #[derive(Debug)]
@@ -33,9 +35,122 @@ enum ItemType {
     Array {pos: u32},
 }
 
+/// Space usage of the storage reported by `Hamt::verify`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HamtUsage {
+    /// Number of stored values
+    pub items: u32,
+    /// Bytes of the reachable items and arrays
+    pub used: u32,
+    /// Bytes of the released arrays in the free lists
+    pub free: u32,
+}
+
+/// Node on the path of the storage proof, from the root down to the item
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProofNode {
+    /// Hashes of the 32 root slots
+    Root(Vec<H256>),
+    /// Tags of the array and hashes of its items in the tag order
+    Array {tags: u32, children: Vec<H256>},
+}
+
+/// Merkle proof of the storage value against `Hamt::root_hash`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageProof {
+    pub nodes: Vec<ProofNode>,
+    /// Item at the end of the path: the requested key, or another key with the same prefix
+    /// proving the absence of the requested one
+    pub item: Option<(U256, U256)>,
+}
+
+/// Item: the rest of the key and the value, little-endian, followed by the cached `item_hash`
+const ITEM_SIZE: u32 = 32 + 32 + 32;
+/// Array header: tags followed by the cached `array_hash`, pointers of the children follow it
+const ARRAY_HEADER: u32 = 4 + 32;
+
+/// Size of the item (type 0) or of the array of `item_type` children
+fn node_size(item_type: u8) -> u32 {
+    match item_type {
+        0 => ITEM_SIZE,
+        _ => ARRAY_HEADER + item_type as u32 * 4,
+    }
+}
+
+/// Hash of the item: `keccak256(0x01, key, value)`, big-endian
+fn item_hash(key: U256, value: U256) -> H256 {
+    keccak256_h256_v(&[&[1u8], u256_to_h256(key).as_bytes(), u256_to_h256(value).as_bytes()])
+}
+
+/// Hash of the array: `keccak256(0x02, tags, children)`, tags little-endian
+fn array_hash(tags: u32, children: &[H256]) -> H256 {
+    let mut data = Vec::with_capacity(1 + 4 + children.len() * 32);
+    data.push(2u8);
+    data.extend_from_slice(&tags.to_le_bytes());
+    for child in children {
+        data.extend_from_slice(child.as_bytes());
+    }
+    keccak256_h256(&data)
+}
+
+/// Hash of the root: `keccak256(slots)`, zero for the empty storage
+fn root_hash(slots: &[H256]) -> H256 {
+    if slots.iter().all(|slot| slot.is_zero()) {
+        return H256::zero();
+    }
+    keccak256_h256_v(&slots.iter().map(|slot| slot.as_bytes()).collect::<Vec<_>>())
+}
+
+/// First key of the variable-length value: `keccak256(key)`, big-endian
+fn bytes_base(key: U256) -> U256 {
+    U256::from_big_endian(keccak256_h256(u256_to_h256(key).as_bytes()).as_bytes())
+}
+
+/// Value of the key proven by the proof against the root: zero if the key is absent,
+/// `None` if the proof is invalid
+pub fn verify_proof(root: H256, key: U256, proof: &StorageProof) -> Option<U256> {
+    let mut expected = root;
+    let mut rest = key;
+    for (level, node) in proof.nodes.iter().enumerate() {
+        let tag = rest.low_u32() & 0b11111;
+        rest = rest >> 5;
+        expected = match node {
+            ProofNode::Root(slots) if level == 0 && slots.len() == 32 => {
+                if root_hash(slots) != expected { return None; }
+                slots[tag as usize]
+            },
+            ProofNode::Array{tags, children} if level > 0 && tags.count_ones() as usize == children.len() => {
+                if array_hash(*tags, children) != expected { return None; }
+                if tags & (1 << tag) == 0 {
+                    return if level + 1 == proof.nodes.len() && proof.item.is_none() { Some(U256::zero()) } else { None };
+                }
+                children[(tags & ((1 << tag) - 1)).count_ones() as usize]
+            },
+            _ => return None,
+        };
+    }
+
+    match proof.item {
+        None if expected.is_zero() => Some(U256::zero()),
+        Some((item_key, value)) if item_hash(item_key, value) == expected => {
+            let levels = proof.nodes.len();
+            let same_path = (0..levels).all(|level| (item_key >> (5 * level)).low_u32() & 0b11111 == (key >> (5 * level)).low_u32() & 0b11111);
+            match (same_path, item_key == key) {
+                (true, true) => Some(value),
+                (true, false) => Some(U256::zero()),
+                _ => None,
+            }
+        },
+        _ => None,
+    }
+}
+
 impl<'a> Hamt<'a> {
+    /// Length of the header: the free lists and the root slots
+    pub const HEADER_LEN: usize = size_of::<u32>() * 32 * 2;
+
     pub fn new(data: &'a mut [u8], reset: bool) -> Result<Self, ProgramError> {
-        let header_len = size_of::<u32>() * 32 * 2;
+        let header_len = Self::HEADER_LEN;
 
         if data.len() < header_len {
             return Err(ProgramError::AccountDataTooSmall);
@@ -58,10 +173,7 @@ impl<'a> Hamt<'a> {
 
     fn allocate_item(&mut self, item_type: u8) -> Result<u32, ProgramError> {
         let free_pos = item_type as u32 * size_of::<u32>() as u32;
-        let size:u32 = match item_type {
-            0 => (256+256)/8,
-            _ => (4+item_type as u32 * 4),
-        };
+        let size = node_size(item_type);
         if item_type < 32 && item_type > 0 {
             let item_pos = self.restore_u32(free_pos);
             if item_pos != 0 {
@@ -84,33 +196,47 @@ impl<'a> Hamt<'a> {
     fn release_item(&mut self, item_type: u8, item_pos: u32) {
         let free_pos = item_type as u32 * size_of::<u32>() as u32;
         if item_type >= 32 || item_type == 0 {panic!("Release unreleased items");};
-        let size:u32 = match item_type {
-            0 => (256+256)/8,
-            _ => (4+item_type as u32 * 4),
-        };
+        let size = node_size(item_type);
         self.save_u32(item_pos, self.restore_u32(free_pos));
         self.save_u32(free_pos, item_pos);
         self.used -= size;
     }
 
-    fn place_item(&mut self, key: U256, value: U256) -> Result<u32, ProgramError> {
+    /// Place the item, `key` is the rest of `full_key` after the tags of the path
+    fn place_item(&mut self, key: U256, value: U256, full_key: U256) -> Result<u32, ProgramError> {
         let pos = self.allocate_item(0)?;
         let ptr = array_mut_ref![self.data, pos as usize, 256/8*2];
         key.to_little_endian(&mut ptr[..256/8]);
         value.to_little_endian(&mut ptr[256/8..]);
+        self.save_hash(pos + 64, &item_hash(full_key, value));
         Ok(pos | 1)
     }
 
     fn place_items2(&mut self, tags: u32, item1: u32, item2: u32) -> Result<u32, ProgramError> {
         let pos = self.allocate_item(2)?;
-        let ptr = array_mut_ref![self.data, pos as usize, 3*4];
-        let (tags_ptr, item1_ptr, item2_ptr) = mut_array_refs!(ptr, 4, 4, 4);
-        *tags_ptr = tags.to_le_bytes();
+        self.save_u32(pos, tags);
+        let ptr = array_mut_ref![self.data, (pos + ARRAY_HEADER) as usize, 2*4];
+        let (item1_ptr, item2_ptr) = mut_array_refs!(ptr, 4, 4);
         *item1_ptr = item1.to_le_bytes();
         *item2_ptr = item2.to_le_bytes();
+        self.update_array_hash(pos);
         Ok(pos)
     }
 
+    fn restore_hash(&self, pos: u32) -> H256 {
+        H256::from_slice(array_ref![self.data, pos as usize, 32])
+    }
+
+    fn save_hash(&mut self, pos: u32, hash: &H256) {
+        array_mut_ref![self.data, pos as usize, 32].copy_from_slice(hash.as_bytes());
+    }
+
+    /// Recompute the cached hash of the array from the cached hashes of its children
+    fn update_array_hash(&mut self, pos: u32) {
+        let hash = array_hash(self.restore_u32(pos), &self.array_children(pos));
+        self.save_hash(pos + 4, &hash);
+    }
+
     fn restore_value(&self, pos: u32) -> U256 {
         let ptr = array_ref![self.data, pos as usize, size_of::<U256>()];
         //println!("Restore value from {:x?}: {:x?}", pos, &ptr[..]);
@@ -144,27 +270,31 @@ impl<'a> Hamt<'a> {
         }
     }
 
+    /// Insert or update the value. Cached hashes are updated along the path of the key only,
+    /// so the cost doesn't depend on the number of stored items.
     pub fn insert(&mut self, key: U256, value: U256) -> Result<(), ProgramError> {
-        let (key, tag) = (key >> 5, key.low_u32() & 0b11111);
+        let (rest, tag) = (key >> 5, key.low_u32() & 0b11111);
         let ptr_pos = 32*4 + tag * 4;
-        let res = self.insert_item(ptr_pos, key, value);
+        let res = self.insert_item(ptr_pos, rest, value, key);
         if let Ok(_) = res {self.item_count += 1;};
         res
     }
 
-    fn insert_item(&mut self, ptr_pos: u32, key: U256, value: U256) -> Result<(), ProgramError> {
+    fn insert_item(&mut self, ptr_pos: u32, key: U256, value: U256, full_key: U256) -> Result<(), ProgramError> {
         match self.get_item(ptr_pos) {
             ItemType::Empty => {
-                let item_pos = self.place_item(key, value)?;
+                let item_pos = self.place_item(key, value, full_key)?;
                 self.save_u32(ptr_pos, item_pos);
             },
             ItemType::Item{pos} => {
                 let old_key = self.restore_value(pos);
                 if old_key == key {
                     self.save_value(pos+size_of::<U256>() as u32, &value);
+                    self.save_hash(pos + 64, &item_hash(full_key, value));
                     return Ok(());
                 } else {
                     let mut ptr_pos = ptr_pos;
+                    let mut chain = Vec::new();
                     let (mut old_key, mut old_tag) = (old_key >> 5, old_key.low_u32() & 0b11111);
                     let (mut new_key, mut new_tag) = (key >> 5, key.low_u32() & 0b11111);
                     loop {
@@ -173,12 +303,14 @@ impl<'a> Hamt<'a> {
 
                         self.save_u32(array_pos, 1<<old_tag);
                         self.save_u32(ptr_pos, array_pos);
-                        ptr_pos = array_pos+4;
+                        chain.push(array_pos);
+                        ptr_pos = array_pos + ARRAY_HEADER;
                         old_tag = old_key.low_u32() & 0b11111; old_key = old_key >> 5;
                         new_tag = new_key.low_u32() & 0b11111; new_key = new_key >> 5;
                     }
 
-                    let item_pos = self.place_item(new_key, value)?;
+                    // The full key of the old item is the same, so its cached hash stays valid
+                    let item_pos = self.place_item(new_key, value, full_key)?;
                     self.save_value(pos, &(old_key));
 
                     let tags = (1 << old_tag) | (1 << new_tag);
@@ -186,6 +318,9 @@ impl<'a> Hamt<'a> {
 
                     let array_pos = self.place_items2(tags, item1_pos, item2_pos)?;
                     self.save_u32(ptr_pos, array_pos);
+                    for array_pos in chain.into_iter().rev() {
+                        self.update_array_hash(array_pos);
+                    }
                 }
                 return Ok(());
             },
@@ -198,17 +333,20 @@ impl<'a> Hamt<'a> {
                     let shift = (tags & ((1 << tag)-1)).count_ones();
                     let (before_bytes, after_bytes) = (shift*4, (total-shift)*4);
                     let array_pos = self.allocate_item((total+1) as u8)?;
-                    let item_pos = self.place_item(key, value)?;
+                    let item_pos = self.place_item(key, value, full_key)?;
+                    let (from, to) = (pos + ARRAY_HEADER, array_pos + ARRAY_HEADER);
                     self.save_u32(array_pos, tags | (1<<tag));
-                    self.data.copy_within((pos+4) as usize..(pos+4+before_bytes) as usize, (array_pos+4) as usize);
-                    self.save_u32(array_pos+4 + before_bytes, item_pos);
-                    self.data.copy_within((pos+4+before_bytes) as usize..(pos+4+before_bytes+after_bytes) as usize, (array_pos+before_bytes+8) as usize);
+                    self.data.copy_within(from as usize..(from+before_bytes) as usize, to as usize);
+                    self.save_u32(to + before_bytes, item_pos);
+                    self.data.copy_within((from+before_bytes) as usize..(from+before_bytes+after_bytes) as usize, (to+before_bytes+4) as usize);
                     self.release_item(total as u8, pos);
                     self.save_u32(ptr_pos, array_pos);
+                    self.update_array_hash(array_pos);
                 } else {
                     // item with this tag already exist in array
                     let shift = (tags & ((1 << tag)-1)).count_ones();
-                    return self.insert_item(pos+4 + shift*4, key, value);
+                    self.insert_item(pos + ARRAY_HEADER + shift*4, key, value, full_key)?;
+                    self.update_array_hash(pos);
                 }
             },
 
@@ -216,6 +354,218 @@ impl<'a> Hamt<'a> {
         Ok(())
     }
 
+    /// Length of the allocated part of the data, header included. Offsets are relative
+    /// to the start of the data, so this prefix can be moved as a whole.
+    pub fn allocated_len(&self) -> usize {
+        self.last_used as usize
+    }
+
+    /// Merkle commitment of the storage: the hash of the cached hashes of the root slots
+    pub fn root_hash(&self) -> H256 {
+        root_hash(&self.root_slots())
+    }
+
+    /// Merkle proof of the value of the key, see `verify_proof`
+    pub fn proof(&self, key: U256) -> StorageProof {
+        let mut nodes = vec![ProofNode::Root(self.root_slots())];
+
+        let (mut rest, tag) = (key >> 5, key.low_u32() & 0b11111);
+        let mut ptr_pos = 32*4 + tag * 4;
+        let mut prefix = U256::from(tag);
+        let mut level = 1;
+        loop {
+            match self.get_item(ptr_pos) {
+                ItemType::Empty => {
+                    return StorageProof {nodes, item: None};
+                },
+                ItemType::Item{pos} => {
+                    let item_key = (self.restore_value(pos) << (5 * level)) | prefix;
+                    let value = self.restore_value(pos+size_of::<U256>() as u32);
+                    return StorageProof {nodes, item: Some((item_key, value))};
+                },
+                ItemType::Array{pos} => {
+                    let tags = self.restore_u32(pos);
+                    nodes.push(ProofNode::Array {tags, children: self.array_children(pos)});
+
+                    let tag = rest.low_u32() & 0b11111;
+                    rest = rest >> 5;
+                    if tags & (1 << tag) == 0 {
+                        return StorageProof {nodes, item: None};
+                    }
+                    let shift = (tags & ((1 << tag)-1)).count_ones();
+                    ptr_pos = pos + ARRAY_HEADER + shift*4;
+                    prefix = prefix | (U256::from(tag) << (5 * level));
+                    level += 1;
+                },
+            }
+        }
+    }
+
+    /// All stored items with their full keys, in the tree order
+    pub fn items(&self) -> Vec<(U256, U256)> {
+        let mut items = Vec::with_capacity(self.item_count as usize);
+        for tag in 0..32 {
+            self.collect_items(32*4 + tag * 4, U256::from(tag), 1, &mut items);
+        }
+        items
+    }
+
+    fn collect_items(&self, ptr_pos: u32, prefix: U256, level: u32, items: &mut Vec<(U256, U256)>) {
+        match self.get_item(ptr_pos) {
+            ItemType::Empty => (),
+            ItemType::Item{pos} => {
+                let key = (self.restore_value(pos) << (5 * level)) | prefix;
+                items.push((key, self.restore_value(pos+size_of::<U256>() as u32)));
+            },
+            ItemType::Array{pos} => {
+                let tags = self.restore_u32(pos);
+                for (i, tag) in (0..32).filter(|tag| tags & (1 << tag) != 0).enumerate() {
+                    self.collect_items(pos + ARRAY_HEADER + i as u32 * 4, prefix | (U256::from(tag) << (5 * level)), level + 1, items);
+                }
+            },
+        }
+    }
+
+    /// Rebuild the storage from scratch: released arrays are reclaimed and zero values,
+    /// which read the same as absent ones, are dropped. Items are collected in memory first,
+    /// so the storage must fit the heap. Returns the number of kept items
+    /// and the allocated length before and after.
+    pub fn compact(&mut self) -> Result<(usize, usize, usize), ProgramError> {
+        let before = self.allocated_len();
+        let mut items = self.items();
+        items.retain(|(_, value)| !value.is_zero());
+
+        let data = std::mem::take(&mut self.data);
+        *self = Hamt::new(data, true)?;
+        for (key, value) in items.iter() {
+            self.insert(*key, *value)?;
+        }
+
+        Ok((items.len(), before, self.allocated_len()))
+    }
+
+    fn root_slots(&self) -> Vec<H256> {
+        (0..32).map(|tag| self.slot_hash(32*4 + tag * 4)).collect()
+    }
+
+    fn array_children(&self, pos: u32) -> Vec<H256> {
+        let total = self.restore_u32(pos).count_ones();
+        (0..total).map(|i| self.slot_hash(pos + ARRAY_HEADER + i * 4)).collect()
+    }
+
+    /// Cached hash of the slot
+    fn slot_hash(&self, ptr_pos: u32) -> H256 {
+        match self.get_item(ptr_pos) {
+            ItemType::Empty => H256::zero(),
+            ItemType::Item{pos} => self.restore_hash(pos + 64),
+            ItemType::Array{pos} => self.restore_hash(pos + 4),
+        }
+    }
+
+    /// Walk the whole structure and check that every item and array lies inside the allocated space,
+    /// no two of them overlap (so there are no cycles or shared nodes), arrays are well-formed,
+    /// cached hashes match the content, and the reachable items together with the free lists
+    /// account for all allocated space.
+    pub fn verify(&self) -> Result<HamtUsage, ProgramError> {
+        let header_len = (size_of::<u32>() * 32 * 2) as u32;
+        if self.last_used < header_len || self.last_used as usize > self.data.len() {
+            debug_print!("Hamt: last_used {} is out of data", self.last_used);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut regions = BTreeMap::new();
+        let mut usage = HamtUsage::default();
+        for tag in 0..32 {
+            self.verify_item(32*4 + tag * 4, U256::from(tag), 1, &mut regions, &mut usage)?;
+        }
+
+        for item_type in 1..32u32 {
+            let mut pos = self.restore_u32(item_type * 4);
+            while pos != 0 {
+                let size = node_size(item_type as u8);
+                self.verify_region(pos, size, &mut regions)?;
+                usage.free += size;
+                pos = self.restore_u32(pos);
+            }
+        }
+
+        if usage.used + usage.free != self.last_used - header_len {
+            debug_print!("Hamt: {} bytes used, {} free, {} allocated", usage.used, usage.free, self.last_used - header_len);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(usage)
+    }
+
+    /// Check the slot, `prefix` is the key bits consumed by `level` tags on the path
+    fn verify_item(&self, ptr_pos: u32, prefix: U256, level: u32, regions: &mut BTreeMap<u32, u32>, usage: &mut HamtUsage) -> Result<(), ProgramError> {
+        match self.get_item(ptr_pos) {
+            ItemType::Empty => Ok(()),
+            ItemType::Item{pos} => {
+                let size = ITEM_SIZE;
+                self.verify_region(pos, size, regions)?;
+                // the key bits consumed by tags on the path are not stored
+                if 5 * level < 256 && (self.restore_value(pos) >> (256 - 5 * level)) != U256::zero() {
+                    debug_print!("Hamt: item at {} has too long key", pos);
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                let key = (self.restore_value(pos) << (5 * level)) | prefix;
+                if item_hash(key, self.restore_value(pos+size_of::<U256>() as u32)) != self.restore_hash(pos + 64) {
+                    debug_print!("Hamt: item at {} has wrong hash", pos);
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                usage.used += size;
+                usage.items += 1;
+                Ok(())
+            },
+            ItemType::Array{pos} => {
+                if 5 * level >= 256 {
+                    debug_print!("Hamt: array at {} is too deep", pos);
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                if pos.checked_add(ARRAY_HEADER).map_or(true, |end| end > self.last_used) {
+                    debug_print!("Hamt: array at {} is out of allocated space", pos);
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                let tags = self.restore_u32(pos);
+                if tags == 0 {
+                    debug_print!("Hamt: array at {} is empty", pos);
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                let size = node_size(tags.count_ones() as u8);
+                self.verify_region(pos, size, regions)?;
+                usage.used += size;
+
+                for (i, tag) in (0..32).filter(|tag| tags & (1 << tag) != 0).enumerate() {
+                    self.verify_item(pos + ARRAY_HEADER + i as u32 * 4, prefix | (U256::from(tag) << (5 * level)), level + 1, regions, usage)?;
+                }
+                if array_hash(tags, &self.array_children(pos)) != self.restore_hash(pos + 4) {
+                    debug_print!("Hamt: array at {} has wrong hash", pos);
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                Ok(())
+            },
+        }
+    }
+
+    /// Register the region `[pos, pos+size)`, it should lie in the allocated space and not overlap others
+    fn verify_region(&self, pos: u32, size: u32, regions: &mut BTreeMap<u32, u32>) -> Result<(), ProgramError> {
+        let header_len = (size_of::<u32>() * 32 * 2) as u32;
+        let end = pos.checked_add(size).ok_or(ProgramError::InvalidAccountData)?;
+        if pos < header_len || pos % 4 != 0 || end > self.last_used {
+            debug_print!("Hamt: region {}..{} is out of allocated space", pos, end);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let before = regions.range(..end).next_back().map_or(false, |(_, &prev_end)| prev_end > pos);
+        if before {
+            debug_print!("Hamt: region {}..{} overlaps another one", pos, end);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        regions.insert(pos, end);
+        Ok(())
+    }
+
     pub fn find(&self, key: U256) -> Option<U256> {
         let (key, tag) = (key >> 5, key.low_u32() & 0b11111);
         let ptr_pos = 32*4 + tag * 4;
@@ -242,11 +592,49 @@ impl<'a> Hamt<'a> {
                     return None;
                 } else {
                     let shift = (tags & ((1 << tag)-1)).count_ones();
-                    return self.find_item(pos+4 + shift*4, key);
+                    return self.find_item(pos + ARRAY_HEADER + shift*4, key);
                 }
             },
         }
     }
+
+    /// Store the variable-length value. It is kept as 32-byte words under the keys following
+    /// `keccak256(key)`, with `len + 1` at that key, so it shares the allocator, the root hash
+    /// and the proofs with `U256` values. Keys of both kinds share one key space.
+    /// The words of a longer previous value are zeroed, their space is not reclaimed.
+    pub fn insert_bytes(&mut self, key: U256, value: &[u8]) -> Result<(), ProgramError> {
+        let base = bytes_base(key);
+        let old_words = self.find(base).map_or(0, |len| (len.as_usize().saturating_sub(1) + 31) / 32);
+
+        self.insert(base, U256::from(value.len() + 1))?;
+        for (i, chunk) in value.chunks(32).enumerate() {
+            let mut word = [0u8; 32];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.insert(base.overflowing_add(U256::from(i + 1)).0, U256::from_big_endian(&word))?;
+        }
+        for i in (value.len() + 31) / 32..old_words {
+            self.insert(base.overflowing_add(U256::from(i + 1)).0, U256::zero())?;
+        }
+        Ok(())
+    }
+
+    /// Variable-length value stored by `insert_bytes`
+    pub fn find_bytes(&self, key: U256) -> Option<Vec<u8>> {
+        let base = bytes_base(key);
+        let len = self.find(base)?.checked_sub(U256::one())?;
+        if len > U256::from(self.data.len()) {
+            return None;
+        }
+        let len = len.as_usize();
+
+        let mut value = Vec::with_capacity((len + 31) / 32 * 32);
+        for i in 0..(len + 31) / 32 {
+            let word = self.find(base.overflowing_add(U256::from(i + 1)).0).unwrap_or_default();
+            value.extend_from_slice(u256_to_h256(word).as_bytes());
+        }
+        value.truncate(len);
+        Some(value)
+    }
 }
 
 #[cfg(test)]
@@ -283,7 +671,7 @@ mod test {
                 ItemType::Array{mut pos} => {
                     let tags = self.restore_u32(pos);
                     println!("{} {:x?} {:x?} => array ({:x}) prefix: {:x}", pr, ptr_pos, tag, tags, prefix);
-                    pos += 4;
+                    pos += ARRAY_HEADER;
                     for t in 0..32 {
                         if tags & (1<<t) != 0 {
                             self.print_item(level+1, prefix | (U256::from(t) << ((level+1)*5)), t, pos);
@@ -296,8 +684,175 @@ mod test {
     }
 
     #[test]
-    fn test_new() -> Result<(), ProgramError> {
+    fn test_proof() -> Result<(), ProgramError> {
+        let mut data = vec!(0u8; (1+32+32)*4 + 16*1024);
+        let mut hamt = Hamt::new(&mut data, true).unwrap();
+        assert_eq!(hamt.root_hash(), H256::zero());
+
+        let mut items = Vec::new();
+        for i in 0..32u64 {
+            items.push((U256::from(0x32440002u64+i*32), U256::from(0x55500+i)));
+            items.push((U256::from(0x31423415u64+i*32*0x60), U256::from(0xdead00+i)));
+        }
+        items.push((random_U256(), random_U256()));
+        for (key, value) in &items {
+            hamt.insert(*key, *value)?;
+        }
+
+        let root = hamt.root_hash();
+        for (key, value) in &items {
+            let proof = hamt.proof(*key);
+            assert_eq!(verify_proof(root, *key, &proof), Some(*value));
+
+            let mut forged = proof.clone();
+            forged.item = Some((*key, *value + 1));
+            assert_eq!(verify_proof(root, *key, &forged), None);
+        }
+
+        for key in &[U256::from(0x32440002u64 + 33*32), U256::from(0x12345678u64), U256::from(0x32440002u64 + (1u64 << 40))] {
+            assert_eq!(hamt.find(*key), None);
+            assert_eq!(verify_proof(root, *key, &hamt.proof(*key)), Some(U256::zero()));
+        }
+
+        hamt.insert(items[0].0, U256::from(1))?;
+        assert_ne!(hamt.root_hash(), root);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_root_hash_is_independent_of_insert_order() -> Result<(), ProgramError> {
+        let mut items: Vec<_> = (0..100u64).map(|i| (U256::from(0x32440002u64 + i*32*32), U256::from(i))).collect();
+        items.extend((0..100u64).map(|i| (random_U256(), U256::from(i))));
+
+        let mut data = vec!(0u8; (1+32+32)*4 + 64*1024);
+        let mut hamt = Hamt::new(&mut data, true)?;
+        for (key, value) in items.iter() {
+            hamt.insert(*key, *value + 1)?;
+        }
+        for (key, value) in items.iter() {
+            hamt.insert(*key, *value)?;
+        }
+
+        let mut reversed_data = vec!(0u8; (1+32+32)*4 + 64*1024);
+        let mut reversed = Hamt::new(&mut reversed_data, true)?;
+        for (key, value) in items.iter().rev() {
+            reversed.insert(*key, *value)?;
+        }
+
+        assert_eq!(hamt.root_hash(), reversed.root_hash());
+        assert_ne!(hamt.root_hash(), H256::zero());
+        assert_eq!(hamt.verify()?.items, 200);
+        assert_eq!(reversed.verify()?.items, 200);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify() -> Result<(), ProgramError> {
+        let mut data = vec!(0u8; (1+32+32)*4 + 64*1024);
+        let mut hamt = Hamt::new(&mut data, true).unwrap();
+        assert_eq!(hamt.verify(), Ok(HamtUsage::default()));
+
+        for i in 0..100u64 {
+            hamt.insert(U256::from(0x32440002u64 + i*32*32), U256::from(i))?;
+            hamt.insert(random_U256(), U256::from(i))?;
+        }
+        let usage = hamt.verify()?;
+        assert_eq!(usage.items, 200);
+        assert!(usage.free > 0);
+        let last_used = hamt.last_used;
+
+        // pointer of the first root slot to the item of another slot
+        let mut corrupted = data.clone();
+        let other = (1..32).map(|tag| u32::from_le_bytes(*array_ref![corrupted, (32+tag)*4, 4])).find(|&ptr| ptr != 0).unwrap();
+        corrupted[32*4..32*4+4].copy_from_slice(&other.to_le_bytes());
+        assert_eq!(Hamt::new(&mut corrupted, false)?.verify(), Err(ProgramError::InvalidAccountData));
+
+        // allocated space beyond the data
+        let mut corrupted = data.clone();
+        corrupted[0..4].copy_from_slice(&(corrupted.len() as u32 + 4).to_le_bytes());
+        assert_eq!(Hamt::new(&mut corrupted, false)?.verify(), Err(ProgramError::InvalidAccountData));
+
+        // space lost from the accounting
+        let mut corrupted = data.clone();
+        corrupted[0..4].copy_from_slice(&(last_used + 64).to_le_bytes());
+        assert_eq!(Hamt::new(&mut corrupted, false)?.verify(), Err(ProgramError::InvalidAccountData));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_allocated() -> Result<(), ProgramError> {
+        let mut data = vec!(0u8; 64 + (1+32+32)*4 + 16*1024);
+        let mut hamt = Hamt::new(&mut data[64..], true)?;
+        for i in 0..50u64 {
+            hamt.insert(U256::from(i * 0x10001), U256::from(i))?;
+        }
+        let root = hamt.root_hash();
+        let len = hamt.allocated_len();
+
+        data.copy_within(64..64 + len, 0);
+        let hamt = Hamt::new(&mut data[..len], false)?;
+        assert_eq!(hamt.root_hash(), root);
+        assert_eq!(hamt.find(U256::from(7 * 0x10001)), Some(U256::from(7)));
+        assert_eq!(hamt.verify()?.items, 50);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes() -> Result<(), ProgramError> {
         let mut data = vec!(0u8; (1+32+32)*4 + 16*1024);
+        let mut hamt = Hamt::new(&mut data, true)?;
+        let long: Vec<u8> = (0..100u8).collect();
+
+        assert_eq!(hamt.find_bytes(U256::from(1)), None);
+        hamt.insert_bytes(U256::from(1), &long)?;
+        hamt.insert_bytes(U256::from(2), &[])?;
+        hamt.insert(U256::from(1), U256::from(7))?;
+        assert_eq!(hamt.find_bytes(U256::from(1)), Some(long.clone()));
+        assert_eq!(hamt.find_bytes(U256::from(2)), Some(Vec::new()));
+        assert_eq!(hamt.find(U256::from(1)), Some(U256::from(7)));
+
+        hamt.insert_bytes(U256::from(1), b"short")?;
+        assert_eq!(hamt.find_bytes(U256::from(1)), Some(b"short".to_vec()));
+        let base = bytes_base(U256::from(1));
+        assert_eq!(hamt.find(base + 4), Some(U256::zero()));
+
+        let proof = hamt.proof(base + 1);
+        assert!(verify_proof(hamt.root_hash(), base + 1, &proof).is_some());
+        assert!(hamt.verify().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact() -> Result<(), ProgramError> {
+        let mut data = vec!(0u8; (1+32+32)*4 + 64*1024);
+        let mut hamt = Hamt::new(&mut data, true)?;
+        for i in 0..200u64 {
+            hamt.insert(U256::from(i * 0x421), U256::from(i + 1))?;
+        }
+        for i in 0..100u64 {
+            hamt.insert(U256::from(i * 0x421), U256::zero())?;
+        }
+        let mut items = hamt.items();
+        assert_eq!(items.len(), 200);
+
+        let (kept, before, after) = hamt.compact()?;
+        assert_eq!(kept, 100);
+        assert!(after < before);
+        assert_eq!(hamt.verify()?.items, 100);
+        items.retain(|(_, value)| !value.is_zero());
+        let mut compacted = hamt.items();
+        items.sort(); compacted.sort();
+        assert_eq!(compacted, items);
+        assert_eq!(hamt.find(U256::from(150 * 0x421)), Some(U256::from(151)));
+        assert_eq!(hamt.find(U256::from(50 * 0x421)), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_new() -> Result<(), ProgramError> {
+        let mut data = vec!(0u8; (1+32+32)*4 + 32*1024);
         let mut hamt = Hamt::new(&mut data, true).unwrap();
 
         hamt.insert(U256::from(0x12345120u64), U256::from(0xabcdefu64))?;
@@ -1,23 +1,73 @@
 use serde::{Serialize, Serializer, Deserialize};
-use solana_program::{program_error::ProgramError, pubkey::Pubkey, instruction::Instruction};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey, instruction::{AccountMeta, Instruction}};
 use std::convert::TryInto;
-use primitive_types::{H160, H256};
+use primitive_types::{H160, H256, U256};
 use evm::backend::Log;
+use crate::return_data::Telemetry;
+use crate::meta_transaction::{MetaTransaction, SignatureFormat};
 
 fn serialize_h160<S>(value: &H160, s: S) -> Result<S::Ok, S::Error> where S: Serializer {
     value.as_fixed_bytes().serialize(s)
 }
 
+/// Call of the `CallBatch` instruction
+#[derive(Serialize, Debug, PartialEq, Eq, Clone)]
+pub struct BatchCall<'a> {
+    /// Called contract
+    #[serde(serialize_with="serialize_h160")]
+    pub to: H160,
+    /// Value transferred to the contract
+    pub value: U256,
+    /// Call data
+    pub data: &'a [u8],
+}
+
+impl<'a> BatchCall<'a> {
+    /// Unpack the call from `to: [u8; 20], value: [u8; 32] big endian, data_len: u32, data`,
+    /// returns the call and the rest of the input
+    fn unpack(input: &'a [u8]) -> Result<(Self, &'a [u8]), ProgramError> {
+        use ProgramError::InvalidInstructionData;
+
+        let to = input.get(..20).ok_or(InvalidInstructionData)?;
+        let value = input.get(20..52).ok_or(InvalidInstructionData)?;
+        let data_len = input.get(52..56).and_then(|v| v.try_into().ok()).map(u32::from_le_bytes).ok_or(InvalidInstructionData)?;
+        let rest = &input[56..];
+        if rest.len() < data_len as usize {
+            return Err(InvalidInstructionData);
+        }
+        let (data, rest) = rest.split_at(data_len as usize);
+
+        Ok((Self {to: H160::from_slice(to), value: U256::from_big_endian(value), data}, rest))
+    }
+
+    /// Pack the call into the data of `CallBatch` instruction
+    pub fn pack(&self, dst: &mut Vec<u8>) {
+        dst.extend_from_slice(self.to.as_bytes());
+        let mut value = [0u8; 32];
+        self.value.to_big_endian(&mut value);
+        dst.extend_from_slice(&value);
+        dst.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        dst.extend_from_slice(self.data);
+    }
+}
+
+/// `Write` offset appending the bytes to the code account
+pub const WRITE_APPEND_OFFSET: u32 = u32::max_value();
+
 /// Create a new account
 #[derive(Serialize, Debug, PartialEq, Eq, Clone)]
 pub enum EvmInstruction<'a> {
     /// Write program data into an account
     ///
+    /// Writes to the code account must be contiguous: each one starts where the previous ones ended,
+    /// the uploaded length is tracked in the account header and checked by `Finalize`.
+    /// `WRITE_APPEND_OFFSET` appends to the uploaded bytes without tracking the offset on the client.
+    ///
     /// # Account references
     ///   0. [WRITE] Account to write to
     ///   1. [SIGNER] Signer for Ether account
     Write {
-        /// Offset at which to write the given bytes
+        /// Offset at which to write the given bytes, or `WRITE_APPEND_OFFSET`
         offset: u32,
         bytes: &'a [u8],
     },
@@ -42,7 +92,8 @@ pub enum EvmInstruction<'a> {
     /// # Account references
     ///   0. [WRITE, SIGNER] Funding account
     ///   1. [WRITE] New account (program_address(ether, nonce))
-    ///   2. (for contract creation) [WRITE] Code account for new contract account
+    ///   2. (for contract creation) [WRITE] Code account for new contract account, the funding account becomes its deployer
+    ///   ... (optional) [WRITE] Alias accounts, registered as in `RegisterAlias` if both are passed
     CreateAccount {
         /// Number of lamports to transfer to the new account
         lamports: u64,
@@ -56,6 +107,10 @@ pub enum EvmInstruction<'a> {
 
         /// Nonce for create valid program_address from ethereum address
         nonce: u8,
+
+        /// Hash the storage keys of the contract, see `Contract::hashed_keys`.
+        /// Optional trailing byte, absent means false
+        hashed_keys: bool,
     },
 
     /// Call Ethereum-contract action
@@ -66,6 +121,9 @@ pub enum EvmInstruction<'a> {
     ///   3. [SIGNER] Signer for caller
     ///   4. [] Clock sysvar
     ///   ... other Ether accounts
+    ///   (optional) [WRITE, SIGNER] Payer for the Ether accounts created by the call.
+    ///              Rent is charged back from the caller Ether account.
+    ///   (optional) [] System program, required to create accounts
     Call {
         /// Call data
         bytes: &'a [u8],
@@ -102,8 +160,12 @@ pub enum EvmInstruction<'a> {
         owner: Pubkey,
     },
 
-    /// Call Ethereum-contract action from raw transaction data
+    /// Call Ethereum-contract action from raw transaction data.
+    /// The caller account of an unknown sender is created on the first transaction,
+    /// funded by the operator, the System program must be passed then
     /// # Account references same as in Call
+    ///   (optional) [WRITE] Receipt account (program_address("receipt", tx_hash)), created by the payer
+    ///   (optional) [WRITE, SIGNER] Operator, the last account, required to create the caller account
     CallFromRawEthereumTX {
         /// Call data
         from_addr: &'a [u8],
@@ -111,8 +173,11 @@ pub enum EvmInstruction<'a> {
         unsigned_msg: &'a [u8],
     },
 
-    /// Call Ethereum-contract action from raw transaction data
+    /// Call Ethereum-contract action from raw transaction data.
+    /// The caller account of an unknown sender is created on the first transaction,
+    /// funded by the operator, the System program must be passed then
     /// # Account references same as in Call
+    ///   (optional) [WRITE] Receipt account (program_address("receipt", tx_hash)), created by the payer
     CheckEtheriumTX {
         /// Call data
         from_addr: &'a [u8],
@@ -125,18 +190,30 @@ pub enum EvmInstruction<'a> {
         /// Contract execution status 
         /// Success - 0x11, 0x12 or 0x13 otherwise Error
         status: u8,
-        /// Returned data
+        /// Returned data followed by `return_data::Telemetry`
         bytes: &'a [u8],
     },
 
     /// Called action event
     OnEvent {
         address: H160,
+        /// Hash of the Ethereum transaction (`transaction::tx_hash`), zero if the instruction doesn't carry a signed transaction
+        tx_hash: H256,
+        /// Depth of the call frame which emitted the event, 0 for the transaction frame,
+        /// `UNKNOWN_EVENT_DEPTH` if the executor doesn't track frames
+        depth: u16,
+        /// Index of the event in the transaction, as in the receipt
+        index: u32,
         topics: Vec<H256>,
         /// Data
         data: &'a [u8],
     },
 
+    /// Begin the iterative execution of raw transaction, the caller account is created as in CallFromRawEthereumTX
+    /// # Account references
+    ///   0. [WRITE] Storage account of the iterative execution
+    ///   ... accounts as in CallFromRawEthereumTX
+    ///   N. [WRITE, SIGNER] Operator, recorded in the storage account, it may cancel the execution
     PartialCallFromRawEthereumTX {
         step_count: u64,
         from_addr: &'a [u8],
@@ -148,11 +225,278 @@ pub enum EvmInstruction<'a> {
         step_count: u64,
     },
 
+    /// Begin the iterative execution of the transaction written to the holder account
+    /// # Account references
+    ///   0. [] Holder account
+    ///   1. [WRITE] Storage account of the iterative execution
+    ///   ... accounts as in Call
+    ///   N. [WRITE, SIGNER] Operator, recorded in the storage account, it may cancel the execution
     ExecuteTrxFromAccountDataIterative {
         step_count: u64,
     },
 
+    /// Abort iterative execution, burn the transaction nonce and unblock accounts.
+    /// The gas used so far and `gas::CANCEL_PENALTY` are charged from the caller to the operator
+    /// at the price of the gas price oracle, the unused prepaid gas stays with the caller.
+    /// The charge is capped at the caller lamports above the rent-exempt minimum, so Cancel doesn't fail on funds.
+    /// # Account references
+    ///   0. [WRITE] Storage account of the iterative execution
+    ///   ... accounts passed to the iterative execution
+    ///   N. [SIGNER] Signer of the caller Ether account, or the operator which started the execution
     Cancel,
+
+    /// Close Ether account with zero nonce and without code,
+    /// which holds no more lamports than required for rent exemption.
+    /// The lamports are refunded to the signer of the account, which must sign the instruction.
+    /// # Account references
+    ///   0. [WRITE] Ether account to delete
+    ///   1. [WRITE, SIGNER] Signer of the Ether account, recipient of the account lamports
+    DeleteAccount,
+
+    /// Register the Ether account in the address registry, so the Solana account can be resolved
+    /// from the Ether address and back by reading `AccountData::Alias` records
+    /// # Account references
+    ///   0. [WRITE, SIGNER] Funding account
+    ///   1. [] Ether account
+    ///   2. [WRITE] Alias of the Ether address (program_address("alias", ether))
+    ///   3. [WRITE] Alias of the Solana account (program_address("alias", account))
+    ///   4. [] System program
+    RegisterAlias,
+
+    /// Deploy the canonical ERC20 wrapper of the SPL mint and register it for the mint.
+    /// Wrapper code is written to the code account as for `Finalize`, the address is
+    /// `erc20_wrapper::wrapper_address(code, mint)`
+    /// # Account references
+    ///   0. [WRITE] Wrapper contract account
+    ///   1. [WRITE] Wrapper code account
+    ///   2. [WRITE] Caller (Ether account)
+    ///   3. [SIGNER] Signer for Ether account
+    ///   4. [] Clock sysvar
+    ///   5. [] SPL mint
+    ///   6. [WRITE] Registry record of the mint (program_address("erc20_wrapper", mint))
+    ///   7. [] System program
+    CreateERC20Wrapper {
+        /// SPL mint to wrap
+        mint: Pubkey,
+    },
+
+    /// Unwrap all SOL of the wrapped SOL account into the balance of the Ether account.
+    /// The token account is closed, its rent goes to the balance too.
+    /// # Account references
+    ///   0. [WRITE] Ether account
+    ///   1. [WRITE] Wrapped SOL token account
+    ///   2. [SIGNER] Owner of the token account
+    ///   3. [] SPL Token program
+    DepositWSOL,
+
+    /// Wrap lamports from the balance of the Ether account into the wrapped SOL account
+    /// # Account references
+    ///   0. [WRITE] Ether account
+    ///   1. [SIGNER] Signer for Ether account
+    ///   2. [WRITE] Wrapped SOL token account
+    ///   3. [] SPL Token program
+    WithdrawWSOL {
+        /// Number of lamports to wrap
+        amount: u64,
+    },
+
+    /// Read state of the Ether account: nonce, balance, code size and code hash.
+    /// The result is `return_data::EtherAccountInfo`, passed back as the result of `OnReturn`
+    /// (or return data with `return-data` feature)
+    /// # Account references
+    ///   0. [] Ether account
+    ///   1. (for contract) [] Contract code account
+    GetAccountInfo {
+        /// Ethereum address of account
+        #[serde(serialize_with="serialize_h160")]
+        ether: H160,
+    },
+
+    /// Read the storage slot of the contract. The result is 32 bytes big endian value of the slot,
+    /// passed back as the result of `OnReturn` (or return data with `return-data` feature)
+    /// # Account references
+    ///   0. [] Contract account (Ether account)
+    ///   1. [] Contract code account
+    GetStorageAt {
+        /// Ethereum address of contract
+        #[serde(serialize_with="serialize_h160")]
+        address: H160,
+        /// Index of the slot, 32 bytes big endian
+        index: U256,
+    },
+
+    /// Execute the calls sequentially in one executor, state changes are applied only when all calls succeed.
+    /// The batch is one transaction, the nonce of the caller is incremented once.
+    /// On the first failure execution stops with the exit status and result of the failed call.
+    /// On success the result is the list of call results, each is `len: u32` followed by the data
+    /// # Account references same as in Call,
+    ///   contract accounts of all calls and their code accounts must be passed in other Ether accounts
+    CallBatch {
+        /// Calls in execution order
+        calls: Vec<BatchCall<'a>>,
+    },
+
+    /// Set the price of gas in lamports, charged from the caller when the iterative execution completes.
+    /// The first update creates the oracle account and its signer becomes the operator,
+    /// later updates must be signed by the operator. The operator registers its EVM address
+    /// as `block.coinbase` with every update.
+    /// # Account references
+    ///   0. [WRITE] Gas price oracle (program_address("gas_price"))
+    ///   1. [WRITE, SIGNER] Operator, funds the oracle account when it is created
+    ///   2. [] Clock sysvar
+    ///   3. [] System program
+    UpdateGasPrice {
+        /// Lamports charged per unit of gas
+        lamports_per_gas: u64,
+
+        /// EVM address of the operator
+        #[serde(serialize_with="serialize_h160")]
+        coinbase: H160,
+    },
+
+    /// Check the integrity of the contract storage, fails with `InvalidAccountData` if it is corrupted.
+    /// On success the result is `[items: u32, used: u32, free: u32]` little endian, see `HamtUsage`,
+    /// passed back as the result of `OnReturn` (or return data with `return-data` feature)
+    /// # Account references
+    ///   0. [] Contract account (Ether account)
+    ///   1. [] Contract code account
+    VerifyStorage {
+        /// Ethereum address of contract
+        #[serde(serialize_with="serialize_h160")]
+        address: H160,
+    },
+
+    /// Replace the contract code with the code written by `Write` to the holder account,
+    /// or only make the contract immutable if the holder is not passed.
+    /// The new code is checked as the created code: EIP-170 size limit and no 0xEF prefix (EIP-3541).
+    /// Allowed to the deployer of the code while the contract is not immutable.
+    /// The storage is kept unless `reset_storage` is set, it must fit the code account after the new code.
+    /// # Account references
+    ///   0. [WRITE] Contract account (Ether account)
+    ///   1. [WRITE] Contract code account
+    ///   2. [] Deployer Ether account, or [SIGNER] Solana wallet of the deployer
+    ///   3. [SIGNER] Signer of the deployer Ether account, if it is passed
+    ///   4. [] Optional holder account with the new code
+    UpdateCode {
+        /// Clear the storage of the contract
+        reset_storage: bool,
+        /// Disable code updates permanently
+        immutable: bool,
+    },
+
+    /// Rebuild the contract storage compactly in place, see `Hamt::compact`.
+    /// The result is `[items: u32, before: u32, after: u32]` little endian, the number of items
+    /// and the allocated storage length before and after, passed back as in `VerifyStorage`.
+    /// Allowed to the deployer of the code and to registered operators.
+    /// # Account references
+    ///   0. [WRITE] Contract account (Ether account)
+    ///   1. [WRITE] Contract code account
+    ///   2. [] Deployer Ether account, or [SIGNER] Solana wallet of the deployer or of an operator
+    ///   3. [SIGNER] Signer of the deployer Ether account, if it is passed,
+    ///      otherwise [] Operator account of the wallet, if the wallet is an operator
+    CompactStorage {
+        /// Ethereum address of contract
+        #[serde(serialize_with="serialize_h160")]
+        address: H160,
+    },
+
+    /// Update the program configuration.
+    /// The first update creates the config account and its signer becomes the admin,
+    /// later updates must be signed by the admin.
+    /// With `operator-allow-list` feature Call-type instructions (Call, CallFromRawEthereumTX,
+    /// ExecuteTrxFromAccountData*, PartialCallFromRawEthereumTX, Continue, CallBatch,
+    /// CallFromMetaTransaction) must pass
+    /// the config account among their accounts, and while `operators_only` is set
+    /// they also must pass the record of a registered operator signing the transaction.
+    /// The native mint replaces lamports with its tokens as the balance of foreign accounts:
+    /// the caller which is not an Ether account has the balance of its token account of the mint,
+    /// if the token account is passed among the accounts.
+    /// The chain id and the legacy transactions flag apply to the transactions of the instructions
+    /// which pass the config account, `CHAIN_ID` without legacy transactions applies otherwise.
+    /// Data: `operators_only: u8, allow_legacy_tx: u8, chain_id: u64, native_mint: Option<[u8; 32]>`
+    /// # Account references
+    ///   0. [WRITE] Config account (program_address("config"))
+    ///   1. [WRITE, SIGNER] Admin, funds the config account when it is created
+    ///   2. [] System program
+    ///   3. [] Native mint, if it is set
+    UpdateConfig {
+        /// Only registered operators may submit Call-type instructions
+        operators_only: bool,
+        /// Transactions signed without chain id (pre-EIP-155) are accepted
+        allow_legacy_tx: bool,
+        /// Chain id accepted in transactions, zero keeps the current one (`CHAIN_ID` when the config is created)
+        chain_id: u64,
+        /// New native mint, the default key restores lamports. The current one is kept if it is omitted.
+        native_mint: Option<Pubkey>,
+    },
+
+    /// Register the operator allowed to submit Call-type instructions, see `UpdateConfig`
+    /// # Account references
+    ///   0. [] Config account (program_address("config"))
+    ///   1. [WRITE, SIGNER] Admin, funds the operator record
+    ///   2. [WRITE] Operator record (program_address("operator", operator))
+    ///   3. [] System program
+    RegisterOperator {
+        /// Solana key of the operator
+        operator: Pubkey,
+    },
+
+    /// Remove the operator record, its lamports are returned to the admin
+    /// # Account references
+    ///   0. [] Config account (program_address("config"))
+    ///   1. [WRITE, SIGNER] Admin
+    ///   2. [WRITE] Operator record (program_address("operator", operator))
+    RemoveOperator {
+        /// Solana key of the operator
+        operator: Pubkey,
+    },
+
+    /// Call Ethereum-contract with the meta-transaction signed by the user, see `meta_transaction`.
+    /// The operator pays the rent and the gas instead of the user, the signed fee is moved
+    /// from the caller Ether account to the operator: lamports for the zero `fee_token`, otherwise
+    /// tokens of the wrapped SPL mint from the associated token account of the caller to the one
+    /// of the operator. The signature is verified by the preceding
+    /// secp256k1 instruction, as `make_secp256k1_instruction(index, message.len(), 2)` builds it.
+    /// The caller account is created as in CallFromRawEthereumTX.
+    /// Data: `format: u8, from: [u8; 20], signature: [u8; 65], message, to: [u8; 20], nonce: u64, fee: u64, fee_token: [u8; 20], data`
+    /// # Account references same as in CallFromRawEthereumTX
+    ///   (optional) [WRITE] Receipt account (program_address("receipt", meta-transaction hash))
+    ///   (fee token) [] Registry record of the mint (program_address("erc20_wrapper", mint))
+    ///   (fee token) [WRITE] Associated token account of the caller Ether account for the mint
+    ///   (fee token) [WRITE] Associated token account of the operator for the mint
+    ///   (fee token) [] SPL Token program
+    ///   [WRITE, SIGNER] Operator, pays for the call
+    CallFromMetaTransaction {
+        /// How the user signed the meta-transaction
+        format: SignatureFormat,
+        /// Signature `[r, s, recovery_id]`
+        signature: &'a [u8],
+        /// Signed message, must match the meta-transaction
+        message: &'a [u8],
+        /// Call signed by the user
+        transaction: MetaTransaction<'a>,
+    },
+
+    /// Append the part of the code written by `Write` to the holder account to the code account,
+    /// then close the holder, its lamports go to the deployer.
+    /// Large contracts are split into parts written to independent holders with parallel transactions,
+    /// the parts are appended in order, after the `len: u64` prefix written to the code account.
+    /// The holder is `create_with_seed(deployer, seed, program_id)` with `len: u64` followed by the part.
+    /// Data: seed
+    /// # Account references
+    ///   0. [WRITE] Code account
+    ///   1. [WRITE] Holder account
+    ///   2. [WRITE, SIGNER] Deployer of the code account, base of the holder address
+    WriteFromHolder {
+        /// Seed of the holder address
+        seed: &'a [u8],
+    },
+
+    /// Read the version of the program, the enabled features and precompiles.
+    /// The result is `version::ProgramVersion`, passed back as the result of `OnReturn`
+    /// (or return data with `return-data` feature)
+    /// # Account references: none
+    GetVersion,
 }
 
 
@@ -185,8 +529,9 @@ impl<'a> EvmInstruction<'a> {
 
                 let (ether, rest) = rest.split_at(20);
                 let ether = H160::from_slice(&*ether); //ether.try_into().map_err(|_| InvalidInstructionData)?;
-                let (nonce, _rest) = rest.split_first().ok_or(InvalidInstructionData)?;
-                EvmInstruction::CreateAccount {lamports, space, ether, nonce: *nonce}
+                let (nonce, rest) = rest.split_first().ok_or(InvalidInstructionData)?;
+                let hashed_keys = rest.first().map_or(false, |flag| *flag != 0);
+                EvmInstruction::CreateAccount {lamports, space, ether, nonce: *nonce, hashed_keys}
             },
             3 => {
                 EvmInstruction::Call {bytes: rest}
@@ -229,8 +574,11 @@ impl<'a> EvmInstruction<'a> {
             7 => {
                 let (address, rest) = rest.split_at(20);
                 let address = H160::from_slice(&*address); //address.try_into().map_err(|_| InvalidInstructionData)?;
+                let tx_hash = rest.get(..32).map(H256::from_slice).ok_or(InvalidInstructionData)?;
+                let depth = rest.get(32..34).and_then(|v| v.try_into().ok()).map(u16::from_le_bytes).ok_or(InvalidInstructionData)?;
+                let index = rest.get(34..38).and_then(|v| v.try_into().ok()).map(u32::from_le_bytes).ok_or(InvalidInstructionData)?;
 
-                let (topics_cnt, mut rest) = rest.split_at(8);
+                let (topics_cnt, mut rest) = rest[38..].split_at(8);
                 let topics_cnt = topics_cnt.try_into().ok().map(u64::from_le_bytes).ok_or(InvalidInstructionData)?;
                 let mut topics = Vec::new();
                 for i in 1..=topics_cnt {
@@ -239,7 +587,7 @@ impl<'a> EvmInstruction<'a> {
                     topics.push(topic);
                     rest = rest2;
                 }
-                EvmInstruction::OnEvent {address, topics, data: rest}
+                EvmInstruction::OnEvent {address, tx_hash, depth, index, topics, data: rest}
             },
             8 => {
                 EvmInstruction::ExecuteTrxFromAccountData
@@ -264,6 +612,98 @@ impl<'a> EvmInstruction<'a> {
             12 => {
                 EvmInstruction::Cancel
             },
+            13 => {
+                EvmInstruction::DeleteAccount
+            },
+            14 => {
+                EvmInstruction::RegisterAlias
+            },
+            15 => {
+                let mint = rest.get(..32).ok_or(InvalidInstructionData)?;
+                let mint = Pubkey::new(mint);
+                EvmInstruction::CreateERC20Wrapper {mint}
+            },
+            16 => {
+                EvmInstruction::DepositWSOL
+            },
+            17 => {
+                let amount = rest.get(..8).ok_or(InvalidInstructionData)?;
+                let amount = amount.try_into().ok().map(u64::from_le_bytes).ok_or(InvalidInstructionData)?;
+                EvmInstruction::WithdrawWSOL {amount}
+            },
+            18 => {
+                let ether = rest.get(..20).ok_or(InvalidInstructionData)?;
+                let ether = H160::from_slice(ether);
+                EvmInstruction::GetAccountInfo {ether}
+            },
+            19 => {
+                let address = rest.get(..20).ok_or(InvalidInstructionData)?;
+                let address = H160::from_slice(address);
+                let index = rest.get(20..52).ok_or(InvalidInstructionData)?;
+                let index = U256::from_big_endian(index);
+                EvmInstruction::GetStorageAt {address, index}
+            },
+            20 => {
+                let mut calls = Vec::new();
+                let mut rest = rest;
+                while !rest.is_empty() {
+                    let (call, rest2) = BatchCall::unpack(rest)?;
+                    calls.push(call);
+                    rest = rest2;
+                }
+                EvmInstruction::CallBatch {calls}
+            },
+            21 => {
+                let lamports_per_gas = rest.get(..8).and_then(|v| v.try_into().ok()).map(u64::from_le_bytes).ok_or(InvalidInstructionData)?;
+                let coinbase = rest.get(8..28).map(H160::from_slice).ok_or(InvalidInstructionData)?;
+                EvmInstruction::UpdateGasPrice {lamports_per_gas, coinbase}
+            },
+            22 => {
+                let address = rest.get(..20).map(H160::from_slice).ok_or(InvalidInstructionData)?;
+                EvmInstruction::VerifyStorage {address}
+            },
+            23 => {
+                let flags = rest.get(..2).ok_or(InvalidInstructionData)?;
+                EvmInstruction::UpdateCode {reset_storage: flags[0] != 0, immutable: flags[1] != 0}
+            },
+            24 => {
+                let address = rest.get(..20).map(H160::from_slice).ok_or(InvalidInstructionData)?;
+                EvmInstruction::CompactStorage {address}
+            },
+            25 => {
+                let operators_only = rest.get(0).ok_or(InvalidInstructionData)?;
+                let allow_legacy_tx = rest.get(1).ok_or(InvalidInstructionData)?;
+                let chain_id = rest.get(2..10).and_then(|v| v.try_into().ok()).map(u64::from_le_bytes).ok_or(InvalidInstructionData)?;
+                let native_mint = rest.get(10..42).map(Pubkey::new);
+                EvmInstruction::UpdateConfig {operators_only: *operators_only != 0, allow_legacy_tx: *allow_legacy_tx != 0, chain_id, native_mint}
+            },
+            26 => {
+                let operator = rest.get(..32).map(Pubkey::new).ok_or(InvalidInstructionData)?;
+                EvmInstruction::RegisterOperator {operator}
+            },
+            27 => {
+                let operator = rest.get(..32).map(Pubkey::new).ok_or(InvalidInstructionData)?;
+                EvmInstruction::RemoveOperator {operator}
+            },
+            28 => {
+                let format = rest.get(0).copied().and_then(SignatureFormat::from_u8).ok_or(InvalidInstructionData)?;
+                let from = rest.get(1..21).map(H160::from_slice).ok_or(InvalidInstructionData)?;
+                let signature = rest.get(21..86).ok_or(InvalidInstructionData)?;
+                let message_end = 86 + format.message_len();
+                let message = rest.get(86..message_end).ok_or(InvalidInstructionData)?;
+                let rest = rest.get(message_end..).ok_or(InvalidInstructionData)?;
+                let to = rest.get(..20).map(H160::from_slice).ok_or(InvalidInstructionData)?;
+                let nonce = rest.get(20..28).and_then(|v| v.try_into().ok()).map(u64::from_le_bytes).ok_or(InvalidInstructionData)?;
+                let fee = rest.get(28..36).and_then(|v| v.try_into().ok()).map(u64::from_le_bytes).ok_or(InvalidInstructionData)?;
+                let fee_token = rest.get(36..56).map(H160::from_slice).ok_or(InvalidInstructionData)?;
+                let data = &rest[56..];
+                let transaction = MetaTransaction {from, to, data, nonce, fee, fee_token};
+                EvmInstruction::CallFromMetaTransaction {format, signature, message, transaction}
+            },
+            29 => {
+                EvmInstruction::WriteFromHolder {seed: rest}
+            },
+            30 => EvmInstruction::GetVersion,
             _ => return Err(InvalidInstructionData),
         })
     }
@@ -273,12 +713,14 @@ impl<'a> EvmInstruction<'a> {
 pub fn on_return(
     myself_program_id: &Pubkey,
     status: u8,
-    result: &Vec<u8>
+    result: &Vec<u8>,
+    telemetry: &Telemetry,
 ) -> Result<Instruction, ProgramError> {
-    let mut data = Vec::with_capacity(2 + result.len());
+    let mut data = Vec::with_capacity(2 + result.len() + Telemetry::LEN);
     data.push(6u8);
     data.push(status);
     data.extend(result);
+    data.extend(telemetry.pack());
 
     Ok(Instruction {
         program_id: *myself_program_id,
@@ -287,15 +729,26 @@ pub fn on_return(
     })
 }
 
+/// Depth of the events emitted by the executor which doesn't track call frames
+pub const UNKNOWN_EVENT_DEPTH: u16 = u16::max_value();
+
 /// Creates a `OnEvent` instruction.
 pub fn on_event(
     myself_program_id: &Pubkey,
-    log: Log
+    log: Log,
+    tx_hash: H256,
+    depth: Option<usize>,
+    index: u32,
 ) -> Result<Instruction, ProgramError> {
+    let depth = depth.map_or(UNKNOWN_EVENT_DEPTH, |depth| depth.try_into().unwrap_or(UNKNOWN_EVENT_DEPTH - 1));
+
     let mut data = Vec::new();
     data.insert(0, 7u8);
 
     data.extend_from_slice(log.address.as_bytes());
+    data.extend_from_slice(tx_hash.as_bytes());
+    data.extend_from_slice(&depth.to_le_bytes());
+    data.extend_from_slice(&index.to_le_bytes());
 
     data.extend_from_slice(&log.topics.len().to_le_bytes());
     for topic in log.topics {
@@ -310,3 +763,26 @@ pub fn on_event(
         data,
     })
 }
+
+/// Creates a `WriteFromHolder` instruction appending the part written to the holder
+/// `create_with_seed(deployer, seed, evm_loader_id)` to the code account.
+pub fn write_from_holder(
+    evm_loader_id: &Pubkey,
+    code: &Pubkey,
+    holder: &Pubkey,
+    deployer: &Pubkey,
+    seed: &str,
+) -> Instruction {
+    let mut data = vec![29u8];
+    data.extend_from_slice(seed.as_bytes());
+
+    Instruction {
+        program_id: *evm_loader_id,
+        accounts: vec![
+            AccountMeta::new(*code, false),
+            AccountMeta::new(*holder, false),
+            AccountMeta::new(*deployer, true),
+        ],
+        data,
+    }
+}
@@ -6,23 +6,35 @@
 //! An ERC20-like Token program for the Solana blockchain
 #[macro_use]
 mod debug;
-// mod error;
+pub mod error;
 pub mod entrypoint;
-//pub mod error;
+pub mod processor;
 //pub mod instruction;
 //pub mod native_mint;
 //pub mod processor;
 //pub mod state;
-mod hamt;
+pub mod hamt;
 pub mod solana_backend;
 pub mod account_data;
 pub mod account_storage;
 pub mod solidity_account;
 mod storage_account;
 pub mod instruction;
-mod transaction;
-mod executor;
-mod executor_state;
+pub mod transaction;
+pub mod executor;
+pub mod executor_state;
+pub mod exit_status;
+pub mod eip712;
+pub mod meta_transaction;
+pub mod return_data;
+pub mod version;
+pub mod cpi;
+pub mod erc20_wrapper;
+pub mod gas;
+pub mod multicall;
+pub mod profile;
+pub mod block;
+pub mod bloom;
 pub mod utils;
 
 
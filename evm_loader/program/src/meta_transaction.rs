@@ -0,0 +1,136 @@
+//! Meta-transactions: calls signed by the user and submitted by an operator
+//!
+//! The user signs the EIP-712 hash of `MetaTransaction`, with `eth_signTypedData` or with
+//! `personal_sign` of the hash. The operator pays the rent and the gas, the signed `fee`
+//! is moved from the user Ether account to the operator as the reimbursement,
+//! in lamports or in tokens of the ERC20 wrapper `fee_token` of an SPL mint.
+
+use primitive_types::{H160, H256, U256};
+use serde::Serialize;
+use solana_program::pubkey::Pubkey;
+use crate::eip712::{self, Domain};
+
+/// Encoded type of the meta-transaction
+pub const META_TRANSACTION_TYPE: &str = "MetaTransaction(address from,address to,bytes data,uint256 nonce,uint256 fee,address feeToken)";
+
+/// Prefix of the message signed with `personal_sign` of the 32 bytes hash
+const PERSONAL_SIGN_PREFIX: &[u8] = b"\x19Ethereum Signed Message:\n32";
+
+/// How the user signed the meta-transaction
+#[derive(Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SignatureFormat {
+    /// `eth_signTypedData`, the signed message is `0x19 0x01 ‖ domainSeparator ‖ hashStruct(tx)`
+    TypedData,
+    /// `personal_sign` of the EIP-712 hash, the signed message is `"\x19Ethereum Signed Message:\n32" ‖ hash`
+    PersonalSign,
+}
+
+impl SignatureFormat {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(SignatureFormat::TypedData),
+            1 => Some(SignatureFormat::PersonalSign),
+            _ => None,
+        }
+    }
+
+    /// Length of the signed message
+    pub fn message_len(self) -> usize {
+        match self {
+            SignatureFormat::TypedData => 2 + 32 + 32,
+            SignatureFormat::PersonalSign => PERSONAL_SIGN_PREFIX.len() + 32,
+        }
+    }
+}
+
+/// Call signed by the user
+#[derive(Serialize, Debug, PartialEq, Eq, Clone)]
+pub struct MetaTransaction<'a> {
+    /// Signer, the caller of the contract
+    pub from: H160,
+    /// Called contract
+    pub to: H160,
+    /// Call data
+    pub data: &'a [u8],
+    /// Transaction count of the caller Ether account
+    pub nonce: u64,
+    /// Lamports, or tokens of `fee_token`, of the caller Ether account paid to the operator
+    pub fee: u64,
+    /// ERC20 wrapper of the SPL mint the fee is paid in, the zero address for lamports
+    pub fee_token: H160,
+}
+
+/// Signing domain of the meta-transactions of the program on the chain,
+/// the program id as the salt separates deployments with the same chain id
+pub fn domain(program_id: &Pubkey, chain_id: U256) -> Domain<'static> {
+    Domain {
+        name: Some("EVM Loader"),
+        version: Some("1"),
+        chain_id: Some(chain_id),
+        verifying_contract: None,
+        salt: Some(H256::from(program_id.to_bytes())),
+    }
+}
+
+impl<'a> MetaTransaction<'a> {
+    /// `hashStruct(tx)`
+    pub fn struct_hash(&self) -> H256 {
+        eip712::struct_hash(&eip712::type_hash(META_TRANSACTION_TYPE), &[
+            eip712::encode_address(self.from),
+            eip712::encode_address(self.to),
+            eip712::encode_bytes(self.data),
+            eip712::encode_uint(U256::from(self.nonce)),
+            eip712::encode_uint(U256::from(self.fee)),
+            eip712::encode_address(self.fee_token),
+        ])
+    }
+
+    /// EIP-712 hash of the meta-transaction, it identifies the transaction in receipts and events
+    pub fn hash(&self, program_id: &Pubkey, chain_id: U256) -> H256 {
+        eip712::typed_data_hash(&domain(program_id, chain_id).separator(), &self.struct_hash())
+    }
+
+    /// Message which keccak256 hash the user signs in the given format
+    pub fn signed_message(&self, program_id: &Pubkey, chain_id: U256, format: SignatureFormat) -> Vec<u8> {
+        let mut message = Vec::with_capacity(format.message_len());
+        match format {
+            SignatureFormat::TypedData => {
+                message.extend_from_slice(&[0x19, 0x01]);
+                message.extend_from_slice(domain(program_id, chain_id).separator().as_bytes());
+                message.extend_from_slice(self.struct_hash().as_bytes());
+            },
+            SignatureFormat::PersonalSign => {
+                message.extend_from_slice(PERSONAL_SIGN_PREFIX);
+                message.extend_from_slice(self.hash(program_id, chain_id).as_bytes());
+            },
+        }
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::keccak256_h256;
+
+    #[test]
+    fn test_signed_message() {
+        let program_id = Pubkey::new_from_array([7u8; 32]);
+        let chain_id = U256::from(111);
+        let tx = MetaTransaction {from: H160::repeat_byte(1), to: H160::repeat_byte(2), data: &[0xa9, 0x05, 0x9c, 0xbb], nonce: 3, fee: 5000, fee_token: H160::zero()};
+
+        let typed = tx.signed_message(&program_id, chain_id, SignatureFormat::TypedData);
+        assert_eq!(typed.len(), SignatureFormat::TypedData.message_len());
+        assert_eq!(keccak256_h256(&typed), tx.hash(&program_id, chain_id));
+
+        let personal = tx.signed_message(&program_id, chain_id, SignatureFormat::PersonalSign);
+        assert_eq!(personal.len(), SignatureFormat::PersonalSign.message_len());
+        assert_eq!(&personal[personal.len() - 32..], tx.hash(&program_id, chain_id).as_bytes());
+
+        let other_program = Pubkey::new_from_array([8u8; 32]);
+        assert_ne!(tx.hash(&other_program, chain_id), tx.hash(&program_id, chain_id));
+        assert_ne!(tx.hash(&program_id, U256::one()), tx.hash(&program_id, chain_id));
+        assert_ne!(MetaTransaction {nonce: 4, ..tx.clone()}.hash(&program_id, chain_id), tx.hash(&program_id, chain_id));
+        assert_ne!(MetaTransaction {fee_token: H160::repeat_byte(3), ..tx.clone()}.hash(&program_id, chain_id), tx.hash(&program_id, chain_id));
+    }
+}
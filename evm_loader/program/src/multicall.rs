@@ -0,0 +1,372 @@
+//! Multicall2 precompile
+//!
+//! Calls to the multicall address are executed by `Machine` the same way as calls
+//! to the deployed `Multicall2` contract: `aggregate` and `blockAndAggregate` revert
+//! when any call fails, `tryAggregate` and `tryBlockAndAggregate` report the failure
+//! in the result unless `requireSuccess` is set. Sub-calls are made with the
+//! multicall address as `msg.sender`.
+
+use evm::Handler;
+use primitive_types::{H160, H256, U256};
+
+/// `aggregate((address,bytes)[])`
+const AGGREGATE: [u8; 4] = [0x25, 0x2d, 0xba, 0x42];
+/// `blockAndAggregate((address,bytes)[])`
+const BLOCK_AND_AGGREGATE: [u8; 4] = [0xc3, 0x07, 0x7f, 0xa9];
+/// `tryAggregate(bool,(address,bytes)[])`
+const TRY_AGGREGATE: [u8; 4] = [0xbc, 0xe3, 0x8b, 0xd7];
+/// `tryBlockAndAggregate(bool,(address,bytes)[])`
+const TRY_BLOCK_AND_AGGREGATE: [u8; 4] = [0x39, 0x95, 0x42, 0xe9];
+/// `getBlockHash(uint256)`
+const GET_BLOCK_HASH: [u8; 4] = [0xee, 0x82, 0xac, 0x5e];
+/// `getBlockNumber()`
+const GET_BLOCK_NUMBER: [u8; 4] = [0x42, 0xcb, 0xb1, 0x5c];
+/// `getCurrentBlockCoinbase()`
+const GET_CURRENT_BLOCK_COINBASE: [u8; 4] = [0xa8, 0xb0, 0x57, 0x4e];
+/// `getCurrentBlockDifficulty()`
+const GET_CURRENT_BLOCK_DIFFICULTY: [u8; 4] = [0x72, 0x42, 0x5d, 0x9d];
+/// `getCurrentBlockGasLimit()`
+const GET_CURRENT_BLOCK_GAS_LIMIT: [u8; 4] = [0x86, 0xd5, 0x16, 0xe8];
+/// `getCurrentBlockTimestamp()`
+const GET_CURRENT_BLOCK_TIMESTAMP: [u8; 4] = [0x0f, 0x28, 0xc9, 0x7d];
+/// `getEthBalance(address)`
+const GET_ETH_BALANCE: [u8; 4] = [0x4d, 0x23, 0x01, 0xcc];
+/// `getLastBlockHash()`
+const GET_LAST_BLOCK_HASH: [u8; 4] = [0x27, 0xe8, 0x6d, 0x6e];
+/// `Error(string)`
+const ERROR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Address of the multicall precompile
+pub fn address() -> H160 {
+    H160::from_slice(&[0xffu8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0x01u8])
+}
+
+/// Layout of the result of the aggregate call
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    /// `(uint256 blockNumber, bytes[] returnData)`
+    Aggregate,
+    /// `(Result[] returnData)`
+    TryAggregate,
+    /// `(uint256 blockNumber, bytes32 blockHash, Result[] returnData)`
+    BlockAndAggregate,
+}
+
+/// Calls of the aggregate request, executed one by one
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub struct Aggregate {
+    /// Failure of any call reverts the whole multicall
+    pub require_success: bool,
+    pub output: Output,
+    /// Target and input of the calls
+    pub calls: Vec<(H160, Vec<u8>)>,
+    /// Success and return data of the calls made so far
+    pub results: Vec<(bool, Vec<u8>)>,
+}
+
+/// Getter of the block or account state, answered without calls
+#[derive(Debug, PartialEq)]
+pub enum Query {
+    BlockHash(U256),
+    BlockNumber,
+    Coinbase,
+    Difficulty,
+    GasLimit,
+    Timestamp,
+    EthBalance(H160),
+    LastBlockHash,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    Aggregate(Aggregate),
+    Query(Query),
+}
+
+impl Request {
+    /// Decode ABI encoded call of the `Multicall2` function, `None` if it is malformed or unknown
+    pub fn decode(input: &[u8]) -> Option<Self> {
+        if input.len() < 4 {
+            return None;
+        }
+        let (selector, args) = input.split_at(4);
+        let selector = [selector[0], selector[1], selector[2], selector[3]];
+
+        let aggregate = |require_success: Option<bool>, calls_offset: usize, output: Output| {
+            let calls = decode_calls(args, calls_offset)?;
+            Some(Request::Aggregate(Aggregate { require_success: require_success?, output, calls, results: Vec::new() }))
+        };
+
+        match selector {
+            AGGREGATE => aggregate(Some(true), 0, Output::Aggregate),
+            BLOCK_AND_AGGREGATE => aggregate(Some(true), 0, Output::BlockAndAggregate),
+            TRY_AGGREGATE => aggregate(read_bool(args, 0), 32, Output::TryAggregate),
+            TRY_BLOCK_AND_AGGREGATE => aggregate(read_bool(args, 0), 32, Output::BlockAndAggregate),
+            GET_BLOCK_HASH => Some(Request::Query(Query::BlockHash(read_word(args, 0)?))),
+            GET_BLOCK_NUMBER => Some(Request::Query(Query::BlockNumber)),
+            GET_CURRENT_BLOCK_COINBASE => Some(Request::Query(Query::Coinbase)),
+            GET_CURRENT_BLOCK_DIFFICULTY => Some(Request::Query(Query::Difficulty)),
+            GET_CURRENT_BLOCK_GAS_LIMIT => Some(Request::Query(Query::GasLimit)),
+            GET_CURRENT_BLOCK_TIMESTAMP => Some(Request::Query(Query::Timestamp)),
+            GET_ETH_BALANCE => Some(Request::Query(Query::EthBalance(read_address(args, 0)?))),
+            GET_LAST_BLOCK_HASH => Some(Request::Query(Query::LastBlockHash)),
+            _ => None,
+        }
+    }
+}
+
+impl Query {
+    /// ABI encoded result of the getter
+    pub fn execute<H: Handler>(&self, handler: &H) -> Vec<u8> {
+        let word = match self {
+            Query::BlockHash(number) => U256::from_big_endian(handler.block_hash(*number).as_bytes()),
+            Query::BlockNumber => handler.block_number(),
+            Query::Coinbase => U256::from_big_endian(H256::from(handler.block_coinbase()).as_bytes()),
+            Query::Difficulty => handler.block_difficulty(),
+            Query::GasLimit => handler.block_gas_limit(),
+            Query::Timestamp => handler.block_timestamp(),
+            Query::EthBalance(address) => handler.balance(*address),
+            Query::LastBlockHash => {
+                let number = handler.block_number().saturating_sub(U256::one());
+                U256::from_big_endian(handler.block_hash(number).as_bytes())
+            },
+        };
+        u256_bytes(word).to_vec()
+    }
+}
+
+impl Aggregate {
+    /// Target and input of the next call, `None` when all calls are made
+    pub fn next_call(&self) -> Option<(H160, Vec<u8>)> {
+        self.calls.get(self.results.len()).cloned()
+    }
+
+    /// Revert reason of the failed call when success is required
+    pub fn failure_reason(&self) -> Vec<u8> {
+        match self.output {
+            Output::Aggregate => encode_error("Multicall aggregate: call failed"),
+            _ => encode_error("Multicall2 aggregate: call failed"),
+        }
+    }
+
+    /// ABI encoded result of the multicall
+    pub fn encode_output(&self, block_number: U256, block_hash: H256) -> Vec<u8> {
+        let mut output = Vec::new();
+        match self.output {
+            Output::Aggregate => {
+                output.extend_from_slice(&u256_bytes(block_number));
+                output.extend_from_slice(&u256_bytes(U256::from(2 * 32)));
+                output.extend(encode_array(self.results.iter().map(|(_, data)| encode_bytes(data)).collect()));
+            },
+            Output::TryAggregate => {
+                output.extend_from_slice(&u256_bytes(U256::from(32)));
+                output.extend(encode_array(self.results.iter().map(|(success, data)| encode_result(*success, data)).collect()));
+            },
+            Output::BlockAndAggregate => {
+                output.extend_from_slice(&u256_bytes(block_number));
+                output.extend_from_slice(block_hash.as_bytes());
+                output.extend_from_slice(&u256_bytes(U256::from(3 * 32)));
+                output.extend(encode_array(self.results.iter().map(|(success, data)| encode_result(*success, data)).collect()));
+            },
+        }
+        output
+    }
+}
+
+fn read_word(data: &[u8], offset: usize) -> Option<U256> {
+    let end = offset.checked_add(32)?;
+    data.get(offset..end).map(U256::from_big_endian)
+}
+
+fn read_usize(data: &[u8], offset: usize) -> Option<usize> {
+    let value = read_word(data, offset)?;
+    if value > U256::from(data.len()) {
+        return None;
+    }
+    Some(value.as_usize())
+}
+
+fn read_bool(data: &[u8], offset: usize) -> Option<bool> {
+    let value = read_word(data, offset)?;
+    if value > U256::one() {
+        return None;
+    }
+    Some(!value.is_zero())
+}
+
+fn read_address(data: &[u8], offset: usize) -> Option<H160> {
+    let word = data.get(offset..offset.checked_add(32)?)?;
+    if word[..12].iter().any(|b| *b != 0) {
+        return None;
+    }
+    Some(H160::from_slice(&word[12..]))
+}
+
+/// Decode `(address target, bytes callData)[]` referenced by the head word at `head`
+fn decode_calls(args: &[u8], head: usize) -> Option<Vec<(H160, Vec<u8>)>> {
+    let array = read_usize(args, head)?;
+    let count = read_usize(args, array)?;
+    let elements = array + 32;
+
+    let mut calls = Vec::with_capacity(count);
+    for i in 0..count {
+        let call = elements.checked_add(read_usize(args, elements + 32 * i)?)?;
+        let target = read_address(args, call)?;
+        let data = call.checked_add(read_usize(args, call + 32)?)?;
+        let len = read_usize(args, data)?;
+        let input = args.get(data + 32..(data + 32).checked_add(len)?)?;
+        calls.push((target, input.to_vec()));
+    }
+    Some(calls)
+}
+
+fn encode_bytes(data: &[u8]) -> Vec<u8> {
+    let padded_len = (data.len() + 31) / 32 * 32;
+    let mut encoded = Vec::with_capacity(32 + padded_len);
+    encoded.extend_from_slice(&u256_bytes(U256::from(data.len())));
+    encoded.extend_from_slice(data);
+    encoded.resize(32 + padded_len, 0);
+    encoded
+}
+
+/// `Result(bool success, bytes returnData)`
+fn encode_result(success: bool, data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    encoded.extend_from_slice(&u256_bytes(U256::from(success as u8)));
+    encoded.extend_from_slice(&u256_bytes(U256::from(2 * 32)));
+    encoded.extend(encode_bytes(data));
+    encoded
+}
+
+/// Dynamic array of dynamic elements: length, element offsets, elements
+fn encode_array(elements: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    encoded.extend_from_slice(&u256_bytes(U256::from(elements.len())));
+
+    let mut offset = 32 * elements.len();
+    for element in elements.iter() {
+        encoded.extend_from_slice(&u256_bytes(U256::from(offset)));
+        offset += element.len();
+    }
+    for element in elements {
+        encoded.extend(element);
+    }
+    encoded
+}
+
+/// Revert data of `require(false, message)`
+fn encode_error(message: &str) -> Vec<u8> {
+    let mut encoded = ERROR.to_vec();
+    encoded.extend_from_slice(&u256_bytes(U256::from(32)));
+    encoded.extend(encode_bytes(message.as_bytes()));
+    encoded
+}
+
+fn u256_bytes(value: U256) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::keccak256_digest;
+
+    fn selector(signature: &str) -> [u8; 4] {
+        let digest = keccak256_digest(signature.as_bytes());
+        [digest[0], digest[1], digest[2], digest[3]]
+    }
+
+    fn encode_calls(calls: &[(H160, Vec<u8>)]) -> Vec<u8> {
+        let elements = calls.iter().map(|(target, data)| {
+            let mut element = H256::from(*target).as_bytes().to_vec();
+            element.extend_from_slice(&u256_bytes(U256::from(2 * 32)));
+            element.extend(encode_bytes(data));
+            element
+        }).collect();
+        encode_array(elements)
+    }
+
+    #[test]
+    fn test_selectors() {
+        assert_eq!(AGGREGATE, selector("aggregate((address,bytes)[])"));
+        assert_eq!(BLOCK_AND_AGGREGATE, selector("blockAndAggregate((address,bytes)[])"));
+        assert_eq!(TRY_AGGREGATE, selector("tryAggregate(bool,(address,bytes)[])"));
+        assert_eq!(TRY_BLOCK_AND_AGGREGATE, selector("tryBlockAndAggregate(bool,(address,bytes)[])"));
+        assert_eq!(GET_BLOCK_HASH, selector("getBlockHash(uint256)"));
+        assert_eq!(GET_BLOCK_NUMBER, selector("getBlockNumber()"));
+        assert_eq!(GET_CURRENT_BLOCK_COINBASE, selector("getCurrentBlockCoinbase()"));
+        assert_eq!(GET_CURRENT_BLOCK_DIFFICULTY, selector("getCurrentBlockDifficulty()"));
+        assert_eq!(GET_CURRENT_BLOCK_GAS_LIMIT, selector("getCurrentBlockGasLimit()"));
+        assert_eq!(GET_CURRENT_BLOCK_TIMESTAMP, selector("getCurrentBlockTimestamp()"));
+        assert_eq!(GET_ETH_BALANCE, selector("getEthBalance(address)"));
+        assert_eq!(GET_LAST_BLOCK_HASH, selector("getLastBlockHash()"));
+        assert_eq!(ERROR, selector("Error(string)"));
+    }
+
+    #[test]
+    fn test_decode_try_aggregate() {
+        let calls = vec![
+            (H160::repeat_byte(1), vec![0x12, 0x34, 0x56, 0x78]),
+            (H160::repeat_byte(2), vec![0xab; 40]),
+        ];
+        let mut input = TRY_AGGREGATE.to_vec();
+        input.extend_from_slice(&u256_bytes(U256::zero()));
+        input.extend_from_slice(&u256_bytes(U256::from(2 * 32)));
+        input.extend(encode_calls(&calls));
+
+        let request = Request::decode(&input).unwrap();
+        assert_eq!(request, Request::Aggregate(Aggregate {
+            require_success: false,
+            output: Output::TryAggregate,
+            calls,
+            results: Vec::new(),
+        }));
+
+        assert_eq!(Request::decode(&input[..input.len() - 32]), None);
+    }
+
+    #[test]
+    fn test_decode_query() {
+        let mut input = GET_ETH_BALANCE.to_vec();
+        input.extend_from_slice(H256::from(H160::repeat_byte(7)).as_bytes());
+        assert_eq!(Request::decode(&input), Some(Request::Query(Query::EthBalance(H160::repeat_byte(7)))));
+
+        assert_eq!(Request::decode(&GET_BLOCK_NUMBER), Some(Request::Query(Query::BlockNumber)));
+        assert_eq!(Request::decode(&[0u8; 4]), None);
+    }
+
+    #[test]
+    fn test_encode_output() {
+        let aggregate = Aggregate {
+            require_success: true,
+            output: Output::Aggregate,
+            calls: Vec::new(),
+            results: vec![(true, vec![0x01; 32]), (true, Vec::new())],
+        };
+        let output = aggregate.encode_output(U256::from(5), H256::zero());
+
+        assert_eq!(output.len(), 2 * 32 + 3 * 32 + 2 * 32 + 32);
+        assert_eq!(output[31], 5);
+        assert_eq!(output[63], 0x40);
+        assert_eq!(output[95], 2);
+        assert_eq!(output[127], 0x40);
+        assert_eq!(output[159], 0x80);
+        assert_eq!(output[191], 32);
+        assert_eq!(&output[192..224], &[0x01; 32][..]);
+        assert_eq!(output[255], 0);
+
+        let aggregate = Aggregate { output: Output::TryAggregate, results: vec![(false, vec![0xff])], ..aggregate };
+        let output = aggregate.encode_output(U256::from(5), H256::zero());
+
+        assert_eq!(output.len(), 32 + 2 * 32 + 4 * 32);
+        assert_eq!(output[31], 0x20);
+        assert_eq!(output[63], 1);
+        assert_eq!(output[95], 0x20);
+        assert_eq!(output[127], 0);
+        assert_eq!(output[159], 0x40);
+        assert_eq!(output[191], 1);
+        assert_eq!(output[192], 0xff);
+    }
+}
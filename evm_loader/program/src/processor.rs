@@ -0,0 +1,2885 @@
+//! Program state processor
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction::{create_account, create_account_with_seed},
+    system_program,
+    instruction::Instruction,
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
+};
+use crate::{
+    error::EvmLoaderError,
+    instruction::{EvmInstruction, BatchCall, WRITE_APPEND_OFFSET, on_return, on_event},
+    account_data::{AccountData, Account, Alias, Contract, GasPrice, Receipt, Config, Operator},
+    account_storage::ProgramAccountStorage,
+    solana_backend::{SolanaBackend, AccountStorage, CHAIN_ID},
+    solidity_account::SolidityAccount,
+    utils::{keccak256_h256, u256_to_h256, ether_to_alias_address, solana_to_alias_address, gas_price_address, receipt_address, config_address, operator_address, ether_to_program_address},
+    meta_transaction::{MetaTransaction, SignatureFormat},
+    transaction::{UnsignedTransaction, check_chain_id, signed_transaction_hash, verify_tx_signature, check_secp256k1_instruction},
+    executor::{Machine, validate_code},
+    executor_state::{ExecutorState, ExecutorSubstate, FrameLog, TransferLedger},
+    storage_account::{StorageAccount, check_accounts_unblocked},
+    return_data::{set_transaction_result, EtherAccountInfo, Telemetry},
+    version::ProgramVersion,
+    exit_status::{exit_status, exit_status_info},
+    erc20_wrapper,
+    gas::{self, GasSettlement},
+    hamt::Hamt,
+    bloom,
+};
+use evm::{
+    backend::{Apply, Backend},
+    ExitReason, ExitFatal, ExitSucceed,
+};
+use primitive_types::{H160, U256, H256};
+
+/// Cross-program invocations performed by the processor.
+///
+/// On-chain this is `ProgramCpi`, which forwards to `solana_program::program`.
+/// Tests can substitute an implementation that records the instructions instead.
+pub trait Cpi {
+    /// Invoke a cross-program instruction
+    fn invoke(&self, instruction: &Instruction, account_infos: &[AccountInfo]) -> ProgramResult;
+
+    /// Invoke a cross-program instruction with program signatures
+    fn invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult;
+}
+
+/// `Cpi` implementation used by the program entrypoint.
+pub struct ProgramCpi;
+
+impl Cpi for ProgramCpi {
+    fn invoke(&self, instruction: &Instruction, account_infos: &[AccountInfo]) -> ProgramResult {
+        solana_program::program::invoke(instruction, account_infos)
+    }
+
+    fn invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        solana_program::program::invoke_signed(instruction, account_infos, signers_seeds)
+    }
+}
+
+/// Signature of the call submitted by the operator, checked for the chain id of the config
+enum SignedCall<'t> {
+    /// Ethereum transaction and its hash
+    Transaction(&'t UnsignedTransaction, H256),
+    /// Meta-transaction and the message the caller signed in the format
+    MetaTransaction(&'t MetaTransaction<'t>, SignatureFormat, &'t [u8]),
+}
+
+impl<'t> SignedCall<'t> {
+    /// Check that the call is signed for the chain id, returns the hash of the transaction
+    fn check(&self, program_id: &Pubkey, chain_id: U256, allow_legacy_tx: bool) -> Result<H256, ProgramError> {
+        match self {
+            SignedCall::Transaction(trx, tx_hash) => {
+                check_chain_id(trx, chain_id, allow_legacy_tx)?;
+                Ok(*tx_hash)
+            },
+            SignedCall::MetaTransaction(transaction, format, message) => {
+                if *message != transaction.signed_message(program_id, chain_id, *format).as_slice() {
+                    debug_print!("Signed message doesn't match the meta-transaction");
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                Ok(transaction.hash(program_id, chain_id))
+            },
+        }
+    }
+}
+
+/// Program state handler.
+pub struct Processor<C: Cpi> {
+    cpi: C,
+}
+
+impl<C: Cpi> Processor<C> {
+    /// Create processor which performs cross-program invocations through `cpi`
+    pub fn new(cpi: C) -> Self {
+        Self { cpi }
+    }
+
+    /// Processes an [Instruction](enum.EvmInstruction.html).
+    pub fn process<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        let instruction = EvmInstruction::unpack(instruction_data)?;
+        debug_print!("Instruction parsed");
+
+        match instruction {
+            EvmInstruction::CreateAccount {lamports, space: _, ether, nonce, hashed_keys} => {
+                self.process_create_account(program_id, accounts, lamports, ether, nonce, hashed_keys)
+            },
+            EvmInstruction::CreateAccountWithSeed {base, seed, lamports, space, owner} => {
+                self.process_create_account_with_seed(program_id, accounts, base, &seed, lamports, space, owner)
+            },
+            EvmInstruction::Write {offset, bytes} => {
+                let account_info_iter = &mut accounts.iter();
+                let account_info = next_account_info(account_info_iter)?;
+                if account_info.owner != program_id {
+                    return Err(ProgramError::InvalidArgument);
+                }
+
+                self.process_write(account_info, offset, &bytes)
+            },
+            EvmInstruction::Finalize => {
+                self.process_finalize(program_id, accounts)
+            },
+            EvmInstruction::WriteFromHolder {seed} => {
+                self.process_write_from_holder(program_id, accounts, seed)
+            },
+            EvmInstruction::GetVersion => {
+                let version = ProgramVersion::current();
+                debug_print!("Version {}, features {:#x}, precompiles {:#x}", version, version.features, version.precompiles);
+                self.invoke_on_return(program_id, accounts, ExitReason::Succeed(ExitSucceed::Returned), &version.pack(), Telemetry::default())
+            },
+            EvmInstruction::Call {bytes} => {
+                check_operator(program_id, accounts)?;
+                self.process_call(program_id, accounts, None, &bytes, u64::max_value(), None, None, None)
+            },
+            EvmInstruction::ExecuteTrxFromAccountData => {
+                check_operator(program_id, accounts)?;
+                self.process_execute_trx_from_account_data(program_id, accounts)
+            },
+            EvmInstruction::ExecuteTrxFromAccountDataIterative {step_count} => {
+                check_operator(program_id, accounts)?;
+                self.process_execute_trx_from_account_data_iterative(program_id, accounts, step_count)
+            },
+            EvmInstruction::CallFromRawEthereumTX {from_addr, sign, unsigned_msg} => {
+                check_operator(program_id, accounts)?;
+                self.process_call_from_raw_ethereum_tx(program_id, accounts, from_addr, sign, unsigned_msg)
+            },
+            EvmInstruction::CallFromMetaTransaction {format, signature: _, message, transaction} => {
+                check_operator(program_id, accounts)?;
+                self.process_call_from_meta_transaction(program_id, accounts, format, message, &transaction)
+            },
+            EvmInstruction::CheckEtheriumTX {from_addr, sign, unsigned_msg} => {
+                self.process_check_etherium_tx(program_id, accounts, from_addr, sign, unsigned_msg)
+            },
+            EvmInstruction::OnReturn {status: _, bytes: _} => {
+                Ok(())
+            },
+            EvmInstruction::OnEvent {..} => {
+                Ok(())
+            },
+            EvmInstruction::PartialCallFromRawEthereumTX {step_count, from_addr, sign, unsigned_msg} => {
+                check_operator(program_id, accounts)?;
+                self.process_partial_call_from_raw_ethereum_tx(program_id, accounts, step_count, from_addr, sign, unsigned_msg)
+            },
+            EvmInstruction::Continue {step_count} => {
+                check_operator(program_id, accounts)?;
+                self.process_continue(program_id, accounts, step_count)
+            },
+            EvmInstruction::Cancel => {
+                self.process_cancel(program_id, accounts)
+            },
+            EvmInstruction::DeleteAccount => {
+                self.process_delete_account(program_id, accounts)
+            },
+            EvmInstruction::RegisterAlias => {
+                self.process_register_alias(program_id, accounts)
+            },
+            EvmInstruction::CreateERC20Wrapper {mint} => {
+                self.process_create_erc20_wrapper(program_id, accounts, mint)
+            },
+            EvmInstruction::DepositWSOL => {
+                self.process_deposit_wsol(program_id, accounts)
+            },
+            EvmInstruction::WithdrawWSOL {amount} => {
+                self.process_withdraw_wsol(program_id, accounts, amount)
+            },
+            EvmInstruction::GetAccountInfo {ether} => {
+                self.process_get_account_info(program_id, accounts, ether)
+            },
+            EvmInstruction::GetStorageAt {address, index} => {
+                self.process_get_storage_at(program_id, accounts, address, index)
+            },
+            EvmInstruction::CallBatch {calls} => {
+                check_operator(program_id, accounts)?;
+                self.process_call_batch(program_id, accounts, &calls)
+            },
+            EvmInstruction::UpdateGasPrice {lamports_per_gas, coinbase} => {
+                self.process_update_gas_price(program_id, accounts, lamports_per_gas, coinbase)
+            },
+            EvmInstruction::VerifyStorage {address} => {
+                self.process_verify_storage(program_id, accounts, address)
+            },
+            EvmInstruction::UpdateCode {reset_storage, immutable} => {
+                self.process_update_code(program_id, accounts, reset_storage, immutable)
+            },
+            EvmInstruction::CompactStorage {address} => {
+                self.process_compact_storage(program_id, accounts, address)
+            },
+            EvmInstruction::UpdateConfig {operators_only, allow_legacy_tx, chain_id, native_mint} => {
+                self.process_update_config(program_id, accounts, operators_only, allow_legacy_tx, chain_id, native_mint)
+            },
+            EvmInstruction::RegisterOperator {operator} => {
+                self.process_register_operator(program_id, accounts, operator)
+            },
+            EvmInstruction::RemoveOperator {operator} => {
+                self.process_remove_operator(program_id, accounts, operator)
+            },
+        }
+    }
+
+    /// Processes an [CreateAccount](enum.EvmInstruction.html) instruction.
+    pub fn process_create_account<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        lamports: u64,
+        ether: H160,
+        nonce: u8,
+        hashed_keys: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let funding_info = next_account_info(account_info_iter)?;
+        let account_info = next_account_info(account_info_iter)?;
+
+        debug_print!("Ether: {} {}", &(hex::encode(ether)), &hex::encode([nonce]));
+
+        let expected_address = Pubkey::create_program_address(&[ether.as_bytes(), &[nonce]], program_id)?;
+        if expected_address != *account_info.key {
+            debug_print!("expected_address != *program_info.key");
+            return Err(ProgramError::InvalidArgument);
+        };
+
+        let code_account_key = {
+            let program_code = next_account_info(account_info_iter)?;
+            if program_code.owner == program_id {
+                if !matches!(AccountData::unpack(&program_code.data.borrow())?, AccountData::Empty) {
+                    debug_print!("Code account {} is already initialized", program_code.key);
+                    return Err(ProgramError::AccountAlreadyInitialized);
+                }
+                let deployer = keccak256_h256(&funding_info.key.to_bytes()).into();
+                let contract_data = AccountData::Contract( Contract {owner: *account_info.key, code_size: 0u32, storage_root: H256::zero(), deployer, immutable: false, hashed_keys, uploaded_len: 0} );
+                contract_data.pack(&mut program_code.data.borrow_mut())?;
+
+                *program_code.key
+            } else {
+                Pubkey::new_from_array([0u8; 32])
+            }
+        };
+
+        let account_data = AccountData::Account( Account {ether, nonce, trx_count: 0u64, signer: *funding_info.key, code_account: code_account_key, blocked: None} );
+        check_rent_exempt(lamports, account_data.size())?;
+
+        let program_seeds = [ether.as_bytes(), &[nonce]];
+        self.cpi.invoke_signed(
+            &create_account(funding_info.key, account_info.key, lamports, account_data.size() as u64, program_id),
+            &accounts, &[&program_seeds[..]]
+        )?;
+        debug_print!("create_account done");
+
+        account_data.pack(&mut account_info.data.borrow_mut())?;
+
+        let (ether_alias, _) = ether_to_alias_address(program_id, &ether);
+        let (solana_alias, _) = solana_to_alias_address(program_id, account_info.key);
+        let ether_alias_info = accounts.iter().find(|info| *info.key == ether_alias);
+        let solana_alias_info = accounts.iter().find(|info| *info.key == solana_alias);
+        if let (Some(ether_alias_info), Some(solana_alias_info)) = (ether_alias_info, solana_alias_info) {
+            let alias = Alias { ether, account: *account_info.key };
+            self.register_alias(program_id, accounts, funding_info, alias, ether_alias_info, solana_alias_info)?;
+        }
+
+        Ok(())
+    }
+
+    /// Create the Ether account of the raw transaction sender on its first transaction,
+    /// funded by the operator, the last account of the instruction, nothing to do if the account exists.
+    /// The account starts with zero nonce and holds only the rent exempt minimum.
+    /// Its signer is not set, so it is used only by signed Ethereum transactions.
+    fn create_caller_account<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        caller_info: &'a AccountInfo<'a>,
+        caller: H160,
+    ) -> ProgramResult {
+        if caller_info.owner == program_id {
+            return Ok(());
+        }
+
+        let (expected_address, nonce) = Pubkey::find_program_address(&[caller.as_bytes()], program_id);
+        if *caller_info.key != expected_address {
+            debug_print!("Invalid caller account {}, expected {}", caller_info.key, expected_address);
+            return Err(ProgramError::InvalidArgument);
+        }
+        let payer_info = operator_info(accounts)?;
+
+        let account_data = AccountData::Account( Account {ether: caller, nonce, trx_count: 0u64, signer: Pubkey::new_from_array([0u8; 32]), code_account: Pubkey::new_from_array([0u8; 32]), blocked: None} );
+        let lamports = Rent::get()?.minimum_balance(account_data.size());
+        self.cpi.invoke_signed(
+            &create_account(payer_info.key, caller_info.key, lamports, account_data.size() as u64, program_id),
+            &accounts, &[&[caller.as_bytes(), &[nonce]]]
+        )?;
+
+        account_data.pack(&mut caller_info.data.borrow_mut())?;
+        debug_print!("Caller account {} created for {}, funded by {}", caller_info.key, &caller, payer_info.key);
+
+        Ok(())
+    }
+
+    /// Processes an [RegisterAlias](enum.EvmInstruction.html) instruction.
+    pub fn process_register_alias<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let funding_info = next_account_info(account_info_iter)?;
+        let account_info = next_account_info(account_info_iter)?;
+        let ether_alias_info = next_account_info(account_info_iter)?;
+        let solana_alias_info = next_account_info(account_info_iter)?;
+
+        if account_info.owner != program_id {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let account_data = AccountData::unpack(&account_info.data.borrow())?;
+        account_data.get_account()?;
+        let account = SolidityAccount::new(program_id, account_info.key, account_info.lamports(), account_data, None)?;
+
+        let alias = Alias { ether: account.get_ether(), account: *account_info.key };
+        self.register_alias(program_id, accounts, funding_info, alias, ether_alias_info, solana_alias_info)
+    }
+
+    /// Create both registry records of the alias, or check the existing ones
+    fn register_alias<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        funding_info: &'a AccountInfo<'a>,
+        alias: Alias,
+        ether_alias_info: &'a AccountInfo<'a>,
+        solana_alias_info: &'a AccountInfo<'a>,
+    ) -> ProgramResult {
+        let (ether_alias, ether_alias_nonce) = ether_to_alias_address(program_id, &alias.ether);
+        let (solana_alias, solana_alias_nonce) = solana_to_alias_address(program_id, &alias.account);
+        if *ether_alias_info.key != ether_alias || *solana_alias_info.key != solana_alias {
+            debug_print!("Invalid alias accounts, expected {} and {}", ether_alias, solana_alias);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let (ether, account) = (alias.ether, alias.account);
+        let records = [
+            (ether_alias_info, [b"alias".as_ref(), ether.as_bytes(), &[ether_alias_nonce]]),
+            (solana_alias_info, [b"alias".as_ref(), account.as_ref(), &[solana_alias_nonce]]),
+        ];
+
+        let alias_data = AccountData::Alias(alias);
+        for (info, seeds) in records.iter() {
+            if info.owner == program_id {
+                let existing = AccountData::unpack(&info.data.borrow())?;
+                let existing = existing.get_alias()?;
+                if existing.ether != ether || existing.account != account {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                continue;
+            }
+
+            let space = alias_data.size();
+            let lamports = Rent::get()?.minimum_balance(space);
+            self.cpi.invoke_signed(
+                &create_account(funding_info.key, info.key, lamports, space as u64, program_id),
+                &accounts, &[&seeds[..]]
+            )?;
+
+            alias_data.pack(&mut info.data.borrow_mut())?;
+        }
+        debug_print!("register_alias done");
+
+        Ok(())
+    }
+
+    /// Processes an [CreateAccountWithSeed](enum.EvmInstruction.html) instruction.
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_create_account_with_seed<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        base: Pubkey,
+        seed: &[u8],
+        lamports: u64,
+        space: u64,
+        owner: Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let funding_info = next_account_info(account_info_iter)?;
+        let created_info = next_account_info(account_info_iter)?;
+        let base_info = next_account_info(account_info_iter)?;
+
+        if base_info.owner != program_id {return Err(ProgramError::InvalidArgument);}
+        let base_info_data = AccountData::unpack(&base_info.data.borrow())?;
+        match base_info_data {
+            AccountData::Account(_) => (),
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let caller = SolidityAccount::new(program_id, base_info.key, (*base_info.lamports.borrow()).clone(), base_info_data, None)?;
+
+        let (caller_ether, caller_nonce) = caller.get_seeds().ok_or(ProgramError::InvalidAccountData)?;
+        let program_seeds = [caller_ether.as_bytes(), &[caller_nonce]];
+        let seed = std::str::from_utf8(&seed).map_err(|_| ProgramError::InvalidArgument)?;
+        debug_print!("{}", &lamports.to_string());
+        debug_print!("{}", &space.to_string());
+        check_rent_exempt(lamports, space as usize)?;
+        self.cpi.invoke_signed(
+            &create_account_with_seed(funding_info.key, created_info.key, &base, &seed, lamports, space, &owner),
+            &accounts, &[&program_seeds[..]]
+        )?;
+        debug_print!("create_account_with_seed done");
+
+        Ok(())
+    }
+
+    /// Processes an [Write](enum.EvmInstruction.html) instruction.
+    pub fn process_write(&self, account_info: &AccountInfo, offset: u32, bytes: &[u8]) -> ProgramResult {
+        let mut data = account_info.data.borrow_mut();
+
+        let mut account_data = AccountData::unpack(&data)?;
+        let offset = match account_data {
+            AccountData::Contract(ref mut acc) => {
+                if acc.code_size != 0 {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                let offset = if offset == WRITE_APPEND_OFFSET { acc.uploaded_len } else { offset };
+                if offset != acc.uploaded_len {
+                    debug_print!("Write at {} must continue {} uploaded bytes", offset, acc.uploaded_len);
+                    return Err(ProgramError::InvalidArgument);
+                }
+                let len: u32 = bytes.len().try_into().map_err(|_| ProgramError::InvalidInstructionData)?;
+                acc.uploaded_len = offset.checked_add(len).ok_or(ProgramError::InvalidInstructionData)?;
+                offset
+            },
+            AccountData::Account(_) => return Err(ProgramError::InvalidAccountData),
+            AccountData::Storage(_) => return Err(ProgramError::InvalidAccountData),
+            AccountData::Alias(_) => return Err(ProgramError::InvalidAccountData),
+            AccountData::GasPrice(_) => return Err(ProgramError::InvalidAccountData),
+            AccountData::Receipt(_) => return Err(ProgramError::InvalidAccountData),
+            AccountData::Config(_) => return Err(ProgramError::InvalidAccountData),
+            AccountData::Operator(_) => return Err(ProgramError::InvalidAccountData),
+            AccountData::Empty => {
+                if offset == WRITE_APPEND_OFFSET {
+                    debug_print!("Append requires the code account");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                offset
+            },
+        };
+
+        let offset = account_data.size() + offset as usize;
+        if data.len() < offset + bytes.len() {
+            debug_print!("Account data too small");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        data[offset .. offset+bytes.len()].copy_from_slice(&bytes);
+
+        if let AccountData::Contract(_) = account_data {
+            account_data.pack(&mut data)?;
+        }
+        Ok(())
+    }
+
+    /// Processes an [WriteFromHolder](enum.EvmInstruction.html) instruction.
+    pub fn process_write_from_holder(&self, program_id: &Pubkey, accounts: &[AccountInfo], seed: &[u8]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let code_info = next_account_info(account_info_iter)?;
+        let holder_info = next_account_info(account_info_iter)?;
+        let deployer_info = next_account_info(account_info_iter)?;
+
+        if !deployer_info.is_signer {
+            debug_print!("Deployer must be signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let seed = std::str::from_utf8(seed).map_err(|_| ProgramError::InvalidArgument)?;
+        let holder_key = Pubkey::create_with_seed(deployer_info.key, seed, program_id)?;
+        if *holder_info.key != holder_key || holder_info.owner != program_id {
+            debug_print!("Holder must be created with seed {} by {}", seed, deployer_info.key);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if code_info.owner != program_id || code_info.key == holder_info.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let deployer: H160 = keccak256_h256(&deployer_info.key.to_bytes()).into();
+        let code_deployer = AccountData::unpack(&code_info.data.borrow())?.get_contract()?.deployer;
+        if code_deployer != deployer {
+            debug_print!("Only the deployer {} can append the code", code_deployer);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let part = read_holder_code(holder_info)?;
+        self.process_write(code_info, WRITE_APPEND_OFFSET, &part)?;
+        debug_print!("{} bytes appended from holder {}", part.len(), holder_info.key);
+
+        let lamports = holder_info.lamports();
+        **holder_info.lamports.borrow_mut() = 0;
+        **deployer_info.lamports.borrow_mut() += lamports;
+        for byte in holder_info.data.borrow_mut().iter_mut() {
+            *byte = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Processes an [Finalize](enum.EvmInstruction.html) instruction.
+    pub fn process_finalize<'a>(&self, program_id: &Pubkey, accounts: &'a [AccountInfo<'a>]) -> ProgramResult {
+        debug_print!("do_finalize");
+
+        let account_info_iter = &mut accounts.iter();
+        let _program_info = next_account_info(account_info_iter)?;
+        let program_code = next_account_info(account_info_iter)?;
+        let caller_info = next_account_info(account_info_iter)?;
+        let signer_info = if caller_info.owner == program_id {
+            next_account_info(account_info_iter)?
+        } else {
+            caller_info
+        };
+
+        check_accounts_unblocked(program_id, accounts, None)?;
+        let mut account_storage = ProgramAccountStorage::new(program_id, accounts)?;
+        if signer_info.is_signer {
+            account_storage.set_payer(signer_info);
+        }
+
+        check_from_or_signer(program_id, account_storage.get_caller_account()?.as_deref(), caller_info, signer_info, None)?;
+
+        let mut execution = {
+            let backend = SolanaBackend::new(&account_storage, Some(accounts));
+            debug_print!("  backend initialized");
+            let mut executor = Machine::new(ExecutorState::new(ExecutorSubstate::new(), backend));
+            debug_print!("  executor initialized");
+
+            let code_data = read_contract_code(program_code)?;
+            let caller = account_storage.origin();
+            let scheme = evm::CreateScheme::Create2 { caller, code_hash: keccak256_h256(&code_data), salt: H256::default() };
+
+            debug_print!("Execute create");
+            let exit_reason = match executor.create_begin(caller, code_data, U256::zero(), scheme, u64::max_value()) {
+                Ok(()) => executor.execute(),
+                Err(reason) => reason,
+            };
+            debug_print!("  create2 done");
+
+            Execution::new(executor, exit_reason)
+        };
+
+        self.settle_execution(program_id, accounts, &mut account_storage, &mut execution, H256::zero())?;
+        account_storage.charge_gas(&execution.gas_settlement)?;
+
+        self.invoke_on_return(&program_id, &accounts, execution.exit_reason, &execution.result, execution.telemetry)?;
+
+        Ok(())
+    }
+
+    /// Processes an [CreateERC20Wrapper](enum.EvmInstruction.html) instruction.
+    pub fn process_create_erc20_wrapper<'a>(&self, program_id: &Pubkey, accounts: &'a [AccountInfo<'a>], mint: Pubkey) -> ProgramResult {
+        debug_print!("create_erc20_wrapper");
+
+        let account_info_iter = &mut accounts.iter();
+        let _program_info = next_account_info(account_info_iter)?;
+        let program_code = next_account_info(account_info_iter)?;
+        let caller_info = next_account_info(account_info_iter)?;
+        let signer_info = if caller_info.owner == program_id {
+            next_account_info(account_info_iter)?
+        } else {
+            caller_info
+        };
+
+        let mint_info = accounts.iter().find(|info| *info.key == mint).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if *mint_info.owner != erc20_wrapper::spl_token::id() {
+            debug_print!("Mint {} is not owned by SPL Token", mint);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let (registry_key, registry_nonce) = erc20_wrapper::registry_address(program_id, &mint);
+        let registry_info = accounts.iter().find(|info| *info.key == registry_key).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if registry_info.owner == program_id {
+            debug_print!("Wrapper for mint {} already exists", mint);
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        check_accounts_unblocked(program_id, accounts, None)?;
+        let mut account_storage = ProgramAccountStorage::new(program_id, accounts)?;
+        if signer_info.is_signer {
+            account_storage.set_payer(signer_info);
+        }
+
+        check_from_or_signer(program_id, account_storage.get_caller_account()?.as_deref(), caller_info, signer_info, None)?;
+
+        let template = read_contract_code(program_code)?;
+        let wrapper = erc20_wrapper::wrapper_address(&template, &mint);
+        if account_storage.contract() != wrapper {
+            debug_print!("Wrapper address mismatch: expected {}", &wrapper.to_string());
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut execution = {
+            let backend = SolanaBackend::new(&account_storage, Some(accounts));
+            let mut executor = Machine::new(ExecutorState::new(ExecutorSubstate::new(), backend));
+
+            let init_code = erc20_wrapper::init_code(&template, &mint);
+            let scheme = evm::CreateScheme::Create2 {
+                caller: erc20_wrapper::factory_address(),
+                code_hash: keccak256_h256(&init_code),
+                salt: erc20_wrapper::salt(&mint),
+            };
+            let exit_reason = match executor.create_begin(erc20_wrapper::factory_address(), init_code, U256::zero(), scheme, u64::max_value()) {
+                Ok(()) => executor.execute(),
+                Err(reason) => reason,
+            };
+            debug_print!("  create2 done");
+
+            Execution::new(executor, exit_reason)
+        };
+
+        let succeed = execution.exit_reason.is_succeed();
+        self.settle_execution(program_id, accounts, &mut account_storage, &mut execution, H256::zero())?;
+
+        if succeed {
+            let registry_data = AccountData::Alias( Alias {ether: wrapper, account: mint} );
+            let space = registry_data.size();
+            let lamports = Rent::get()?.minimum_balance(space);
+            let seeds = erc20_wrapper::registry_seeds(&mint);
+            let registry_seeds = [seeds[0], seeds[1], &[registry_nonce]];
+            self.cpi.invoke_signed(
+                &create_account(signer_info.key, registry_info.key, lamports, space as u64, program_id),
+                &accounts, &[&registry_seeds[..]]
+            )?;
+            registry_data.pack(&mut registry_info.data.borrow_mut())?;
+            debug_print!("Wrapper {} registered for mint {}", &wrapper.to_string(), mint);
+        }
+
+        self.invoke_on_return(&program_id, &accounts, execution.exit_reason, &Vec::new(), execution.telemetry)?;
+
+        Ok(())
+    }
+
+    /// Processes an [Call](enum.EvmInstruction.html) instruction.
+    /// `gas_limit` is the limit of the signed transaction, `u64::max_value()` for calls without one.
+    /// The signed call is checked for the chain id of the config passed among the accounts.
+    /// `sponsor_fee` is the fee and the fee token the caller of the sponsored call pays to the payer.
+    fn process_call<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        signed: Option<SignedCall>,
+        instruction_data: &[u8],
+        gas_limit: u64,
+        from_info: Option<(H160, u64)>,
+        to: Option<H160>,
+        sponsor_fee: Option<(u64, H160)>,
+    ) -> ProgramResult
+    {
+        debug_print!("do_call");
+
+        let account_info_iter = &mut accounts.iter();
+        let _program_info = next_account_info(account_info_iter)?;
+        let _program_code = next_account_info(account_info_iter)?;
+        let caller_info = next_account_info(account_info_iter)?;
+        let signer_info = if caller_info.owner == program_id {
+            next_account_info(account_info_iter)?
+        } else {
+            caller_info
+        };
+
+        check_accounts_unblocked(program_id, accounts, None)?;
+        let mut account_storage = ProgramAccountStorage::new(program_id, accounts)?;
+        if signer_info.is_signer {
+            account_storage.set_payer(signer_info);
+        }
+
+        check_from_or_signer(program_id, account_storage.get_caller_account()?.as_deref(), caller_info, signer_info, from_info)?;
+        check_contract(&account_storage, to)?;
+        let tx_hash = match signed {
+            Some(signed) => Some(signed.check(program_id, account_storage.chain_id(), account_storage.allow_legacy_tx())?),
+            None => None,
+        };
+        if sponsor_fee.is_some() {
+            if account_storage.payer().is_none() {
+                debug_print!("Sponsored call requires the payer");
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            account_storage.sponsor();
+        }
+
+        debug_print!("   caller: {}", &account_storage.origin().to_string());
+        debug_print!(" contract: {}", &account_storage.contract().to_string());
+
+        account_storage.check_gas_prepaid(gas_limit)?;
+        let mut execution = {
+            let backend = SolanaBackend::new(&account_storage, Some(accounts));
+            debug_print!("  backend initialized");
+
+            let mut executor = Machine::new(ExecutorState::new(ExecutorSubstate::new(), backend));
+            debug_print!("Executor initialized");
+
+            executor.call_begin(account_storage.origin(), account_storage.contract(), instruction_data.to_vec(), gas_limit);
+            let exit_reason = executor.execute();
+
+            debug_print!("Call done");
+
+            Execution::new(executor, exit_reason)
+        };
+
+        let (logs_count, logs_bloom) = self.settle_execution(program_id, accounts, &mut account_storage, &mut execution, tx_hash.unwrap_or_default())?;
+        account_storage.charge_gas(&execution.gas_settlement)?;
+
+        match sponsor_fee {
+            Some((fee, fee_token)) if fee_token.is_zero() => account_storage.reimburse_payer(fee)?,
+            Some((fee, fee_token)) => self.reimburse_payer_in_tokens(program_id, accounts, &account_storage, fee, fee_token)?,
+            None => {},
+        }
+
+        if let Some(tx_hash) = tx_hash {
+            let receipt = Receipt {
+                tx_hash,
+                status: exit_status(&execution.exit_reason),
+                gas_used: execution.gas_settlement.gas_used,
+                logs_count,
+                logs_bloom,
+                slot: account_storage.block_number().as_u64(),
+            };
+            self.write_receipt(program_id, accounts, account_storage.payer(), receipt)?;
+        }
+
+        self.invoke_on_return(&program_id, &accounts, execution.exit_reason, &execution.result, execution.telemetry)?;
+
+        Ok(())
+    }
+
+    /// Move the fee in tokens of the ERC20 wrapper `fee_token` from the associated token account
+    /// of the caller to the one of the payer. The wrapped mint is taken from its registry record.
+    fn reimburse_payer_in_tokens<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        account_storage: &ProgramAccountStorage<'a>,
+        fee: u64,
+        fee_token: H160,
+    ) -> ProgramResult {
+        if fee == 0 {
+            return Ok(());
+        }
+        let payer_info = account_storage.payer().ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        let mint = accounts.iter()
+            .filter(|info| info.owner == program_id)
+            .find_map(|info| match AccountData::unpack(&info.data.borrow()) {
+                Ok(AccountData::Alias(alias)) if alias.ether == fee_token
+                    && erc20_wrapper::registry_address(program_id, &alias.account).0 == *info.key => Some(alias.account),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                debug_print!("Registry record of the fee token {} is not passed", fee_token);
+                ProgramError::NotEnoughAccountKeys
+            })?;
+
+        let caller = account_storage.origin();
+        let (caller_key, caller_nonce) = ether_to_program_address(program_id, &caller);
+        let source = erc20_wrapper::associated_token_address(&caller_key, &mint);
+        let destination = erc20_wrapper::associated_token_address(payer_info.key, &mint);
+        debug_print!("Move {} tokens of {} from {} to {}", fee, mint, source, destination);
+        self.cpi.invoke_signed(
+            &erc20_wrapper::spl_token::transfer(&source, &destination, &caller_key, fee),
+            &accounts, &[&[caller.as_bytes(), &[caller_nonce]]]
+        )
+    }
+
+    /// Create the receipt account of the Ethereum transaction, if it is passed among the accounts.
+    /// The account is `receipt_address(tx_hash)` funded by the payer.
+    fn write_receipt<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        payer_info: Option<&'a AccountInfo<'a>>,
+        receipt: Receipt,
+    ) -> ProgramResult {
+        let tx_hash = receipt.tx_hash;
+        let (receipt_key, nonce) = receipt_address(program_id, &tx_hash);
+        let receipt_info = match accounts.iter().find(|info| *info.key == receipt_key) {
+            Some(receipt_info) => receipt_info,
+            None => return Ok(()),
+        };
+        if receipt_info.owner == program_id {
+            debug_print!("Receipt {} already exists", receipt_key);
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let payer_info = payer_info.ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        let receipt_data = AccountData::Receipt(receipt);
+        let space = receipt_data.size();
+        let lamports = Rent::get()?.minimum_balance(space);
+        self.cpi.invoke_signed(
+            &create_account(payer_info.key, receipt_info.key, lamports, space as u64, program_id),
+            &accounts, &[&[b"receipt".as_ref(), tx_hash.as_bytes(), &[nonce]]]
+        )?;
+
+        receipt_data.pack(&mut receipt_info.data.borrow_mut())?;
+        debug_print!("Receipt {} written", receipt_key);
+
+        Ok(())
+    }
+
+    /// Processes an [CallBatch](enum.EvmInstruction.html) instruction.
+    pub fn process_call_batch<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        calls: &[BatchCall],
+    ) -> ProgramResult
+    {
+        debug_print!("do_call_batch");
+        if calls.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let account_info_iter = &mut accounts.iter();
+        let _program_info = next_account_info(account_info_iter)?;
+        let _program_code = next_account_info(account_info_iter)?;
+        let caller_info = next_account_info(account_info_iter)?;
+        let signer_info = if caller_info.owner == program_id {
+            next_account_info(account_info_iter)?
+        } else {
+            caller_info
+        };
+
+        check_accounts_unblocked(program_id, accounts, None)?;
+        let mut account_storage = ProgramAccountStorage::new(program_id, accounts)?;
+        if signer_info.is_signer {
+            account_storage.set_payer(signer_info);
+        }
+
+        check_from_or_signer(program_id, account_storage.get_caller_account()?.as_deref(), caller_info, signer_info, None)?;
+
+        debug_print!("   caller: {}", &account_storage.origin().to_string());
+
+        let mut execution = {
+            let backend = SolanaBackend::new(&account_storage, Some(accounts));
+            debug_print!("  backend initialized");
+
+            let mut executor = Machine::new(ExecutorState::new(ExecutorSubstate::new(), backend));
+            debug_print!("Executor initialized");
+
+            let mut exit_reason = ExitReason::Succeed(ExitSucceed::Stopped);
+            let mut result = Vec::new();
+            for (index, call) in calls.iter().enumerate() {
+                debug_print!("Call {}: {}", index, &call.to.to_string());
+                // The nonce of the caller is incremented once per batch
+                let begin = if index == 0 {
+                    executor.call_begin_with_value(account_storage.origin(), call.to, call.data.to_vec(), call.value, u64::max_value())
+                } else {
+                    executor.call_next_with_value(account_storage.origin(), call.to, call.data.to_vec(), call.value, u64::max_value())
+                };
+                exit_reason = match begin {
+                    Ok(()) => executor.execute(),
+                    Err(reason) => reason,
+                };
+                let call_result = executor.return_value();
+
+                if !exit_reason.is_succeed() {
+                    debug_print!("Call {} failed, batch is reverted", index);
+                    result = call_result;
+                    break;
+                }
+                result.extend_from_slice(&(call_result.len() as u32).to_le_bytes());
+                result.extend_from_slice(&call_result);
+            }
+
+            Execution { result, ..Execution::new(executor, exit_reason) }
+        };
+
+        self.settle_execution(program_id, accounts, &mut account_storage, &mut execution, H256::zero())?;
+        account_storage.charge_gas(&execution.gas_settlement)?;
+
+        self.invoke_on_return(&program_id, &accounts, execution.exit_reason, &execution.result, execution.telemetry)?;
+
+        Ok(())
+    }
+
+    /// Processes an [ExecuteTrxFromAccountData](enum.EvmInstruction.html) instruction.
+    pub fn process_execute_trx_from_account_data<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+    ) -> ProgramResult {
+        debug_print!("Execute transaction from account data");
+
+        let account_info_iter = &mut accounts.iter();
+        let trx_info = next_account_info(account_info_iter)?;
+
+        let (unsigned_msg, signature) = read_holder_transaction(trx_info)?;
+
+        if let Err(e) = verify_tx_signature(&signature, &unsigned_msg) {
+            debug_print!("{}", e);
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let tx_hash = signed_transaction_hash(&unsigned_msg, &signature).map_err(|_| ProgramError::InvalidInstructionData)?;
+        let trx: UnsignedTransaction = rlp::decode(&unsigned_msg).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+        check_accounts_unblocked(program_id, &accounts[1..], None)?;
+        let mut account_storage = ProgramAccountStorage::new(program_id, &accounts[1..])?;
+
+        let mut execution = {
+            let caller = account_storage.get_caller_account()?.ok_or(ProgramError::InvalidArgument)?;
+            if caller.get_nonce() != trx.nonce {
+                debug_print!("Invalid nonce: actual {}, expect {}", trx.nonce, caller.get_nonce());
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            let caller_ether = caller.get_ether();
+
+            let backend = SolanaBackend::new(&account_storage, Some(accounts));
+            debug_print!("  backend initialized");
+
+            check_chain_id(&trx, backend.chain_id(), account_storage.allow_legacy_tx())?;
+
+            let mut executor = Machine::new(ExecutorState::new(ExecutorSubstate::new(), backend));
+            debug_print!("Executor initialized");
+
+            let exit_reason = match trx.to {
+                None => {
+                    let scheme = evm::CreateScheme::Legacy { caller: caller_ether };
+                    match executor.create_begin(caller_ether, trx.call_data, U256::zero(), scheme, u64::max_value()) {
+                        Ok(()) => executor.execute(),
+                        Err(reason) => reason,
+                    }
+                },
+                Some(_) => {
+                    debug_print!("Not supported");
+                    ExitReason::Fatal(ExitFatal::NotSupported)
+                },
+            };
+
+            Execution { result: Vec::new(), ..Execution::new(executor, exit_reason) }
+        };
+
+        self.settle_execution(program_id, accounts, &mut account_storage, &mut execution, tx_hash)?;
+        account_storage.charge_gas(&execution.gas_settlement)?;
+
+        self.invoke_on_return(&program_id, &accounts, execution.exit_reason, &execution.result, execution.telemetry)?;
+
+        Ok(())
+    }
+
+    /// Processes an [ExecuteTrxFromAccountDataIterative](enum.EvmInstruction.html) instruction.
+    pub fn process_execute_trx_from_account_data_iterative<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        step_count: u64,
+    ) -> ProgramResult {
+        debug_print!("Execute iterative transaction from account data");
+        let account_info_iter = &mut accounts.iter();
+        let holder_info = next_account_info(account_info_iter)?;
+        let storage_info = next_account_info(account_info_iter)?;
+
+        let accounts = &accounts[1..];
+
+        let (unsigned_msg, signature) = read_holder_transaction(holder_info)?;
+        if let Err(e) = verify_tx_signature(&signature, &unsigned_msg) {
+            debug_print!("{}", e);
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let tx_hash = signed_transaction_hash(&unsigned_msg, &signature).map_err(|_| ProgramError::InvalidInstructionData)?;
+        let trx: UnsignedTransaction = rlp::decode(&unsigned_msg).map_err(|_| ProgramError::InvalidInstructionData)?;
+        if trx.to.is_some() {
+            debug_print!("This is not deploy contract transaction");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        check_accounts_unblocked(program_id, &accounts[1..], None)?;
+        let account_storage = ProgramAccountStorage::new(program_id, &accounts[1..])?;
+
+        let caller = account_storage.get_caller_account()?.ok_or(ProgramError::InvalidArgument)?;
+        if caller.get_nonce() != trx.nonce {
+            debug_print!("Invalid nonce: actual {}, expect {}", trx.nonce, caller.get_nonce());
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let caller_ether = caller.get_ether();
+        debug_print!("   caller: {}", &caller_ether.to_string());
+
+        let operator = operator_info(accounts)?;
+        let mut storage = StorageAccount::new(storage_info, accounts, caller_ether, trx.nonce, tx_hash, *operator.key)?;
+
+        let backend = SolanaBackend::new(&account_storage, Some(accounts));
+        debug_print!("  backend initialized");
+
+        check_chain_id(&trx, backend.chain_id(), account_storage.allow_legacy_tx())?;
+
+        let executor_state = ExecutorState::new(ExecutorSubstate::new(), backend);
+        let mut executor = Machine::new(executor_state);
+
+        debug_print!("Executor initialized");
+
+        account_storage.check_gas_prepaid(gas::gas_limit(trx.gas_limit))?;
+        let scheme = evm::CreateScheme::Legacy { caller: caller_ether };
+        executor.create_begin(caller_ether, trx.call_data, trx.value, scheme, gas::gas_limit(trx.gas_limit))
+            .map_err(|reason| match reason {
+                ExitReason::Error(evm::ExitError::OutOfFund) => ProgramError::InsufficientFunds,
+                _ => ProgramError::InvalidInstructionData,
+            })?;
+        executor.execute_n_steps(step_count).unwrap();
+        account_storage.check_touched_accounts(executor.touched_accounts())?;
+
+        self.emit_frame_logs(program_id, accounts, executor.drain_committed_logs(), tx_hash)?;
+
+        debug_print!("save");
+        executor.save_into(&mut storage)?;
+        storage.block_accounts(program_id, accounts)
+    }
+
+    /// Processes an [CallFromRawEthereumTX](enum.EvmInstruction.html) instruction.
+    pub fn process_call_from_raw_ethereum_tx<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        from_addr: &[u8],
+        sign: &[u8],
+        unsigned_msg: &[u8],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let _program_info = next_account_info(account_info_iter)?;
+        let _program_code = next_account_info(account_info_iter)?;
+        let caller_info = next_account_info(account_info_iter)?;
+        let sysvar_info = next_account_info(account_info_iter)?;
+        let _clock_info = next_account_info(account_info_iter)?;
+
+        check_secp256k1_instruction(sysvar_info, unsigned_msg.len(), 1u16)?;
+
+        let caller = H160::from_slice(from_addr);
+        let trx: UnsignedTransaction = rlp::decode(unsigned_msg).map_err(|_| ProgramError::InvalidInstructionData)?;
+        let contract = trx.to.ok_or(ProgramError::InvalidInstructionData)?;
+        let tx_hash = signed_transaction_hash(unsigned_msg, sign).map_err(|_| ProgramError::InvalidInstructionData)?;
+        self.create_caller_account(program_id, accounts, caller_info, caller)?;
+
+        self.process_call(program_id, accounts, Some(SignedCall::Transaction(&trx, tx_hash)), &trx.call_data, gas::gas_limit(trx.gas_limit), Some( (caller, trx.nonce) ), Some(contract), None)
+    }
+
+    /// Processes an [CallFromMetaTransaction](enum.EvmInstruction.html) instruction.
+    pub fn process_call_from_meta_transaction<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        format: SignatureFormat,
+        message: &[u8],
+        transaction: &MetaTransaction,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let _program_info = next_account_info(account_info_iter)?;
+        let _program_code = next_account_info(account_info_iter)?;
+        let caller_info = next_account_info(account_info_iter)?;
+        let sysvar_info = next_account_info(account_info_iter)?;
+
+        check_secp256k1_instruction(sysvar_info, message.len(), 2u16)?;
+        self.create_caller_account(program_id, accounts, caller_info, transaction.from)?;
+
+        let signed = SignedCall::MetaTransaction(transaction, format, message);
+        self.process_call(program_id, accounts, Some(signed), transaction.data, u64::max_value(), Some( (transaction.from, transaction.nonce) ), Some(transaction.to), Some((transaction.fee, transaction.fee_token)))
+    }
+
+    /// Processes an [CheckEtheriumTX](enum.EvmInstruction.html) instruction.
+    pub fn process_check_etherium_tx<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        from_addr: &[u8],
+        sign: &[u8],
+        unsigned_msg: &[u8],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_info = next_account_info(account_info_iter)?;
+        let _program_code = next_account_info(account_info_iter)?;
+        let caller_info = next_account_info(account_info_iter)?;
+        let sysvar_info = next_account_info(account_info_iter)?;
+        let _clock_info = next_account_info(account_info_iter)?;
+
+        check_secp256k1_instruction(sysvar_info, unsigned_msg.len(), 1u16)?;
+
+        let caller = H160::from_slice(from_addr);
+        let trx: UnsignedTransaction = rlp::decode(unsigned_msg).map_err(|_| ProgramError::InvalidInstructionData)?;
+        let contract = trx.to.ok_or(ProgramError::InvalidInstructionData)?;
+
+        let program_eth: H160 = keccak256_h256(&program_info.key.to_bytes()).into();
+
+        debug_print!("caller: {}", &caller.to_string());
+        debug_print!("contract: {}", &contract.to_string());
+        debug_print!("program_eth: {}", &program_eth.to_string());
+
+        if program_eth != contract {
+            debug_print!("Add valid account signer");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if caller_info.owner != program_id {
+            debug_print!("Add valid account signer");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let tx_hash = signed_transaction_hash(unsigned_msg, sign).map_err(|_| ProgramError::InvalidInstructionData)?;
+        self.process_call(program_id, accounts, Some(SignedCall::Transaction(&trx, tx_hash)), &trx.call_data, gas::gas_limit(trx.gas_limit), Some( (caller, trx.nonce) ), Some(contract), None)
+    }
+
+    /// Processes an [PartialCallFromRawEthereumTX](enum.EvmInstruction.html) instruction.
+    pub fn process_partial_call_from_raw_ethereum_tx<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        step_count: u64,
+        from_addr: &[u8],
+        sign: &[u8],
+        unsigned_msg: &[u8],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let storage_info = next_account_info(account_info_iter)?;
+        let _program_info = next_account_info(account_info_iter)?;
+        let _program_code = next_account_info(account_info_iter)?;
+        let caller_info = next_account_info(account_info_iter)?;
+        let sysvar_info = next_account_info(account_info_iter)?;
+
+        check_secp256k1_instruction(sysvar_info, unsigned_msg.len(), 9u16)?;
+
+        let caller = H160::from_slice(from_addr);
+        let trx: UnsignedTransaction = rlp::decode(unsigned_msg).map_err(|_| ProgramError::InvalidInstructionData)?;
+        let contract = trx.to.ok_or(ProgramError::InvalidInstructionData)?;
+        let tx_hash = signed_transaction_hash(unsigned_msg, sign).map_err(|_| ProgramError::InvalidInstructionData)?;
+        self.create_caller_account(program_id, accounts, caller_info, caller)?;
+
+        let operator = operator_info(accounts)?;
+        let mut storage = StorageAccount::new(storage_info, accounts, caller, trx.nonce, tx_hash, *operator.key)?;
+
+        self.do_partial_call(&mut storage, program_id, step_count, &accounts[1..], &trx, Some( (caller, trx.nonce) ), Some(contract), tx_hash)?;
+
+        storage.block_accounts(program_id, accounts)
+    }
+
+    /// Processes an [Continue](enum.EvmInstruction.html) instruction.
+    pub fn process_continue<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        step_count: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let storage_info = next_account_info(account_info_iter)?;
+
+        let mut storage = StorageAccount::restore(storage_info)?;
+        storage.check_accounts(program_id, accounts)?;
+
+        let caller_and_nonce = storage.caller_and_nonce()?;
+
+        let exit_reason = self.do_continue(&mut storage, program_id, step_count, &accounts[1..], Some(caller_and_nonce))?;
+        if exit_reason != None {
+            storage.unblock_accounts_and_destroy(program_id, accounts)?;
+        }
+
+        Ok(())
+    }
+
+    /// Processes an [Cancel](enum.EvmInstruction.html) instruction.
+    pub fn process_cancel<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+    ) -> ProgramResult {
+        let (canceller_info, accounts) = accounts.split_last().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let account_info_iter = &mut accounts.iter();
+        let storage_info = next_account_info(account_info_iter)?;
+
+        let storage = StorageAccount::restore(storage_info)?;
+        storage.check_accounts(program_id, accounts)?;
+
+        let (caller, nonce) = storage.caller_and_nonce()?;
+        let caller_info = accounts.iter()
+            .filter(|a| a.owner == program_id)
+            .find(|a| matches!(AccountData::unpack(&a.data.borrow()), Ok(AccountData::Account(ref acc)) if acc.ether == caller))
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        {
+            let mut data = caller_info.try_borrow_mut_data()?;
+            let mut account_data = AccountData::unpack(&data)?;
+            let account = account_data.get_mut_account()?;
+
+            let operator = storage.operator()?;
+            if !canceller_info.is_signer || (*canceller_info.key != account.signer && *canceller_info.key != operator) {
+                debug_print!("Cancel must be signed by the caller signer {} or the operator {}", &account.signer, &operator);
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            if account.trx_count == nonce {
+                debug_print!("Burn nonce {} of {}", nonce, &caller.to_string());
+                account.trx_count += 1;
+                account_data.pack(&mut data)?;
+            }
+        }
+
+        self.settle_cancelled_gas(&storage, program_id, accounts, caller_info)?;
+
+        storage.unblock_accounts_and_destroy(program_id, accounts)?;
+
+        Ok(())
+    }
+
+    /// Charge the gas used by the cancelled execution and the cancel penalty from the caller to the operator
+    /// which started it, at the price of the gas price oracle. The unused part of the prepaid gas stays with the caller.
+    /// Cancel must release the locked accounts anyway, so the charge is capped at the caller lamports above the rent-exempt minimum.
+    fn settle_cancelled_gas<'a>(
+        &self,
+        storage: &StorageAccount,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        caller_info: &'a AccountInfo<'a>,
+    ) -> ProgramResult {
+        let operator = storage.operator()?;
+        let operator_info = accounts.iter().find(|a| *a.key == operator).ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        let (settlement, lamports_per_gas) = {
+            let account_storage = ProgramAccountStorage::new(program_id, &accounts[1..])?;
+            let backend = SolanaBackend::new(&account_storage, Some(&accounts[1..]));
+            let executor = Machine::restore(storage, backend)?;
+            (executor.gas_settlement().with_penalty(gas::CANCEL_PENALTY), account_storage.gas_price().as_u64())
+        };
+        if caller_info.key == operator_info.key {
+            return Ok(());
+        }
+        let fee = settlement.charged_lamports(lamports_per_gas).unwrap_or(u64::max_value());
+        let available = caller_info.lamports().saturating_sub(Rent::get()?.minimum_balance(caller_info.data_len()));
+        let fee = fee.min(available);
+        if fee == 0 {
+            return Ok(());
+        }
+
+        debug_print!("Charge {} lamports for {} gas of the cancelled execution, {} unused", &fee, settlement.gas_used, settlement.unused());
+        let operator_lamports = operator_info.lamports().checked_add(fee).ok_or(ProgramError::InvalidArgument)?;
+        **caller_info.lamports.borrow_mut() = caller_info.lamports() - fee;
+        **operator_info.lamports.borrow_mut() = operator_lamports;
+
+        Ok(())
+    }
+
+    /// Processes an [DeleteAccount](enum.EvmInstruction.html) instruction.
+    pub fn process_delete_account<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let signer_info = next_account_info(account_info_iter)?;
+
+        if account_info.owner != program_id || account_info.key == signer_info.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let account_data = AccountData::unpack(&account_info.data.borrow())?;
+        let account = account_data.get_account()?;
+        if !signer_info.is_signer || *signer_info.key != account.signer {
+            debug_print!("Account {} must be deleted by its signer {}", account_info.key, account.signer);
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if account.trx_count != 0 || account.code_account != Pubkey::new_from_array([0u8; 32]) || account.blocked.is_some() {
+            debug_print!("Account {} is not empty", account_info.key);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let minimum_balance = Rent::get()?.minimum_balance(account_info.data_len());
+        let lamports = account_info.lamports();
+        if lamports > minimum_balance {
+            debug_print!("Account {} has balance {}, rent exempt minimum {}", account_info.key, lamports, minimum_balance);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        debug_print!("Delete account {}, refund {} lamports to {}", account_info.key, lamports, signer_info.key);
+        **signer_info.lamports.borrow_mut() = signer_info.lamports().checked_add(lamports).ok_or(ProgramError::InvalidArgument)?;
+        **account_info.lamports.borrow_mut() = 0;
+        account_info.data.borrow_mut().iter_mut().for_each(|byte| *byte = 0);
+
+        Ok(())
+    }
+
+    /// Processes an [DepositWSOL](enum.EvmInstruction.html) instruction.
+    pub fn process_deposit_wsol<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let token_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        if account_info.owner != program_id {
+            return Err(ProgramError::InvalidArgument);
+        }
+        AccountData::unpack(&account_info.data.borrow())?.get_account()?;
+        check_accounts_unblocked(program_id, &accounts[..1], None)?;
+        check_wsol_account(token_info)?;
+
+        let lamports = token_info.lamports();
+        self.cpi.invoke(
+            &erc20_wrapper::spl_token::close_account(token_info.key, account_info.key, owner_info.key),
+            &accounts
+        )?;
+        debug_print!("Deposit {} lamports to {}", lamports, account_info.key);
+
+        Ok(())
+    }
+
+    /// Processes an [WithdrawWSOL](enum.EvmInstruction.html) instruction.
+    pub fn process_withdraw_wsol<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let signer_info = next_account_info(account_info_iter)?;
+        let token_info = next_account_info(account_info_iter)?;
+
+        if account_info.owner != program_id {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let account_data = AccountData::unpack(&account_info.data.borrow())?;
+        let account = account_data.get_account()?;
+        if account.code_account != Pubkey::new_from_array([0u8; 32]) {
+            debug_print!("Contract account {} can't withdraw", account_info.key);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if !signer_info.is_signer || *signer_info.key != account.signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        check_accounts_unblocked(program_id, &accounts[..1], None)?;
+        check_wsol_account(token_info)?;
+
+        let minimum_balance = Rent::get()?.minimum_balance(account_info.data_len());
+        let lamports = account_info.lamports().checked_sub(amount).ok_or(ProgramError::InsufficientFunds)?;
+        if lamports < minimum_balance {
+            debug_print!("Account {} balance after withdraw {} is below rent exempt minimum {}", account_info.key, lamports, minimum_balance);
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        **account_info.lamports.borrow_mut() = lamports;
+        **token_info.lamports.borrow_mut() = token_info.lamports().checked_add(amount).ok_or(ProgramError::InvalidArgument)?;
+
+        self.cpi.invoke(&erc20_wrapper::spl_token::sync_native(token_info.key), &accounts)?;
+        debug_print!("Withdraw {} lamports from {}", amount, account_info.key);
+
+        Ok(())
+    }
+
+    /// Processes an [UpdateGasPrice](enum.EvmInstruction.html) instruction.
+    pub fn process_update_gas_price<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        lamports_per_gas: u64,
+        coinbase: H160,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let gas_price_info = next_account_info(account_info_iter)?;
+        let operator_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        if !operator_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (gas_price_key, nonce) = gas_price_address(program_id);
+        if *gas_price_info.key != gas_price_key {
+            debug_print!("Invalid gas price account, expected {}", gas_price_key);
+            return Err(ProgramError::InvalidArgument);
+        }
+        let slot = Clock::from_account_info(clock_info)?.slot;
+
+        if gas_price_info.owner != program_id {
+            let gas_price = AccountData::GasPrice(GasPrice { operator: *operator_info.key, lamports_per_gas, slot, coinbase });
+            let space = gas_price.size();
+            let lamports = Rent::get()?.minimum_balance(space);
+            self.cpi.invoke_signed(
+                &create_account(operator_info.key, gas_price_info.key, lamports, space as u64, program_id),
+                &accounts, &[&[b"gas_price".as_ref(), &[nonce]]]
+            )?;
+
+            gas_price.pack(&mut gas_price_info.data.borrow_mut())?;
+            debug_print!("Gas price oracle created, operator {}", operator_info.key);
+        } else {
+            let mut data = gas_price_info.try_borrow_mut_data()?;
+            let mut account_data = AccountData::unpack(&data)?;
+            let gas_price = account_data.get_mut_gas_price()?;
+            if gas_price.operator != *operator_info.key {
+                debug_print!("Gas price must be signed by the operator {}", &gas_price.operator);
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            gas_price.lamports_per_gas = lamports_per_gas;
+            gas_price.slot = slot;
+            gas_price.coinbase = coinbase;
+            account_data.pack(&mut data)?;
+        }
+        debug_print!("Gas price {} lamports, coinbase {}", lamports_per_gas, &coinbase);
+
+        Ok(())
+    }
+
+    /// Processes an [UpdateConfig](enum.EvmInstruction.html) instruction.
+    pub fn process_update_config<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        operators_only: bool,
+        allow_legacy_tx: bool,
+        chain_id: u64,
+        native_mint: Option<Pubkey>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let _system_info = next_account_info(account_info_iter);
+
+        if !admin_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (config_key, nonce) = config_address(program_id);
+        if *config_info.key != config_key {
+            debug_print!("Invalid config account, expected {}", config_key);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let native_decimals = match native_mint {
+            Some(mint) if mint != Pubkey::default() => {
+                let mint_info = next_account_info(account_info_iter)?;
+                if *mint_info.key != mint || *mint_info.owner != erc20_wrapper::spl_token::id() {
+                    debug_print!("Native mint account {} is not passed", mint);
+                    return Err(ProgramError::InvalidArgument);
+                }
+                let decimals = erc20_wrapper::mint_decimals(&mint_info.data.borrow()).ok_or(ProgramError::InvalidAccountData)?;
+                if decimals > 18 {
+                    debug_print!("Native mint has {} decimals, at most 18 are supported", decimals);
+                    return Err(ProgramError::InvalidArgument);
+                }
+                decimals
+            },
+            _ => 0,
+        };
+
+        if config_info.owner != program_id {
+            let config = AccountData::Config(Config {
+                admin: *admin_info.key,
+                operators_only,
+                native_mint: native_mint.unwrap_or_default(),
+                native_decimals,
+                chain_id: if chain_id == 0 { CHAIN_ID } else { chain_id },
+                allow_legacy_tx,
+            });
+            let space = config.size();
+            let lamports = Rent::get()?.minimum_balance(space);
+            self.cpi.invoke_signed(
+                &create_account(admin_info.key, config_info.key, lamports, space as u64, program_id),
+                &accounts, &[&[b"config".as_ref(), &[nonce]]]
+            )?;
+
+            config.pack(&mut config_info.data.borrow_mut())?;
+            debug_print!("Config created, admin {}", admin_info.key);
+        } else {
+            let mut data = config_info.try_borrow_mut_data()?;
+            let mut account_data = AccountData::unpack(&data)?;
+            let config = account_data.get_mut_config()?;
+            if config.admin != *admin_info.key {
+                debug_print!("Config must be signed by the admin {}", &config.admin);
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            config.operators_only = operators_only;
+            config.allow_legacy_tx = allow_legacy_tx;
+            if chain_id != 0 {
+                config.chain_id = chain_id;
+            }
+            if let Some(native_mint) = native_mint {
+                config.native_mint = native_mint;
+                config.native_decimals = native_decimals;
+            }
+            account_data.pack(&mut data)?;
+        }
+        debug_print!("Operators only {}, legacy transactions {}", operators_only, allow_legacy_tx);
+        if let Some(native_mint) = native_mint {
+            debug_print!("Native mint {}", native_mint);
+        }
+
+        Ok(())
+    }
+
+    /// Processes an [RegisterOperator](enum.EvmInstruction.html) instruction.
+    pub fn process_register_operator<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        operator: Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let operator_info = next_account_info(account_info_iter)?;
+
+        check_config_admin(program_id, config_info, admin_info)?;
+        let (operator_key, nonce) = operator_address(program_id, &operator);
+        if *operator_info.key != operator_key {
+            debug_print!("Invalid operator record, expected {}", operator_key);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if operator_info.owner == program_id {
+            debug_print!("Operator {} is already registered", operator);
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        let record = AccountData::Operator(Operator { key: operator });
+        let space = record.size();
+        let lamports = Rent::get()?.minimum_balance(space);
+        self.cpi.invoke_signed(
+            &create_account(admin_info.key, operator_info.key, lamports, space as u64, program_id),
+            &accounts, &[&[b"operator".as_ref(), operator.as_ref(), &[nonce]]]
+        )?;
+
+        record.pack(&mut operator_info.data.borrow_mut())?;
+        debug_print!("Operator {} registered", operator);
+
+        Ok(())
+    }
+
+    /// Processes an [RemoveOperator](enum.EvmInstruction.html) instruction.
+    pub fn process_remove_operator<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        operator: Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let operator_info = next_account_info(account_info_iter)?;
+
+        check_config_admin(program_id, config_info, admin_info)?;
+        let (operator_key, _) = operator_address(program_id, &operator);
+        if *operator_info.key != operator_key || operator_info.owner != program_id {
+            debug_print!("Invalid operator record, expected {}", operator_key);
+            return Err(ProgramError::InvalidArgument);
+        }
+        AccountData::unpack(&operator_info.data.borrow())?.get_operator()?;
+
+        let lamports = operator_info.lamports();
+        **admin_info.lamports.borrow_mut() = admin_info.lamports().checked_add(lamports).ok_or(ProgramError::InvalidArgument)?;
+        **operator_info.lamports.borrow_mut() = 0;
+        operator_info.data.borrow_mut().iter_mut().for_each(|byte| *byte = 0);
+        debug_print!("Operator {} removed", operator);
+
+        Ok(())
+    }
+
+    /// Processes an [GetAccountInfo](enum.EvmInstruction.html) instruction.
+    pub fn process_get_account_info<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        ether: H160,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let code_info = next_account_info(account_info_iter).ok();
+
+        let account = load_solidity_account(program_id, account_info, code_info)?;
+        if account.get_ether() != ether {
+            debug_print!("Account {} is not the account of {}", account_info.key, ether);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let info = EtherAccountInfo {
+            nonce: account.get_nonce(),
+            balance: account_info.lamports(),
+            code_size: account.code_size() as u64,
+            code_hash: account.code_hash(),
+        };
+        self.invoke_on_return(program_id, accounts, ExitReason::Succeed(ExitSucceed::Returned), &info.pack(), Telemetry::default())
+    }
+
+    /// Processes an [GetStorageAt](enum.EvmInstruction.html) instruction.
+    pub fn process_get_storage_at<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        address: H160,
+        index: U256,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let code_info = next_account_info(account_info_iter).ok();
+
+        let account = load_solidity_account(program_id, account_info, code_info)?;
+        if account.get_ether() != address {
+            debug_print!("Account {} is not the account of {}", account_info.key, address);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let value = u256_to_h256(account.get_storage(&index));
+        self.invoke_on_return(program_id, accounts, ExitReason::Succeed(ExitSucceed::Returned), &value.as_bytes().to_vec(), Telemetry::default())
+    }
+
+    /// Processes an [VerifyStorage](enum.EvmInstruction.html) instruction.
+    pub fn process_verify_storage<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        address: H160,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let code_info = next_account_info(account_info_iter)?;
+        let deployer_info = next_account_info(account_info_iter)?;
+        let deployer = signed_deployer(program_id, deployer_info, account_info_iter)?;
+        let is_operator = deployer_info.owner != program_id && next_account_info(account_info_iter)
+            .map_or(false, |record_info| is_operator_record(program_id, deployer_info, record_info));
+
+        let account = load_solidity_account(program_id, account_info, Some(code_info))?;
+        if account.get_ether() != address {
+            debug_print!("Account {} is not the account of {}", account_info.key, address);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let usage = account.verify_storage()?;
+        debug_print!("Storage verified: {} items, {} bytes used, {} bytes free", usage.items, usage.used, usage.free);
+
+        let mut result = Vec::with_capacity(12);
+        result.extend_from_slice(&usage.items.to_le_bytes());
+        result.extend_from_slice(&usage.used.to_le_bytes());
+        result.extend_from_slice(&usage.free.to_le_bytes());
+        self.invoke_on_return(program_id, accounts, ExitReason::Succeed(ExitSucceed::Returned), &result, Telemetry::default())
+    }
+
+    /// Processes an [CompactStorage](enum.EvmInstruction.html) instruction.
+    pub fn process_compact_storage<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        address: H160,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let code_info = next_account_info(account_info_iter)?;
+        let deployer_info = next_account_info(account_info_iter)?;
+        let deployer = signed_deployer(program_id, deployer_info, account_info_iter)?;
+        let is_operator = deployer_info.owner != program_id && next_account_info(account_info_iter)
+            .map_or(false, |record_info| is_operator_record(program_id, deployer_info, record_info));
+
+        let account = load_solidity_account(program_id, account_info, Some(code_info))?;
+        if account.get_ether() != address {
+            debug_print!("Account {} is not the account of {}", account_info.key, address);
+            return Err(ProgramError::InvalidArgument);
+        }
+        check_accounts_unblocked(program_id, accounts, None)?;
+
+        let (items, before, after) = {
+            let mut data = code_info.data.borrow_mut();
+            let mut contract_data = AccountData::unpack(&data)?;
+            let header_size = contract_data.size();
+            let contract = contract_data.get_mut_contract()?;
+            if contract.code_size == 0 {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            if contract.deployer != deployer && !is_operator {
+                debug_print!("Only the deployer {} or an operator can compact the storage", contract.deployer);
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let storage_offset = header_size + contract.code_size as usize;
+            let mut storage = Hamt::new(&mut data[storage_offset..], false)?;
+            let (items, before, after) = storage.compact()?;
+            contract.storage_root = storage.root_hash();
+            contract_data.pack(&mut data)?;
+            (items, before, after)
+        };
+        debug_print!("Storage compacted: {} items, {} -> {} bytes", items, before, after);
+
+        let mut result = Vec::with_capacity(12);
+        result.extend_from_slice(&(items as u32).to_le_bytes());
+        result.extend_from_slice(&(before as u32).to_le_bytes());
+        result.extend_from_slice(&(after as u32).to_le_bytes());
+        self.invoke_on_return(program_id, accounts, ExitReason::Succeed(ExitSucceed::Returned), &result, Telemetry::default())
+    }
+
+    /// Processes an [UpdateCode](enum.EvmInstruction.html) instruction.
+    pub fn process_update_code<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        reset_storage: bool,
+        immutable: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let code_info = next_account_info(account_info_iter)?;
+        let deployer_info = next_account_info(account_info_iter)?;
+        let deployer = signed_deployer(program_id, deployer_info, account_info_iter)?;
+        let code = match next_account_info(account_info_iter) {
+            Ok(holder_info) if holder_info.owner == program_id => Some(read_holder_code(holder_info)?),
+            Ok(_) => return Err(ProgramError::IncorrectProgramId),
+            Err(_) => None,
+        };
+
+        if account_info.owner != program_id || code_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        check_accounts_unblocked(program_id, accounts, None)?;
+        let account = load_solidity_account(program_id, account_info, Some(code_info))?;
+        debug_print!("Update code of {}", account.get_ether());
+
+        let mut data = code_info.data.borrow_mut();
+        let mut contract_data = AccountData::unpack(&data)?;
+        let header_size = contract_data.size();
+        let contract = contract_data.get_mut_contract()?;
+        if contract.code_size == 0 {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if contract.immutable {
+            debug_print!("Contract is immutable");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if contract.deployer != deployer {
+            debug_print!("Only the deployer {} can update the code", contract.deployer);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if let Some(ref code) = code {
+            if code.is_empty() {
+                return Err(ProgramError::InvalidArgument);
+            }
+            if let Err(err) = validate_code(code) {
+                debug_print!("Invalid code: {:?}", err);
+                return Err(EvmLoaderError::InvalidCode.into());
+            }
+        }
+
+        let old_storage = header_size + contract.code_size as usize;
+        let new_storage = header_size + code.as_ref().map_or(contract.code_size as usize, Vec::len);
+        if new_storage + Hamt::HEADER_LEN > data.len() {
+            debug_print!("Storage does not fit the code account after the new code");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        if reset_storage {
+            let storage = Hamt::new(&mut data[new_storage..], true)?;
+            contract.storage_root = storage.root_hash();
+        } else if new_storage != old_storage {
+            let storage_len = Hamt::new(&mut data[old_storage..], false)?.allocated_len();
+            if new_storage + storage_len > data.len() || old_storage + storage_len > data.len() {
+                debug_print!("Storage does not fit the code account after the new code");
+                return Err(ProgramError::AccountDataTooSmall);
+            }
+            data.copy_within(old_storage..old_storage + storage_len, new_storage);
+        }
+
+        if let Some(code) = code {
+            contract.code_size = code.len().try_into().map_err(|_| ProgramError::AccountDataTooSmall)?;
+            data[header_size..new_storage].copy_from_slice(&code);
+        }
+        contract.immutable = immutable;
+        contract_data.pack(&mut data)?;
+        debug_print!("Code updated: size {}, storage reset {}, immutable {}", new_storage - header_size, reset_storage, immutable);
+
+        Ok(())
+    }
+
+    fn do_partial_call<'a>(
+        &self,
+        storage: &mut StorageAccount,
+        program_id: &Pubkey,
+        step_count: u64,
+        accounts: &'a [AccountInfo<'a>],
+        trx: &UnsignedTransaction,
+        from_info: Option<(H160, u64)>,
+        to: Option<H160>,
+        tx_hash: H256,
+    ) -> ProgramResult
+    {
+        debug_print!("do_partial_call");
+
+        let account_info_iter = &mut accounts.iter();
+        let program_info = next_account_info(account_info_iter)?;
+        let _program_code = next_account_info(account_info_iter)?;
+        let caller_info = next_account_info(account_info_iter)?;
+        let signer_info = if caller_info.owner == program_id {
+            next_account_info(account_info_iter)?
+        } else {
+            caller_info
+        };
+
+        if program_info.owner != program_id {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        check_accounts_unblocked(program_id, accounts, None)?;
+        let account_storage = ProgramAccountStorage::new(program_id, accounts)?;
+
+        check_from_or_signer(program_id, account_storage.get_caller_account()?.as_deref(), caller_info, signer_info, from_info)?;
+        check_contract(&account_storage, to)?;
+
+        let backend = SolanaBackend::new(&account_storage, Some(accounts));
+        debug_print!("  backend initialized");
+
+        check_chain_id(trx, backend.chain_id(), account_storage.allow_legacy_tx())?;
+
+        let executor_state = ExecutorState::new(ExecutorSubstate::new(), backend);
+        let mut executor = Machine::new(executor_state);
+
+        debug_print!("Executor initialized");
+
+        debug_print!("   caller: {}", &account_storage.origin().to_string());
+        debug_print!(" contract: {}", &account_storage.contract().to_string());
+
+        let gas_limit = gas::gas_limit(trx.gas_limit);
+        account_storage.check_gas_prepaid(gas_limit)?;
+        executor.call_begin(account_storage.origin(), account_storage.contract(), trx.call_data.clone(), gas_limit);
+        executor.execute_n_steps(step_count).unwrap();
+        account_storage.check_touched_accounts(executor.touched_accounts())?;
+
+        self.emit_frame_logs(program_id, accounts, executor.drain_committed_logs(), tx_hash)?;
+
+        debug_print!("save");
+        executor.save_into(storage)?;
+
+        debug_print!("partial call complete");
+
+        Ok(())
+    }
+
+    fn do_continue<'a>(
+        &self,
+        storage: &mut StorageAccount,
+        program_id: &Pubkey,
+        step_count: u64,
+        accounts: &'a [AccountInfo<'a>],
+        from_info: Option<(H160, u64)>,
+    ) -> Result<Option<ExitReason>, ProgramError>
+    {
+        debug_print!("do_continue");
+        let tx_hash = storage.tx_hash()?;
+
+        let account_info_iter = &mut accounts.iter();
+        let _program_info = next_account_info(account_info_iter)?;
+        let _program_code = next_account_info(account_info_iter)?;
+        let caller_info = next_account_info(account_info_iter)?;
+        let signer_info = if caller_info.owner == program_id {
+            next_account_info(account_info_iter)?
+        } else {
+            caller_info
+        };
+
+        let mut account_storage = ProgramAccountStorage::new(program_id, accounts)?;
+        if signer_info.is_signer {
+            account_storage.set_payer(signer_info);
+        }
+
+        check_from_or_signer(program_id, account_storage.get_caller_account()?.as_deref(), caller_info, signer_info, from_info)?;
+
+        let mut execution = {
+            let backend = SolanaBackend::new(&account_storage, Some(accounts));
+            debug_print!("  backend initialized");
+
+            let mut executor = Machine::restore(storage, backend)?;
+            debug_print!("Executor restored");
+
+            let exit_reason = match executor.execute_n_steps(step_count) {
+                Ok(()) => {
+                    account_storage.check_touched_accounts(executor.touched_accounts())?;
+                    self.emit_frame_logs(program_id, accounts, executor.drain_committed_logs(), tx_hash)?;
+                    executor.save_into(storage)?;
+                    debug_print!("{} steps executed", step_count);
+                    return Ok(None);
+                }
+                Err(reason) => reason
+            };
+
+            debug_print!("Call done");
+
+            Execution::new(executor, exit_reason)
+        };
+
+        self.settle_execution(program_id, accounts, &mut account_storage, &mut execution, tx_hash)?;
+        account_storage.charge_gas(&execution.gas_settlement)?;
+
+        self.invoke_on_return(&program_id, &accounts, execution.exit_reason.clone(), &execution.result, execution.telemetry)?;
+
+        Ok(Some(execution.exit_reason))
+    }
+
+    /// Check the accounts touched by the finished execution, apply its changes and emit its logs if it succeeded.
+    /// Returns the count and the bloom filter of the emitted logs.
+    fn settle_execution<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        account_storage: &mut ProgramAccountStorage<'a>,
+        execution: &mut Execution,
+        tx_hash: H256,
+    ) -> Result<(u64, bloom::Bloom), ProgramError> {
+        account_storage.check_touched_accounts(execution.touched_accounts.iter().copied())?;
+
+        let mut logs_bloom: bloom::Bloom = [0u8; bloom::BLOOM_SIZE];
+        let (applies, logs, transfers) = match execution.applies_logs.take() {
+            Some(applies_logs) => applies_logs,
+            None => return Ok((0, logs_bloom)),
+        };
+
+        account_storage.apply_with_transfers(&self.cpi, applies, &transfers)?;
+        debug_print!("Applies done");
+
+        let logs_count = logs.len() as u64;
+        for frame_log in &logs {
+            bloom::accrue_log(&mut logs_bloom, &frame_log.log);
+        }
+        self.emit_frame_logs(program_id, accounts, logs, tx_hash)?;
+
+        Ok((logs_count, logs_bloom))
+    }
+
+    /// Emit `OnEvent` for the logs of `Machine`, tagged with their call frames
+    fn emit_frame_logs<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        logs: Vec<FrameLog>,
+        tx_hash: H256,
+    ) -> ProgramResult {
+        for frame_log in logs {
+            self.cpi.invoke(&on_event(program_id, frame_log.log, tx_hash, Some(frame_log.depth), frame_log.index)?, &accounts)?;
+        }
+
+        Ok(())
+    }
+
+    fn invoke_on_return<'a>(
+        &self,
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        exit_reason: ExitReason,
+        result: &Vec<u8>,
+        telemetry: Telemetry,) -> ProgramResult
+    {
+        let (exit_status, description) = exit_status_info(&exit_reason);
+        debug_print!("Exit status {:#x}: {}", exit_status, description);
+        let telemetry = Telemetry { heap_high_water: heap_high_water(), ..telemetry };
+
+        debug_print!("{}", &hex::encode(&result));
+        debug_print!("{:?}", &telemetry);
+
+        if cfg!(feature = "return-data") {
+            set_transaction_result(exit_status, &result, &telemetry);
+        } else {
+            let ix = on_return(program_id, exit_status, &result, &telemetry).unwrap();
+            self.cpi.invoke(
+                &ix,
+                &accounts
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Heap high-water mark, known only when the program allocator is used
+fn heap_high_water() -> u32 {
+    #[cfg(all(target_arch = "bpf", not(feature = "no-entrypoint")))]
+    let high_water = crate::entrypoint::FreeListAllocator::high_water();
+    #[cfg(not(all(target_arch = "bpf", not(feature = "no-entrypoint"))))]
+    let high_water = 0;
+
+    high_water.try_into().unwrap_or(u32::max_value())
+}
+
+/// Check that `lamports` are enough for rent exemption of the account with `space` bytes
+fn check_rent_exempt(lamports: u64, space: usize) -> ProgramResult {
+    let minimum_balance = Rent::get()?.minimum_balance(space);
+    if lamports < minimum_balance {
+        solana_program::msg!("Account is not rent exempt: {} lamports for {} bytes, required {}", lamports, space, minimum_balance);
+        return Err(EvmLoaderError::NotRentExempt.into());
+    }
+
+    Ok(())
+}
+
+/// Load Ether account with the code account when it is a contract
+fn load_solidity_account<'a>(
+    program_id: &Pubkey,
+    account_info: &'a AccountInfo<'a>,
+    code_info: Option<&'a AccountInfo<'a>>,
+) -> Result<SolidityAccount<'a>, ProgramError> {
+    if account_info.owner != program_id {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let account_data = AccountData::unpack(&account_info.data.borrow())?;
+    let code_account = account_data.get_account()?.code_account;
+
+    let code_data = if code_account == Pubkey::new_from_array([0u8; 32]) {
+        None
+    } else {
+        let code_info = code_info.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if *code_info.key != code_account {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let code_data = code_info.data.clone();
+        let code_acc = AccountData::unpack(&code_data.borrow())?;
+        code_acc.get_contract()?;
+        Some((code_acc, code_data))
+    };
+
+    SolidityAccount::new(program_id, account_info.key, account_info.lamports(), account_data, code_data)
+}
+
+/// Outcome of a finished `Machine` execution, the state changes are kept only if it succeeded
+struct Execution {
+    exit_reason: ExitReason,
+    result: Vec<u8>,
+    applies_logs: Option<(Vec<Apply<BTreeMap<U256, U256>>>, Vec<FrameLog>, TransferLedger)>,
+    touched_accounts: BTreeSet<H160>,
+    gas_settlement: GasSettlement,
+    telemetry: Telemetry,
+}
+
+impl Execution {
+    fn new<B: Backend>(executor: Machine<'_, B>, exit_reason: ExitReason) -> Self {
+        let result = executor.return_value();
+        let gas_settlement = executor.gas_settlement();
+        let touched_accounts = executor.touched_accounts();
+        let telemetry = Telemetry {
+            steps: executor.executed_steps(),
+            touched_accounts: touched_accounts.len().try_into().unwrap_or(u32::max_value()),
+            ..Telemetry::default()
+        };
+
+        let applies_logs = if exit_reason.is_succeed() {
+            debug_print!("Succeed execution");
+            let (_, applies_logs) = executor.into_state().deconstruct();
+            Some(applies_logs)
+        } else {
+            None
+        };
+
+        Self { exit_reason, result, applies_logs, touched_accounts, gas_settlement, telemetry }
+    }
+}
+
+/// Read contract code written to the code account, the length prefix must cover all uploaded bytes
+fn read_contract_code(code_info: &AccountInfo) -> Result<Vec<u8>, ProgramError> {
+    let data = code_info.data.borrow();
+    let contract_info_data = AccountData::unpack(&data)?;
+    let uploaded_len = match contract_info_data {
+        AccountData::Contract (ref contract) => contract.uploaded_len as usize,
+        _ => return Err(ProgramError::InvalidAccountData),
+    };
+
+    let (_contract_header, rest) = data.split_at(contract_info_data.size());
+    let code_len = rest.get(..8).and_then(|v| v.try_into().ok()).map(u64::from_le_bytes).ok_or(ProgramError::InvalidAccountData)?;
+    if code_len.checked_add(8) != Some(uploaded_len as u64) {
+        debug_print!("Code length {} doesn't match {} uploaded bytes", code_len, uploaded_len);
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let code = rest.get(8..uploaded_len).ok_or(ProgramError::InvalidAccountData)?;
+    Ok(code.to_vec())
+}
+
+/// Read the code written by `Write` to the holder account: `len: u64` followed by the code
+fn read_holder_code(holder_info: &AccountInfo) -> Result<Vec<u8>, ProgramError> {
+    let data = holder_info.data.borrow();
+    let account_info_data = AccountData::unpack(&data)?;
+    match account_info_data {
+        AccountData::Empty => (),
+        _ => return Err(ProgramError::InvalidAccountData),
+    };
+
+    let rest = &data[account_info_data.size()..];
+    let code_len = rest.get(..8).and_then(|v| v.try_into().ok()).map(u64::from_le_bytes).ok_or(ProgramError::InvalidAccountData)?;
+    let code = rest.get(8..).and_then(|v| v.get(..code_len as usize)).ok_or(ProgramError::InvalidAccountData)?;
+    Ok(code.to_vec())
+}
+
+/// Check that the account is an SPL Token account of wrapped SOL
+fn check_wsol_account(token_info: &AccountInfo) -> ProgramResult {
+    if *token_info.owner != erc20_wrapper::spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = token_info.data.borrow();
+    if data.len() < 32 || data[..32] != erc20_wrapper::spl_token::native_mint::id().to_bytes() {
+        debug_print!("Account {} is not a wrapped SOL account", token_info.key);
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    Ok(())
+}
+
+/// Read signature and unsigned transaction stored in the holder account
+fn read_holder_transaction(holder_info: &AccountInfo) -> Result<(Vec<u8>, Vec<u8>), ProgramError> {
+    let data = holder_info.data.borrow();
+    let account_info_data = AccountData::unpack(&data)?;
+    match account_info_data {
+        AccountData::Empty => (),
+        _ => return Err(ProgramError::InvalidAccountData),
+    };
+
+    let (_acc_header, rest) = data.split_at(account_info_data.size());
+    let (signature, rest) = rest.split_at(65);
+    let (trx_len, rest) = rest.split_at(8);
+    let trx_len = trx_len.try_into().ok().map(u64::from_le_bytes).unwrap();
+    let (trx, _rest) = rest.split_at(trx_len as usize);
+    Ok((trx.to_vec(), signature.to_vec()))
+}
+
+/// Check that the admin of the initialized config account signs the instruction
+fn check_config_admin(program_id: &Pubkey, config_info: &AccountInfo, admin_info: &AccountInfo) -> ProgramResult {
+    if !admin_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *config_info.key != config_address(program_id).0 || config_info.owner != program_id {
+        debug_print!("Invalid config account {}", config_info.key);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let account_data = AccountData::unpack(&config_info.data.borrow())?;
+    let config = account_data.get_config()?;
+    if config.admin != *admin_info.key {
+        debug_print!("Must be signed by the admin {}", &config.admin);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    Ok(())
+}
+
+/// Ether address of the deployer, which is the signed Ether account or the Solana wallet signer.
+/// The signer of the Ether account is taken from the iterator when the Ether account is passed.
+fn signed_deployer<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    program_id: &Pubkey,
+    deployer_info: &AccountInfo,
+    account_info_iter: &mut I,
+) -> Result<H160, ProgramError> {
+    if deployer_info.owner == program_id {
+        let signer_info = next_account_info(account_info_iter)?;
+        let deployer_acc = AccountData::unpack(&deployer_info.data.borrow())?;
+        let deployer_acc = deployer_acc.get_account()?;
+        if !signer_info.is_signer || *signer_info.key != deployer_acc.signer {
+            debug_print!("Deployer account must be signed by its signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(deployer_acc.ether)
+    } else {
+        if !deployer_info.is_signer {
+            debug_print!("Deployer must be signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(keccak256_h256(&deployer_info.key.to_bytes()).into())
+    }
+}
+
+/// `record_info` is the operator account registered for the `signer` by `RegisterOperator`
+fn is_operator_record(program_id: &Pubkey, signer: &AccountInfo, record_info: &AccountInfo) -> bool {
+    *record_info.key == operator_address(program_id, signer.key).0 && record_info.owner == program_id
+        && AccountData::unpack(&record_info.data.borrow()).ok()
+            .map_or(false, |data| matches!(data, AccountData::Operator(ref record) if record.key == *signer.key))
+}
+
+/// With `operator-allow-list` feature, check that the config account is passed and,
+/// while `operators_only` is set, some signer of the instruction is a registered operator
+/// whose record is passed too
+fn check_operator(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    if !cfg!(feature = "operator-allow-list") {
+        return Ok(());
+    }
+
+    let config_key = config_address(program_id).0;
+    let config_info = accounts.iter().find(|info| *info.key == config_key).ok_or_else(|| {
+        debug_print!("Config account {} is not passed", config_key);
+        ProgramError::NotEnoughAccountKeys
+    })?;
+    if config_info.owner != program_id {
+        return Ok(());
+    }
+    let account_data = AccountData::unpack(&config_info.data.borrow())?;
+    if !account_data.get_config()?.operators_only {
+        return Ok(());
+    }
+
+    let is_operator = |signer: &AccountInfo| {
+        accounts.iter().any(|record_info| is_operator_record(program_id, signer, record_info))
+    };
+    if !accounts.iter().any(|info| info.is_signer && is_operator(info)) {
+        debug_print!("Instruction must be signed by a registered operator");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    Ok(())
+}
+
+/// Check that the contract account is the destination of the transaction, if it is known
+fn check_contract(account_storage: &ProgramAccountStorage, to: Option<H160>) -> ProgramResult {
+    match to {
+        Some(to) if to != account_storage.contract() => {
+            debug_print!("Contract account {} doesn't match the transaction destination {}", &account_storage.contract(), &to);
+            Err(EvmLoaderError::ContractMismatch.into())
+        },
+        _ => Ok(()),
+    }
+}
+
+fn check_from_or_signer<'a>(
+    program_id: &Pubkey,
+    caller_opt: Option<&SolidityAccount<'a>>,
+    caller_info: &'a AccountInfo<'a>,
+    signer_info: &'a AccountInfo<'a>,
+    from_info: Option<(H160, u64)>,
+) ->  ProgramResult
+{
+    if caller_info.owner == program_id {
+        if caller_opt.is_some() {
+            let caller = caller_opt.unwrap();
+
+            let caller_signer = caller.get_signer();
+            let caller_ether = caller.get_ether();
+            let caller_nonce = caller.get_nonce();
+
+            if from_info.is_none() {
+                if caller_signer != *signer_info.key || !signer_info.is_signer {
+                    debug_print!("Add valid account signer");
+                    debug_print!("   caller signer: {}", &caller_signer.to_string());
+                    debug_print!("   signer pubkey: {}", &signer_info.key.to_string());
+                    debug_print!("is signer signer: {}", &signer_info.is_signer.to_string());
+
+                    return Err(ProgramError::InvalidArgument);
+                }
+            } else {
+                let (from, nonce) = from_info.unwrap();
+                if caller_ether != from {
+                    debug_print!("Invalid caller account");
+                    debug_print!("   caller addres: {}", &caller_ether.to_string());
+                    debug_print!("     from addres: {}", &from.to_string());
+
+                    return Err(EvmLoaderError::CallerMismatch.into());
+                }
+                if caller_nonce != nonce {
+                    debug_print!("Invalid Ethereum transaction nonce");
+                    debug_print!("     tx nonce: {}", &nonce.to_string());
+                    debug_print!("    acc nonce: {}", &caller_nonce.to_string());
+
+                    return Err(ProgramError::InvalidArgument);
+                }
+            }
+        } else {
+            return Err(ProgramError::InvalidArgument);
+        }
+    }
+
+    Ok(())
+}
+
+/// Operator of the instruction: the last account, a writable signer owned by the system program.
+/// It funds the accounts created for the caller and starts iterative transactions.
+fn operator_info<'a>(accounts: &'a [AccountInfo<'a>]) -> Result<&'a AccountInfo<'a>, ProgramError> {
+    let operator_info = accounts.last().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if !operator_info.is_signer {
+        debug_print!("Operator {} must sign the instruction", operator_info.key);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !operator_info.is_writable || !system_program::check_id(operator_info.owner) {
+        debug_print!("Operator {} must be a writable system account", operator_info.key);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    Ok(operator_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{account_data::Account, cpi::parse_on_return, instruction::write_from_holder, return_data::{get_return_data, parse_transaction_result}};
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct RecordingCpi {
+        invoked: RefCell<Vec<Instruction>>,
+    }
+
+    impl Cpi for RecordingCpi {
+        fn invoke(&self, instruction: &Instruction, _account_infos: &[AccountInfo]) -> ProgramResult {
+            self.invoked.borrow_mut().push(instruction.clone());
+            Ok(())
+        }
+
+        fn invoke_signed(&self, instruction: &Instruction, _account_infos: &[AccountInfo], _signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+            self.invoked.borrow_mut().push(instruction.clone());
+            Ok(())
+        }
+    }
+
+    fn write_instruction(offset: u32, bytes: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8, 0, 0, 0];
+        data.extend_from_slice(&offset.to_le_bytes());
+        data.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        data.extend_from_slice(bytes);
+        data
+    }
+
+    #[test]
+    fn test_write_to_empty_account() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let mut lamports = 0u64;
+        let mut data = vec![0u8; 32];
+        let accounts = [AccountInfo::new(&key, false, true, &mut lamports, &mut data, &program_id, false, 0)];
+
+        let processor = Processor::new(RecordingCpi::default());
+        processor.process(&program_id, &accounts, &write_instruction(4, &[1, 2, 3])).unwrap();
+
+        assert_eq!(&accounts[0].data.borrow()[5..8], &[1, 2, 3]);
+        assert!(processor.cpi.invoked.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_write_out_of_bounds() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let mut lamports = 0u64;
+        let mut data = vec![0u8; 8];
+        let accounts = [AccountInfo::new(&key, false, true, &mut lamports, &mut data, &program_id, false, 0)];
+
+        let processor = Processor::new(RecordingCpi::default());
+        assert_eq!(
+            processor.process(&program_id, &accounts, &write_instruction(4, &[1, 2, 3, 4])),
+            Err(ProgramError::AccountDataTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_write_to_code_account() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let mut lamports = 0u64;
+        let contract = AccountData::Contract(Contract {
+            owner: Pubkey::new_unique(),
+            code_size: 0,
+            storage_root: H256::zero(),
+            deployer: H160::zero(),
+            immutable: false,
+            hashed_keys: false,
+            uploaded_len: 0,
+        });
+        let mut data = vec![0u8; contract.size() + 16];
+        contract.pack(&mut data).unwrap();
+        let accounts = [AccountInfo::new(&key, false, true, &mut lamports, &mut data, &program_id, false, 0)];
+
+        let processor = Processor::new(RecordingCpi::default());
+        processor.process(&program_id, &accounts, &write_instruction(0, &3u64.to_le_bytes())).unwrap();
+        assert_eq!(
+            processor.process(&program_id, &accounts, &write_instruction(9, &[1])),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            processor.process(&program_id, &accounts, &write_instruction(4, &[1])),
+            Err(ProgramError::InvalidArgument)
+        );
+        processor.process(&program_id, &accounts, &write_instruction(8, &[1, 2])).unwrap();
+        assert_eq!(read_contract_code(&accounts[0]), Err(ProgramError::InvalidAccountData));
+
+        processor.process(&program_id, &accounts, &write_instruction(WRITE_APPEND_OFFSET, &[3])).unwrap();
+        assert_eq!(read_contract_code(&accounts[0]), Ok(vec![1, 2, 3]));
+        assert_eq!(AccountData::unpack(&accounts[0].data.borrow()).unwrap().get_contract().unwrap().uploaded_len, 11);
+    }
+
+    #[test]
+    fn test_write_from_holder() {
+        let program_id = Pubkey::new_unique();
+        let code_key = Pubkey::new_unique();
+        let deployer_key = Pubkey::new_unique();
+        let holder_key = Pubkey::create_with_seed(&deployer_key, "part.0", &program_id).unwrap();
+        let contract = AccountData::Contract(Contract {
+            owner: Pubkey::new_unique(),
+            code_size: 0,
+            storage_root: H256::zero(),
+            deployer: keccak256_h256(&deployer_key.to_bytes()).into(),
+            immutable: false,
+            hashed_keys: false,
+            uploaded_len: 8,
+        });
+        let mut code_lamports = 0u64;
+        let mut code_data = vec![0u8; contract.size() + 16];
+        contract.pack(&mut code_data).unwrap();
+        let mut holder_lamports = 100u64;
+        let mut holder_data = vec![0u8; 1 + 8 + 3];
+        holder_data[1..9].copy_from_slice(&3u64.to_le_bytes());
+        holder_data[9..].copy_from_slice(&[1, 2, 3]);
+        let mut deployer_lamports = 5u64;
+        let mut deployer_data = vec![];
+        let system_id = solana_program::system_program::id();
+        let accounts = [
+            AccountInfo::new(&code_key, false, true, &mut code_lamports, &mut code_data, &program_id, false, 0),
+            AccountInfo::new(&holder_key, false, true, &mut holder_lamports, &mut holder_data, &program_id, false, 0),
+            AccountInfo::new(&deployer_key, true, true, &mut deployer_lamports, &mut deployer_data, &system_id, false, 0),
+        ];
+
+        let processor = Processor::new(RecordingCpi::default());
+        let wrong_seed = write_from_holder(&program_id, &code_key, &holder_key, &deployer_key, "part.1");
+        assert_eq!(
+            processor.process(&program_id, &accounts, &wrong_seed.data),
+            Err(ProgramError::InvalidArgument)
+        );
+        let instruction = write_from_holder(&program_id, &code_key, &holder_key, &deployer_key, "part.0");
+        let foreign = AccountData::Contract(Contract { deployer: H160::repeat_byte(1), ..contract.get_contract().unwrap().clone() });
+        foreign.pack(&mut accounts[0].data.borrow_mut()).unwrap();
+        assert_eq!(
+            processor.process(&program_id, &accounts, &instruction.data),
+            Err(ProgramError::InvalidArgument)
+        );
+        contract.pack(&mut accounts[0].data.borrow_mut()).unwrap();
+        processor.process(&program_id, &accounts, &instruction.data).unwrap();
+
+        assert_eq!(AccountData::unpack(&accounts[0].data.borrow()).unwrap().get_contract().unwrap().uploaded_len, 11);
+        assert_eq!(accounts[1].lamports(), 0);
+        assert_eq!(accounts[2].lamports(), 105);
+        assert!(accounts[1].data.borrow().iter().all(|byte| *byte == 0));
+    }
+
+    struct RentStubs;
+
+    impl solana_program::program_stubs::SyscallStubs for RentStubs {
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            #[allow(clippy::cast_ptr_alignment)]
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            solana_program::entrypoint::SUCCESS
+        }
+    }
+
+    #[test]
+    fn test_deploy_from_holder() {
+        solana_program::program_stubs::set_syscall_stubs(Box::new(RentStubs));
+
+        let program_id = Pubkey::new_unique();
+        let creator_key = Pubkey::new_unique();
+        let creator: H160 = keccak256_h256(&creator_key.to_bytes()).into();
+
+        // PUSH10 RETURN_42, PUSH1 0, MSTORE, PUSH1 10, PUSH1 22, RETURN
+        let mut init_code = vec![0x69];
+        init_code.extend_from_slice(&RETURN_42);
+        init_code.extend_from_slice(&[0x60, 0x00, 0x52, 0x60, 0x0a, 0x60, 0x16, 0xf3]);
+        let code_hash = keccak256_h256(&init_code);
+        let contract: H160 = keccak256_h256(&[&[0xff], creator.as_bytes(), H256::zero().as_bytes(), code_hash.as_bytes()].concat()).into();
+        let (contract_key, nonce) = ether_to_program_address(&program_id, &contract);
+
+        let code_key = Pubkey::create_with_seed(&creator_key, "code", &program_id).unwrap();
+        let holder_key = Pubkey::create_with_seed(&creator_key, "code.0", &program_id).unwrap();
+        let account_size = 1 + Account::SIZE;
+        let account_rent = Rent::default().minimum_balance(account_size);
+        let mut contract_data = vec![0u8; account_size];
+        let mut code_data = vec![0u8; 1 + Contract::SIZE + 1024];
+        let mut holder_data = vec![0u8; 1 + 8 + init_code.len()];
+        holder_data[1..9].copy_from_slice(&(init_code.len() as u64).to_le_bytes());
+        holder_data[9..].copy_from_slice(&init_code);
+        let mut clock_data = bincode::serialize(&Clock::default()).unwrap();
+        let (mut contract_lamports, mut code_lamports, mut holder_lamports) = (account_rent, 0u64, 100u64);
+        let (mut creator_lamports, mut clock_lamports) = (0u64, 0u64);
+        let mut creator_data = vec![];
+        let system_id = solana_program::system_program::id();
+        let sysvar_id = solana_program::sysvar::id();
+        let clock_id = solana_program::sysvar::clock::id();
+        let contract_info = AccountInfo::new(&contract_key, false, true, &mut contract_lamports, &mut contract_data, &program_id, false, 0);
+        let code_info = AccountInfo::new(&code_key, false, true, &mut code_lamports, &mut code_data, &program_id, false, 0);
+        let holder_info = AccountInfo::new(&holder_key, false, true, &mut holder_lamports, &mut holder_data, &program_id, false, 0);
+        let creator_info = AccountInfo::new(&creator_key, true, true, &mut creator_lamports, &mut creator_data, &system_id, false, 0);
+        let clock_info = AccountInfo::new(&clock_id, false, false, &mut clock_lamports, &mut clock_data, &sysvar_id, false, 0);
+
+        let processor = Processor::new(RecordingCpi::default());
+        let mut create_account = vec![2u8, 0, 0, 0];
+        create_account.extend_from_slice(&account_rent.to_le_bytes());
+        create_account.extend_from_slice(&(account_size as u64).to_le_bytes());
+        create_account.extend_from_slice(contract.as_bytes());
+        create_account.extend_from_slice(&[nonce, 0]);
+        let create_accounts = [creator_info.clone(), contract_info.clone(), code_info.clone()];
+        processor.process(&program_id, &create_accounts, &create_account).unwrap();
+        assert_eq!(
+            processor.process(&program_id, &create_accounts, &create_account),
+            Err(ProgramError::AccountAlreadyInitialized)
+        );
+
+        processor.process(&program_id, &[code_info.clone()], &write_instruction(0, &(init_code.len() as u64).to_le_bytes())).unwrap();
+        let append = write_from_holder(&program_id, &code_key, &holder_key, &creator_key, "code.0");
+        processor.process(&program_id, &[code_info.clone(), holder_info.clone(), creator_info.clone()], &append.data).unwrap();
+        assert_eq!(holder_info.lamports(), 0);
+
+        let finalize_accounts = [contract_info.clone(), code_info.clone(), creator_info, clock_info];
+        processor.process(&program_id, &finalize_accounts, &[1u8, 0, 0, 0]).unwrap();
+        let (status, _) = last_transaction_result(&processor);
+        assert!(crate::cpi::is_succeed_status(status));
+
+        let code_data = code_info.data.borrow();
+        let contract_data = AccountData::unpack(&code_data).unwrap();
+        let code = contract_data.get_contract().unwrap();
+        assert_eq!(code.owner, contract_key);
+        assert_eq!(code.deployer, creator);
+        assert_eq!(&code_data[contract_data.size()..contract_data.size() + RETURN_42.len()], &RETURN_42);
+    }
+
+    #[test]
+    fn test_write_to_foreign_account() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let mut lamports = 0u64;
+        let mut data = vec![0u8; 32];
+        let accounts = [AccountInfo::new(&key, false, true, &mut lamports, &mut data, &owner, false, 0)];
+
+        let processor = Processor::new(RecordingCpi::default());
+        assert_eq!(
+            processor.process(&program_id, &accounts, &write_instruction(0, &[1])),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_get_account_info() {
+        let program_id = Pubkey::new_unique();
+        let ether = H160::repeat_byte(1);
+        let (key, nonce) = ether_to_program_address(&program_id, &ether);
+        let account_data = AccountData::Account(Account {
+            ether,
+            nonce,
+            trx_count: 5,
+            signer: Pubkey::new_unique(),
+            code_account: Pubkey::new_from_array([0u8; 32]),
+            blocked: None,
+        });
+        let mut lamports = 1000u64;
+        let mut data = vec![0u8; account_data.size()];
+        account_data.pack(&mut data).unwrap();
+        let accounts = [AccountInfo::new(&key, false, false, &mut lamports, &mut data, &program_id, false, 0)];
+
+        let processor = Processor::new(RecordingCpi::default());
+        let ix = crate::cpi::get_account_info(&program_id, &key, None, &ether);
+        processor.process(&program_id, &accounts, &ix.data).unwrap();
+
+        let data = if cfg!(feature = "return-data") {
+            get_return_data().unwrap().1
+        } else {
+            let invoked = processor.cpi.invoked.borrow();
+            assert!(parse_on_return(&invoked[0].data).is_some());
+            invoked[0].data[1..].to_vec()
+        };
+        let (status, result, telemetry) = parse_transaction_result(&data).unwrap();
+        assert_eq!(status, 0x12);
+        assert_eq!(telemetry, Telemetry::default());
+        assert_eq!(EtherAccountInfo::unpack(result), Some(EtherAccountInfo {
+            nonce: 5,
+            balance: 1000,
+            code_size: 0,
+            code_hash: keccak256_h256(&[]),
+        }));
+
+        let other = crate::cpi::get_account_info(&program_id, &key, None, &H160::repeat_byte(2));
+        assert_eq!(processor.process(&program_id, &accounts, &other.data), Err(ProgramError::InvalidArgument));
+    }
+
+    /// Runtime code returning 42 as uint256
+    const RETURN_42: [u8; 10] = [0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+
+    fn contract_account(program_id: &Pubkey, ether: H160, code_key: Pubkey) -> (Pubkey, Vec<u8>) {
+        let (key, nonce) = ether_to_program_address(program_id, &ether);
+        let account_data = AccountData::Account(Account {
+            ether,
+            nonce,
+            trx_count: 0,
+            signer: Pubkey::new_from_array([0u8; 32]),
+            code_account: code_key,
+            blocked: None,
+        });
+        let mut data = vec![0u8; account_data.size()];
+        account_data.pack(&mut data).unwrap();
+        (key, data)
+    }
+
+    fn code_account(owner: Pubkey, deployer: H160, code: &[u8], uploaded: &[u8]) -> Vec<u8> {
+        let contract = AccountData::Contract(Contract {
+            owner,
+            code_size: code.len() as u32,
+            storage_root: H256::zero(),
+            deployer,
+            immutable: false,
+            hashed_keys: false,
+            uploaded_len: uploaded.len() as u32,
+        });
+        let mut data = vec![0u8; contract.size() + 1024];
+        contract.pack(&mut data).unwrap();
+        let header_size = contract.size();
+        data[header_size..header_size + code.len()].copy_from_slice(code);
+        data[header_size..header_size + uploaded.len()].copy_from_slice(uploaded);
+        data
+    }
+
+    fn last_transaction_result(processor: &Processor<RecordingCpi>) -> (u8, Vec<u8>) {
+        let data = if cfg!(feature = "return-data") {
+            get_return_data().unwrap().1
+        } else {
+            let invoked = processor.cpi.invoked.borrow();
+            let on_return = invoked.last().unwrap();
+            assert!(parse_on_return(&on_return.data).is_some());
+            on_return.data[1..].to_vec()
+        };
+        let (status, result, _) = parse_transaction_result(&data).unwrap();
+        (status, result.to_vec())
+    }
+
+    #[test]
+    fn test_call() {
+        let program_id = Pubkey::new_unique();
+        let contract = H160::repeat_byte(2);
+        let code_key = Pubkey::new_unique();
+        let (contract_key, mut contract_data) = contract_account(&program_id, contract, code_key);
+        let mut code_data = code_account(contract_key, H160::zero(), &RETURN_42, &[]);
+        let caller_key = Pubkey::new_unique();
+        let mut clock_data = bincode::serialize(&Clock::default()).unwrap();
+        let (mut contract_lamports, mut code_lamports, mut caller_lamports, mut clock_lamports) = (0u64, 0u64, 0u64, 0u64);
+        let mut caller_data = vec![];
+        let system_id = solana_program::system_program::id();
+        let sysvar_id = solana_program::sysvar::id();
+        let clock_id = solana_program::sysvar::clock::id();
+        let accounts = [
+            AccountInfo::new(&contract_key, false, true, &mut contract_lamports, &mut contract_data, &program_id, false, 0),
+            AccountInfo::new(&code_key, false, true, &mut code_lamports, &mut code_data, &program_id, false, 0),
+            AccountInfo::new(&caller_key, true, false, &mut caller_lamports, &mut caller_data, &system_id, false, 0),
+            AccountInfo::new(&clock_id, false, false, &mut clock_lamports, &mut clock_data, &sysvar_id, false, 0),
+        ];
+
+        let processor = Processor::new(RecordingCpi::default());
+        processor.process(&program_id, &accounts, &[3u8]).unwrap();
+
+        if !cfg!(feature = "return-data") {
+            assert_eq!(processor.cpi.invoked.borrow().len(), 1);
+        }
+        let (status, result) = last_transaction_result(&processor);
+        assert!(crate::cpi::is_succeed_status(status));
+        assert_eq!(result, u256_to_h256(U256::from(42)).as_bytes());
+    }
+
+    #[test]
+    fn test_call_batch_increments_nonce_once() {
+        let program_id = Pubkey::new_unique();
+        let contract = H160::repeat_byte(2);
+        let code_key = Pubkey::new_unique();
+        let (contract_key, mut contract_data) = contract_account(&program_id, contract, code_key);
+        let mut code_data = code_account(contract_key, H160::zero(), &RETURN_42, &[]);
+        let signer_key = Pubkey::new_unique();
+        let caller = H160::repeat_byte(3);
+        let (caller_key, caller_nonce) = ether_to_program_address(&program_id, &caller);
+        let caller_account = AccountData::Account(Account {
+            ether: caller,
+            nonce: caller_nonce,
+            trx_count: 0,
+            signer: signer_key,
+            code_account: Pubkey::new_from_array([0u8; 32]),
+            blocked: None,
+        });
+        let mut caller_data = vec![0u8; caller_account.size()];
+        caller_account.pack(&mut caller_data).unwrap();
+        let mut clock_data = bincode::serialize(&Clock::default()).unwrap();
+        let (mut contract_lamports, mut code_lamports, mut caller_lamports) = (0u64, 0u64, 0u64);
+        let (mut signer_lamports, mut clock_lamports) = (0u64, 0u64);
+        let mut signer_data = vec![];
+        let system_id = solana_program::system_program::id();
+        let sysvar_id = solana_program::sysvar::id();
+        let clock_id = solana_program::sysvar::clock::id();
+        let accounts = [
+            AccountInfo::new(&contract_key, false, true, &mut contract_lamports, &mut contract_data, &program_id, false, 0),
+            AccountInfo::new(&code_key, false, true, &mut code_lamports, &mut code_data, &program_id, false, 0),
+            AccountInfo::new(&caller_key, false, true, &mut caller_lamports, &mut caller_data, &program_id, false, 0),
+            AccountInfo::new(&signer_key, true, false, &mut signer_lamports, &mut signer_data, &system_id, false, 0),
+            AccountInfo::new(&clock_id, false, false, &mut clock_lamports, &mut clock_data, &sysvar_id, false, 0),
+        ];
+
+        let mut data = vec![20u8];
+        let call = BatchCall { to: contract, value: U256::zero(), data: &[] };
+        call.pack(&mut data);
+        call.pack(&mut data);
+        let processor = Processor::new(RecordingCpi::default());
+        processor.process(&program_id, &accounts, &data).unwrap();
+
+        let (status, _) = last_transaction_result(&processor);
+        assert!(crate::cpi::is_succeed_status(status));
+        assert_eq!(AccountData::unpack(&accounts[2].data.borrow()).unwrap().get_account().unwrap().trx_count, 1);
+    }
+
+    #[test]
+    fn test_finalize() {
+        let program_id = Pubkey::new_unique();
+        let caller_key = Pubkey::new_unique();
+        let caller: H160 = keccak256_h256(&caller_key.to_bytes()).into();
+
+        // PUSH10 RETURN_42, PUSH1 0, MSTORE, PUSH1 10, PUSH1 22, RETURN
+        let mut init_code = vec![0x69];
+        init_code.extend_from_slice(&RETURN_42);
+        init_code.extend_from_slice(&[0x60, 0x00, 0x52, 0x60, 0x0a, 0x60, 0x16, 0xf3]);
+        let mut uploaded = (init_code.len() as u64).to_le_bytes().to_vec();
+        uploaded.extend_from_slice(&init_code);
+
+        let code_hash = keccak256_h256(&init_code);
+        let contract: H160 = keccak256_h256(&[&[0xff], caller.as_bytes(), H256::zero().as_bytes(), code_hash.as_bytes()].concat()).into();
+        let code_key = Pubkey::new_unique();
+        let (contract_key, mut contract_data) = contract_account(&program_id, contract, code_key);
+        let mut code_data = code_account(contract_key, H160::zero(), &[], &uploaded);
+        let mut clock_data = bincode::serialize(&Clock::default()).unwrap();
+        let (mut contract_lamports, mut code_lamports, mut caller_lamports, mut clock_lamports) = (0u64, 0u64, 0u64, 0u64);
+        let mut caller_data = vec![];
+        let system_id = solana_program::system_program::id();
+        let sysvar_id = solana_program::sysvar::id();
+        let clock_id = solana_program::sysvar::clock::id();
+        let accounts = [
+            AccountInfo::new(&contract_key, false, true, &mut contract_lamports, &mut contract_data, &program_id, false, 0),
+            AccountInfo::new(&code_key, false, true, &mut code_lamports, &mut code_data, &program_id, false, 0),
+            AccountInfo::new(&caller_key, true, false, &mut caller_lamports, &mut caller_data, &system_id, false, 0),
+            AccountInfo::new(&clock_id, false, false, &mut clock_lamports, &mut clock_data, &sysvar_id, false, 0),
+        ];
+
+        let processor = Processor::new(RecordingCpi::default());
+        processor.process(&program_id, &accounts, &[1u8, 0, 0, 0]).unwrap();
+
+        let (status, _) = last_transaction_result(&processor);
+        assert!(crate::cpi::is_succeed_status(status));
+
+        let code_data = accounts[1].data.borrow();
+        let contract_data = AccountData::unpack(&code_data).unwrap();
+        let code = contract_data.get_contract().unwrap();
+        assert_eq!(code.code_size as usize, RETURN_42.len());
+        assert_eq!(code.deployer, caller);
+        assert_eq!(&code_data[contract_data.size()..contract_data.size() + RETURN_42.len()], &RETURN_42);
+        assert_eq!(AccountData::unpack(&accounts[0].data.borrow()).unwrap().get_account().unwrap().trx_count, 1);
+    }
+
+    #[test]
+    fn test_update_code_longer_than_account() {
+        let program_id = Pubkey::new_unique();
+        let deployer_key = Pubkey::new_unique();
+        let code_key = Pubkey::new_unique();
+        let holder_key = Pubkey::new_unique();
+        let (contract_key, mut contract_data) = contract_account(&program_id, H160::repeat_byte(2), code_key);
+        let deployer: H160 = keccak256_h256(&deployer_key.to_bytes()).into();
+        let mut code_data = code_account(contract_key, deployer, &RETURN_42, &[]);
+        let new_code = vec![0u8; code_data.len()];
+        let mut holder_data = vec![0u8; 1 + 8 + new_code.len()];
+        holder_data[1..9].copy_from_slice(&(new_code.len() as u64).to_le_bytes());
+        let (mut contract_lamports, mut code_lamports, mut deployer_lamports, mut holder_lamports) = (0u64, 0u64, 0u64, 0u64);
+        let mut deployer_data = vec![];
+        let system_id = solana_program::system_program::id();
+        let accounts = [
+            AccountInfo::new(&contract_key, false, true, &mut contract_lamports, &mut contract_data, &program_id, false, 0),
+            AccountInfo::new(&code_key, false, true, &mut code_lamports, &mut code_data, &program_id, false, 0),
+            AccountInfo::new(&deployer_key, true, false, &mut deployer_lamports, &mut deployer_data, &system_id, false, 0),
+            AccountInfo::new(&holder_key, false, false, &mut holder_lamports, &mut holder_data, &program_id, false, 0),
+        ];
+
+        let processor = Processor::new(RecordingCpi::default());
+        for reset_storage in [1u8, 0u8].iter() {
+            assert_eq!(
+                processor.process(&program_id, &accounts, &[23u8, *reset_storage, 0]),
+                Err(ProgramError::AccountDataTooSmall)
+            );
+        }
+        let code_data = accounts[1].data.borrow();
+        assert_eq!(AccountData::unpack(&code_data).unwrap().get_contract().unwrap().code_size as usize, RETURN_42.len());
+    }
+
+    #[test]
+    fn test_get_version() {
+        let program_id = Pubkey::new_unique();
+        let processor = Processor::new(RecordingCpi::default());
+        let ix = crate::cpi::get_version(&program_id);
+        processor.process(&program_id, &[], &ix.data).unwrap();
+
+        let data = if cfg!(feature = "return-data") {
+            get_return_data().unwrap().1
+        } else {
+            let invoked = processor.cpi.invoked.borrow();
+            invoked[0].data[1..].to_vec()
+        };
+        let (status, result, _) = parse_transaction_result(&data).unwrap();
+        assert_eq!(status, 0x12);
+        assert_eq!(ProgramVersion::unpack(result), Some(ProgramVersion::current()));
+    }
+
+    #[test]
+    fn test_delete_account_requires_signer() {
+        let program_id = Pubkey::new_unique();
+        let ether = H160::repeat_byte(1);
+        let (key, nonce) = ether_to_program_address(&program_id, &ether);
+        let signer = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let account_data = AccountData::Account(Account {
+            ether,
+            nonce,
+            trx_count: 0,
+            signer,
+            code_account: Pubkey::new_from_array([0u8; 32]),
+            blocked: None,
+        });
+        let mut lamports = 1000u64;
+        let mut data = vec![0u8; account_data.size()];
+        account_data.pack(&mut data).unwrap();
+        let mut signer_lamports = 0u64;
+        let mut other_lamports = 0u64;
+        let mut signer_data = vec![];
+        let mut other_data = vec![];
+        let system_id = solana_program::system_program::id();
+        let account_info = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &program_id, false, 0);
+        let signer_info = AccountInfo::new(&signer, false, true, &mut signer_lamports, &mut signer_data, &system_id, false, 0);
+        let other_info = AccountInfo::new(&other, true, true, &mut other_lamports, &mut other_data, &system_id, false, 0);
+
+        let processor = Processor::new(RecordingCpi::default());
+        assert_eq!(
+            processor.process(&program_id, &[account_info.clone(), signer_info], &[13u8]),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            processor.process(&program_id, &[account_info.clone(), other_info], &[13u8]),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(account_info.lamports(), 1000);
+    }
+
+    #[test]
+    fn test_executing_account_lock() {
+        let program_id = Pubkey::new_unique();
+        let storage = Pubkey::new_unique();
+        let ether = H160::repeat_byte(1);
+        let (key, nonce) = ether_to_program_address(&program_id, &ether);
+        let account_data = AccountData::Account(Account {
+            ether,
+            nonce,
+            trx_count: 0,
+            signer: Pubkey::new_unique(),
+            code_account: Pubkey::new_from_array([0u8; 32]),
+            blocked: Some(storage),
+        });
+        let mut lamports = 1000u64;
+        let mut data = vec![0u8; account_data.size()];
+        account_data.pack(&mut data).unwrap();
+        let accounts = [AccountInfo::new(&key, false, true, &mut lamports, &mut data, &program_id, false, 0)];
+
+        let previous = crate::storage_account::swap_account_lock(&accounts[0], Some(program_id)).unwrap();
+        assert_eq!(previous, Some(storage));
+        assert_eq!(check_accounts_unblocked(&program_id, &accounts, Some(&storage)), Err(EvmLoaderError::Reentrancy.into()));
+
+        crate::storage_account::swap_account_lock(&accounts[0], previous).unwrap();
+        assert_eq!(check_accounts_unblocked(&program_id, &accounts, Some(&storage)), Ok(()));
+        assert_eq!(check_accounts_unblocked(&program_id, &accounts, None), Err(EvmLoaderError::AccountBlocked.into()));
+    }
+}
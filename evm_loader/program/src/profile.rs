@@ -0,0 +1,126 @@
+//! Per-opcode profile of the execution
+//!
+//! The emulator enables the profile on `Machine` to count opcodes and their modeled cost
+//! per contract, so hotspots exceeding the compute budget are found before deployment.
+//! The program never enables it.
+
+use std::collections::BTreeMap;
+use primitive_types::H160;
+
+/// Executions and modeled cost of an opcode
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeStats {
+    pub count: u64,
+    /// Sum of the base gas cost of the executions
+    pub cost: u64,
+}
+
+impl OpcodeStats {
+    fn add(&mut self, count: u64, cost: u64) {
+        self.count += count;
+        self.cost += cost;
+    }
+}
+
+/// Opcode statistics by contract, the contract is the address of the executing context
+#[derive(Debug, Default, Clone)]
+pub struct Profile {
+    pub contracts: BTreeMap<H160, BTreeMap<u8, OpcodeStats>>,
+}
+
+impl Profile {
+    pub fn record(&mut self, contract: H160, opcode: u8, cost: u64) {
+        self.contracts.entry(contract).or_default().entry(opcode).or_default().add(1, cost);
+    }
+
+    /// Opcode statistics of all contracts
+    #[must_use]
+    pub fn opcodes(&self) -> BTreeMap<u8, OpcodeStats> {
+        let mut opcodes: BTreeMap<u8, OpcodeStats> = BTreeMap::new();
+        for stats in self.contracts.values() {
+            for (opcode, stat) in stats {
+                opcodes.entry(*opcode).or_default().add(stat.count, stat.cost);
+            }
+        }
+        opcodes
+    }
+
+    /// Totals of the contract
+    #[must_use]
+    pub fn contract_total(&self, contract: &H160) -> OpcodeStats {
+        let mut total = OpcodeStats::default();
+        for stat in self.contracts.get(contract).into_iter().flat_map(BTreeMap::values) {
+            total.add(stat.count, stat.cost);
+        }
+        total
+    }
+}
+
+/// Mnemonic of the opcode, `None` for undefined opcodes
+#[must_use]
+pub fn opcode_name(opcode: u8) -> Option<&'static str> {
+    const PUSH: [&str; 32] = ["PUSH1", "PUSH2", "PUSH3", "PUSH4", "PUSH5", "PUSH6", "PUSH7", "PUSH8",
+        "PUSH9", "PUSH10", "PUSH11", "PUSH12", "PUSH13", "PUSH14", "PUSH15", "PUSH16",
+        "PUSH17", "PUSH18", "PUSH19", "PUSH20", "PUSH21", "PUSH22", "PUSH23", "PUSH24",
+        "PUSH25", "PUSH26", "PUSH27", "PUSH28", "PUSH29", "PUSH30", "PUSH31", "PUSH32"];
+    const DUP: [&str; 16] = ["DUP1", "DUP2", "DUP3", "DUP4", "DUP5", "DUP6", "DUP7", "DUP8",
+        "DUP9", "DUP10", "DUP11", "DUP12", "DUP13", "DUP14", "DUP15", "DUP16"];
+    const SWAP: [&str; 16] = ["SWAP1", "SWAP2", "SWAP3", "SWAP4", "SWAP5", "SWAP6", "SWAP7", "SWAP8",
+        "SWAP9", "SWAP10", "SWAP11", "SWAP12", "SWAP13", "SWAP14", "SWAP15", "SWAP16"];
+    const LOG: [&str; 5] = ["LOG0", "LOG1", "LOG2", "LOG3", "LOG4"];
+
+    let name = match opcode {
+        0x00 => "STOP", 0x01 => "ADD", 0x02 => "MUL", 0x03 => "SUB", 0x04 => "DIV", 0x05 => "SDIV",
+        0x06 => "MOD", 0x07 => "SMOD", 0x08 => "ADDMOD", 0x09 => "MULMOD", 0x0a => "EXP", 0x0b => "SIGNEXTEND",
+        0x10 => "LT", 0x11 => "GT", 0x12 => "SLT", 0x13 => "SGT", 0x14 => "EQ", 0x15 => "ISZERO",
+        0x16 => "AND", 0x17 => "OR", 0x18 => "XOR", 0x19 => "NOT", 0x1a => "BYTE",
+        0x1b => "SHL", 0x1c => "SHR", 0x1d => "SAR",
+        0x20 => "SHA3",
+        0x30 => "ADDRESS", 0x31 => "BALANCE", 0x32 => "ORIGIN", 0x33 => "CALLER", 0x34 => "CALLVALUE",
+        0x35 => "CALLDATALOAD", 0x36 => "CALLDATASIZE", 0x37 => "CALLDATACOPY", 0x38 => "CODESIZE",
+        0x39 => "CODECOPY", 0x3a => "GASPRICE", 0x3b => "EXTCODESIZE", 0x3c => "EXTCODECOPY",
+        0x3d => "RETURNDATASIZE", 0x3e => "RETURNDATACOPY", 0x3f => "EXTCODEHASH",
+        0x40 => "BLOCKHASH", 0x41 => "COINBASE", 0x42 => "TIMESTAMP", 0x43 => "NUMBER",
+        0x44 => "DIFFICULTY", 0x45 => "GASLIMIT", 0x46 => "CHAINID", 0x47 => "SELFBALANCE",
+        0x50 => "POP", 0x51 => "MLOAD", 0x52 => "MSTORE", 0x53 => "MSTORE8", 0x54 => "SLOAD",
+        0x55 => "SSTORE", 0x56 => "JUMP", 0x57 => "JUMPI", 0x58 => "PC", 0x59 => "MSIZE",
+        0x5a => "GAS", 0x5b => "JUMPDEST",
+        0x60..=0x7f => PUSH[(opcode - 0x60) as usize],
+        0x80..=0x8f => DUP[(opcode - 0x80) as usize],
+        0x90..=0x9f => SWAP[(opcode - 0x90) as usize],
+        0xa0..=0xa4 => LOG[(opcode - 0xa0) as usize],
+        0xf0 => "CREATE", 0xf1 => "CALL", 0xf2 => "CALLCODE", 0xf3 => "RETURN", 0xf4 => "DELEGATECALL",
+        0xf5 => "CREATE2", 0xfa => "STATICCALL", 0xfd => "REVERT", 0xfe => "INVALID", 0xff => "SUICIDE",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_totals() {
+        let a = H160::repeat_byte(1);
+        let b = H160::repeat_byte(2);
+        let mut profile = Profile::default();
+        profile.record(a, 0x55, 5000);
+        profile.record(a, 0x55, 5000);
+        profile.record(a, 0x01, 3);
+        profile.record(b, 0x55, 5000);
+
+        assert_eq!(profile.contract_total(&a), OpcodeStats { count: 3, cost: 10_003 });
+        assert_eq!(profile.contract_total(&H160::zero()), OpcodeStats::default());
+        assert_eq!(profile.opcodes()[&0x55], OpcodeStats { count: 3, cost: 15_000 });
+    }
+
+    #[test]
+    fn test_opcode_name() {
+        assert_eq!(opcode_name(0x55), Some("SSTORE"));
+        assert_eq!(opcode_name(0x7f), Some("PUSH32"));
+        assert_eq!(opcode_name(0x8f), Some("DUP16"));
+        assert_eq!(opcode_name(0xa4), Some("LOG4"));
+        assert_eq!(opcode_name(0x0c), None);
+    }
+}
@@ -0,0 +1,199 @@
+//! Transaction result passed back to the caller through return data
+//!
+//! solana-program 1.6 does not wrap the return data syscalls yet,
+//! so they are declared here directly.
+//! Layout of the data is `[exit_status, result..., telemetry]`, the same as `OnReturn` instruction.
+
+use solana_program::pubkey::Pubkey;
+use primitive_types::H256;
+use std::convert::TryInto;
+
+/// Maximum size of return data accepted by the runtime
+pub const MAX_RETURN_DATA: usize = 1024;
+
+#[cfg(target_arch = "bpf")]
+extern "C" {
+    fn sol_set_return_data(data: *const u8, length: u64);
+    fn sol_get_return_data(data: *mut u8, length: u64, program_id: *mut Pubkey) -> u64;
+}
+
+#[cfg(not(target_arch = "bpf"))]
+thread_local! {
+    static RETURN_DATA: std::cell::RefCell<Option<(Pubkey, Vec<u8>)>> = std::cell::RefCell::new(None);
+}
+
+/// Set return data of the current instruction
+#[cfg(target_arch = "bpf")]
+pub fn set_return_data(data: &[u8]) {
+    unsafe { sol_set_return_data(data.as_ptr(), data.len() as u64) };
+}
+
+/// Set return data of the current instruction
+#[cfg(not(target_arch = "bpf"))]
+pub fn set_return_data(data: &[u8]) {
+    RETURN_DATA.with(|cell| *cell.borrow_mut() = Some((Pubkey::default(), data.to_vec())));
+}
+
+/// Get return data of the last invoked instruction and the program which set it
+#[cfg(target_arch = "bpf")]
+pub fn get_return_data() -> Option<(Pubkey, Vec<u8>)> {
+    let mut buf = [0u8; MAX_RETURN_DATA];
+    let mut program_id = Pubkey::default();
+
+    let size = unsafe { sol_get_return_data(buf.as_mut_ptr(), buf.len() as u64, &mut program_id) };
+    if size == 0 {
+        None
+    } else {
+        let size = std::cmp::min(size as usize, MAX_RETURN_DATA);
+        Some((program_id, buf[..size].to_vec()))
+    }
+}
+
+/// Get return data of the last invoked instruction and the program which set it
+#[cfg(not(target_arch = "bpf"))]
+pub fn get_return_data() -> Option<(Pubkey, Vec<u8>)> {
+    RETURN_DATA.with(|cell| cell.borrow().clone())
+}
+
+/// Store exit status, result and telemetry of the transaction as return data.
+/// Result is truncated to fit into `MAX_RETURN_DATA`.
+pub fn set_transaction_result(exit_status: u8, result: &[u8], telemetry: &Telemetry) {
+    let len = std::cmp::min(result.len(), MAX_RETURN_DATA - 1 - Telemetry::LEN);
+
+    let mut data = Vec::with_capacity(len + 1 + Telemetry::LEN);
+    data.push(exit_status);
+    data.extend_from_slice(&result[..len]);
+    data.extend_from_slice(&telemetry.pack());
+
+    set_return_data(&data);
+}
+
+/// Split return data into exit status, result and telemetry
+#[must_use]
+pub fn parse_transaction_result(data: &[u8]) -> Option<(u8, &[u8], Telemetry)> {
+    let (status, rest) = data.split_first()?;
+    let split = rest.len().checked_sub(Telemetry::LEN)?;
+    let (result, telemetry) = rest.split_at(split);
+    Some((*status, result, Telemetry::unpack(telemetry)?))
+}
+
+/// Resource usage of the instruction, passed after the result to tune step counts of iterative execution.
+/// Layout is `[heap_high_water: u32, steps: u64, touched_accounts: u32]`, integers are little endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Telemetry {
+    /// Maximum heap space used by the instruction, zero if the program allocator is not used
+    pub heap_high_water: u32,
+    /// EVM steps executed by the instruction, zero if the executor does not count them
+    pub steps: u64,
+    /// Number of Ether accounts touched by the execution, zero if the executor does not track them
+    pub touched_accounts: u32,
+}
+
+impl Telemetry {
+    /// Size of packed telemetry
+    pub const LEN: usize = 4 + 8 + 4;
+
+    /// Pack telemetry into the tail of the instruction result
+    #[must_use]
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::LEN);
+        data.extend_from_slice(&self.heap_high_water.to_le_bytes());
+        data.extend_from_slice(&self.steps.to_le_bytes());
+        data.extend_from_slice(&self.touched_accounts.to_le_bytes());
+        data
+    }
+
+    /// Unpack telemetry from the tail of the instruction result
+    #[must_use]
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::LEN {
+            return None;
+        }
+        Some(Self {
+            heap_high_water: data[0..4].try_into().ok().map(u32::from_le_bytes)?,
+            steps: data[4..12].try_into().ok().map(u64::from_le_bytes)?,
+            touched_accounts: data[12..16].try_into().ok().map(u32::from_le_bytes)?,
+        })
+    }
+}
+
+/// Ether account state returned by `GetAccountInfo`.
+/// Layout is `[nonce: u64, balance: u64, code_size: u64, code_hash: [u8; 32]]`, integers are little endian.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EtherAccountInfo {
+    /// Transaction count of the account
+    pub nonce: u64,
+    /// Balance of the account in lamports
+    pub balance: u64,
+    /// Size of the contract code, zero for account without code
+    pub code_size: u64,
+    /// Keccak256 hash of the contract code
+    pub code_hash: H256,
+}
+
+impl EtherAccountInfo {
+    /// Size of packed account state
+    pub const LEN: usize = 8 + 8 + 8 + 32;
+
+    /// Pack account state into result of the instruction
+    #[must_use]
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::LEN);
+        data.extend_from_slice(&self.nonce.to_le_bytes());
+        data.extend_from_slice(&self.balance.to_le_bytes());
+        data.extend_from_slice(&self.code_size.to_le_bytes());
+        data.extend_from_slice(self.code_hash.as_bytes());
+        data
+    }
+
+    /// Unpack account state from result of the instruction
+    #[must_use]
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::LEN {
+            return None;
+        }
+        let u64_at = |offset: usize| data[offset..offset + 8].try_into().ok().map(u64::from_le_bytes);
+        Some(Self {
+            nonce: u64_at(0)?,
+            balance: u64_at(8)?,
+            code_size: u64_at(16)?,
+            code_hash: H256::from_slice(&data[24..]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_result_roundtrip() {
+        let telemetry = Telemetry { heap_high_water: 1024, steps: 500, touched_accounts: 3 };
+        set_transaction_result(0x12, &[1, 2, 3], &telemetry);
+
+        let (_program_id, data) = get_return_data().unwrap();
+        assert_eq!(parse_transaction_result(&data), Some((0x12, &[1u8, 2, 3][..], telemetry)));
+    }
+
+    #[test]
+    fn test_transaction_result_truncated() {
+        let telemetry = Telemetry { heap_high_water: 1, steps: 2, touched_accounts: 3 };
+        set_transaction_result(0x12, &[0u8; MAX_RETURN_DATA * 2], &telemetry);
+
+        let (_program_id, data) = get_return_data().unwrap();
+        assert_eq!(data.len(), MAX_RETURN_DATA);
+        assert_eq!(parse_transaction_result(&data).map(|(_, _, telemetry)| telemetry), Some(telemetry));
+        assert_eq!(parse_transaction_result(&[]), None);
+        assert_eq!(parse_transaction_result(&[0x12, 1, 2]), None);
+    }
+
+    #[test]
+    fn test_ether_account_info_roundtrip() {
+        let info = EtherAccountInfo { nonce: 1, balance: 2, code_size: 3, code_hash: H256::repeat_byte(4) };
+        let data = info.pack();
+
+        assert_eq!(data.len(), EtherAccountInfo::LEN);
+        assert_eq!(EtherAccountInfo::unpack(&data), Some(info));
+        assert_eq!(EtherAccountInfo::unpack(&data[1..]), None);
+    }
+}
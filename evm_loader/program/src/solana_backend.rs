@@ -1,6 +1,6 @@
 use evm::{
     backend::{Basic, Backend},
-    CreateScheme, Capture, Transfer, ExitReason, Code
+    CreateScheme, Capture, Transfer, ExitReason, ExitError, Code
 };
 use core::convert::Infallible;
 use primitive_types::{H160, H256, U256};
@@ -9,23 +9,106 @@ use solana_program::{
     pubkey::Pubkey,
     instruction::{Instruction, AccountMeta},
     program::invoke_signed,
+    pubkey::MAX_SEED_LEN,
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    system_instruction::MAX_PERMITTED_DATA_LENGTH,
+    system_program,
+    sysvar::rent::Rent,
 };
-use std::convert::TryInto;
 use arrayref::{array_ref, array_refs};
 use crate::{
+    account_data::AccountData,
+    erc20_wrapper,
+    multicall,
     solidity_account::SolidityAccount,
+    storage_account::swap_account_lock,
     utils::{keccak256_h256, keccak256_h256_v, keccak256_digest},
 };
 
+/// Maximum number of accounts of the instruction invoked by the Solana precompile
+pub const MAX_CPI_ACCOUNTS: usize = 64;
+/// Maximum data length of the instruction invoked by the Solana precompile
+pub const MAX_CPI_DATA_LEN: usize = 10 * 1024;
+
+/// Maximum length of the memo attached by the Solana precompile
+pub const MAX_MEMO_LEN: usize = 566;
+
+/// SPL Memo program
+pub mod spl_memo {
+    solana_program::declare_id!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+}
+
+/// Size of the account entry of the Solana precompile call: translate, signer, writable flags and the key
+const CPI_ACCOUNT_SIZE: usize = 1 + 1 + 1 + 32;
+
+/// Parse the Solana precompile call `program_id: [u8; 32], accounts_len: u16 (big-endian),
+/// accounts: [(translate, signer, writable, key); accounts_len], data`.
+/// Accounts with the translate flag keep the Ether address in the last 20 bytes of the key.
+fn parse_cpi_instruction(input: &[u8]) -> Result<(Pubkey, Vec<(bool, AccountMeta)>, &[u8]), ExitError> {
+    if input.len() < 32 + 2 {
+        return Err(ExitError::OutOfOffset);
+    }
+    let (program_id, input) = input.split_at(32);
+    let (accounts_len, input) = input.split_at(2);
+    let accounts_len = usize::from(u16::from_be_bytes([accounts_len[0], accounts_len[1]]));
+    if accounts_len > MAX_CPI_ACCOUNTS {
+        debug_print!("Too many accounts: {}", accounts_len);
+        return Err(ExitError::OutOfOffset);
+    }
+
+    let accounts_size = accounts_len * CPI_ACCOUNT_SIZE;
+    if input.len() < accounts_size {
+        return Err(ExitError::OutOfOffset);
+    }
+    let (accounts_data, data) = input.split_at(accounts_size);
+    if data.len() > MAX_CPI_DATA_LEN {
+        debug_print!("Instruction data is too long: {}", data.len());
+        return Err(ExitError::OutOfOffset);
+    }
+
+    let accounts = accounts_data.chunks_exact(CPI_ACCOUNT_SIZE)
+        .map(|entry| {
+            let meta = AccountMeta {
+                is_signer: entry[1] != 0,
+                is_writable: entry[2] != 0,
+                pubkey: Pubkey::new(&entry[3..]),
+            };
+            (entry[0] != 0, meta)
+        })
+        .collect();
+
+    Ok((Pubkey::new(program_id), accounts, data))
+}
+
+/// Chain id of the network, signed in the transactions as EIP-155 requires
+pub const CHAIN_ID: u64 = 111;
+
 pub trait AccountStorage {
+    /// Apply `f` to the account, or return `d()` if it is not available.
+    /// This is a query: the account is not recorded as used, touched accounts are registered separately
     fn apply_to_account<U, D, F>(&self, address: &H160, d: D, f: F) -> U
     where F: FnOnce(&SolidityAccount) -> U,
           D: FnOnce() -> U;
 
     fn contract(&self) -> H160;
     fn origin(&self) -> H160;
+    /// Current slot, see `block` for the mapping of slots to blocks
     fn block_number(&self) -> U256;
+    /// Unix timestamp of the current slot
     fn block_timestamp(&self) -> U256;
+    /// Hash of the block from `SlotHashes` sysvar, zero when it is unavailable
+    fn block_hash(&self, _number: U256) -> H256 { H256::default() }
+    /// EVM address registered by the operator in the gas price oracle
+    fn block_coinbase(&self) -> H160 { H160::default() }
+    /// `prevrandao` derived from `SlotHashes` sysvar, zero when it is unavailable
+    fn block_randomness(&self) -> U256 { U256::zero() }
+    /// Epoch of the current slot
+    fn epoch(&self) -> U256 { U256::zero() }
+    /// Chain id accepted in transactions, the config account sets it
+    fn chain_id(&self) -> U256;
+    /// Lamports required for the account of `data_len` bytes to be rent exempt
+    fn rent_exempt_minimum(&self, data_len: usize) -> u64 { Rent::default().minimum_balance(data_len) }
 
     fn get_account_solana_address(&self, address: &H160) -> Option<Pubkey> { self.apply_to_account(address, || None, |account| Some(account.get_solana_address())) }
     fn exists(&self, address: &H160) -> bool { self.apply_to_account(address, || false, |_| true) }
@@ -34,7 +117,15 @@ pub trait AccountStorage {
     fn code_size(&self, address: &H160) -> usize { self.apply_to_account(address, || 0, |account| account.code_size()) }
     fn code(&self, address: &H160) -> Code { self.apply_to_account(address, || Code::Vec{ code: Vec::new() }, |account| account.get_code(*address)) }
     fn storage(&self, address: &H160, index: &U256) -> U256 { self.apply_to_account(address, || U256::zero(), |account| account.get_storage(index)) }
-    fn seeds(&self, address: &H160) -> Option<(H160, u8)> {self.apply_to_account(&address, || None, |account| Some(account.get_seeds())) }
+    fn seeds(&self, address: &H160) -> Option<(H160, u8)> {self.apply_to_account(&address, || None, |account| account.get_seeds()) }
+    /// Owner and data of a Solana account which is not an Ether account, if it is available
+    fn solana_account_data(&self, _key: &Pubkey) -> Option<(Pubkey, Vec<u8>)> { None }
+    /// Token account holding the NFT of the mint, when accounts are not passed to the backend
+    fn find_nft_account(&self, _mint: &Pubkey) -> Option<Pubkey> { None }
+    /// EVM loader program
+    fn program_id(&self) -> Pubkey;
+    /// Lamports per unit of gas from the gas price oracle, zero without the oracle
+    fn gas_price(&self) -> U256 { U256::zero() }
 }
 
 pub struct SolanaBackend<'a, 's, S> {
@@ -44,10 +135,144 @@ pub struct SolanaBackend<'a, 's, S> {
 
 impl<'a, 's, S> SolanaBackend<'a, 's, S> where S: AccountStorage {
     pub fn new(account_storage: &'s S, account_infos: Option<&'a [AccountInfo<'a>]>) -> Self {
-        debug_print!("backend::new"); 
+        evm_trace!("backend::new"); 
         Self { account_storage, account_infos }
     }
 
+    /// Owner and data of the Solana account from the instruction accounts or from the account storage
+    fn solana_account_data(&self, key: &Pubkey) -> Option<(Pubkey, Vec<u8>)> {
+        if let Some(account_infos) = self.account_infos {
+            return account_infos.iter()
+                .find(|info| info.key == key)
+                .map(|info| (*info.owner, info.data.borrow().to_vec()));
+        }
+
+        self.account_storage.solana_account_data(key)
+    }
+
+    /// ABI encoded `(string name, string symbol, uint8 decimals)` of the SPL mint.
+    /// Name and symbol are empty if the Metaplex metadata account is not available.
+    fn token_metadata(&self, mint: &Pubkey) -> Option<Vec<u8>> {
+        let (owner, mint_data) = self.solana_account_data(mint)?;
+        if owner != erc20_wrapper::spl_token::id() {
+            return None;
+        }
+        let decimals = erc20_wrapper::mint_decimals(&mint_data)?;
+
+        let metadata_key = erc20_wrapper::metadata_address(mint);
+        let (name, symbol) = self.solana_account_data(&metadata_key)
+            .filter(|(owner, _)| *owner == erc20_wrapper::metaplex_metadata::id())
+            .and_then(|(_, metadata)| erc20_wrapper::metadata_name_symbol(mint, &metadata))
+            .unwrap_or_default();
+
+        Some(erc20_wrapper::encode_token_metadata(&name, &symbol, decimals))
+    }
+
+    /// ABI encoded `uint256` balance of the holder's associated token account for the SPL mint.
+    /// Zero if the associated token account is not created yet, but it must be supplied anyway.
+    fn token_balance(&self, mint: &Pubkey, holder: &H160) -> Option<Vec<u8>> {
+        let wallet = self.account_storage.get_account_solana_address(holder)?;
+        let token_account = erc20_wrapper::associated_token_address(&wallet, mint);
+
+        let (owner, data) = self.solana_account_data(&token_account)?;
+        let amount = if owner == erc20_wrapper::spl_token::id() {
+            erc20_wrapper::token_account_amount(&data, mint)?
+        } else if system_program::check_id(&owner) && data.is_empty() {
+            0
+        } else {
+            return None;
+        };
+
+        Some(erc20_wrapper::encode_balance(amount))
+    }
+
+    /// Ether address of the Solana wallet: address of the Ether account,
+    /// or `keccak256(wallet)` as for callers which are not Ether accounts
+    fn wallet_ether_address(&self, wallet: &Pubkey) -> H160 {
+        if let Some((owner, data)) = self.solana_account_data(wallet) {
+            if owner == self.account_storage.program_id() {
+                if let Ok(AccountData::Account(account)) = AccountData::unpack(&data) {
+                    return account.ether;
+                }
+            }
+        }
+
+        keccak256_h256(&wallet.to_bytes()).into()
+    }
+
+    /// ABI encoded `address` of the Metaplex NFT holder.
+    /// The token account and the metadata account must be available.
+    fn nft_owner(&self, mint: &Pubkey) -> Option<Vec<u8>> {
+        let metadata_key = erc20_wrapper::metadata_address(mint);
+        let (metadata_owner, metadata) = self.solana_account_data(&metadata_key)?;
+        if metadata_owner != erc20_wrapper::metaplex_metadata::id() {
+            return None;
+        }
+        erc20_wrapper::metadata_name_symbol(mint, &metadata)?;
+
+        let token_account = match self.account_infos {
+            Some(account_infos) => account_infos.iter()
+                .find(|info| *info.owner == erc20_wrapper::spl_token::id() && erc20_wrapper::nft_holder(&info.data.borrow(), mint).is_some())
+                .map(|info| *info.key),
+            None => self.account_storage.find_nft_account(mint),
+        }?;
+
+        let (owner, data) = self.solana_account_data(&token_account)?;
+        if owner != erc20_wrapper::spl_token::id() {
+            return None;
+        }
+        let wallet = erc20_wrapper::nft_holder(&data, mint)?;
+
+        Some(H256::from(self.wallet_ether_address(&wallet)).as_bytes().to_vec())
+    }
+
+    /// ABI encoded `address` of the ERC20 wrapper of the SPL mint, from the registry record.
+    /// The record is created with the wrapper, so its address is the CREATE2 derivation checked at that time.
+    fn wrapper_of(&self, mint: &Pubkey) -> Option<Vec<u8>> {
+        let program_id = self.account_storage.program_id();
+        let (registry_key, _) = erc20_wrapper::registry_address(&program_id, mint);
+        let (owner, data) = self.solana_account_data(&registry_key)?;
+        if owner != program_id {
+            return None;
+        }
+        let wrapper = erc20_wrapper::registry_wrapper(&data, mint)?;
+
+        Some(H256::from(wrapper).as_bytes().to_vec())
+    }
+
+    /// ABI encoded `(uint256 rentExemptMinimum, uint256 epoch, uint256 slot)`, the minimum is for `data_len` bytes
+    fn sysvars(&self, data_len: usize) -> Vec<u8> {
+        let rent_exempt_minimum = U256::from(self.account_storage.rent_exempt_minimum(data_len));
+
+        let mut result = vec![0u8; 3 * 32];
+        rent_exempt_minimum.to_big_endian(&mut result[..32]);
+        self.account_storage.epoch().to_big_endian(&mut result[32..64]);
+        self.account_storage.block_number().to_big_endian(&mut result[64..]);
+        result
+    }
+
+    /// Attach the memo to the transaction, signed by the contract account.
+    /// Without the accounts (emulation) only the Memo program is recorded as required.
+    fn memo(&self, memo: &[u8]) -> ProgramResult {
+        if self.account_infos.is_none() {
+            self.solana_account_data(&spl_memo::id());
+            return Ok(());
+        }
+
+        let contract_key = self.account_storage.get_account_solana_address(&self.account_storage.contract())
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let (contract_eth, contract_nonce) = self.account_storage.seeds(&self.account_storage.contract())
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let contract_seeds = [contract_eth.as_bytes(), &[contract_nonce]];
+
+        let instruction = Instruction {
+            program_id: spl_memo::id(),
+            accounts: vec![AccountMeta::new_readonly(contract_key, true)],
+            data: memo.to_vec(),
+        };
+        self.invoke_locked(&instruction, &[&contract_seeds[..]])
+    }
+
     fn is_solana_address(&self, code_address: &H160) -> bool {
         *code_address == Self::system_account()
     }
@@ -64,6 +289,26 @@ impl<'a, 's, S> SolanaBackend<'a, 's, S> where S: AccountStorage {
         H160::from_slice(&[0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0x01u8])
     }
 
+    /// Invoke the Solana program with the contract account locked against re-entering
+    fn invoke_locked(&self, instruction: &Instruction, signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        let account_infos = self.account_infos.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let contract_key = self.account_storage.get_account_solana_address(&self.account_storage.contract());
+        let contract_info = account_infos.iter().find(|info| Some(*info.key) == contract_key);
+
+        let previous_lock = match contract_info {
+            Some(contract_info) => Some(swap_account_lock(contract_info, Some(self.account_storage.program_id()))?),
+            None => None,
+        };
+
+        let result = invoke_signed(instruction, account_infos, signers_seeds);
+
+        if let (Some(contract_info), Some(previous_lock)) = (contract_info, previous_lock) {
+            swap_account_lock(contract_info, previous_lock)?;
+        }
+
+        result
+    }
+
     pub fn call_inner_ecrecover(&self,
         code_address: H160,
         _transfer: Option<Transfer>,
@@ -104,19 +349,19 @@ impl<'a, 's, S> SolanaBackend<'a, 's, S> where S: AccountStorage {
 }
 
 impl<'a, 's, S> Backend for SolanaBackend<'a, 's, S> where S: AccountStorage {
-    fn gas_price(&self) -> U256 { U256::zero() }
+    fn gas_price(&self) -> U256 { self.account_storage.gas_price() }
     fn origin(&self) -> H160 { self.account_storage.origin() }
-    fn block_hash(&self, _number: U256) -> H256 { H256::default() }
+    fn block_hash(&self, number: U256) -> H256 { self.account_storage.block_hash(number) }
     fn block_number(&self) -> U256 {
         self.account_storage.block_number()
     }
-    fn block_coinbase(&self) -> H160 { H160::default() }
+    fn block_coinbase(&self) -> H160 { self.account_storage.block_coinbase() }
     fn block_timestamp(&self) -> U256 {
         self.account_storage.block_timestamp()
     }
-    fn block_difficulty(&self) -> U256 { U256::zero() }
+    fn block_difficulty(&self) -> U256 { self.account_storage.block_randomness() }
     fn block_gas_limit(&self) -> U256 { U256::zero() }
-    fn chain_id(&self) -> U256 { U256::from(111) }
+    fn chain_id(&self) -> U256 { self.account_storage.chain_id() }
 
     fn exists(&self, address: H160) -> bool {
         self.account_storage.exists(&address)
@@ -137,15 +382,15 @@ impl<'a, 's, S> Backend for SolanaBackend<'a, 's, S> where S: AccountStorage {
         self.account_storage.storage(&address, &index)
     }
 
+    /// No alias is registered here: the Solana account of a created contract is
+    /// `program_address([ether])` and stores its Ether address, so it is found
+    /// by `AccountStorage` in any later transaction without extra state.
     fn create(&self, _scheme: &CreateScheme, _address: &H160) {
         if let CreateScheme::Create2 {caller, code_hash, salt} = _scheme {
             debug_print!("CreateScheme2 {} from {} {} {} {}", &hex::encode(_address), &hex::encode(caller), &hex::encode(code_hash), &hex::encode(salt), "" /*dummy arg for use correct message function*/);
         } else {
             debug_print!("Call create");
         }
-    /*    let account = if let CreateScheme::Create2{salt,..} = scheme
-                {Pubkey::new(&salt.to_fixed_bytes())} else {Pubkey::default()};
-        self.add_alias(address, &account);*/
     }
 
     fn call_inner(&self,
@@ -161,70 +406,59 @@ impl<'a, 's, S> Backend for SolanaBackend<'a, 's, S> where S: AccountStorage {
             return self.call_inner_ecrecover(code_address, _transfer, input, _target_gas, _is_static, _take_l64, _take_stipend);
         }
 
+        if code_address == multicall::address() {
+            debug_print!("multicall is supported by Machine only");
+            return Some(Capture::Exit((ExitReason::Fatal(evm::ExitFatal::NotSupported), Vec::new())));
+        }
+
         if !self.is_solana_address(&code_address) {
             return None;
         }
 
-        debug_print!("Call inner");
-        debug_print!("{}", &code_address.to_string());
-        debug_print!("{}", &hex::encode(&input));
+        evm_debug!("Call inner");
+        evm_debug!("{}", &code_address.to_string());
+        evm_debug!("{}", &hex::encode(&input));
 
-        let (cmd, input) = input.split_at(1);
-        match cmd[0] {
+        let (cmd, input) = match input.split_first() {
+            Some((cmd, input)) => (*cmd, input),
+            None => return Some(Capture::Exit((ExitReason::Error(evm::ExitError::OutOfOffset), Vec::new()))),
+        };
+        match cmd {
             0 => {
-                let (program_id, input) = input.split_at(32);
-                let program_id = Pubkey::new(program_id);
-        
-                let (acc_length, input) = input.split_at(2);
-                let acc_length = acc_length.try_into().ok().map(u16::from_be_bytes).unwrap();
-                
-                let mut accounts = Vec::new();
-                for i in 0..acc_length {
-                    let data = array_ref![input, 35*i as usize, 35];
-                    let (translate, signer, writable, pubkey) = array_refs![data, 1, 1, 1, 32];
-                    let pubkey = if translate[0] != 0 {
-                        match self.account_storage.get_account_solana_address(&H160::from_slice(&pubkey[12..])) {
-                            Some(key) => key.clone(),
-                            None => { return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new()))); },
-                        }
-                    } else {
-                        Pubkey::new(pubkey)
-                    };
-                    accounts.push(AccountMeta {
-                        is_signer: signer[0] != 0,
-                        is_writable: writable[0] != 0,
-                        pubkey: pubkey,
-                    });
-                    debug_print!("Acc: {}", pubkey);
+                let (program_id, cpi_accounts, input) = match parse_cpi_instruction(input) {
+                    Ok(parsed) => parsed,
+                    Err(err) => return Some(Capture::Exit((ExitReason::Error(err), Vec::new()))),
                 };
-        
-                let (_, input) = input.split_at(35 * acc_length as usize);
-                debug_print!("{}", &hex::encode(&input));
 
-                let (contract_eth, contract_nonce) = self.account_storage.seeds(&self.account_storage.contract()).unwrap();   // do_call already check existence of Ethereum account with such index
-                let contract_seeds = [contract_eth.as_bytes(), &[contract_nonce]];
+                let mut accounts = Vec::with_capacity(cpi_accounts.len());
+                for (translate, mut meta) in cpi_accounts {
+                    if translate {
+                        meta.pubkey = match self.account_storage.get_account_solana_address(&H160::from_slice(&meta.pubkey.as_ref()[12..])) {
+                            Some(key) => key,
+                            None => { return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new()))); },
+                        };
+                    }
+                    evm_debug!("Acc: {}", meta.pubkey);
+                    accounts.push(meta);
+                }
+                evm_debug!("{}", &hex::encode(&input));
 
-                debug_print!("account_infos");
-                for info in self.account_infos.unwrap() {
-                    debug_print!("  {}", info.key);
+                let (contract_eth, contract_nonce) = match self.account_storage.seeds(&self.account_storage.contract()) {
+                    Some(seeds) => seeds,
+                    None => return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new()))),
                 };
-                let result : solana_program::entrypoint::ProgramResult;
-                match self.account_storage.seeds(&self.account_storage.origin()) {
+                let contract_seeds = [contract_eth.as_bytes(), &[contract_nonce]];
+
+                let instruction = Instruction{program_id, accounts: accounts, data: input.to_vec()};
+                let result = match self.account_storage.seeds(&self.account_storage.origin()) {
                     Some((sender_eth, sender_nonce)) => {
                         let sender_seeds = [sender_eth.as_bytes(), &[sender_nonce]];
-                        result = invoke_signed(
-                            &Instruction{program_id, accounts: accounts, data: input.to_vec()},
-                            &self.account_infos.unwrap(), &[&sender_seeds[..], &contract_seeds[..]]
-                        );
-
+                        self.invoke_locked(&instruction, &[&sender_seeds[..], &contract_seeds[..]])
                     }
                     None => {
-                        result = invoke_signed(
-                            &Instruction{program_id, accounts: accounts, data: input.to_vec()},
-                            &self.account_infos.unwrap(), &[&contract_seeds[..]]
-                        );
+                        self.invoke_locked(&instruction, &[&contract_seeds[..]])
                     }
-                }
+                };
                 if let Err(err) = result {
                     debug_print!("result: {}", err);
                     return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));
@@ -232,6 +466,9 @@ impl<'a, 's, S> Backend for SolanaBackend<'a, 's, S> where S: AccountStorage {
                 return Some(Capture::Exit((ExitReason::Succeed(evm::ExitSucceed::Stopped), Vec::new())));
             },
             1 => {
+                if input.len() < 66 {
+                    return Some(Capture::Exit((ExitReason::Error(evm::ExitError::OutOfOffset), Vec::new())));
+                }
                 let data = array_ref![input, 0, 66];
                 let (tr_base, tr_owner, base, owner) = array_refs![data, 1, 1, 32, 32];
 
@@ -250,6 +487,9 @@ impl<'a, 's, S> Backend for SolanaBackend<'a, 's, S> where S: AccountStorage {
                 } else {Pubkey::new(owner)};
 
                 let (_, seed) = input.split_at(66);
+                if seed.len() > MAX_SEED_LEN {
+                    return Some(Capture::Exit((ExitReason::Error(evm::ExitError::OutOfOffset), Vec::new())));
+                }
                 let seed = if let Ok(seed) = std::str::from_utf8(&seed) {seed}
                 else {return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));};
 
@@ -259,6 +499,88 @@ impl<'a, 's, S> Backend for SolanaBackend<'a, 's, S> where S: AccountStorage {
                 debug_print!("result: {}", &hex::encode(pubkey.as_ref()));
                 return Some(Capture::Exit((ExitReason::Succeed(evm::ExitSucceed::Returned), pubkey.as_ref().to_vec())));
             },
+            2 => {
+                if input.len() < 32 {
+                    return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));
+                }
+                let mint = Pubkey::new(&input[..32]);
+
+                return match self.token_metadata(&mint) {
+                    Some(result) => Some(Capture::Exit((ExitReason::Succeed(evm::ExitSucceed::Returned), result))),
+                    None => Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new()))),
+                };
+            },
+            3 => {
+                if input.len() < 64 {
+                    return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));
+                }
+                let mint = Pubkey::new(&input[..32]);
+                let holder = H160::from_slice(&input[44..64]);
+
+                return match self.token_balance(&mint, &holder) {
+                    Some(result) => Some(Capture::Exit((ExitReason::Succeed(evm::ExitSucceed::Returned), result))),
+                    None => Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new()))),
+                };
+            },
+            4 => {
+                if input.len() < 32 {
+                    return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));
+                }
+                let mint = Pubkey::new(&input[..32]);
+
+                return match self.nft_owner(&mint) {
+                    Some(result) => Some(Capture::Exit((ExitReason::Succeed(evm::ExitSucceed::Returned), result))),
+                    None => Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new()))),
+                };
+            },
+            5 => {
+                if input.len() < 64 {
+                    return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));
+                }
+                let mint = Pubkey::new(&input[..32]);
+                let holder = H160::from_slice(&input[44..64]);
+
+                return match self.account_storage.get_account_solana_address(&holder) {
+                    Some(wallet) => {
+                        let token_account = erc20_wrapper::associated_token_address(&wallet, &mint);
+                        Some(Capture::Exit((ExitReason::Succeed(evm::ExitSucceed::Returned), token_account.to_bytes().to_vec())))
+                    },
+                    None => Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new()))),
+                };
+            },
+            6 => {
+                if input.len() < 32 {
+                    return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));
+                }
+                let mint = Pubkey::new(&input[..32]);
+
+                return match self.wrapper_of(&mint) {
+                    Some(result) => Some(Capture::Exit((ExitReason::Succeed(evm::ExitSucceed::Returned), result))),
+                    None => Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new()))),
+                };
+            },
+            7 => {
+                if input.len() > MAX_MEMO_LEN || std::str::from_utf8(input).is_err() {
+                    return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));
+                }
+
+                if let Err(err) = self.memo(input) {
+                    debug_print!("memo: {}", err);
+                    return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));
+                }
+                return Some(Capture::Exit((ExitReason::Succeed(evm::ExitSucceed::Stopped), Vec::new())));
+            },
+            8 => {
+                if input.len() < 32 {
+                    return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));
+                }
+                let data_len = U256::from_big_endian(&input[..32]);
+                if data_len > U256::from(MAX_PERMITTED_DATA_LENGTH) {
+                    return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));
+                }
+
+                return Some(Capture::Exit((ExitReason::Succeed(evm::ExitSucceed::Returned), self.sysvars(data_len.as_usize()))));
+            },
             _ => {
                 return Some(Capture::Exit((ExitReason::Error(evm::ExitError::InvalidRange), Vec::new())));
             }
@@ -332,6 +654,35 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_parse_cpi_instruction() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let mut input = program_id.to_bytes().to_vec();
+        input.extend_from_slice(&1u16.to_be_bytes());
+        input.extend_from_slice(&[0, 1, 1]);
+        input.extend_from_slice(key.as_ref());
+        input.extend_from_slice(&[7, 8]);
+
+        let (parsed_id, accounts, data) = parse_cpi_instruction(&input).unwrap();
+        assert_eq!(parsed_id, program_id);
+        assert_eq!(accounts, vec![(false, AccountMeta::new(key, true))]);
+        assert_eq!(data, &[7, 8]);
+
+        assert_eq!(parse_cpi_instruction(&input[..33]), Err(ExitError::OutOfOffset));
+        assert_eq!(parse_cpi_instruction(&input[..34 + 20]), Err(ExitError::OutOfOffset));
+
+        let mut too_many = program_id.to_bytes().to_vec();
+        too_many.extend_from_slice(&((MAX_CPI_ACCOUNTS + 1) as u16).to_be_bytes());
+        too_many.resize(too_many.len() + (MAX_CPI_ACCOUNTS + 1) * CPI_ACCOUNT_SIZE, 0);
+        assert_eq!(parse_cpi_instruction(&too_many), Err(ExitError::OutOfOffset));
+
+        let mut too_long = program_id.to_bytes().to_vec();
+        too_long.extend_from_slice(&0u16.to_be_bytes());
+        too_long.resize(too_long.len() + MAX_CPI_DATA_LEN + 1, 0);
+        assert_eq!(parse_cpi_instruction(&too_long), Err(ExitError::OutOfOffset));
+    }
+
     #[test]
     fn test_solidity_address() -> Result<(), ProgramError> {
 //        let account = Pubkey::from_str("Bfj8CF5ywavXyqkkuKSXt5AVhMgxUJgHfQsQjPc1JKzj").unwrap();
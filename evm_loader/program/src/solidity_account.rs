@@ -1,6 +1,6 @@
 use crate::{
     account_data::AccountData,
-    hamt::Hamt,
+    hamt::{Hamt, HamtUsage},
     utils::{keccak256_h256, u256_to_h256},
 };
 use evm::backend::Basic;
@@ -16,25 +16,49 @@ use std::rc::Rc;
 use std::convert::TryInto;
 
 
+/// Account behind the Ether address.
+///
+/// Ether accounts are program accounts with `AccountData::Account` data.
+/// Foreign accounts are owned by other programs, e.g. the Solana user calling the contract:
+/// they have `AccountData::Empty` data and expose only the balance, without nonce, code or storage.
 #[derive(Debug, Clone)]
 pub struct SolidityAccount<'a> {
     account_data: AccountData,
     solana_address: &'a Pubkey,
     code_data: Option<(AccountData, Rc<RefCell<&'a mut [u8]>>)>,
-    lamports: u64,
+    balance: U256,
 }
 
 impl<'a> SolidityAccount<'a> {
-    pub fn new(solana_address: &'a Pubkey, lamports: u64, account_data: AccountData, code_data: Option<(AccountData, Rc<RefCell<&'a mut [u8]>>)>) -> Result<Self, ProgramError> {
-        debug_print!("  SolidityAccount::new");
-        Ok(Self{account_data, solana_address, code_data, lamports})
+    pub fn new(program_id: &Pubkey, solana_address: &'a Pubkey, lamports: u64, account_data: AccountData, code_data: Option<(AccountData, Rc<RefCell<&'a mut [u8]>>)>) -> Result<Self, ProgramError> {
+        evm_trace!("  SolidityAccount::new");
+
+        let account = account_data.get_account()?;
+        let expected_address = Pubkey::create_program_address(&[account.ether.as_bytes(), &[account.nonce]], program_id)?;
+        if expected_address != *solana_address {
+            debug_print!("Invalid bump seed for account {}: expected {}", solana_address, expected_address);
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        Ok(Self{account_data, solana_address, code_data, balance: lamports.into()})
+    }
+
+    /// Account which is not owned by the program, its data is never read
+    pub fn foreign(solana_address: &'a Pubkey, balance: U256) -> Self {
+        evm_trace!("  SolidityAccount::foreign");
+
+        Self{account_data: AccountData::Empty, solana_address, code_data: None, balance}
+    }
+
+    pub fn is_foreign(&self) -> bool {
+        matches!(self.account_data, AccountData::Empty)
     }
 
     pub fn get_signer(&self) -> Pubkey {AccountData::get_account(&self.account_data).unwrap().signer}
 
     pub fn get_ether(&self) -> H160 {AccountData::get_account(&self.account_data).unwrap().ether}
 
-    pub fn get_nonce(&self) -> u64 {AccountData::get_account(&self.account_data).unwrap().trx_count}
+    pub fn get_nonce(&self) -> u64 {AccountData::get_account(&self.account_data).map_or(0, |account| account.trx_count)}
 
     fn code<U, F>(&self, f: F) -> U
     where F: FnOnce(&[u8]) -> U {
@@ -83,7 +107,7 @@ impl<'a> SolidityAccount<'a> {
 
         if code_size > 0 {
             let mut data = self.code_data.as_ref().unwrap().1.borrow_mut();
-            debug_print!("Storage data borrowed");
+            evm_trace!("Storage data borrowed");
             let mut hamt = Hamt::new(&mut data[contract_data.size()+code_size..], false)?;
             Ok(f(&mut hamt))
         } else {
@@ -95,18 +119,19 @@ impl<'a> SolidityAccount<'a> {
         *self.solana_address
     }
 
-    pub fn get_seeds(&self) -> (H160, u8) { (AccountData::get_account(&self.account_data).unwrap().ether, AccountData::get_account(&self.account_data).unwrap().nonce) }
+    /// Seeds of the account program address: ether and bump seed stored at creation, `None` for foreign accounts
+    pub fn get_seeds(&self) -> Option<(H160, u8)> { AccountData::get_account(&self.account_data).ok().map(|account| (account.ether, account.nonce)) }
     
     pub fn basic(&self) -> Basic {
         Basic { 
-            balance: self.lamports.into(), 
-            nonce: U256::from(AccountData::get_account(&self.account_data).unwrap().trx_count), }
+            balance: self.balance, 
+            nonce: U256::from(self.get_nonce()), }
         
     }
     
     pub fn code_hash(&self) -> H256 {
         self.code(|d| {
-            debug_print!("{}", &hex::encode(&d[0..32]));
+            evm_trace!("{}", &hex::encode(&d[..d.len().min(32)]));
             keccak256_h256(d)
         })
     }
@@ -119,26 +144,43 @@ impl<'a> SolidityAccount<'a> {
         self.code(|d| Code::AccountRef{ ptr: d.as_ptr(), len: d.len(), account })
     }
     
+    /// Key of the storage index in the HAMT, see `Contract::storage_key`
+    fn storage_key(&self, index: U256) -> U256 {
+        match self.code_data {
+            Some((ref contract_data, _)) => AccountData::get_contract(contract_data).map_or(index, |contract| contract.storage_key(index)),
+            None => index,
+        }
+    }
+
     pub fn get_storage(&self, index: &U256) -> U256 {
-        let value = self.storage(|storage| storage.find(*index)).unwrap_or_default();
+        let key = self.storage_key(*index);
+        let value = self.storage(|storage| storage.find(key)).unwrap_or_default();
         if let Some(v) = value { v } else { U256::zero() }
     }
 
+    /// Check the integrity of the storage, see `Hamt::verify`
+    pub fn verify_storage(&self) -> Result<HamtUsage, ProgramError> {
+        self.storage(|storage| storage.verify())?
+    }
+
     pub fn update<I>(
         &mut self,
         account_info: &'a AccountInfo<'a>,
         solidity_address: H160,
         nonce: U256,
-        lamports: u64,
+        lamports: Option<u64>,
         code: &Option<Vec<u8>>,
+        deployer: H160,
         storage_items: I,
         reset_storage: bool,
     ) -> Result<(), ProgramError>
     where I: IntoIterator<Item = (U256, U256)> 
     {
-        debug_print!("Update: {}, {}, {}, {:?}, {}", solidity_address, nonce, lamports, if let Some(_) = code {"Exist"} else {"Empty"}, reset_storage);
+        evm_debug!("Update: {}, {}, {:?}, {:?}, {}", solidity_address, nonce, lamports, if let Some(_) = code {"Exist"} else {"Empty"}, reset_storage);
         let mut data = (*account_info.data).borrow_mut();
-        **(*account_info.lamports).borrow_mut() = lamports;
+        if let Some(lamports) = lamports {
+            **(*account_info.lamports).borrow_mut() = lamports;
+        }
 
         /*let mut current_code_size = match self.account_data {
             AccountData::Empty => 0,
@@ -148,7 +190,7 @@ impl<'a> SolidityAccount<'a> {
         AccountData::get_mut_account(&mut self.account_data)?.trx_count = nonce.as_u64();
 
         if let Some(code) = code {
-            debug_print!("Write contract");
+            evm_debug!("Write contract");
             match self.code_data {
                 Some((ref mut contract_data, ref mut code_data)) => {
                     let mut code_data = code_data.borrow_mut();
@@ -158,12 +200,13 @@ impl<'a> SolidityAccount<'a> {
                         return Err(ProgramError::AccountAlreadyInitialized);
                     };
                     contract.code_size = code.len().try_into().map_err(|_| ProgramError::AccountDataTooSmall)?;
+                    contract.deployer = deployer;
         
-                    debug_print!("Write contract header");
+                    evm_debug!("Write contract header");
                     contract_data.pack(&mut code_data)?;
-                    debug_print!("Write code");
+                    evm_debug!("Write code");
                     code_data[contract_data.size()..contract_data.size()+code.len()].copy_from_slice(&code);
-                    debug_print!("Code written");
+                    evm_debug!("Code written");
                 },
                 None => {
                     debug_print!("Expected code account");
@@ -172,26 +215,31 @@ impl<'a> SolidityAccount<'a> {
             }
         }
 
-        debug_print!("Write account data");        
+        evm_debug!("Write account data");        
         self.account_data.pack(&mut data)?;
 
         let mut storage_iter = storage_items.into_iter().peekable();
         let exist_items = if let Some(_) = storage_iter.peek() {true} else {false};
         if reset_storage || exist_items {
-            debug_print!("Update storage");
+            evm_debug!("Update storage");
             match self.code_data {
-                Some((ref contract_data, ref mut code_data)) => {
+                Some((ref mut contract_data, ref mut code_data)) => {
                     let mut code_data = code_data.borrow_mut();
         
-                    let contract = AccountData::get_contract(&contract_data)?;
+                    let header_size = contract_data.size();
+                    let contract = AccountData::get_mut_contract(contract_data)?;
                     if contract.code_size == 0 {return Err(ProgramError::UninitializedAccount);};
         
-                    let mut storage = Hamt::new(&mut code_data[contract_data.size()+(contract.code_size as usize)..], reset_storage)?;
-                    debug_print!("Storage initialized");
-                    for (key, value) in storage_iter {
-                        debug_print!("Storage value: {} = {}", &key.to_string(), &value.to_string());
-                        storage.insert(key, value)?;
+                    let mut storage = Hamt::new(&mut code_data[header_size+(contract.code_size as usize)..], reset_storage)?;
+                    evm_debug!("Storage initialized");
+                    for (index, value) in storage_iter {
+                        evm_trace!("Storage value: {} = {}", &index.to_string(), &value.to_string());
+                        storage.insert(contract.storage_key(index), value)?;
                     }
+                    contract.storage_root = storage.root_hash();
+
+                    evm_debug!("Write contract header");
+                    contract_data.pack(&mut code_data)?;
                 },
                 None => {
                     debug_print!("Expected code account");
@@ -200,7 +248,7 @@ impl<'a> SolidityAccount<'a> {
             }
         }
 
-        debug_print!("Account updated");
+        evm_debug!("Account updated");
         Ok(())
     }
 }
@@ -1,13 +1,62 @@
 use crate::{
-    account_data::{ Storage, AccountData }
+    account_data::{ Storage, AccountData },
+    error::EvmLoaderError,
 };
-use primitive_types::{ H160} ;
+use primitive_types::{ H160, H256 };
 use solana_program::{
     account_info::AccountInfo,
     pubkey::Pubkey,
     program_error::ProgramError,
 };
+use solana_program::keccak::hash;
 use serde::{ Serialize, de::DeserializeOwned };
+use std::convert::TryInto;
+
+/// Version of the iterative execution data layout
+const DATA_VERSION: u8 = 2;
+/// Header of the iterative execution data: version, data length and checksum
+const HEADER_SIZE: usize = 1 + 8 + 8;
+
+/// Check that accounts are not locked by an iterative transaction, other than `storage`,
+/// and are not executing a call to a Solana program
+pub fn check_accounts_unblocked(program_id: &Pubkey, accounts: &[AccountInfo], storage: Option<&Pubkey>) -> Result<(), ProgramError> {
+    for account_info in accounts.iter().filter(|a| a.owner == program_id) {
+        let data = account_info.try_borrow_data()?;
+        if let AccountData::Account(account) = AccountData::unpack(&data)? {
+            match account.blocked {
+                Some(ref blocked_by) if blocked_by == program_id => {
+                    debug_print!("Account {} is executing", account_info.key);
+                    return Err(EvmLoaderError::Reentrancy.into());
+                },
+                Some(ref blocked_by) if Some(blocked_by) != storage => {
+                    debug_print!("Account {} is blocked by {}", account_info.key, blocked_by);
+                    return Err(EvmLoaderError::AccountBlocked.into());
+                },
+                _ => (),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Lock the Ether account with `blocked` and return the previous lock.
+/// During a call to a Solana program the contract account is locked by the EVM loader itself,
+/// so re-entering instructions fail with `Reentrancy`.
+pub fn swap_account_lock(account_info: &AccountInfo, blocked: Option<Pubkey>) -> Result<Option<Pubkey>, ProgramError> {
+    let mut data = account_info.try_borrow_mut_data()?;
+    let mut account_data = AccountData::unpack(&data)?;
+    let account = account_data.get_mut_account()?;
+    let previous = std::mem::replace(&mut account.blocked, blocked);
+    account_data.pack(&mut data)?;
+
+    Ok(previous)
+}
+
+fn checksum(data: &[u8]) -> u64 {
+    let digest = hash(data).to_bytes();
+    u64::from_le_bytes(digest[..8].try_into().unwrap())
+}
 
 
 pub struct StorageAccount<'a> {
@@ -16,12 +65,12 @@ pub struct StorageAccount<'a> {
 }
 
 impl<'a> StorageAccount<'a> {
-    pub fn new(info: &'a AccountInfo<'a>, accounts: &[AccountInfo], caller: H160, nonce: u64) -> Result<Self, ProgramError> {
+    pub fn new(info: &'a AccountInfo<'a>, accounts: &[AccountInfo], caller: H160, nonce: u64, tx_hash: H256, operator: Pubkey) -> Result<Self, ProgramError> {
         let account_data = info.try_borrow_data()?;
 
         if let AccountData::Empty = AccountData::unpack(&account_data)? {
             let data = AccountData::Storage(
-                Storage { caller, nonce, accounts_len: accounts.len(), executor_data_size: 0, evm_data_size: 0 }
+                Storage { caller, nonce, tx_hash, operator, accounts_len: accounts.len(), executor_data_size: 0, evm_data_size: 0 }
             );
             Ok(Self { info, data })
         } else {
@@ -61,6 +110,18 @@ impl<'a> StorageAccount<'a> {
         Ok((storage.caller, storage.nonce))
     }
 
+    /// Operator which started the transaction
+    pub fn operator(&self) -> Result<Pubkey, ProgramError> {
+        let storage = AccountData::get_storage(&self.data)?;
+        Ok(storage.operator)
+    }
+
+    /// Hash of the transaction executed in the storage account
+    pub fn tx_hash(&self) -> Result<H256, ProgramError> {
+        let storage = AccountData::get_storage(&self.data)?;
+        Ok(storage.tx_hash)
+    }
+
     pub fn accounts(&self) -> Result<Vec<Pubkey>, ProgramError> {
         let storage = AccountData::get_storage(&self.data)?;
 
@@ -93,6 +154,10 @@ impl<'a> StorageAccount<'a> {
         for account_info in accounts.iter().filter(|a| a.owner == program_id) {
             let data = account_info.try_borrow_data()?;
             if let AccountData::Account(account) = AccountData::unpack(&data)? {
+                if Some(program_id) == account.blocked.as_ref() {
+                    debug_print!("Account {} is executing", account_info.key);
+                    return Err(EvmLoaderError::Reentrancy.into());
+                }
                 if Some(self.info.unsigned_key()) != account.blocked.as_ref() {
                     return Err(ProgramError::NotEnoughAccountKeys);
                 }
@@ -103,6 +168,8 @@ impl<'a> StorageAccount<'a> {
     }
 
     pub fn block_accounts(&mut self, program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+        check_accounts_unblocked(program_id, accounts, Some(self.info.unsigned_key()))?;
+
         {
             let storage = AccountData::get_storage(&self.data)?;
             if storage.accounts_len != accounts.len() {
@@ -137,28 +204,48 @@ impl<'a> StorageAccount<'a> {
         Ok(())
     }
 
+    /// Size of the account required to store accounts list and serialized data
+    pub fn required_size<T: Serialize, E: Serialize>(&self, evm_data: &T, executor_data: &E) -> Result<usize, ProgramError> {
+        let evm_data_size = bincode::serialized_size(&evm_data).map_err(|_| EvmLoaderError::StorageSerialization)? as usize;
+        let executor_data_size = bincode::serialized_size(&executor_data).map_err(|_| EvmLoaderError::StorageSerialization)? as usize;
+
+        let (_, accounts_end) = self.accounts_region()?;
+        Ok(accounts_end + HEADER_SIZE + evm_data_size + executor_data_size)
+    }
+
     pub fn serialize<T: Serialize, E: Serialize>(&mut self, evm_data: &T, executor_data: &E) -> Result<(), ProgramError> {
+        let required_size = self.required_size(evm_data, executor_data)?;
+        if self.info.data_len() < required_size {
+            debug_print!("Storage account too small: {} bytes, required {}", self.info.data_len(), required_size);
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
         {
             let storage = AccountData::get_mut_storage(&mut self.data)?;
-            storage.evm_data_size = bincode::serialized_size(&evm_data).map_err(|_| ProgramError::InvalidInstructionData)? as usize;
-            storage.executor_data_size = bincode::serialized_size(&executor_data).map_err(|_| ProgramError::InvalidInstructionData)? as usize;
+            storage.evm_data_size = bincode::serialized_size(&evm_data).map_err(|_| EvmLoaderError::StorageSerialization)? as usize;
+            storage.executor_data_size = bincode::serialized_size(&executor_data).map_err(|_| EvmLoaderError::StorageSerialization)? as usize;
         }
         
         let mut account_data = self.info.try_borrow_mut_data()?;
         {
-            let (start, mid, end) = self.storage_region()?;
-            if (account_data.len() < end) {
-                return Err(ProgramError::AccountDataTooSmall);
-            }
+            let (header, start, mid, end) = self.storage_region()?;
 
             {
                 let buffer = &mut account_data[start..mid];
-                bincode::serialize_into(buffer, &evm_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+                bincode::serialize_into(buffer, &evm_data).map_err(|_| EvmLoaderError::StorageSerialization)?;
             }
             {
                 let buffer = &mut account_data[mid..end];
-                bincode::serialize_into(buffer, &executor_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+                bincode::serialize_into(buffer, &executor_data).map_err(|_| EvmLoaderError::StorageSerialization)?;
             }
+
+            let length = (end - start) as u64;
+            let checksum = checksum(&account_data[start..end]);
+
+            let header = &mut account_data[header..start];
+            header[0] = DATA_VERSION;
+            header[1..9].copy_from_slice(&length.to_le_bytes());
+            header[9..17].copy_from_slice(&checksum.to_le_bytes());
         }
 
         AccountData::pack(&self.data, &mut account_data)?;
@@ -169,18 +256,32 @@ impl<'a> StorageAccount<'a> {
     pub fn deserialize<T: DeserializeOwned, E: DeserializeOwned>(&self) -> Result<(T, E), ProgramError> {
         let account_data = self.info.try_borrow_data()?;
 
-        let (start, mid, end) = self.storage_region()?;
+        let (header, start, mid, end) = self.storage_region()?;
         if (account_data.len() < end) {
             return Err(ProgramError::AccountDataTooSmall);
         }
 
+        {
+            let header = &account_data[header..start];
+            if header[0] != DATA_VERSION {
+                debug_print!("Unsupported iterative execution data version {}", header[0]);
+                return Err(EvmLoaderError::StorageVersionMismatch.into());
+            }
+
+            let length = u64::from_le_bytes(header[1..9].try_into().unwrap());
+            let expected_checksum = u64::from_le_bytes(header[9..17].try_into().unwrap());
+            if length != (end - start) as u64 || expected_checksum != checksum(&account_data[start..end]) {
+                return Err(EvmLoaderError::StorageCorrupted.into());
+            }
+        }
+
         let evm_data: T = {
             let buffer = &account_data[start..mid];
-            bincode::deserialize_from(buffer).map_err(|_| ProgramError::InvalidInstructionData)?
+            bincode::deserialize_from(buffer).map_err(|_| EvmLoaderError::StorageSerialization)?
         };
         let executor_data: E = {
             let buffer = &account_data[mid..end];
-            bincode::deserialize_from(buffer).map_err(|_| ProgramError::InvalidInstructionData)?
+            bincode::deserialize_from(buffer).map_err(|_| EvmLoaderError::StorageSerialization)?
         };
 
         Ok((evm_data, executor_data))
@@ -195,13 +296,14 @@ impl<'a> StorageAccount<'a> {
         Ok((begin, end))
     }
 
-    fn storage_region(&self) -> Result<(usize, usize, usize), ProgramError> {
+    fn storage_region(&self) -> Result<(usize, usize, usize, usize), ProgramError> {
         let storage = AccountData::get_storage(&self.data)?;
 
-        let begin = AccountData::size(&self.data) + (storage.accounts_len * 32);
+        let header = AccountData::size(&self.data) + (storage.accounts_len * 32);
+        let begin = header + HEADER_SIZE;
         let mid = begin + storage.evm_data_size;
         let end = mid + storage.executor_data_size;
 
-        Ok((begin, mid, end))
+        Ok((header, begin, mid, end))
     }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use primitive_types::{H160, U256};
+use primitive_types::{H160, H256, U256};
 use serde::{Deserialize, Serialize};
 use solana_program::{ 
     sysvar::instructions::{load_current_index, load_instruction_at},
@@ -8,7 +8,7 @@ use solana_program::{
     secp256k1_program,
 };
 use std::convert::Into;
-use crate::utils::keccak256_digest;
+use crate::utils::{keccak256_digest, keccak256_h256};
 
 #[derive(Default, Serialize, Deserialize, Debug)]
 struct SecpSignatureOffsets {
@@ -21,30 +21,49 @@ struct SecpSignatureOffsets {
     message_instruction_index: u8,
 }
 
+/// Signature verified by the secp256k1 instruction: `eth_address: [u8; 20], signature: [u8; 65], message`
+/// stored from `data_start` in the data of the instruction `instruction_index`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SecpSignature {
+    pub instruction_index: u16,
+    pub data_start: u16,
+    pub message_len: usize,
+}
+
+/// Maximum number of signatures of one secp256k1 instruction
+pub const MAX_SECP_SIGNATURES: usize = u8::max_value() as usize;
+
 pub fn make_secp256k1_instruction(instruction_index: u16, message_len: usize, data_start: u16) -> Vec<u8> {
-    let mut instruction_data = vec![];
+    make_secp256k1_instruction_multi(&[SecpSignature {instruction_index, data_start, message_len}])
+}
 
-    const NUMBER_OF_SIGNATURES: u8 = 1;
+/// Data of the secp256k1 instruction verifying all the signatures,
+/// at most `MAX_SECP_SIGNATURES` of them
+pub fn make_secp256k1_instruction_multi(signatures: &[SecpSignature]) -> Vec<u8> {
     const ETH_SIZE: u16 = 20;
     const SIGN_SIZE: u16 = 65;
-    let ETH_OFFSET: u16 = data_start;
-    let SIGN_OFFSET: u16 = ETH_OFFSET + ETH_SIZE;
-    let MSG_OFFSET: u16 = SIGN_OFFSET + SIGN_SIZE;
-
-    let offsets = SecpSignatureOffsets {
-        signature_offset: SIGN_OFFSET as u16,
-        signature_instruction_index: instruction_index as u8,
-        eth_address_offset: ETH_OFFSET as u16,
-        eth_address_instruction_index: instruction_index as u8,
-        message_data_offset: MSG_OFFSET as u16,
-        message_data_size: message_len as u16,
-        message_instruction_index: instruction_index as u8,
-    };
 
-    let bin_offsets = bincode::serialize(&offsets).unwrap();
+    let mut instruction_data = vec![];
+    instruction_data.push(signatures.len() as u8);
+
+    for signature in signatures {
+        let eth_offset: u16 = signature.data_start;
+        let sign_offset: u16 = eth_offset + ETH_SIZE;
+        let msg_offset: u16 = sign_offset + SIGN_SIZE;
+
+        let offsets = SecpSignatureOffsets {
+            signature_offset: sign_offset,
+            signature_instruction_index: signature.instruction_index as u8,
+            eth_address_offset: eth_offset,
+            eth_address_instruction_index: signature.instruction_index as u8,
+            message_data_offset: msg_offset,
+            message_data_size: signature.message_len as u16,
+            message_instruction_index: signature.instruction_index as u8,
+        };
 
-    instruction_data.push(NUMBER_OF_SIGNATURES);
-    instruction_data.extend(&bin_offsets);
+        let bin_offsets = bincode::serialize(&offsets).unwrap();
+        instruction_data.extend(&bin_offsets);
+    }
 
     instruction_data
 }
@@ -52,12 +71,25 @@ pub fn make_secp256k1_instruction(instruction_index: u16, message_len: usize, da
 pub fn check_secp256k1_instruction(sysvar_info: &AccountInfo, message_len: usize, data_offset: u16) -> ProgramResult
 {
     let current_instruction = load_current_index(&sysvar_info.try_borrow_data()?);
-    let index = current_instruction - 1;
+    check_secp256k1_instruction_multi(sysvar_info, &[SecpSignature {instruction_index: current_instruction, data_start: data_offset, message_len}])
+}
+
+/// Check that the instruction preceding the current one is the secp256k1 instruction
+/// verifying exactly the given signatures, in the given order
+pub fn check_secp256k1_instruction_multi(sysvar_info: &AccountInfo, signatures: &[SecpSignature]) -> ProgramResult
+{
+    if signatures.is_empty() || signatures.len() > MAX_SECP_SIGNATURES {
+        debug_print!("wrong number of signatures {}", signatures.len());
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let current_instruction = load_current_index(&sysvar_info.try_borrow_data()?);
+    let index = current_instruction.checked_sub(1).ok_or(ProgramError::MissingRequiredSignature)?;
 
     match load_instruction_at(index.into(), &sysvar_info.try_borrow_data()?) {
         Ok(instr) => {
             if secp256k1_program::check_id(&instr.program_id) {
-                let reference_instruction = make_secp256k1_instruction(current_instruction, message_len, data_offset);
+                let reference_instruction = make_secp256k1_instruction_multi(signatures);
                 if reference_instruction != instr.data {
                     debug_print!("wrong keccak instruction data");
                     debug_print!("instruction: {}", &hex::encode(&instr.data));
@@ -86,12 +118,14 @@ pub struct UnsignedTransaction {
     pub to: Option<H160>,
     pub value: U256,
     pub call_data: Vec<u8>,
-    pub chain_id: U256,
+    /// Chain id signed as EIP-155 requires, `None` for legacy transactions
+    pub chain_id: Option<U256>,
 }
 
 impl rlp::Decodable for UnsignedTransaction {
     fn decode(rlp: &rlp::Rlp) -> Result<Self, rlp::DecoderError> {
-        if rlp.item_count()? != 9 {
+        let item_count = rlp.item_count()?;
+        if item_count != 6 && item_count != 9 {
             return Err(rlp::DecoderError::RlpIncorrectListLen);
         }
 
@@ -113,18 +147,78 @@ impl rlp::Decodable for UnsignedTransaction {
             },
             value: rlp.val_at(4)?,
             call_data: rlp.val_at(5)?,
-            chain_id: rlp.val_at(6)?,
+            chain_id: if item_count == 9 {
+                // EIP-155 signing data: chain_id, 0, 0
+                let r: U256 = rlp.val_at(7)?;
+                let s: U256 = rlp.val_at(8)?;
+                if !r.is_zero() || !s.is_zero() {
+                    return Err(rlp::DecoderError::Custom("EIP-155 signing data must end with 0, 0"));
+                }
+                Some(rlp.val_at(6)?)
+            } else {
+                None
+            },
         };
 
         Ok(tx)
     }
 }
 
-pub fn get_data(raw_tx: &[u8]) -> (u64, Option<H160>, Vec<u8>) {
-    let tx: Result<UnsignedTransaction, _> = rlp::decode(&raw_tx);
-    let tx = tx.unwrap();
+/// Replay protection: the transaction must be signed for `chain_id` (EIP-155).
+/// Legacy transactions without chain id are accepted only if `allow_legacy_tx` is set in the config account.
+pub fn check_chain_id(tx: &UnsignedTransaction, chain_id: U256, allow_legacy_tx: bool) -> ProgramResult {
+    match tx.chain_id {
+        Some(tx_chain_id) if tx_chain_id == chain_id => Ok(()),
+        Some(tx_chain_id) => {
+            debug_print!("Invalid chain id: actual {}, expect {}", tx_chain_id, chain_id);
+            Err(ProgramError::InvalidInstructionData)
+        },
+        None if allow_legacy_tx => Ok(()),
+        None => {
+            debug_print!("Transaction without chain id is rejected");
+            Err(ProgramError::InvalidInstructionData)
+        },
+    }
+}
 
-    (tx.nonce, tx.to, tx.call_data)
+/// Ethereum hash of the transaction: keccak256 of the raw RLP of the signed transaction.
+/// Program, emulator and proxy identify transactions, receipts and logs by this hash.
+pub fn tx_hash(signed_trx: &[u8]) -> H256 {
+    keccak256_h256(signed_trx)
+}
+
+/// Raw RLP of the signed transaction rebuilt from the unsigned message
+/// and the `[r, s, recovery_id]` signature
+pub fn signed_transaction_rlp(unsigned_trx: &[u8], signature: &[u8]) -> Result<Vec<u8>, rlp::DecoderError> {
+    let rlp = rlp::Rlp::new(unsigned_trx);
+    let item_count = rlp.item_count()?;
+    if (item_count != 6 && item_count != 9) || signature.len() != 65 {
+        return Err(rlp::DecoderError::RlpIncorrectListLen);
+    }
+
+    let v = if item_count == 9 {
+        let chain_id: U256 = rlp.val_at(6)?;
+        chain_id * 2 + 35 + signature[64]
+    } else {
+        U256::from(27 + signature[64])
+    };
+    let r = U256::from_big_endian(&signature[0..32]);
+    let s = U256::from_big_endian(&signature[32..64]);
+
+    let mut stream = rlp::RlpStream::new_list(9);
+    for i in 0..6 {
+        stream.append_raw(rlp.at(i)?.as_raw(), 1);
+    }
+    stream.append(&v);
+    stream.append(&r);
+    stream.append(&s);
+
+    Ok(stream.out())
+}
+
+/// Hash of the signed transaction rebuilt from the unsigned message and the signature
+pub fn signed_transaction_hash(unsigned_trx: &[u8], signature: &[u8]) -> Result<H256, rlp::DecoderError> {
+    signed_transaction_rlp(unsigned_trx, signature).map(|signed_trx| tx_hash(&signed_trx))
 }
 
 pub fn verify_tx_signature(signature: &[u8], unsigned_trx: &[u8]) -> Result<(), secp256k1::Error> {
@@ -141,3 +235,98 @@ pub fn verify_tx_signature(signature: &[u8], unsigned_trx: &[u8]) -> Result<(),
         Err(secp256k1::Error::InvalidSignature)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::{pubkey::Pubkey, sysvar};
+
+    #[test]
+    fn test_signed_transaction_hash() {
+        // EIP-155 example transaction
+        let unsigned_trx = hex::decode("ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080").unwrap();
+        let mut signature = hex::decode("28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa63627667cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").unwrap();
+        signature.push(0);
+
+        let hash = signed_transaction_hash(&unsigned_trx, &signature).unwrap();
+        assert_eq!(hex::encode(hash.as_bytes()), "33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788");
+
+        let signed_trx = hex::decode("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").unwrap();
+        assert_eq!(signed_transaction_rlp(&unsigned_trx, &signature).unwrap(), signed_trx);
+        assert_eq!(tx_hash(&signed_trx), hash);
+    }
+
+    #[test]
+    fn test_make_secp256k1_instruction_multi() {
+        let single = make_secp256k1_instruction(2, 100, 9);
+        assert_eq!(single, make_secp256k1_instruction_multi(&[SecpSignature {instruction_index: 2, data_start: 9, message_len: 100}]));
+        assert_eq!(single, hex::decode("011d00020900025e00640002").unwrap());
+
+        let signatures = [
+            SecpSignature {instruction_index: 1, data_start: 1, message_len: 100},
+            SecpSignature {instruction_index: 1, data_start: 186, message_len: 40},
+        ];
+        let multi = make_secp256k1_instruction_multi(&signatures);
+        assert_eq!(multi.len(), 1 + 2 * 11);
+        assert_eq!(multi[0], 2);
+        assert_eq!(&multi[1..12], &make_secp256k1_instruction(1, 100, 1)[1..]);
+        assert_eq!(&multi[12..], &make_secp256k1_instruction(1, 40, 186)[1..]);
+    }
+
+    /// Data of the instructions sysvar with no accounts in the instructions, `current` is the executed one
+    fn instructions_sysvar_data(instructions: &[(Pubkey, Vec<u8>)], current: u16) -> Vec<u8> {
+        let mut data = (instructions.len() as u16).to_le_bytes().to_vec();
+        data.resize(2 + 2 * instructions.len(), 0);
+        for (i, (program_id, instruction_data)) in instructions.iter().enumerate() {
+            let offset = data.len() as u16;
+            data[2 + 2*i..4 + 2*i].copy_from_slice(&offset.to_le_bytes());
+            data.extend_from_slice(&0u16.to_le_bytes());
+            data.extend_from_slice(program_id.as_ref());
+            data.extend_from_slice(&(instruction_data.len() as u16).to_le_bytes());
+            data.extend_from_slice(instruction_data);
+        }
+        data.extend_from_slice(&current.to_le_bytes());
+        data
+    }
+
+    fn check_with_sysvar(instructions: &[(Pubkey, Vec<u8>)], current: u16) -> ProgramResult {
+        let key = sysvar::instructions::id();
+        let owner = sysvar::id();
+        let mut lamports = 0;
+        let mut data = instructions_sysvar_data(instructions, current);
+        let sysvar_info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        check_secp256k1_instruction(&sysvar_info, 100, 1)
+    }
+
+    #[test]
+    fn test_check_secp256k1_instruction() {
+        let program_id = Pubkey::new_unique();
+        let secp = (secp256k1_program::id(), make_secp256k1_instruction(1, 100, 1));
+        let call = (program_id, vec![5]);
+
+        assert_eq!(check_with_sysvar(&[secp.clone(), call.clone()], 1), Ok(()));
+        // Nothing precedes the first instruction
+        assert_eq!(check_with_sysvar(&[call.clone()], 0), Err(ProgramError::MissingRequiredSignature));
+        // Offsets must point to the current instruction data
+        let other_offsets = (secp256k1_program::id(), make_secp256k1_instruction(1, 100, 2));
+        assert_eq!(check_with_sysvar(&[other_offsets, call.clone()], 1), Err(ProgramError::InvalidInstructionData));
+        let other_index = (secp256k1_program::id(), make_secp256k1_instruction(0, 100, 1));
+        assert_eq!(check_with_sysvar(&[other_index, call.clone()], 1), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(check_with_sysvar(&[(program_id, secp.1), call], 1), Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn test_check_chain_id() {
+        let unsigned_trx = hex::decode("ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080").unwrap();
+        let tx: UnsignedTransaction = rlp::decode(&unsigned_trx).unwrap();
+        assert_eq!(tx.chain_id, Some(U256::one()));
+        assert_eq!(check_chain_id(&tx, U256::one(), false), Ok(()));
+        assert_eq!(check_chain_id(&tx, U256::from(111), true), Err(ProgramError::InvalidInstructionData));
+
+        let legacy_trx = hex::decode("e9098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080").unwrap();
+        let legacy: UnsignedTransaction = rlp::decode(&legacy_trx).unwrap();
+        assert_eq!(legacy.chain_id, None);
+        assert_eq!(check_chain_id(&legacy, U256::one(), false), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(check_chain_id(&legacy, U256::one(), true), Ok(()));
+    }
+}
@@ -1,5 +1,5 @@
 use primitive_types::{H160, H256, U256};
-use solana_program::pubkey::Pubkey;
+use solana_program::pubkey::{Pubkey, PubkeyError};
 use solana_program::keccak::{hash, hashv};
 
 pub fn keccak256_h256(data: &[u8]) -> H256 {
@@ -23,3 +23,50 @@ pub fn u256_to_h256(value: U256) -> H256 {
 pub fn solidity_address(key: &Pubkey) -> H160 {
     H256::from_slice(key.as_ref()).into()
 }
+
+/// Address and bump seed of the Ether account: `program_address([ether])`
+pub fn ether_to_program_address(program_id: &Pubkey, ether: &H160) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ether.as_bytes()], program_id)
+}
+
+/// Seed used for accounts derived from the Ether address
+pub fn ether_to_seed(ether: &H160) -> String {
+    bs58::encode(ether.as_bytes()).into_string()
+}
+
+/// Address and seed of the account created by `base` for the Ether address: `create_with_seed(base, bs58(ether), program_id)`
+pub fn ether_to_seeded_address(base: &Pubkey, ether: &H160, program_id: &Pubkey) -> Result<(Pubkey, String), PubkeyError> {
+    let seed = ether_to_seed(ether);
+    let address = Pubkey::create_with_seed(base, &seed, program_id)?;
+    Ok((address, seed))
+}
+
+/// Address and bump seed of the registry record for the Ether address: `program_address(["alias", ether])`
+pub fn ether_to_alias_address(program_id: &Pubkey, ether: &H160) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"alias", ether.as_bytes()], program_id)
+}
+
+/// Address and bump seed of the registry record for the Solana account: `program_address(["alias", key])`
+pub fn solana_to_alias_address(program_id: &Pubkey, key: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"alias", key.as_ref()], program_id)
+}
+
+/// Address and bump seed of the gas price oracle account: `program_address(["gas_price"])`
+pub fn gas_price_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"gas_price"], program_id)
+}
+
+/// Address and bump seed of the program configuration account: `program_address(["config"])`
+pub fn config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config"], program_id)
+}
+
+/// Address and bump seed of the operator record: `program_address(["operator", key])`
+pub fn operator_address(program_id: &Pubkey, key: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"operator", key.as_ref()], program_id)
+}
+
+/// Address and bump seed of the receipt of the Ethereum transaction: `program_address(["receipt", tx_hash])`
+pub fn receipt_address(program_id: &Pubkey, tx_hash: &H256) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"receipt", tx_hash.as_bytes()], program_id)
+}
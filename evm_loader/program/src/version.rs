@@ -0,0 +1,130 @@
+//! Version and features of the program build
+//!
+//! `GetVersion` passes back `ProgramVersion` of the deployed program, so clients built from
+//! other sources check the features they rely on instead of assuming a matching build.
+
+use std::convert::TryInto;
+
+/// Features of the build, bits of `ProgramVersion::features`
+pub mod feature {
+    /// Gas is charged in lamports by the gas price oracle
+    pub const GAS_METERING: u64 = 1 << 0;
+    /// Transactions are executed in steps by `ExecuteTrxFromAccountDataIterative` and `Continue`
+    pub const ITERATIVE_EXECUTION: u64 = 1 << 1;
+    /// Result of the transaction is passed with return data instead of `OnReturn` (`return-data` feature)
+    pub const RETURN_DATA: u64 = 1 << 2;
+    /// Reserved, transactions signed without chain id are accepted by `allow_legacy_tx` of the config account
+    pub const LEGACY_TX: u64 = 1 << 3;
+    /// Call-type instructions are restricted to registered operators (`operator-allow-list` feature)
+    pub const OPERATOR_ALLOW_LIST: u64 = 1 << 4;
+    /// Calls signed by the user are submitted by an operator with `CallFromMetaTransaction`
+    pub const META_TRANSACTIONS: u64 = 1 << 5;
+    /// Code is uploaded to holder accounts in parallel and appended with `WriteFromHolder`
+    pub const HOLDER_UPLOAD: u64 = 1 << 6;
+}
+
+/// Precompiled contracts, bits of `ProgramVersion::precompiles`
+pub mod precompile {
+    /// `ecrecover` at `0x01`
+    pub const ECRECOVER: u64 = 1 << 0;
+    /// Invocation of Solana programs at `0xff00...00`
+    pub const SOLANA: u64 = 1 << 1;
+    /// Batch of calls at `multicall::address()`
+    pub const MULTICALL: u64 = 1 << 2;
+}
+
+/// Version of the program, the result of `GetVersion`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    /// Bits of `feature`
+    pub features: u64,
+    /// Bits of `precompile`
+    pub precompiles: u64,
+}
+
+impl ProgramVersion {
+    /// Size of packed version
+    pub const LEN: usize = 2 + 2 + 2 + 8 + 8;
+
+    /// Version and features of this build
+    #[must_use]
+    pub fn current() -> Self {
+        let mut features = feature::GAS_METERING | feature::ITERATIVE_EXECUTION | feature::META_TRANSACTIONS | feature::HOLDER_UPLOAD;
+        if cfg!(feature = "return-data") {
+            features |= feature::RETURN_DATA;
+        }
+        if cfg!(feature = "operator-allow-list") {
+            features |= feature::OPERATOR_ALLOW_LIST;
+        }
+
+        Self {
+            major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+            minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+            patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0),
+            features,
+            precompiles: precompile::ECRECOVER | precompile::SOLANA | precompile::MULTICALL,
+        }
+    }
+
+    /// All bits of `features` are enabled
+    #[must_use]
+    pub fn has_features(&self, features: u64) -> bool {
+        self.features & features == features
+    }
+
+    /// Pack version into result of the instruction
+    #[must_use]
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::LEN);
+        data.extend_from_slice(&self.major.to_le_bytes());
+        data.extend_from_slice(&self.minor.to_le_bytes());
+        data.extend_from_slice(&self.patch.to_le_bytes());
+        data.extend_from_slice(&self.features.to_le_bytes());
+        data.extend_from_slice(&self.precompiles.to_le_bytes());
+        data
+    }
+
+    /// Unpack version from result of the instruction
+    #[must_use]
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::LEN {
+            return None;
+        }
+        let u16_at = |offset: usize| data[offset..offset + 2].try_into().ok().map(u16::from_le_bytes);
+        let u64_at = |offset: usize| data[offset..offset + 8].try_into().ok().map(u64::from_le_bytes);
+        Some(Self {
+            major: u16_at(0)?,
+            minor: u16_at(2)?,
+            patch: u16_at(4)?,
+            features: u64_at(6)?,
+            precompiles: u64_at(14)?,
+        })
+    }
+}
+
+impl std::fmt::Display for ProgramVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_version_roundtrip() {
+        let version = ProgramVersion::current();
+        assert_eq!(version.to_string(), env!("CARGO_PKG_VERSION"));
+        assert!(version.has_features(feature::GAS_METERING | feature::ITERATIVE_EXECUTION));
+        assert_eq!(version.has_features(feature::RETURN_DATA), cfg!(feature = "return-data"));
+
+        let data = version.pack();
+        assert_eq!(data.len(), ProgramVersion::LEN);
+        assert_eq!(ProgramVersion::unpack(&data), Some(version));
+        assert_eq!(ProgramVersion::unpack(&data[1..]), None);
+    }
+}
@@ -0,0 +1,146 @@
+//! End-to-end tests of the BPF program: deploy a contract with CreateAccount/Write/Finalize
+//! and call it, so OnReturn and OnEvent are invoked through the entrypoint.
+//!
+//! Build the program with `cargo build-bpf` and run with `cargo test-bpf`.
+
+#![cfg(feature = "test-bpf")]
+
+use evm_loader::{
+    account_data::AccountData,
+    hamt::Hamt,
+    utils::{keccak256_h256, keccak256_h256_v, ether_to_program_address, ether_to_seeded_address},
+};
+use primitive_types::{H160, U256};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{clock, rent},
+};
+use solana_program_test::{BanksClient, ProgramTest};
+use solana_sdk::{signature::{Keypair, Signer}, transaction::Transaction, hash::Hash};
+
+/// Runtime code: store 42 at slot 0, emit LOG1(topic 1, data 42) and return 42
+const RUNTIME_CODE: &[u8] = &[
+    0x60, 0x2a, 0x60, 0x00, 0x55,
+    0x60, 0x2a, 0x60, 0x00, 0x52,
+    0x60, 0x01, 0x60, 0x20, 0x60, 0x00, 0xa1,
+    0x60, 0x20, 0x60, 0x00, 0xf3,
+];
+
+/// Init code copying `RUNTIME_CODE` placed right after it into memory and returning it
+fn contract_code() -> Vec<u8> {
+    let mut code = vec![0x60, RUNTIME_CODE.len() as u8, 0x80, 0x60, 0x0b, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3];
+    code.extend_from_slice(RUNTIME_CODE);
+    code
+}
+
+struct Deployed {
+    ether: H160,
+    account: Pubkey,
+    code_account: Pubkey,
+}
+
+async fn process(banks_client: &mut BanksClient, payer: &Keypair, recent_blockhash: Hash, instructions: &[Instruction]) {
+    let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+    transaction.sign(&[payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+/// Deploy the contract the same way as `deploy` command of the CLI
+async fn deploy(program_id: &Pubkey, banks_client: &mut BanksClient, payer: &Keypair, recent_blockhash: Hash) -> Deployed {
+    let code = contract_code();
+    let creator: H160 = keccak256_h256(&payer.pubkey().to_bytes()).into();
+    let ether: H160 = keccak256_h256_v(&[&[0xff], creator.as_bytes(), &[0u8; 32], keccak256_h256(&code).as_bytes()]).into();
+    let (account, nonce) = ether_to_program_address(program_id, &ether);
+    let (code_account, seed) = ether_to_seeded_address(&payer.pubkey(), &ether, program_id).unwrap();
+
+    let rent = banks_client.get_rent().await.unwrap();
+    let code_space = 1 + evm_loader::account_data::Contract::SIZE + code.len() + 2 * 1024;
+
+    let mut create = vec![2u8, 0, 0, 0];
+    create.extend_from_slice(&rent.minimum_balance(1 + evm_loader::account_data::Account::SIZE).to_le_bytes());
+    create.extend_from_slice(&0u64.to_le_bytes());
+    create.extend_from_slice(ether.as_bytes());
+    create.push(nonce);
+    process(banks_client, payer, recent_blockhash, &[
+        system_instruction::create_account_with_seed(&payer.pubkey(), &code_account, &payer.pubkey(), &seed,
+            rent.minimum_balance(code_space), code_space as u64, program_id),
+        Instruction {
+            program_id: *program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(account, false),
+                AccountMeta::new(code_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: create,
+        },
+    ]).await;
+
+    let mut content = (code.len() as u64).to_le_bytes().to_vec();
+    content.extend_from_slice(&code);
+    let mut write = vec![0u8, 0, 0, 0];
+    write.extend_from_slice(&0u32.to_le_bytes());
+    write.extend_from_slice(&(content.len() as u64).to_le_bytes());
+    write.extend_from_slice(&content);
+    process(banks_client, payer, recent_blockhash, &[Instruction {
+        program_id: *program_id,
+        accounts: vec![AccountMeta::new(code_account, false), AccountMeta::new(payer.pubkey(), true)],
+        data: write,
+    }]).await;
+
+    process(banks_client, payer, recent_blockhash, &[Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(account, false),
+            AccountMeta::new(code_account, false),
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new_readonly(clock::id(), false),
+            AccountMeta::new_readonly(rent::id(), false),
+            AccountMeta::new_readonly(*program_id, false),
+        ],
+        data: vec![1u8, 0, 0, 0],
+    }]).await;
+
+    Deployed { ether, account, code_account }
+}
+
+#[tokio::test]
+async fn test_deploy_and_call() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, recent_blockhash) = ProgramTest::new("evm_loader", program_id, None).start().await;
+
+    let contract = deploy(&program_id, &mut banks_client, &payer, recent_blockhash).await;
+
+    let account = banks_client.get_account(contract.account).await.unwrap().unwrap();
+    let account_data = AccountData::unpack(&account.data).unwrap();
+    assert_eq!(account_data.get_account().unwrap().ether, contract.ether);
+    assert_eq!(account_data.get_account().unwrap().code_account, contract.code_account);
+
+    let code_account = banks_client.get_account(contract.code_account).await.unwrap().unwrap();
+    let code_data = AccountData::unpack(&code_account.data).unwrap();
+    let header = code_data.size();
+    let code_size = code_data.get_contract().unwrap().code_size as usize;
+    assert_eq!(&code_account.data[header..header + code_size], RUNTIME_CODE);
+
+    // Call emits the event and returns the value through OnEvent and OnReturn invoked by the program itself
+    process(&mut banks_client, &payer, recent_blockhash, &[Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(contract.account, false),
+            AccountMeta::new(contract.code_account, false),
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new_readonly(clock::id(), false),
+            AccountMeta::new_readonly(program_id, false),
+        ],
+        data: vec![3u8],
+    }]).await;
+
+    let mut code_account = banks_client.get_account(contract.code_account).await.unwrap().unwrap();
+    let code_data = AccountData::unpack(&code_account.data).unwrap();
+    let storage_root = code_data.get_contract().unwrap().storage_root;
+    let storage = Hamt::new(&mut code_account.data[header + code_size..], false).unwrap();
+    assert_eq!(storage.find(U256::zero()), Some(U256::from(42)));
+    assert_eq!(storage.root_hash(), storage_root);
+}
@@ -121,26 +121,22 @@ class EvmLoaderTests2(unittest.TestCase):
         mint = Token(http_client, mintId, tokenId, self.acc)
         print("Mint: {} -> 0x{}".format(mintId, bytes(mintId).hex()))
 
-        erc20Id = self.loader.deployChecked("erc20wrapper.bin")["programId"]
-        print("ERC20Wrapper program:", erc20Id)
         seed = "btc3"
         seedData = bytes(seed, 'utf8')
-        
-        # Call setToken for erc20
-        data = (bytes.fromhex("036131bdab") + # setToken(uint256,string)
-                bytes(mintId) +
-                bytes.fromhex("%064x"%0x40) +
-                bytes.fromhex("%064x"%len(seedData)) +
-                seedData + bytes(32-len(seedData))
-               )
-        print('setToken arguments:', data.hex())
-        result = self.loader.call(
-                contract=erc20Id,
-                caller=PublicKey(self.caller),
-                signer=self.acc,
-                data=data,
-                accs=None)
-        print('setToken result:', result.hex())
+
+        # Append constructor(uint256,string) arguments to the wrapper bytecode
+        with open("erc20wrapper.bin", mode='rb') as file:
+            initCode = (file.read() +
+                    bytes(mintId) +
+                    bytes.fromhex("%064x"%0x40) +
+                    bytes.fromhex("%064x"%len(seedData)) +
+                    seedData + bytes(32-len(seedData))
+                   )
+        with open("erc20wrapper_init.bin", mode='wb') as file:
+            file.write(initCode)
+
+        erc20Id = self.loader.deployChecked("erc20wrapper_init.bin")["programId"]
+        print("ERC20Wrapper program:", erc20Id)
 
 
         balanceAccount = self.loader.accountWithSeed(PublicKey(self.caller), seed, tokenId)
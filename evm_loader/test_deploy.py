@@ -155,7 +155,7 @@ class DeployTest(unittest.TestCase):
         # Execute deploy transaction
         trx = Transaction()
 
-        trx.add(createAccountWithSeed(base, base, seed, 10**9, 1+32+4+len(msg)+2048, PublicKey(evm_loader_id)))
+        trx.add(createAccountWithSeed(base, base, seed, 10**9, 1+32+4+32+20+1+1+len(msg)+2048, PublicKey(evm_loader_id)))
 
         trx.add(TransactionInstruction(program_id=evm_loader_id,
             #data=create_account_layout(10**9, len(msg)+2048, contract_eth, contract_nonce),
@@ -194,7 +194,7 @@ class EventTest(unittest.TestCase):
                 data = b58decode(result['meta']['innerInstructions'][0]['instructions'][0]['data'])
                 self.assertEqual(data[:1], b'\x06') # 6 means OnReturn
                 self.assertLess(data[1], 0xd0)  # less 0xd0 - success
-                self.assertEqual(data[2:], bytes().fromhex("%064x" % 0x3))
+                self.assertEqual(data[2:-16], bytes().fromhex("%064x" % 0x3))  # followed by 16 bytes of telemetry
 
     def test_addReturnEvent(self):
         func_name = abi.function_signature_to_4byte_selector('addReturnEvent(uint8,uint8)')
@@ -210,9 +210,9 @@ class EventTest(unittest.TestCase):
                 data = b58decode(result['meta']['innerInstructions'][0]['instructions'][0]['data'])
                 self.assertEqual(data[:1], b'\x07')  # 7 means OnEvent
                 self.assertEqual(data[1:21], self.reId_eth)
-                self.assertEqual(data[21:29], bytes().fromhex('%016x' % 1)[::-1])  # topics len
-                self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
-                self.assertEqual(data[61:93], bytes().fromhex("%064x" % 0x3))  # sum
+                self.assertEqual(data[59:67], bytes().fromhex('%016x' % 1)[::-1])  # topics len
+                self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
+                self.assertEqual(data[99:131], bytes().fromhex("%064x" % 0x3))  # sum
                 data = b58decode(result['meta']['innerInstructions'][0]['instructions'][1]['data'])
                 self.assertEqual(data[:1], b'\x06')   # 6 means OnReturn
                 self.assertLess(data[1], 0xd0)  # less 0xd0 - success
@@ -232,15 +232,15 @@ class EventTest(unittest.TestCase):
                 data = b58decode(result['meta']['innerInstructions'][0]['instructions'][0]['data'])
                 # self.assertEqual(data[:1], b'\x07')
                 self.assertEqual(data[1:21], self.reId_eth)
-                self.assertEqual(data[21:29], bytes().fromhex('%016x' % 1)[::-1])  # topics len
-                self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
-                self.assertEqual(data[61:93], bytes().fromhex("%064x" % 0x3))  # sum
+                self.assertEqual(data[59:67], bytes().fromhex('%016x' % 1)[::-1])  # topics len
+                self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
+                self.assertEqual(data[99:131], bytes().fromhex("%064x" % 0x3))  # sum
                 data = b58decode(result['meta']['innerInstructions'][0]['instructions'][1]['data'])
                 self.assertEqual(data[:1], b'\x07')  # 7 means OnEvent
                 self.assertEqual(data[1:21], self.reId_eth)
-                self.assertEqual(data[21:29], bytes().fromhex('%016x' % 1)[::-1])  # topics len
-                self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
-                self.assertEqual(data[61:93], bytes().fromhex("%064x" % 0x5))  # sum
+                self.assertEqual(data[59:67], bytes().fromhex('%016x' % 1)[::-1])  # topics len
+                self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
+                self.assertEqual(data[99:131], bytes().fromhex("%064x" % 0x5))  # sum
                 data = b58decode(result['meta']['innerInstructions'][0]['instructions'][2]['data'])
                 self.assertEqual(data[:1], b'\x06')   # 6 means OnReturn
                 self.assertLess(data[1], 0xd0)  # less 0xd0 - success
@@ -277,15 +277,15 @@ class EventTest(unittest.TestCase):
         data = b58decode(result['meta']['innerInstructions'][0]['instructions'][0]['data'])
         self.assertEqual(data[:1], b'\x07')  # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_eth)
-        self.assertEqual(data[21:29], bytes().fromhex('%016x' % 1)[::-1])  # topics len
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
-        self.assertEqual(data[61:93], bytes().fromhex("%064x" % 0x3))  # sum
+        self.assertEqual(data[59:67], bytes().fromhex('%016x' % 1)[::-1])  # topics len
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
+        self.assertEqual(data[99:131], bytes().fromhex("%064x" % 0x3))  # sum
         data = b58decode(result['meta']['innerInstructions'][0]['instructions'][1]['data'])
         self.assertEqual(data[:1], b'\x07')  # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_eth)
-        self.assertEqual(data[21:29], bytes().fromhex('%016x' % 1)[::-1])  # topics len
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
-        self.assertEqual(data[61:93], bytes().fromhex("%064x" % 0x5))  # sum
+        self.assertEqual(data[59:67], bytes().fromhex('%016x' % 1)[::-1])  # topics len
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
+        self.assertEqual(data[99:131], bytes().fromhex("%064x" % 0x5))  # sum
         data = b58decode(result['meta']['innerInstructions'][0]['instructions'][2]['data'])
         self.assertEqual(data[:1], b'\x06')   # 6 means OnReturn
         self.assertLess(data[1], 0xd0)  # less 0xd0 - success
@@ -296,15 +296,15 @@ class EventTest(unittest.TestCase):
         data = b58decode(result['meta']['innerInstructions'][1]['instructions'][0]['data'])
         self.assertEqual(data[:1], b'\x07')  # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_eth)
-        self.assertEqual(data[21:29], bytes().fromhex('%016x' % 1)[::-1])  # topics len
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
-        self.assertEqual(data[61:93], bytes().fromhex("%064x" % 0x7))  # sum
+        self.assertEqual(data[59:67], bytes().fromhex('%016x' % 1)[::-1])  # topics len
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
+        self.assertEqual(data[99:131], bytes().fromhex("%064x" % 0x7))  # sum
         data = b58decode(result['meta']['innerInstructions'][1]['instructions'][1]['data'])
         self.assertEqual(data[:1], b'\x07')  # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_eth)
-        self.assertEqual(data[21:29], bytes().fromhex('%016x' % 1)[::-1])  # topics len
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
-        self.assertEqual(data[61:93], bytes().fromhex("%064x" % 0xb))  # sum
+        self.assertEqual(data[59:67], bytes().fromhex('%016x' % 1)[::-1])  # topics len
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Added(uint8)'))  #topics
+        self.assertEqual(data[99:131], bytes().fromhex("%064x" % 0xb))  # sum
         data = b58decode(result['meta']['innerInstructions'][1]['instructions'][2]['data'])
         self.assertEqual(data[:1], b'\x06')   # 6 means OnReturn
         self.assertLess(data[1], 0xd0)  # less 0xd0 - success
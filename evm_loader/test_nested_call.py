@@ -168,27 +168,27 @@ class EventTest(unittest.TestCase):
         data = b58decode(result['meta']['innerInstructions'][0]['instructions'][0]['data'])
         self.assertEqual(data[:1], b'\x07') # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_reciever_eth)
-        count_topics = int().from_bytes(data[21:29], 'little')
+        count_topics = int().from_bytes(data[59:67], 'little')
         self.assertEqual(count_topics, 1)
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Foo(address,uint256,string)'))
-        self.assertEqual(data[61:93], bytes.fromhex("%024x" %0x0 + self.reId_caller_eth.hex()))
-        self.assertEqual(data[93:125], bytes.fromhex("%064x" %0x0))
-        self.assertEqual(data[125:157], bytes.fromhex("%062x" %0x0 + "60"))
-        self.assertEqual(data[157:189], bytes.fromhex("%062x" %0x0 + "08"))
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Foo(address,uint256,string)'))
+        self.assertEqual(data[99:131], bytes.fromhex("%024x" %0x0 + self.reId_caller_eth.hex()))
+        self.assertEqual(data[131:163], bytes.fromhex("%064x" %0x0))
+        self.assertEqual(data[163:195], bytes.fromhex("%062x" %0x0 + "60"))
+        self.assertEqual(data[195:227], bytes.fromhex("%062x" %0x0 + "08"))
         s = "call foo".encode("utf-8")
-        self.assertEqual(data[189:221], bytes.fromhex('{:0<64}'.format(s.hex())))
+        self.assertEqual(data[227:259], bytes.fromhex('{:0<64}'.format(s.hex())))
 
         # emit Result(success, data);
         data = b58decode(result['meta']['innerInstructions'][0]['instructions'][1]['data'])
         self.assertEqual(data[:1], b'\x07') # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_caller_eth)
-        count_topics = int().from_bytes(data[21:29], 'little')
+        count_topics = int().from_bytes(data[59:67], 'little')
         self.assertEqual(count_topics, 1)
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Result(bool,bytes)'))
-        self.assertEqual(data[61:93], bytes.fromhex("%062x" %0x0 + "01"))
-        self.assertEqual(data[93:125], bytes.fromhex("%062x" %0x0 + "40"))
-        self.assertEqual(data[125:157], bytes.fromhex("%062x" %0x0 + "20"))
-        self.assertEqual(data[157:189], bytes.fromhex("%062x" %0x0 + hex(124)[2:]))
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Result(bool,bytes)'))
+        self.assertEqual(data[99:131], bytes.fromhex("%062x" %0x0 + "01"))
+        self.assertEqual(data[131:163], bytes.fromhex("%062x" %0x0 + "40"))
+        self.assertEqual(data[163:195], bytes.fromhex("%062x" %0x0 + "20"))
+        self.assertEqual(data[195:227], bytes.fromhex("%062x" %0x0 + hex(124)[2:]))
 
     def test_ecrecover(self):
         tx = {'to': solana2ether(self.reId_caller), 'value': 1, 'gas': 1, 'gasPrice': 1,
@@ -218,34 +218,34 @@ class EventTest(unittest.TestCase):
         data = b58decode(result['meta']['innerInstructions'][0]['instructions'][0]['data'])
         self.assertEqual(data[:1], b'\x07') # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_recover_eth)
-        count_topics = int().from_bytes(data[21:29], 'little')
+        count_topics = int().from_bytes(data[59:67], 'little')
         self.assertEqual(count_topics, 1)
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Recovered(address)'))
-        self.assertEqual(data[61:93], bytes.fromhex("%024x" %0x0 + self.caller_ether.hex()))
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Recovered(address)'))
+        self.assertEqual(data[99:131], bytes.fromhex("%024x" %0x0 + self.caller_ether.hex()))
 
         # emit Response_recovery_signer(success, data));
         data = b58decode(result['meta']['innerInstructions'][0]['instructions'][1]['data'])
         self.assertEqual(data[:1], b'\x07') # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_reciever_eth)
-        count_topics = int().from_bytes(data[21:29], 'little')
+        count_topics = int().from_bytes(data[59:67], 'little')
         self.assertEqual(count_topics, 1)
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Response_recovery_signer(bool,bytes)'))
-        self.assertEqual(data[61:93], bytes.fromhex("%062x" %0x0 + "01"))
-        self.assertEqual(data[93:125], bytes.fromhex("%062x" %0x0 + "40"))
-        self.assertEqual(data[125:157], bytes.fromhex("%062x" %0x0 + "20"))
-        self.assertEqual(data[157:189], bytes.fromhex("%024x" %0x0 + self.caller_ether.hex()))
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Response_recovery_signer(bool,bytes)'))
+        self.assertEqual(data[99:131], bytes.fromhex("%062x" %0x0 + "01"))
+        self.assertEqual(data[131:163], bytes.fromhex("%062x" %0x0 + "40"))
+        self.assertEqual(data[163:195], bytes.fromhex("%062x" %0x0 + "20"))
+        self.assertEqual(data[195:227], bytes.fromhex("%024x" %0x0 + self.caller_ether.hex()))
 
         #  emit Result(success, data);
         data = b58decode(result['meta']['innerInstructions'][0]['instructions'][2]['data'])
         self.assertEqual(data[:1], b'\x07') # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_caller_eth)
-        count_topics = int().from_bytes(data[21:29], 'little')
+        count_topics = int().from_bytes(data[59:67], 'little')
         self.assertEqual(count_topics, 1)
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Result(bool,bytes)'))
-        self.assertEqual(data[61:93], bytes.fromhex("%062x" %0x0 + "01"))
-        self.assertEqual(data[93:125], bytes.fromhex("%062x" %0x0 + "40"))
-        self.assertEqual(data[125:157], bytes.fromhex("%062x" %0x0 + "20"))
-        self.assertEqual(data[157:189], bytes.fromhex("%062x" %0x0 + "01"))
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Result(bool,bytes)'))
+        self.assertEqual(data[99:131], bytes.fromhex("%062x" %0x0 + "01"))
+        self.assertEqual(data[131:163], bytes.fromhex("%062x" %0x0 + "40"))
+        self.assertEqual(data[163:195], bytes.fromhex("%062x" %0x0 + "20"))
+        self.assertEqual(data[195:227], bytes.fromhex("%062x" %0x0 + "01"))
 
 
     def test_create2_opcode(self):
@@ -279,24 +279,24 @@ class EventTest(unittest.TestCase):
         data = b58decode(result['meta']['innerInstructions'][0]['instructions'][0]['data'])
         self.assertEqual(data[:1], b'\x07') # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_create_receiver_eth)
-        count_topics = int().from_bytes(data[21:29], 'little')
+        count_topics = int().from_bytes(data[59:67], 'little')
         self.assertEqual(count_topics, 1)
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Foo(address,uint256,string)'))
-        self.assertEqual(data[61:93], bytes.fromhex("%024x" %0x0 + self.reId_create_caller_eth.hex()))
-        self.assertEqual(data[93:125], bytes.fromhex("%064x" %0x0))
-        self.assertEqual(data[125:157], bytes.fromhex("%062x" %0x0 + "60"))
-        self.assertEqual(data[157:189], bytes.fromhex("%062x" %0x0 + "08"))
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Foo(address,uint256,string)'))
+        self.assertEqual(data[99:131], bytes.fromhex("%024x" %0x0 + self.reId_create_caller_eth.hex()))
+        self.assertEqual(data[131:163], bytes.fromhex("%064x" %0x0))
+        self.assertEqual(data[163:195], bytes.fromhex("%062x" %0x0 + "60"))
+        self.assertEqual(data[195:227], bytes.fromhex("%062x" %0x0 + "08"))
         s = "call foo".encode("utf-8")
-        self.assertEqual(data[189:221], bytes.fromhex('{:0<64}'.format(s.hex())))
+        self.assertEqual(data[227:259], bytes.fromhex('{:0<64}'.format(s.hex())))
 
         # emit Result_foo(result)
         data = b58decode(result['meta']['innerInstructions'][0]['instructions'][1]['data'])
         self.assertEqual(data[:1], b'\x07') # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_create_caller_eth)
-        count_topics = int().from_bytes(data[21:29], 'little')
+        count_topics = int().from_bytes(data[59:67], 'little')
         self.assertEqual(count_topics, 1)
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Result_foo(uint256)'))
-        self.assertEqual(data[61:93], bytes.fromhex("%062x" %0x0 + hex(124)[2:]))
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Result_foo(uint256)'))
+        self.assertEqual(data[99:131], bytes.fromhex("%062x" %0x0 + hex(124)[2:]))
 
     def test_nested_revert(self):
         func_name = abi.function_signature_to_4byte_selector('callFoo(address)')
@@ -312,10 +312,10 @@ class EventTest(unittest.TestCase):
         data = b58decode(result['meta']['innerInstructions'][0]['instructions'][0]['data'])
         self.assertEqual(data[:1], b'\x07') # 7 means OnEvent
         self.assertEqual(data[1:21], self.reId_caller_eth)
-        count_topics = int().from_bytes(data[21:29], 'little')
+        count_topics = int().from_bytes(data[59:67], 'little')
         self.assertEqual(count_topics, 1)
-        self.assertEqual(data[29:61], abi.event_signature_to_log_topic('Result(bool,bytes)'))
-        self.assertEqual(data[61:93], bytes.fromhex("%062x" %0x0 + "00")) # result false
-        self.assertEqual(data[93:125], bytes.fromhex("%062x" %0x0 + "40"))
-        self.assertEqual(data[125:157], bytes.fromhex("%062x" %0x0 + "00"))
+        self.assertEqual(data[67:99], abi.event_signature_to_log_topic('Result(bool,bytes)'))
+        self.assertEqual(data[99:131], bytes.fromhex("%062x" %0x0 + "00")) # result false
+        self.assertEqual(data[131:163], bytes.fromhex("%062x" %0x0 + "40"))
+        self.assertEqual(data[163:195], bytes.fromhex("%062x" %0x0 + "00"))
 
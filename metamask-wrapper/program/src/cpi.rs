@@ -0,0 +1,168 @@
+//! Helpers for other programs to route transfers through the wrapper.
+//!
+//! Each helper builds the wrapper instruction from account infos and invokes it
+//! with signers_seeds of the calling program, so program derived addresses can
+//! sign as multisig signers.
+
+use crate::instruction;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+};
+
+/// Accounts of the wrapper transfer instructions.
+/// `remaining_accounts` are Token-2022 mint followed by extra accounts of the transfer hook.
+#[derive(Clone)]
+pub struct TransferAccounts<'a> {
+    pub wrapper_program: AccountInfo<'a>,
+    pub token_program: AccountInfo<'a>,
+    pub source: AccountInfo<'a>,
+    pub destination: AccountInfo<'a>,
+    pub authority: AccountInfo<'a>,
+    pub mint_info: AccountInfo<'a>,
+    pub config: AccountInfo<'a>,
+    pub remaining_accounts: Vec<AccountInfo<'a>>,
+}
+
+impl<'a> TransferAccounts<'a> {
+    fn account_infos(&self, extra: &[AccountInfo<'a>]) -> Vec<AccountInfo<'a>> {
+        let mut account_infos = vec![
+            self.token_program.clone(),
+            self.source.clone(),
+            self.destination.clone(),
+            self.authority.clone(),
+            self.mint_info.clone(),
+            self.config.clone(),
+        ];
+        account_infos.extend(extra.iter().cloned());
+        account_infos.extend(self.remaining_accounts.iter().cloned());
+        account_infos.push(self.wrapper_program.clone());
+        account_infos
+    }
+
+    /// Remaining accounts followed by the wrapper program, which is invoked to emit events
+    fn append_remaining(&self, ix: &mut Instruction) {
+        ix.accounts.extend(self.remaining_accounts.iter().map(|account| AccountMeta {
+            pubkey: *account.key,
+            is_signer: account.is_signer,
+            is_writable: account.is_writable,
+        }));
+        ix.accounts.push(AccountMeta::new_readonly(*self.wrapper_program.key, false));
+    }
+}
+
+/// Invoke `Transfer` from Ethereum account balance
+pub fn transfer<'a>(
+    accounts: &TransferAccounts<'a>,
+    amount: u64,
+    nonce: u8,
+    eth_token: [u8; 20],
+    eth_acc: [u8; 20],
+    signers_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut ix = instruction::transfer(
+        accounts.wrapper_program.key,
+        accounts.token_program.key,
+        accounts.source.key,
+        accounts.destination.key,
+        accounts.authority.key,
+        accounts.mint_info.key,
+        accounts.config.key,
+        amount,
+        nonce,
+        eth_token,
+        eth_acc,
+    )?;
+    accounts.append_remaining(&mut ix);
+    invoke_signed(&ix, &accounts.account_infos(&[]), signers_seeds)
+}
+
+/// Invoke `TransferFrom` approved to the spender, `accounts.authority` is authority of spender
+pub fn transfer_from<'a>(
+    accounts: &TransferAccounts<'a>,
+    amount: u64,
+    nonce: u8,
+    eth_token: [u8; 20],
+    spender: [u8; 20],
+    signers_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut ix = instruction::transfer_from(
+        accounts.wrapper_program.key,
+        accounts.token_program.key,
+        accounts.source.key,
+        accounts.destination.key,
+        accounts.authority.key,
+        accounts.mint_info.key,
+        accounts.config.key,
+        amount,
+        nonce,
+        eth_token,
+        spender,
+    )?;
+    accounts.append_remaining(&mut ix);
+    invoke_signed(&ix, &accounts.account_infos(&[]), signers_seeds)
+}
+
+/// Invoke `TransferWithMemo` with reference keys
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_with_memo<'a>(
+    accounts: &TransferAccounts<'a>,
+    memo_program: &AccountInfo<'a>,
+    references: &[AccountInfo<'a>],
+    amount: u64,
+    nonce: u8,
+    eth_token: [u8; 20],
+    eth_acc: [u8; 20],
+    memo: &str,
+    signers_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let reference_keys: Vec<_> = references.iter().map(|reference| *reference.key).collect();
+    let mut ix = instruction::transfer_with_memo(
+        accounts.wrapper_program.key,
+        accounts.token_program.key,
+        accounts.source.key,
+        accounts.destination.key,
+        accounts.authority.key,
+        accounts.mint_info.key,
+        accounts.config.key,
+        &reference_keys,
+        amount,
+        nonce,
+        eth_token,
+        eth_acc,
+        memo,
+    )?;
+    accounts.append_remaining(&mut ix);
+
+    let mut extra = vec![memo_program.clone()];
+    extra.extend(references.iter().cloned());
+    invoke_signed(&ix, &accounts.account_infos(&extra), signers_seeds)
+}
+
+/// Invoke `TransferMultisig`, `accounts.authority` is multisig owner of source account.
+/// Signers which are program derived addresses of the caller sign with signers_seeds.
+pub fn transfer_multisig<'a>(
+    accounts: &TransferAccounts<'a>,
+    multisig_signers: &[AccountInfo<'a>],
+    amount: u64,
+    eth_token: [u8; 20],
+    signers_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let signer_pubkeys: Vec<_> = multisig_signers.iter().map(|signer| signer.key).collect();
+    let mut ix = instruction::transfer_multisig(
+        accounts.wrapper_program.key,
+        accounts.token_program.key,
+        accounts.source.key,
+        accounts.destination.key,
+        accounts.authority.key,
+        accounts.mint_info.key,
+        accounts.config.key,
+        &signer_pubkeys,
+        amount,
+        eth_token,
+    )?;
+    accounts.append_remaining(&mut ix);
+    invoke_signed(&ix, &accounts.account_infos(multisig_signers), signers_seeds)
+}
@@ -26,6 +26,38 @@ pub enum MetamaskError {
     /// Invalid instruction number passed in.
     #[error("Invalid instruction")]
     InvalidInstruction,
+
+    /// Permit message doesn't match instruction or owner nonce.
+    #[error("Invalid permit")]
+    InvalidPermit,
+
+    /// Permit deadline has passed.
+    #[error("Permit expired")]
+    PermitExpired,
+
+    /// Mint of the token account is not registered or doesn't match registry info.
+    #[error("Unknown mint")]
+    UnknownMint,
+
+    /// Spender is not delegate of the account or delegated amount is less than transferred.
+    #[error("Insufficient allowance")]
+    InsufficientAllowance,
+
+    /// Authority is not derived from Ethereum token and account or doesn't own token account.
+    #[error("Invalid authority")]
+    InvalidAuthority,
+
+    /// Registry info of the mint is already created.
+    #[error("Registry already exists")]
+    RegistryAlreadyExists,
+
+    /// Transfers are paused by admin.
+    #[error("Program is paused")]
+    Paused,
+
+    /// Tokens of registered Ethereum account balance can't be rescued.
+    #[error("Token account is registered balance")]
+    BalanceRegistered,
 }
 impl From<MetamaskError> for ProgramError {
     fn from(e: MetamaskError) -> Self {
@@ -0,0 +1,76 @@
+//! ERC20-style events of wrapper activity.
+//!
+//! Events are emitted as `OnEvent` instruction invoked by the wrapper itself (as `OnEvent` of evm_loader),
+//! so indexers can reconstruct Transfer and Approval events from inner instructions of the transaction.
+//! Topics after the event signature are Solana token accounts, which map to Ethereum accounts through `BalanceInfo`.
+
+use crate::instruction::MetamaskInstruction;
+use solana_program::{keccak::hash, pubkey::Pubkey};
+
+/// Signature of ERC20 Transfer event
+pub const TRANSFER_SIGNATURE: &[u8] = b"Transfer(address,address,uint256)";
+/// Signature of ERC20 Approval event
+pub const APPROVAL_SIGNATURE: &[u8] = b"Approval(address,address,uint256)";
+
+fn value_data(value: u64) -> Vec<u8> {
+    let mut data = vec![0_u8; 32];
+    data[24..].copy_from_slice(&value.to_be_bytes());
+    data
+}
+
+/// `Transfer(from, to, value)` event of the Ethereum token
+pub fn transfer(eth_token: &[u8;20], source: &Pubkey, destination: &Pubkey, amount: u64) -> MetamaskInstruction {
+    MetamaskInstruction::OnEvent {
+        address: *eth_token,
+        topics: vec![hash(TRANSFER_SIGNATURE).to_bytes(), source.to_bytes(), destination.to_bytes()],
+        data: value_data(amount),
+    }
+}
+
+/// `Approval(owner, spender, value)` event of the Ethereum token, zero value when delegate is revoked
+pub fn approval(eth_token: &[u8;20], source: &Pubkey, delegate: &Pubkey, amount: u64) -> MetamaskInstruction {
+    MetamaskInstruction::OnEvent {
+        address: *eth_token,
+        topics: vec![hash(APPROVAL_SIGNATURE).to_bytes(), source.to_bytes(), delegate.to_bytes()],
+        data: value_data(amount),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn test_transfer_event() {
+        let (source, destination) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let event = transfer(&[1; 20], &source, &destination, 0x0102);
+        let expected_data = {
+            let mut data = vec![0_u8; 30];
+            data.extend_from_slice(&[1, 2]);
+            data
+        };
+        assert_eq!(event, MetamaskInstruction::OnEvent {
+            address: [1; 20],
+            topics: vec![
+                // keccak256("Transfer(address,address,uint256)")
+                hex::decode("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef").unwrap()[..].try_into().unwrap(),
+                source.to_bytes(),
+                destination.to_bytes(),
+            ],
+            data: expected_data,
+        });
+    }
+
+    #[test]
+    fn test_approval_event() {
+        let (source, delegate) = (Pubkey::new_unique(), Pubkey::new_unique());
+        if let MetamaskInstruction::OnEvent { topics, .. } = approval(&[1; 20], &source, &delegate, 0) {
+            // keccak256("Approval(address,address,uint256)")
+            assert_eq!(hex::encode(topics[0]), "8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925");
+            assert_eq!(topics[1..], [source.to_bytes(), delegate.to_bytes()]);
+        } else {
+            panic!("OnEvent expected");
+        }
+    }
+}
@@ -2,18 +2,20 @@
 
 #![allow(clippy::too_many_arguments)]
 
-use crate::error::MetamaskError;
+use crate::{error::MetamaskError, permit::{self, PermitMessage}};
+use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     pubkey::Pubkey,
+    system_program, sysvar,
 };
-use std::convert::TryInto;
 use std::mem::size_of;
 
 /// Instructions supported by the MetamaskWrapper program.
+/// Instruction data is borsh serialized: variant index followed by fields.
 #[repr(C)]
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, PartialEq)]
 pub enum MetamaskInstruction {
     /// Initialize info about Ethereum account.
     ///
@@ -85,10 +87,14 @@ pub enum MetamaskInstruction {
     ///
     /// Accounts expected by this instruction:
     ///
-    ///   0. `[]` token program id
+    ///   0. `[]` token program id (SPL Token or Token-2022)
     ///   1. `[writable]` source account (must be owned by authority)
     ///   2. `[writable]` destination account
     ///   3. `[]` authority for source account (derived from create_program_address([eth_token, eth_acc], program_id))
+    ///   4. `[]` mint_info of source account mint (derived from create_program_address([mint, 'mint'], program_id))
+    ///   5. `[]` config (derived from create_program_address(['config'], program_id)), transfer fails when paused
+    ///   6. `[]` mint (Token-2022 only)
+    ///   7. ..7+N `[]` extra accounts of the transfer hook, including hook program (Token-2022 only)
     ///
     Transfer {
         /// The amount of tokens to transfer
@@ -108,6 +114,7 @@ pub enum MetamaskInstruction {
     ///   0. `[writable]` source account (derived from create_program_address([eth_acc, 'lamports'], program_id))
     ///   1. `[writable]` destination account
     ///   2. `[]` system_id
+    ///   3. `[]` config (derived from create_program_address(['config'], program_id)), transfer fails when paused
     TransferLamports {
         /// The amount of lamports to transfer
         amount: u64,
@@ -116,147 +123,412 @@ pub enum MetamaskInstruction {
         /// Ethereum source account address
         eth_acc: [u8;20],
     },
+
+    /// Initialize registry info about SPL mint.
+    ///
+    /// The `InitializeMint` instruction create new account and store
+    /// mint, its decimals and address of the EVM token wrapper. New account address
+    /// derived from create_program_address([mint, 'mint'], program_id).
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` mint_info (derived from create_program_address([mint, 'mint'], program_id))
+    ///   1. `[]` SPL token mint (SPL Token or Token-2022)
+    ///   2. `[]` system program id
+    ///   3. `[]` rent program id
+    ///   4. `[signer]` creator of new account
+    ///
+    InitializeMint {
+        /// Ethereum token address
+        eth_token: [u8;20],
+        /// Nonce derived from find_program_address([mint, 'mint'], program_id)
+        nonce: u8,
+    },
+
+    /// Approve Ethereum account as spender of the Ethereum account balance (ERC20 `approve`).
+    ///
+    /// The allowance is stored as SPL token delegate of the source account, delegate is
+    /// authority of the spender. New approval replaces previous one, zero amount revokes
+    /// the delegate.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` token program id
+    ///   1. `[writable]` source account (must be owned by authority)
+    ///   2. `[]` delegate: authority of spender (derived from create_program_address([eth_token, spender], program_id))
+    ///   3. `[]` authority for source account (derived from create_program_address([eth_token, eth_acc], program_id))
+    ///   4. `[]` mint_info of source account mint (derived from create_program_address([mint, 'mint'], program_id))
+    ///
+    Approve {
+        /// The amount of tokens spender is allowed to transfer
+        amount: u64,
+        /// Nonce derived from find_program_address([eth_token, eth_acc], program_id)
+        nonce: u8,
+        /// Ethereum token address
+        eth_token: [u8;20],
+        /// Ethereum owner account address
+        eth_acc: [u8;20],
+    },
+
+    /// Transfer token from account approved to the spender (ERC20 `transferFrom`).
+    ///
+    /// Transferred amount is deducted from the delegated amount of the source account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` token program id
+    ///   1. `[writable]` source account (delegated to authority)
+    ///   2. `[writable]` destination account
+    ///   3. `[]` authority of spender (derived from create_program_address([eth_token, eth_acc], program_id))
+    ///   4. `[]` mint_info of source account mint (derived from create_program_address([mint, 'mint'], program_id))
+    ///   5. `[]` config (derived from create_program_address(['config'], program_id)), transfer fails when paused
+    ///   6. `[]` mint (Token-2022 only)
+    ///   7. ..7+N `[]` extra accounts of the transfer hook, including hook program (Token-2022 only)
+    ///
+    TransferFrom {
+        /// The amount of tokens to transfer
+        amount: u64,
+        /// Nonce derived from find_program_address([eth_token, eth_acc], program_id)
+        nonce: u8,
+        /// Ethereum token address
+        eth_token: [u8;20],
+        /// Ethereum spender account address
+        eth_acc: [u8;20],
+    },
+
+    /// Approve spender by permit signed by owner Ethereum account (EIP-2612 `permit`).
+    ///
+    /// Preceding instruction must be secp256k1 program instruction which verifies
+    /// owner, signature and message stored in this instruction (see `PERMIT_SIGNED_DATA_OFFSET`).
+    /// Permit nonce is transaction count of owner account info and is incremented.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` token program id
+    ///   1. `[writable]` source account (must be owned by authority)
+    ///   2. `[]` delegate: authority of spender (derived from create_program_address([eth_token, spender, spender_nonce], program_id))
+    ///   3. `[]` authority for source account (derived from create_program_address([eth_token, owner], program_id))
+    ///   4. `[]` mint_info of source account mint (derived from create_program_address([mint, 'mint'], program_id))
+    ///   5. `[writable]` account_info of owner (derived from create_program_address([owner], program_id))
+    ///   6. `[]` sysvar instructions
+    ///   7. `[]` config (derived from create_program_address(['config'], program_id)), provides chain id of the domain
+    ///
+    Permit {
+        /// Nonce derived from find_program_address([eth_token, owner], program_id)
+        nonce: u8,
+        /// Nonce derived from find_program_address([eth_token, spender], program_id)
+        spender_nonce: u8,
+        /// Ethereum token address
+        eth_token: [u8;20],
+        /// Ethereum spender account address
+        spender: [u8;20],
+        /// The amount of tokens spender is allowed to transfer
+        value: u64,
+        /// Unix timestamp until permit is valid
+        deadline: u64,
+        /// Ethereum owner account address
+        owner: [u8;20],
+        /// Signature of the message with recovery id
+        signature: [u8;65],
+        /// Signed message
+        message: PermitMessage,
+    },
+
+    /// Initialize program config with admin and recovery address.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` config (derived from find_program_address(['config'], program_id))
+    ///   1. `[]` system program id
+    ///   2. `[]` rent program id
+    ///   3. `[signer]` creator of new account
+    ///
+    InitializeConfig {
+        /// Account allowed to pause program and rescue tokens
+        admin: Pubkey,
+        /// Owner of token accounts which receive rescued tokens
+        recovery: Pubkey,
+        /// Nonce derived from find_program_address(['config'], program_id)
+        nonce: u8,
+        /// Chain id of the permit domain, same as chain id of evm_loader
+        chain_id: u64,
+    },
+
+    /// Pause processing of transfers.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` config
+    ///   1. `[signer]` admin
+    ///
+    Pause,
+
+    /// Resume processing of transfers.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` config
+    ///   1. `[signer]` admin
+    ///
+    Unpause,
+
+    /// Move tokens mistakenly sent to account owned by program address which is not
+    /// registered balance of Ethereum account to the token account of recovery address.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` token program id
+    ///   1. `[]` config
+    ///   2. `[signer]` admin
+    ///   3. `[writable]` source account (must be owned by authority)
+    ///   4. `[]` authority for source account (derived from create_program_address([eth_token, eth_acc], program_id))
+    ///   5. `[writable]` destination account owned by recovery address
+    ///   6. `[]` mint (Token-2022 only)
+    ///   7. ..7+N `[]` extra accounts of the transfer hook, including hook program (Token-2022 only)
+    ///
+    RescueTokens {
+        /// The amount of tokens to rescue
+        amount: u64,
+        /// Nonce derived from find_program_address([eth_token, eth_acc], program_id)
+        nonce: u8,
+        /// Ethereum token address (first seed of authority)
+        eth_token: [u8;20],
+        /// Ethereum account address (second seed of authority)
+        eth_acc: [u8;20],
+    },
+
+    /// Set operator and fee of `TransferWithFee`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` config
+    ///   1. `[signer]` admin
+    ///
+    SetFee {
+        /// Owner of token accounts which receive fees
+        operator: Pubkey,
+        /// Fee in basis points of transferred amount
+        fee_basis_points: u16,
+    },
+
+    /// Transfer token from Ethereum account balance paying fee to operator in the same token,
+    /// so relayer can submit transfer for user without SOL. Destination receives amount without fee.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` token program id
+    ///   1. `[writable]` source account (must be owned by authority)
+    ///   2. `[writable]` destination account
+    ///   3. `[]` authority for source account (derived from create_program_address([eth_token, eth_acc], program_id))
+    ///   4. `[]` mint_info of source account mint (derived from create_program_address([mint, 'mint'], program_id))
+    ///   5. `[]` config (derived from create_program_address(['config'], program_id)), transfer fails when paused
+    ///   6. `[writable]` fee account owned by operator
+    ///   7. `[]` mint (Token-2022 only)
+    ///   8. ..8+N `[]` extra accounts of the transfer hook, including hook program (Token-2022 only)
+    ///
+    TransferWithFee {
+        /// The amount of tokens to transfer including fee
+        amount: u64,
+        /// Nonce derived from find_program_address([eth_token, eth_acc], program_id)
+        nonce: u8,
+        /// Ethereum token address
+        eth_token: [u8;20],
+        /// Ethereum source account address
+        eth_acc: [u8;20],
+    },
+
+    /// Transfer token from Ethereum account balance with SPL memo signed by authority
+    /// and reference keys (as in Solana Pay), so payments can be found and reconciled.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` token program id
+    ///   1. `[writable]` source account (must be owned by authority)
+    ///   2. `[writable]` destination account
+    ///   3. `[]` authority for source account (derived from create_program_address([eth_token, eth_acc], program_id))
+    ///   4. `[]` mint_info of source account mint (derived from create_program_address([mint, 'mint'], program_id))
+    ///   5. `[]` config (derived from create_program_address(['config'], program_id)), transfer fails when paused
+    ///   6. `[]` SPL memo program id
+    ///   7. ..7+R `[]` reference keys
+    ///   7+R. `[]` mint (Token-2022 only)
+    ///   8+R. ..8+R+N `[]` extra accounts of the transfer hook, including hook program (Token-2022 only)
+    ///
+    TransferWithMemo {
+        /// The amount of tokens to transfer
+        amount: u64,
+        /// Nonce derived from find_program_address([eth_token, eth_acc], program_id)
+        nonce: u8,
+        /// Ethereum token address
+        eth_token: [u8;20],
+        /// Ethereum source account address
+        eth_acc: [u8;20],
+        /// UTF-8 memo
+        memo: String,
+        /// Number of reference keys
+        reference_count: u8,
+    },
+
+    /// Transfer token from account owned by SPL Token multisig, e.g. treasury account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` token program id
+    ///   1. `[writable]` source account (must be owned by multisig)
+    ///   2. `[writable]` destination account
+    ///   3. `[]` multisig owner of source account
+    ///   4. `[]` mint_info of source account mint (derived from create_program_address([mint, 'mint'], program_id))
+    ///   5. `[]` config (derived from create_program_address(['config'], program_id)), transfer fails when paused
+    ///   6. ..6+M `[signer]` signers of multisig
+    ///   6+M. `[]` mint (Token-2022 only)
+    ///   7+M. ..7+M+N `[]` extra accounts of the transfer hook, including hook program (Token-2022 only)
+    ///
+    TransferMultisig {
+        /// The amount of tokens to transfer
+        amount: u64,
+        /// Ethereum token address
+        eth_token: [u8;20],
+        /// Number of multisig signers
+        signer_count: u8,
+    },
+
+    /// Transfer token from Ethereum account balance to associated token account of the wallet.
+    /// Associated token account is created (funded by payer) when it does not exist yet.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` token program id (SPL Token or Token-2022)
+    ///   1. `[writable]` source account (must be owned by authority)
+    ///   2. `[writable]` destination associated token account of the wallet
+    ///   3. `[]` authority for source account (derived from create_program_address([eth_token, eth_acc], program_id))
+    ///   4. `[]` mint_info of source account mint (derived from create_program_address([mint, 'mint'], program_id))
+    ///   5. `[]` config (derived from create_program_address(['config'], program_id)), transfer fails when paused
+    ///   6. `[writable,signer]` payer for creation of associated token account
+    ///   7. `[]` wallet, owner of destination account
+    ///   8. `[]` mint
+    ///   9. `[]` system program id
+    ///   10. `[]` associated token account program id
+    ///   11. `[]` rent program id
+    ///   12. ..12+N `[]` extra accounts of the transfer hook, including hook program (Token-2022 only)
+    ///
+    TransferToWallet {
+        /// The amount of tokens to transfer
+        amount: u64,
+        /// Nonce derived from find_program_address([eth_token, eth_acc], program_id)
+        nonce: u8,
+        /// Ethereum token address
+        eth_token: [u8;20],
+        /// Ethereum source account address
+        eth_acc: [u8;20],
+    },
+
+    /// ERC20-style event of the Ethereum token, invoked by the wrapper itself and does nothing.
+    /// Only inner instructions invoked by the wrapper are events of the wrapper.
+    ///
+    /// No accounts expected by this instruction.
+    ///
+    OnEvent {
+        /// Ethereum token address
+        address: [u8;20],
+        /// Event signature followed by indexed arguments
+        topics: Vec<[u8;32]>,
+        /// Not indexed arguments
+        data: Vec<u8>,
+    },
 }
 
+/// Offset of owner, signature and message in the data of `Permit` instruction
+pub const PERMIT_SIGNED_DATA_OFFSET: u16 = 59;
+
 impl MetamaskInstruction {
     /// Unpacks a byte buffer into a [MetamaskInstruction](enum.MetamaskInstruction.html).
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        let (&tag, rest) = input.split_first().ok_or(MetamaskError::InvalidInstruction)?;
-        Ok(match tag {
-            0 => {     // Initialize account
-                let (eth_acc_slice, rest) = rest.split_at(20);
-                let (&nonce, _rest) = rest.split_first().ok_or(MetamaskError::InvalidInstruction)?;
-
-                let mut eth_acc: [u8;20] = Default::default();
-                eth_acc.copy_from_slice(&eth_acc_slice);
-
-                Self::InitializeAccount {eth_acc, nonce,}
-            }
-            1 => {     // Initialize token
-                let (token, rest) = Self::unpack_pubkey(rest)?;
-                let (eth_token_slice, rest) = rest.split_at(20);
-                let (&nonce, _rest) = rest.split_first().ok_or(MetamaskError::InvalidInstruction)?;
-
-                let mut eth_token: [u8;20] = Default::default();
-                eth_token.copy_from_slice(&eth_token_slice);
-
-                Self::InitializeToken {token, eth_token, nonce,}
-            }
-            2 => {     // Initialize balance
-                let (account, rest) = Self::unpack_pubkey(rest)?;
-                let (eth_token_slice, rest) = rest.split_at(20);
-                let (eth_acc_slice, rest) = rest.split_at(20);
-                let (&nonce, _rest) = rest.split_first().ok_or(MetamaskError::InvalidInstruction)?;
-
-                let mut eth_token: [u8;20] = Default::default();
-                let mut eth_acc: [u8;20] = Default::default();
-                eth_token.copy_from_slice(&eth_token_slice);
-                eth_acc.copy_from_slice(&eth_acc_slice);
-
-                Self::InitializeBalance {account, eth_token, eth_acc, nonce,}
-            }
-            3 => {
-                let (amount, rest) = rest.split_at(8);
-                let (&nonce, rest) = rest.split_first().ok_or(MetamaskError::InvalidInstruction)?;
-                let (eth_token_slice, rest) = rest.split_at(20);
-                let (eth_acc_slice, _rest) = rest.split_at(20);
-                let amount = amount
-                    .try_into()
-                    .ok()
-                    .map(u64::from_le_bytes)
-                    .ok_or(MetamaskError::InvalidInstruction)?;
-
-                let mut eth_token : [u8; 20] = Default::default();
-                let mut eth_acc : [u8; 20] = Default::default();
-                eth_token.copy_from_slice(&eth_token_slice);
-                eth_acc.copy_from_slice(&eth_acc_slice);
-
-                Self::Transfer {amount, nonce, eth_token, eth_acc,}
-            }
-            4 => {
-                let (amount, rest) = rest.split_at(8);
-                let (&nonce, rest) = rest.split_first().ok_or(MetamaskError::InvalidInstruction)?;
-                let (eth_acc_slice, _rest) = rest.split_at(20);
-                let amount = amount
-                    .try_into()
-                    .ok()
-                    .map(u64::from_le_bytes)
-                    .ok_or(MetamaskError::InvalidInstruction)?;
-
-                let mut eth_acc : [u8; 20] = Default::default();
-                eth_acc.copy_from_slice(&eth_acc_slice);
-
-                Self::TransferLamports {amount, nonce, eth_acc,}
-            }
-            _ => return Err(MetamaskError::InvalidInstruction.into()),
-        })
-    }
-
-    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
-        if input.len() >= 32 {
-            let (key, rest) = input.split_at(32);
-            let pk = Pubkey::new(key);
-            Ok((pk, rest))
-        } else {
-            Err(MetamaskError::InvalidInstruction.into())
-        }
+        Self::try_from_slice(input).map_err(|_| MetamaskError::InvalidInstruction.into())
     }
 
     /// Packs a [MetamaskInstruction](enum.MetamaskInstruction.html) into a byte buffer.
     pub fn pack(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(size_of::<Self>());
-        match *self {
-            Self::InitializeAccount {eth_acc, nonce,} => {
-                buf.push(0);
-                buf.extend_from_slice(eth_acc.as_ref());
-                buf.push(nonce);
-            }
-            Self::InitializeToken {token, eth_token, nonce,} => {
-                buf.push(1);
-                buf.extend_from_slice(token.as_ref());
-                buf.extend_from_slice(eth_token.as_ref());
-                buf.push(nonce);
-            }
-            Self::InitializeBalance {account, eth_token, eth_acc, nonce,} => {
-                buf.push(2);
-                buf.extend_from_slice(account.as_ref());
-                buf.extend_from_slice(eth_token.as_ref());
-                buf.extend_from_slice(eth_acc.as_ref());
-                buf.push(nonce);
-            }
-            Self::Transfer { amount, nonce, eth_token, eth_acc } => {
-                buf.push(3);
-                buf.extend_from_slice(&amount.to_le_bytes());
-                buf.push(nonce);
-                buf.extend_from_slice(&eth_token);
-                buf.extend_from_slice(&eth_acc);
-            }
-            Self::TransferLamports { amount, nonce, eth_acc, } => {
-                buf.push(4);
-                buf.extend_from_slice(&amount.to_le_bytes());
-                buf.push(nonce);
-                buf.extend_from_slice(&eth_acc);
-            }
-        }
-        buf
+        self.try_to_vec().unwrap()
     }
 }
 
-/// Creates an 'initialize' instruction.
+/// Creates an `InitializeAccount` instruction.
 pub fn initialize_account(
     wrapper_program: &Pubkey,
     account_info: &Pubkey,
+    creator: &Pubkey,
     eth_acc: &[u8;20],
     nonce: u8,
 ) -> Result<Instruction, ProgramError> {
-    let init_data = MetamaskInstruction::InitializeAccount {
+    let data = MetamaskInstruction::InitializeAccount {
         eth_acc: *eth_acc,
-        nonce: nonce,
-    };
-    let data = init_data.pack();
+        nonce,
+    }.pack();
 
     let accounts = vec![
         AccountMeta::new(*account_info, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new(*creator, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *wrapper_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `InitializeToken` instruction.
+pub fn initialize_token(
+    wrapper_program: &Pubkey,
+    token_info: &Pubkey,
+    creator: &Pubkey,
+    token: &Pubkey,
+    eth_token: &[u8;20],
+    nonce: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = MetamaskInstruction::InitializeToken {
+        token: *token,
+        eth_token: *eth_token,
+        nonce,
+    }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*token_info, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new(*creator, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *wrapper_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `InitializeBalance` instruction.
+pub fn initialize_balance(
+    wrapper_program: &Pubkey,
+    balance_info: &Pubkey,
+    creator: &Pubkey,
+    account: &Pubkey,
+    eth_token: &[u8;20],
+    eth_acc: &[u8;20],
+    nonce: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = MetamaskInstruction::InitializeBalance {
+        account: *account,
+        eth_token: *eth_token,
+        eth_acc: *eth_acc,
+        nonce,
+    }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*balance_info, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new(*creator, true),
     ];
 
     Ok(Instruction {
@@ -269,9 +541,12 @@ pub fn initialize_account(
 /// Creates a `Transfer` instruction.
 pub fn transfer(
     program_id: &Pubkey,
+    token_program_id: &Pubkey,
     source_pubkey: &Pubkey,
     destination_pubkey: &Pubkey,
     authority_pubkey: &Pubkey,
+    mint_info_pubkey: &Pubkey,
+    config_pubkey: &Pubkey,
     amount: u64,
     nonce: u8,
     eth_token: [u8; 20],
@@ -279,11 +554,458 @@ pub fn transfer(
 ) -> Result<Instruction, ProgramError> {
     let data = MetamaskInstruction::Transfer { amount, nonce, eth_token, eth_acc }.pack();
 
-    let mut accounts = Vec::with_capacity(4);
-    accounts.push(AccountMeta::new(*program_id, false));
-    accounts.push(AccountMeta::new(*source_pubkey, false));
-    accounts.push(AccountMeta::new(*destination_pubkey, false));
-    accounts.push(AccountMeta::new_readonly(*authority_pubkey, false));
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*mint_info_pubkey, false),
+        AccountMeta::new_readonly(*config_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `TransferLamports` instruction.
+pub fn transfer_lamports(
+    program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    config_pubkey: &Pubkey,
+    amount: u64,
+    nonce: u8,
+    eth_acc: [u8; 20],
+) -> Result<Instruction, ProgramError> {
+    let data = MetamaskInstruction::TransferLamports { amount, nonce, eth_acc }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*config_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `Approve` instruction.
+pub fn approve(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    delegate_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    mint_info_pubkey: &Pubkey,
+    amount: u64,
+    nonce: u8,
+    eth_token: [u8; 20],
+    eth_acc: [u8; 20],
+) -> Result<Instruction, ProgramError> {
+    let data = MetamaskInstruction::Approve { amount, nonce, eth_token, eth_acc }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new_readonly(*delegate_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*mint_info_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `TransferFrom` instruction.
+pub fn transfer_from(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    mint_info_pubkey: &Pubkey,
+    config_pubkey: &Pubkey,
+    amount: u64,
+    nonce: u8,
+    eth_token: [u8; 20],
+    eth_acc: [u8; 20],
+) -> Result<Instruction, ProgramError> {
+    let data = MetamaskInstruction::TransferFrom { amount, nonce, eth_token, eth_acc }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*mint_info_pubkey, false),
+        AccountMeta::new_readonly(*config_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `Permit` instruction. It must be preceded by instruction returned from
+/// `permit::secp256k1_instruction_data` with index of this instruction.
+pub fn permit(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    delegate_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    mint_info_pubkey: &Pubkey,
+    account_info_pubkey: &Pubkey,
+    config_pubkey: &Pubkey,
+    nonce: u8,
+    spender_nonce: u8,
+    eth_token: [u8; 20],
+    spender: [u8; 20],
+    value: u64,
+    deadline: u64,
+    owner: [u8; 20],
+    signature: [u8; 65],
+    permit_nonce: u64,
+    chain_id: u64,
+) -> Result<Instruction, ProgramError> {
+    let message = PermitMessage(permit::message(chain_id, &eth_token, &owner, &spender, value, permit_nonce, deadline));
+    let data = MetamaskInstruction::Permit {
+        nonce, spender_nonce, eth_token, spender, value, deadline, owner, signature, message,
+    }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new_readonly(*delegate_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*mint_info_pubkey, false),
+        AccountMeta::new(*account_info_pubkey, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        AccountMeta::new_readonly(*config_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `InitializeMint` instruction.
+pub fn initialize_mint(
+    wrapper_program: &Pubkey,
+    mint_info: &Pubkey,
+    mint: &Pubkey,
+    creator: &Pubkey,
+    eth_token: &[u8;20],
+    nonce: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = MetamaskInstruction::InitializeMint {
+        eth_token: *eth_token,
+        nonce,
+    }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*mint_info, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new(*creator, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *wrapper_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `InitializeConfig` instruction.
+pub fn initialize_config(
+    wrapper_program: &Pubkey,
+    config: &Pubkey,
+    creator: &Pubkey,
+    admin: &Pubkey,
+    recovery: &Pubkey,
+    nonce: u8,
+    chain_id: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = MetamaskInstruction::InitializeConfig {
+        admin: *admin,
+        recovery: *recovery,
+        nonce,
+        chain_id,
+    }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*config, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new(*creator, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *wrapper_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `Pause` instruction.
+pub fn pause(
+    wrapper_program: &Pubkey,
+    config: &Pubkey,
+    admin: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *wrapper_program,
+        accounts: vec![
+            AccountMeta::new(*config, false),
+            AccountMeta::new_readonly(*admin, true),
+        ],
+        data: MetamaskInstruction::Pause.pack(),
+    })
+}
+
+/// Creates an `Unpause` instruction.
+pub fn unpause(
+    wrapper_program: &Pubkey,
+    config: &Pubkey,
+    admin: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *wrapper_program,
+        accounts: vec![
+            AccountMeta::new(*config, false),
+            AccountMeta::new_readonly(*admin, true),
+        ],
+        data: MetamaskInstruction::Unpause.pack(),
+    })
+}
+
+/// Creates a `RescueTokens` instruction.
+pub fn rescue_tokens(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    config_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    amount: u64,
+    nonce: u8,
+    eth_token: [u8; 20],
+    eth_acc: [u8; 20],
+) -> Result<Instruction, ProgramError> {
+    let data = MetamaskInstruction::RescueTokens { amount, nonce, eth_token, eth_acc }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*config_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `SetFee` instruction.
+pub fn set_fee(
+    wrapper_program: &Pubkey,
+    config: &Pubkey,
+    admin: &Pubkey,
+    operator: &Pubkey,
+    fee_basis_points: u16,
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *wrapper_program,
+        accounts: vec![
+            AccountMeta::new(*config, false),
+            AccountMeta::new_readonly(*admin, true),
+        ],
+        data: MetamaskInstruction::SetFee { operator: *operator, fee_basis_points }.pack(),
+    })
+}
+
+/// Creates a `TransferWithFee` instruction.
+pub fn transfer_with_fee(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    mint_info_pubkey: &Pubkey,
+    config_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    amount: u64,
+    nonce: u8,
+    eth_token: [u8; 20],
+    eth_acc: [u8; 20],
+) -> Result<Instruction, ProgramError> {
+    let data = MetamaskInstruction::TransferWithFee { amount, nonce, eth_token, eth_acc }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*mint_info_pubkey, false),
+        AccountMeta::new_readonly(*config_pubkey, false),
+        AccountMeta::new(*fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `TransferWithMemo` instruction.
+pub fn transfer_with_memo(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    mint_info_pubkey: &Pubkey,
+    config_pubkey: &Pubkey,
+    references: &[Pubkey],
+    amount: u64,
+    nonce: u8,
+    eth_token: [u8; 20],
+    eth_acc: [u8; 20],
+    memo: &str,
+) -> Result<Instruction, ProgramError> {
+    if references.len() > u8::MAX as usize {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = MetamaskInstruction::TransferWithMemo {
+        amount, nonce, eth_token, eth_acc,
+        memo: memo.to_string(),
+        reference_count: references.len() as u8,
+    }.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*mint_info_pubkey, false),
+        AccountMeta::new_readonly(*config_pubkey, false),
+        AccountMeta::new_readonly(spl_memo::id(), false),
+    ];
+    accounts.extend(references.iter().map(|reference| AccountMeta::new_readonly(*reference, false)));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `TransferMultisig` instruction.
+pub fn transfer_multisig(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    multisig_pubkey: &Pubkey,
+    mint_info_pubkey: &Pubkey,
+    config_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+    eth_token: [u8; 20],
+) -> Result<Instruction, ProgramError> {
+    if signer_pubkeys.len() > spl_token::instruction::MAX_SIGNERS {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = MetamaskInstruction::TransferMultisig {
+        amount,
+        eth_token,
+        signer_count: signer_pubkeys.len() as u8,
+    }.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*multisig_pubkey, false),
+        AccountMeta::new_readonly(*mint_info_pubkey, false),
+        AccountMeta::new_readonly(*config_pubkey, false),
+    ];
+    accounts.extend(signer_pubkeys.iter().map(|signer| AccountMeta::new_readonly(**signer, true)));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Address of associated token account of the wallet for mint of the token program
+pub fn get_associated_token_address(
+    wallet_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[wallet_pubkey.as_ref(), token_program_id.as_ref(), mint_pubkey.as_ref()],
+        &spl_associated_token_account::id(),
+    ).0
+}
+
+/// Creates a `TransferToWallet` instruction.
+pub fn transfer_to_wallet(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    mint_info_pubkey: &Pubkey,
+    config_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    amount: u64,
+    nonce: u8,
+    eth_token: [u8; 20],
+    eth_acc: [u8; 20],
+) -> Result<Instruction, ProgramError> {
+    let data = MetamaskInstruction::TransferToWallet { amount, nonce, eth_token, eth_acc }.pack();
+    let destination_pubkey = get_associated_token_address(wallet_pubkey, mint_pubkey, token_program_id);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(destination_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*mint_info_pubkey, false),
+        AccountMeta::new_readonly(*config_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(*wallet_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
 
     Ok(Instruction {
         program_id: *program_id,
@@ -302,3 +1024,178 @@ pub fn unpack<T>(input: &[u8]) -> Result<&T, ProgramError> {
     let val: &T = unsafe { &*(&input[1] as *const u8 as *const T) };
     Ok(val)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check_pack_unpack(instruction: MetamaskInstruction, expected: Vec<u8>) {
+        let packed = instruction.pack();
+        assert_eq!(packed, expected);
+        let unpacked = MetamaskInstruction::unpack(&expected).unwrap();
+        assert_eq!(unpacked, instruction);
+    }
+
+    #[test]
+    fn test_instruction_packing() {
+        let token = Pubkey::new(&[3_u8; 32]);
+        let eth_token = [1_u8; 20];
+        let eth_acc = [2_u8; 20];
+
+        let mut expect = vec![0];
+        expect.extend_from_slice(&eth_acc);
+        expect.push(255);
+        check_pack_unpack(MetamaskInstruction::InitializeAccount { eth_acc, nonce: 255 }, expect);
+
+        let mut expect = vec![1];
+        expect.extend_from_slice(token.as_ref());
+        expect.extend_from_slice(&eth_token);
+        expect.push(254);
+        check_pack_unpack(MetamaskInstruction::InitializeToken { token, eth_token, nonce: 254 }, expect);
+
+        let mut expect = vec![2];
+        expect.extend_from_slice(token.as_ref());
+        expect.extend_from_slice(&eth_token);
+        expect.extend_from_slice(&eth_acc);
+        expect.push(253);
+        check_pack_unpack(MetamaskInstruction::InitializeBalance { account: token, eth_token, eth_acc, nonce: 253 }, expect);
+
+        let mut expect = vec![3, 1, 2, 0, 0, 0, 0, 0, 0, 252];
+        expect.extend_from_slice(&eth_token);
+        expect.extend_from_slice(&eth_acc);
+        check_pack_unpack(MetamaskInstruction::Transfer { amount: 513, nonce: 252, eth_token, eth_acc }, expect);
+
+        let mut expect = vec![4, 1, 0, 0, 0, 0, 0, 0, 0, 251];
+        expect.extend_from_slice(&eth_acc);
+        check_pack_unpack(MetamaskInstruction::TransferLamports { amount: 1, nonce: 251, eth_acc }, expect);
+
+        let mut expect = vec![5];
+        expect.extend_from_slice(&eth_token);
+        expect.push(250);
+        check_pack_unpack(MetamaskInstruction::InitializeMint { eth_token, nonce: 250 }, expect);
+
+        let mut expect = vec![6, 0, 0, 0, 0, 0, 0, 0, 0, 249];
+        expect.extend_from_slice(&eth_token);
+        expect.extend_from_slice(&eth_acc);
+        check_pack_unpack(MetamaskInstruction::Approve { amount: 0, nonce: 249, eth_token, eth_acc }, expect);
+
+        let mut expect = vec![7, 255, 255, 255, 255, 255, 255, 255, 255, 248];
+        expect.extend_from_slice(&eth_token);
+        expect.extend_from_slice(&eth_acc);
+        check_pack_unpack(MetamaskInstruction::TransferFrom { amount: u64::MAX, nonce: 248, eth_token, eth_acc }, expect);
+
+        let recovery = Pubkey::new(&[4_u8; 32]);
+        let mut expect = vec![9];
+        expect.extend_from_slice(token.as_ref());
+        expect.extend_from_slice(recovery.as_ref());
+        expect.push(246);
+        expect.extend_from_slice(&111_u64.to_le_bytes());
+        check_pack_unpack(MetamaskInstruction::InitializeConfig { admin: token, recovery, nonce: 246, chain_id: 111 }, expect);
+
+        check_pack_unpack(MetamaskInstruction::Pause, vec![10]);
+        check_pack_unpack(MetamaskInstruction::Unpause, vec![11]);
+
+        let mut expect = vec![12, 7, 0, 0, 0, 0, 0, 0, 0, 245];
+        expect.extend_from_slice(&eth_token);
+        expect.extend_from_slice(&eth_acc);
+        check_pack_unpack(MetamaskInstruction::RescueTokens { amount: 7, nonce: 245, eth_token, eth_acc }, expect);
+
+        let mut expect = vec![13];
+        expect.extend_from_slice(recovery.as_ref());
+        expect.extend_from_slice(&[0x10, 0x27]);
+        check_pack_unpack(MetamaskInstruction::SetFee { operator: recovery, fee_basis_points: 10_000 }, expect);
+
+        let mut expect = vec![14, 0, 1, 0, 0, 0, 0, 0, 0, 244];
+        expect.extend_from_slice(&eth_token);
+        expect.extend_from_slice(&eth_acc);
+        check_pack_unpack(MetamaskInstruction::TransferWithFee { amount: 256, nonce: 244, eth_token, eth_acc }, expect);
+
+        let mut expect = vec![15, 1, 0, 0, 0, 0, 0, 0, 0, 243];
+        expect.extend_from_slice(&eth_token);
+        expect.extend_from_slice(&eth_acc);
+        expect.extend_from_slice(&[5, 0, 0, 0]);
+        expect.extend_from_slice(b"order");
+        expect.push(2);
+        check_pack_unpack(MetamaskInstruction::TransferWithMemo {
+            amount: 1, nonce: 243, eth_token, eth_acc, memo: "order".to_string(), reference_count: 2,
+        }, expect);
+
+        let mut expect = vec![16, 2, 0, 0, 0, 0, 0, 0, 0];
+        expect.extend_from_slice(&eth_token);
+        expect.push(3);
+        check_pack_unpack(MetamaskInstruction::TransferMultisig { amount: 2, eth_token, signer_count: 3 }, expect);
+
+        let mut expect = vec![17, 3, 0, 0, 0, 0, 0, 0, 0, 242];
+        expect.extend_from_slice(&eth_token);
+        expect.extend_from_slice(&eth_acc);
+        check_pack_unpack(MetamaskInstruction::TransferToWallet { amount: 3, nonce: 242, eth_token, eth_acc }, expect);
+
+        let mut expect = vec![18];
+        expect.extend_from_slice(&eth_token);
+        expect.extend_from_slice(&[1, 0, 0, 0]);
+        expect.extend_from_slice(&[5_u8; 32]);
+        expect.extend_from_slice(&[2, 0, 0, 0, 7, 8]);
+        check_pack_unpack(MetamaskInstruction::OnEvent { address: eth_token, topics: vec![[5; 32]], data: vec![7, 8] }, expect);
+    }
+
+    #[test]
+    fn test_permit_packing() {
+        let eth_token = [1_u8; 20];
+        let spender = [2_u8; 20];
+        let owner = [4_u8; 20];
+        let signature = [5_u8; 65];
+        let message = PermitMessage(permit::message(111, &eth_token, &owner, &spender, 10, 1, 1000));
+
+        let mut expect = vec![8, 247, 251];
+        expect.extend_from_slice(&eth_token);
+        expect.extend_from_slice(&spender);
+        expect.extend_from_slice(&10_u64.to_le_bytes());
+        expect.extend_from_slice(&1000_u64.to_le_bytes());
+        assert_eq!(expect.len(), PERMIT_SIGNED_DATA_OFFSET as usize);
+        expect.extend_from_slice(&owner);
+        expect.extend_from_slice(&signature);
+        expect.extend_from_slice(&message.0);
+        check_pack_unpack(MetamaskInstruction::Permit {
+            nonce: 247, spender_nonce: 251, eth_token, spender, value: 10, deadline: 1000, owner, signature, message,
+        }, expect);
+    }
+
+    #[test]
+    fn test_unpack_invalid() {
+        assert_eq!(MetamaskInstruction::unpack(&[]), Err(MetamaskError::InvalidInstruction.into()));
+        assert_eq!(MetamaskInstruction::unpack(&[19]), Err(MetamaskError::InvalidInstruction.into()));
+        assert_eq!(MetamaskInstruction::unpack(&[0, 1, 2, 3]), Err(MetamaskError::InvalidInstruction.into()));
+
+        let mut data = MetamaskInstruction::InitializeMint { eth_token: [1; 20], nonce: 1 }.pack();
+        data.push(0);
+        assert_eq!(MetamaskInstruction::unpack(&data), Err(MetamaskError::InvalidInstruction.into()));
+    }
+
+    #[test]
+    fn test_builders_account_order() {
+        let program_id = Pubkey::new_unique();
+        let account_info = Pubkey::new_unique();
+        let creator = Pubkey::new_unique();
+        let ix = initialize_account(&program_id, &account_info, &creator, &[1; 20], 1).unwrap();
+        let keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(keys, vec![account_info, system_program::id(), sysvar::rent::id(), creator]);
+        assert!(ix.accounts[3].is_signer);
+
+        let token_program = spl_token::id();
+        let (source, destination, authority, mint_info) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let config = Pubkey::new_unique();
+        let ix = transfer(&program_id, &token_program, &source, &destination, &authority, &mint_info, &config,
+            1, 1, [1; 20], [2; 20]).unwrap();
+        let keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(keys, vec![token_program, source, destination, authority, mint_info, config]);
+        assert!(ix.accounts[1].is_writable && ix.accounts[2].is_writable);
+
+        let (wallet, mint, payer) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = transfer_to_wallet(&program_id, &token_program, &source, &wallet, &mint, &authority, &mint_info,
+            &config, &payer, 1, 1, [1; 20], [2; 20]).unwrap();
+        assert_eq!(ix.accounts[2].pubkey, spl_associated_token_account::get_associated_token_address(&wallet, &mint));
+        assert!(ix.accounts[6].is_signer && ix.accounts[6].is_writable);
+        assert_eq!(ix.accounts[10].pubkey, spl_associated_token_account::id());
+    }
+}
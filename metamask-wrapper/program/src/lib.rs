@@ -1,10 +1,15 @@
 //! A simple program that receives transfer operations from metamask-wrapper and transfers different tokens using another programs.
 
+#[cfg(feature = "cpi")]
+pub mod cpi;
 pub mod entrypoint;
 pub mod error;
+pub mod event;
 pub mod instruction;
+pub mod permit;
 pub mod processor;
 pub mod state;
+pub mod token_2022;
 
 // Export current solana-sdk types for downstream users who may also be building with a different
 // solana-sdk version
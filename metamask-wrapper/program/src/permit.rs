@@ -0,0 +1,164 @@
+//! EIP-2612 permit: typed data approval signed by Ethereum account
+//! and verified by preceding secp256k1 program instruction
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    info,
+    keccak::{hash, hashv},
+    program_error::ProgramError,
+    secp256k1_program,
+    sysvar::instructions::{load_current_index, load_instruction_at},
+};
+
+/// Name of the domain
+pub const DOMAIN_NAME: &str = "Metamask Wrapper";
+/// Version of the domain
+pub const DOMAIN_VERSION: &str = "1";
+
+/// Size of signature with recovery id
+pub const SIGNATURE_LEN: usize = 65;
+/// Size of signed message: '\x19\x01' ++ domainSeparator ++ hashStruct(message)
+pub const MESSAGE_LEN: usize = 66;
+
+/// Signed message stored in instruction data as raw bytes, so secp256k1 instruction can refer to it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PermitMessage(pub [u8; MESSAGE_LEN]);
+
+impl BorshSerialize for PermitMessage {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.0)
+    }
+}
+
+impl BorshDeserialize for PermitMessage {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        if buf.len() < MESSAGE_LEN {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Unexpected length of input"));
+        }
+        let mut message = [0_u8; MESSAGE_LEN];
+        message.copy_from_slice(&buf[..MESSAGE_LEN]);
+        *buf = &buf[MESSAGE_LEN..];
+        Ok(PermitMessage(message))
+    }
+}
+
+const DOMAIN_TYPE: &[u8] = b"EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+const PERMIT_TYPE: &[u8] = b"Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)";
+
+fn word_u64(value: u64) -> [u8; 32] {
+    let mut word = [0_u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+fn word_address(address: &[u8; 20]) -> [u8; 32] {
+    let mut word = [0_u8; 32];
+    word[12..].copy_from_slice(address);
+    word
+}
+
+/// Domain separator of the Ethereum token on the chain with chain_id
+pub fn domain_separator(chain_id: u64, eth_token: &[u8; 20]) -> [u8; 32] {
+    hashv(&[
+        &hash(DOMAIN_TYPE).to_bytes(),
+        &hash(DOMAIN_NAME.as_bytes()).to_bytes(),
+        &hash(DOMAIN_VERSION.as_bytes()).to_bytes(),
+        &word_u64(chain_id),
+        &word_address(eth_token),
+    ]).to_bytes()
+}
+
+/// Hash of the Permit structure
+pub fn struct_hash(owner: &[u8; 20], spender: &[u8; 20], value: u64, nonce: u64, deadline: u64) -> [u8; 32] {
+    hashv(&[
+        &hash(PERMIT_TYPE).to_bytes(),
+        &word_address(owner),
+        &word_address(spender),
+        &word_u64(value),
+        &word_u64(nonce),
+        &word_u64(deadline),
+    ]).to_bytes()
+}
+
+/// Message which keccak256 hash is signed by eth_signTypedData
+pub fn message(
+    chain_id: u64,
+    eth_token: &[u8; 20],
+    owner: &[u8; 20],
+    spender: &[u8; 20],
+    value: u64,
+    nonce: u64,
+    deadline: u64,
+) -> [u8; MESSAGE_LEN] {
+    let mut message = [0_u8; MESSAGE_LEN];
+    message[0] = 0x19;
+    message[1] = 0x01;
+    message[2..34].copy_from_slice(&domain_separator(chain_id, eth_token));
+    message[34..].copy_from_slice(&struct_hash(owner, spender, value, nonce, deadline));
+    message
+}
+
+/// Data of secp256k1 instruction which checks single signature stored in the instruction
+/// with instruction_index as eth_address, signature and message starting at data_start.
+pub fn secp256k1_instruction_data(instruction_index: u8, data_start: u16) -> Vec<u8> {
+    let eth_address_offset = data_start;
+    let signature_offset = eth_address_offset + 20;
+    let message_data_offset = signature_offset + SIGNATURE_LEN as u16;
+
+    let mut data = Vec::with_capacity(12);
+    data.push(1); // number of signatures
+    data.extend_from_slice(&signature_offset.to_le_bytes());
+    data.push(instruction_index);
+    data.extend_from_slice(&eth_address_offset.to_le_bytes());
+    data.push(instruction_index);
+    data.extend_from_slice(&message_data_offset.to_le_bytes());
+    data.extend_from_slice(&(MESSAGE_LEN as u16).to_le_bytes());
+    data.push(instruction_index);
+    data
+}
+
+/// Check that previous instruction of the transaction verifies signature stored in current instruction
+pub fn check_secp256k1_instruction(sysvar_info: &AccountInfo, data_start: u16) -> ProgramResult {
+    let data = sysvar_info.try_borrow_data()?;
+    let current_instruction = load_current_index(&data);
+    if current_instruction == 0 {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let instruction = load_instruction_at((current_instruction - 1) as usize, &data)
+        .map_err(|_| ProgramError::MissingRequiredSignature)?;
+    if !secp256k1_program::check_id(&instruction.program_id) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if instruction.data != secp256k1_instruction_data(current_instruction as u8, data_start) {
+        info!("Wrong secp256k1 instruction data");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secp256k1_instruction_data() {
+        let data = secp256k1_instruction_data(1, 58);
+        assert_eq!(data, vec![1, 78, 0, 1, 58, 0, 1, 143, 0, 66, 0, 1]);
+    }
+
+    #[test]
+    fn test_message() {
+        let eth_token = [1_u8; 20];
+        let owner = [2_u8; 20];
+        let spender = [3_u8; 20];
+        let msg = message(111, &eth_token, &owner, &spender, 1000, 1, 1_700_000_000);
+        assert_eq!(&msg[..2], &[0x19, 0x01]);
+        assert_eq!(&msg[2..34], &domain_separator(111, &eth_token));
+        assert_ne!(msg, message(111, &eth_token, &owner, &spender, 1000, 2, 1_700_000_000));
+        assert_ne!(msg, message(111, &[4_u8; 20], &owner, &spender, 1000, 1, 1_700_000_000));
+        assert_ne!(msg, message(112, &eth_token, &owner, &spender, 1000, 1, 1_700_000_000));
+    }
+}
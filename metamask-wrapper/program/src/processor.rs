@@ -3,19 +3,25 @@
 
 use crate::{
     error::MetamaskError,
-    instruction::MetamaskInstruction,
-    state::{AccountInfo as AccInfo, TokenInfo, BalanceInfo,},
+    event,
+    instruction::{MetamaskInstruction, PERMIT_SIGNED_DATA_OFFSET},
+    permit,
+    state::{AccountInfo as AccInfo, TokenInfo, BalanceInfo, MintInfo, Config, CONFIG_SEED, find_config_address,},
+    token_2022,
 };
 use num_traits::FromPrimitive;
 #[cfg(target_arch = "bpf")]
-use solana_program::program::invoke_signed;
+use solana_program::program::{invoke, invoke_signed};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     decode_error::DecodeError,
     entrypoint::ProgramResult,
     info,
-    program_error::PrintProgramError,
-    program_pack::Pack,
+    instruction::{AccountMeta, Instruction},
+    program_error::{PrintProgramError, ProgramError},
+    program_option::COption,
+    program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
     sysvar::{rent::Rent, Sysvar},
 };
@@ -158,9 +164,323 @@ impl Processor {
         Ok(())
     }
 
+    /// Transfer is routed only for mints registered with the same Ethereum token
+    /// and to the token program which owns source account
+    fn check_mint_info(
+        program_id: &Pubkey,
+        token_program: &AccountInfo,
+        mint_info: &AccountInfo,
+        source: &AccountInfo,
+        eth_token: &[u8;20],
+    ) -> Result<(MintInfo, spl_token::state::Account), ProgramError> {
+        if !token_2022::is_token_program(token_program.key) || source.owner != token_program.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if mint_info.owner != program_id {
+            return Err(MetamaskError::UnknownMint.into());
+        }
+        let info = MintInfo::unpack(&mint_info.data.borrow())?;
+        let source_account = Self::unpack_token_account(source)?;
+        if info.mint != source_account.mint {
+            return Err(MetamaskError::UnknownMint.into());
+        }
+        if info.eth_token != *eth_token {
+            return Err(MetamaskError::TokenNotRegistered.into());
+        }
+        Ok((info, source_account))
+    }
+
+    /// Base state of SPL Token or Token-2022 account
+    fn unpack_token_account(account: &AccountInfo) -> Result<spl_token::state::Account, ProgramError> {
+        let data = account.data.borrow();
+        if data.len() < spl_token::state::Account::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        spl_token::state::Account::unpack(&data[..spl_token::state::Account::LEN])
+    }
+
+    /// Decimals of SPL Token or Token-2022 mint
+    fn mint_decimals(mint: &AccountInfo) -> Result<u8, ProgramError> {
+        if !token_2022::is_token_program(mint.owner) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // Token-2022 mint data starts with base mint followed by extensions
+        let mint_data = mint.data.borrow();
+        if mint_data.len() < spl_token::state::Mint::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(spl_token::state::Mint::unpack(&mint_data[..spl_token::state::Mint::LEN])?.decimals)
+    }
+
+    /// Config must be the account at canonical config address
+    fn load_config(program_id: &Pubkey, config_info: &AccountInfo) -> Result<Config, ProgramError> {
+        if config_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let config = Config::unpack(&config_info.data.borrow())?;
+        let expected = Pubkey::create_program_address(&[CONFIG_SEED, &[config.nonce]], program_id)?;
+        if *config_info.key != expected {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(config)
+    }
+
+    /// Transfers are processed only while program is not paused
+    fn check_not_paused(program_id: &Pubkey, config_info: &AccountInfo) -> ProgramResult {
+        if Self::load_config(program_id, config_info)?.paused {
+            return Err(MetamaskError::Paused.into());
+        }
+        Ok(())
+    }
+
+    /// Load config and check that admin signed the instruction
+    fn load_config_by_admin(
+        program_id: &Pubkey,
+        config_info: &AccountInfo,
+        admin: &AccountInfo,
+    ) -> Result<Config, ProgramError> {
+        let config = Self::load_config(program_id, config_info)?;
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *admin.key != config.admin {
+            return Err(MetamaskError::InvalidAuthority.into());
+        }
+        Ok(config)
+    }
+
+    /// Checks of transfer from account owned by authority derived from seeds
+    #[allow(clippy::too_many_arguments)]
+    fn check_transfer(
+        program_id: &Pubkey,
+        token_program: &AccountInfo,
+        source: &AccountInfo,
+        authority: &AccountInfo,
+        mint_info: &AccountInfo,
+        config_info: &AccountInfo,
+        eth_token: &[u8;20],
+        seeds: &[&[u8]],
+    ) -> Result<(MintInfo, Config), ProgramError> {
+        let config = Self::load_config(program_id, config_info)?;
+        if config.paused {
+            return Err(MetamaskError::Paused.into());
+        }
+        let (info, source_account) = Self::check_mint_info(program_id, token_program, mint_info, source, eth_token)?;
+
+        Self::check_authority(program_id, authority, seeds)?;
+        if source_account.owner != *authority.key {
+            return Err(MetamaskError::InvalidAuthority.into());
+        }
+        Ok((info, config))
+    }
+
+    /// Authority must be derived from seeds
+    fn check_authority(program_id: &Pubkey, authority: &AccountInfo, seeds: &[&[u8]]) -> ProgramResult {
+        let expected = Pubkey::create_program_address(seeds, program_id)
+            .map_err(|_| MetamaskError::InvalidAuthority)?;
+        if *authority.key != expected {
+            return Err(MetamaskError::InvalidAuthority.into());
+        }
+        Ok(())
+    }
+
+    /// Set delegate of source account signed by authority, zero amount revokes delegate
+    fn invoke_approve<'a>(
+        token_program: &AccountInfo<'a>,
+        source: &AccountInfo<'a>,
+        delegate: &AccountInfo<'a>,
+        authority: &AccountInfo<'a>,
+        amount: u64,
+        signers: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if amount == 0 {
+            let ix = spl_token::instruction::revoke(
+                token_program.key,
+                source.key,
+                authority.key,
+                &[],
+            )?;
+            return invoke_signed(
+                &ix,
+                &[source.clone(), authority.clone(), token_program.clone()],
+                signers,
+            );
+        }
+
+        let ix = spl_token::instruction::approve(
+            token_program.key,
+            source.key,
+            delegate.key,
+            authority.key,
+            &[],
+            amount,
+        )?;
+        invoke_signed(
+            &ix,
+            &[source.clone(), delegate.clone(), authority.clone(), token_program.clone()],
+            signers,
+        )
+    }
+
+    /// Transfer tokens signed by authority. Token-2022 mints require TransferChecked
+    /// with the mint account followed by extra accounts of the transfer hook.
+    #[allow(clippy::too_many_arguments)]
+    fn invoke_transfer<'a>(
+        token_program: &AccountInfo<'a>,
+        source: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        authority: &AccountInfo<'a>,
+        info: &MintInfo,
+        remaining_accounts: &[AccountInfo<'a>],
+        amount: u64,
+        signers: &[&[&[u8]]],
+    ) -> ProgramResult {
+        Self::invoke_transfer_multisig(
+            token_program, source, destination, authority, &[], info,
+            remaining_accounts, amount, signers,
+        )
+    }
+
+    /// Transfer tokens from account owned by authority, when authority is SPL Token
+    /// multisig the multisig_signers are passed to the token program
+    #[allow(clippy::too_many_arguments)]
+    fn invoke_transfer_multisig<'a>(
+        token_program: &AccountInfo<'a>,
+        source: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        authority: &AccountInfo<'a>,
+        multisig_signers: &[AccountInfo<'a>],
+        info: &MintInfo,
+        remaining_accounts: &[AccountInfo<'a>],
+        amount: u64,
+        signers: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let signer_pubkeys: Vec<&Pubkey> = multisig_signers.iter().map(|signer| signer.key).collect();
+        if *token_program.key == spl_token::id() {
+            let ix = spl_token::instruction::transfer(
+                token_program.key,
+                source.key,
+                destination.key,
+                authority.key,
+                &signer_pubkeys,
+                amount,
+            )?;
+            let mut account_infos = vec![source.clone(), destination.clone(), authority.clone()];
+            account_infos.extend(multisig_signers.iter().cloned());
+            account_infos.push(token_program.clone());
+            return invoke_signed(&ix, &account_infos, signers);
+        }
+
+        let (mint, extra_accounts) = remaining_accounts.split_first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if *mint.key != info.mint {
+            return Err(MetamaskError::TokenNotRegistered.into());
+        }
+        {
+            let mint_data = mint.data.borrow();
+            let received = token_2022::expected_received_amount(&mint_data, amount, Clock::get()?.epoch);
+            info!(&format!("Expected received amount: {}", received));
+            if let Some(hook_program) = token_2022::transfer_hook_program_id(&mint_data) {
+                if !extra_accounts.iter().any(|account| *account.key == hook_program) {
+                    return Err(ProgramError::NotEnoughAccountKeys);
+                }
+            }
+        }
+
+        let mut ix = spl_token::instruction::transfer_checked(
+            token_program.key,
+            source.key,
+            mint.key,
+            destination.key,
+            authority.key,
+            &signer_pubkeys,
+            amount,
+            info.decimals,
+        )?;
+        ix.accounts.extend(extra_accounts.iter().map(|account| AccountMeta {
+            pubkey: *account.key,
+            is_signer: account.is_signer,
+            is_writable: account.is_writable,
+        }));
+
+        let mut account_infos = vec![source.clone(), mint.clone(), destination.clone(), authority.clone()];
+        account_infos.extend(multisig_signers.iter().cloned());
+        account_infos.extend(extra_accounts.iter().cloned());
+        account_infos.push(token_program.clone());
+        invoke_signed(&ix, &account_infos, signers)
+    }
+
+    /// Emit event by invoking `OnEvent` of the wrapper itself. The wrapper program account
+    /// must be passed to the instruction, otherwise event data is written to the log.
+    fn emit_event(program_id: &Pubkey, accounts: &[AccountInfo], event: MetamaskInstruction) -> ProgramResult {
+        let data = event.pack();
+        match accounts.iter().find(|account| account.key == program_id) {
+            Some(program) => {
+                let ix = Instruction {
+                    program_id: *program_id,
+                    accounts: vec![],
+                    data,
+                };
+                invoke(&ix, &[program.clone()])
+            }
+            None => {
+                info!(&format!("Event: {}", hex::encode(&data)));
+                Ok(())
+            }
+        }
+    }
+
+    /// Processes an [InitializeMint](enum.Instruction.html).
+    pub fn process_initialize_mint(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        eth_token: &[u8;20],
+        nonce: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let mint = next_account_info(account_info_iter)?;
+        let system_id = next_account_info(account_info_iter)?;
+        let rent_id = next_account_info(account_info_iter)?;
+        let user = next_account_info(account_info_iter)?;
+
+        info!(&bs58::encode(mint_info.key).into_string());
+        info!(&bs58::encode(mint.key).into_string());
+        info!(&hex::encode(eth_token));
+
+        let decimals = Self::mint_decimals(mint)?;
+
+        let rent = &Rent::from_account_info(rent_id)?;
+
+        let seeds = [mint.key.as_ref(), "mint".as_ref(), &[nonce]];
+        let signers = &[&seeds[..]];
+
+        let ix = solana_program::system_instruction::create_account(
+            user.key,
+            mint_info.key,
+            rent.minimum_balance(MintInfo::LEN),
+            MintInfo::LEN as u64,
+            program_id,);
+        invoke_signed(&ix, &[mint_info.clone(), system_id.clone(), user.clone()], signers)?;
+        info!("Create account done");
+
+        let info = MintInfo::unpack_unchecked(&mint_info.data.borrow())?;
+        if info.is_initialized() {
+            return Err(MetamaskError::RegistryAlreadyExists.into());
+        }
+
+        let obj = MintInfo {
+            mint: *mint.key,
+            decimals,
+            eth_token: *eth_token,
+        };
+        MintInfo::pack(obj, &mut mint_info.data.borrow_mut())?;
+        Ok(())
+    }
+
     /// Processes an [Transfer](enum.Instruction.html).
     pub fn process_transfer(
         accounts: &[AccountInfo],
+        program_id: &Pubkey,
         amount: u64,
         nonce: u8,
         eth_token: &[u8;20],
@@ -172,27 +492,362 @@ impl Processor {
         let source = next_account_info(account_info_iter)?;
         let destination = next_account_info(account_info_iter)?;
         let authority = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let config = next_account_info(account_info_iter)?;
 
         let seeds = [&eth_token[..20], &eth_acc[..20], &[nonce]];
+        let (info, _) = Self::check_transfer(program_id, token_program, source, authority, mint_info, config, eth_token, &seeds)?;
+
         let signers = &[&seeds[..]];
-        let ix = spl_token::instruction::transfer(
-            token_program.key,
-            source.key,
-            destination.key,
-            authority.key,
-            &[],
-            amount,
+        Self::invoke_transfer(
+            token_program, source, destination, authority, &info,
+            account_info_iter.as_slice(), amount, signers,
         )?;
-        invoke_signed(
-            &ix,
-            &[source.clone(), destination.clone(), authority.clone(), token_program.clone()],
-            signers,
-        )
+        Self::emit_event(program_id, accounts, event::transfer(eth_token, source.key, destination.key, amount))
+    }
+
+    /// Processes a [TransferToWallet](enum.Instruction.html).
+    pub fn process_transfer_to_wallet(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        amount: u64,
+        nonce: u8,
+        eth_token: &[u8;20],
+        eth_acc: &[u8;20],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_program = next_account_info(account_info_iter)?;
+        let source = next_account_info(account_info_iter)?;
+        let destination = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let config = next_account_info(account_info_iter)?;
+        let payer = next_account_info(account_info_iter)?;
+        let wallet = next_account_info(account_info_iter)?;
+        let mint = next_account_info(account_info_iter)?;
+        let system_id = next_account_info(account_info_iter)?;
+        let associated_token_program = next_account_info(account_info_iter)?;
+        let rent_id = next_account_info(account_info_iter)?;
+
+        let seeds = [&eth_token[..20], &eth_acc[..20], &[nonce]];
+        let (info, _) = Self::check_transfer(program_id, token_program, source, authority, mint_info, config, eth_token, &seeds)?;
+        if *mint.key != info.mint {
+            return Err(MetamaskError::UnknownMint.into());
+        }
+        if *associated_token_program.key != spl_associated_token_account::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // Associated token account program checks that destination is derived from wallet and mint
+        if destination.data_is_empty() {
+            info!("Create associated token account");
+            let ix = Instruction {
+                program_id: *associated_token_program.key,
+                accounts: vec![
+                    AccountMeta::new(*payer.key, true),
+                    AccountMeta::new(*destination.key, false),
+                    AccountMeta::new_readonly(*wallet.key, false),
+                    AccountMeta::new_readonly(*mint.key, false),
+                    AccountMeta::new_readonly(*system_id.key, false),
+                    AccountMeta::new_readonly(*token_program.key, false),
+                    AccountMeta::new_readonly(*rent_id.key, false),
+                ],
+                data: vec![],
+            };
+            invoke(
+                &ix,
+                &[
+                    payer.clone(), destination.clone(), wallet.clone(), mint.clone(),
+                    system_id.clone(), token_program.clone(), rent_id.clone(),
+                    associated_token_program.clone(),
+                ],
+            )?;
+        } else if Self::unpack_token_account(destination)?.owner != *wallet.key {
+            return Err(MetamaskError::InvalidAuthority.into());
+        }
+
+        // Token-2022 transfer expects mint followed by extra accounts of the transfer hook
+        let mut remaining_accounts = vec![mint.clone()];
+        remaining_accounts.extend(account_info_iter.cloned());
+
+        let signers = &[&seeds[..]];
+        Self::invoke_transfer(
+            token_program, source, destination, authority, &info,
+            &remaining_accounts, amount, signers,
+        )?;
+        Self::emit_event(program_id, accounts, event::transfer(eth_token, source.key, destination.key, amount))
+    }
+
+    /// Processes a [TransferMultisig](enum.Instruction.html).
+    pub fn process_transfer_multisig(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        amount: u64,
+        eth_token: &[u8;20],
+        signer_count: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_program = next_account_info(account_info_iter)?;
+        let source = next_account_info(account_info_iter)?;
+        let destination = next_account_info(account_info_iter)?;
+        let multisig = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let config = next_account_info(account_info_iter)?;
+
+        let signer_count = signer_count as usize;
+        let remaining_accounts = account_info_iter.as_slice();
+        if remaining_accounts.len() < signer_count {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (multisig_signers, remaining_accounts) = remaining_accounts.split_at(signer_count);
+
+        Self::check_not_paused(program_id, config)?;
+        let (info, source_account) = Self::check_mint_info(program_id, token_program, mint_info, source, eth_token)?;
+        if source_account.owner != *multisig.key || multisig.owner != token_program.key {
+            return Err(MetamaskError::InvalidAuthority.into());
+        }
+
+        // Token program verifies signatures too, check here to return clear error
+        let multisig_state = spl_token::state::Multisig::unpack(&multisig.data.borrow())?;
+        let valid_signers = multisig_signers.iter()
+            .filter(|signer| signer.is_signer)
+            .filter(|signer| multisig_state.signers[..multisig_state.n as usize].contains(signer.key))
+            .count();
+        if valid_signers < multisig_state.m as usize {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Self::invoke_transfer_multisig(
+            token_program, source, destination, multisig, multisig_signers, &info,
+            remaining_accounts, amount, &[],
+        )?;
+        Self::emit_event(program_id, accounts, event::transfer(eth_token, source.key, destination.key, amount))
+    }
+
+    /// Processes a [TransferWithMemo](enum.Instruction.html).
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_transfer_with_memo(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        amount: u64,
+        nonce: u8,
+        eth_token: &[u8;20],
+        eth_acc: &[u8;20],
+        memo: &str,
+        reference_count: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_program = next_account_info(account_info_iter)?;
+        let source = next_account_info(account_info_iter)?;
+        let destination = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let config = next_account_info(account_info_iter)?;
+        let memo_program = next_account_info(account_info_iter)?;
+
+        // Reference keys are only recorded in transaction for indexers
+        for _ in 0..reference_count {
+            next_account_info(account_info_iter)?;
+        }
+
+        let seeds = [&eth_token[..20], &eth_acc[..20], &[nonce]];
+        let (info, _) = Self::check_transfer(program_id, token_program, source, authority, mint_info, config, eth_token, &seeds)?;
+        if *memo_program.key != spl_memo::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let signers = &[&seeds[..]];
+        let ix = spl_memo::build_memo(memo.as_bytes(), &[authority.key]);
+        invoke_signed(&ix, &[authority.clone(), memo_program.clone()], signers)?;
+
+        Self::invoke_transfer(
+            token_program, source, destination, authority, &info,
+            account_info_iter.as_slice(), amount, signers,
+        )?;
+        Self::emit_event(program_id, accounts, event::transfer(eth_token, source.key, destination.key, amount))
+    }
+
+    /// Processes a [TransferWithFee](enum.Instruction.html).
+    pub fn process_transfer_with_fee(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        amount: u64,
+        nonce: u8,
+        eth_token: &[u8;20],
+        eth_acc: &[u8;20],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_program = next_account_info(account_info_iter)?;
+        let source = next_account_info(account_info_iter)?;
+        let destination = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let fee_account = next_account_info(account_info_iter)?;
+
+        let seeds = [&eth_token[..20], &eth_acc[..20], &[nonce]];
+        let (info, config) = Self::check_transfer(program_id, token_program, source, authority, mint_info, config_info, eth_token, &seeds)?;
+
+        let fee = config.transfer_fee(amount);
+        let signers = &[&seeds[..]];
+        let remaining_accounts = account_info_iter.as_slice();
+        if fee > 0 {
+            if fee_account.owner != token_program.key {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            if Self::unpack_token_account(fee_account)?.owner != config.operator {
+                return Err(MetamaskError::InvalidAuthority.into());
+            }
+            info!(&format!("Transfer fee: {}", fee));
+            Self::invoke_transfer(
+                token_program, source, fee_account, authority, &info,
+                remaining_accounts, fee, signers,
+            )?;
+            Self::emit_event(program_id, accounts, event::transfer(eth_token, source.key, fee_account.key, fee))?;
+        }
+
+        Self::invoke_transfer(
+            token_program, source, destination, authority, &info,
+            remaining_accounts, amount - fee, signers,
+        )?;
+        Self::emit_event(program_id, accounts, event::transfer(eth_token, source.key, destination.key, amount - fee))
+    }
+
+    /// Processes an [Approve](enum.Instruction.html).
+    pub fn process_approve(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        amount: u64,
+        nonce: u8,
+        eth_token: &[u8;20],
+        eth_acc: &[u8;20],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_program = next_account_info(account_info_iter)?;
+        let source = next_account_info(account_info_iter)?;
+        let delegate = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+
+        let (_, source_account) = Self::check_mint_info(program_id, token_program, mint_info, source, eth_token)?;
+
+        let seeds = [&eth_token[..20], &eth_acc[..20], &[nonce]];
+        Self::check_authority(program_id, authority, &seeds)?;
+        if source_account.owner != *authority.key {
+            return Err(MetamaskError::InvalidAuthority.into());
+        }
+
+        let signers = &[&seeds[..]];
+        Self::invoke_approve(token_program, source, delegate, authority, amount, signers)?;
+        Self::emit_event(program_id, accounts, event::approval(eth_token, source.key, delegate.key, amount))
+    }
+
+    /// Processes an [Permit](enum.Instruction.html).
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_permit(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        nonce: u8,
+        spender_nonce: u8,
+        eth_token: &[u8;20],
+        spender: &[u8;20],
+        value: u64,
+        deadline: u64,
+        owner: &[u8;20],
+        message: &[u8;66],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_program = next_account_info(account_info_iter)?;
+        let source = next_account_info(account_info_iter)?;
+        let delegate = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let sysvar_info = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+
+        let (_, source_account) = Self::check_mint_info(program_id, token_program, mint_info, source, eth_token)?;
+        permit::check_secp256k1_instruction(sysvar_info, PERMIT_SIGNED_DATA_OFFSET)?;
+        let config = Self::load_config(program_id, config_info)?;
+
+        if owner_info.owner != program_id {
+            return Err(MetamaskError::InvalidPermit.into());
+        }
+        let mut owner_data = AccInfo::unpack_unchecked(&owner_info.data.borrow())?;
+        if owner_data.eth_acc != *owner {
+            return Err(MetamaskError::InvalidPermit.into());
+        }
+
+        let expected = permit::message(config.chain_id, eth_token, owner, spender, value, u64::from(owner_data.trx_count), deadline);
+        if *message != expected {
+            return Err(MetamaskError::InvalidPermit.into());
+        }
+        if Clock::get()?.unix_timestamp as u64 > deadline {
+            return Err(MetamaskError::PermitExpired.into());
+        }
+
+        owner_data.trx_count = owner_data.trx_count.checked_add(1).ok_or(MetamaskError::InvalidPermit)?;
+        AccInfo::pack(owner_data, &mut owner_info.data.borrow_mut())?;
+
+        let seeds = [&eth_token[..20], &owner[..20], &[nonce]];
+        Self::check_authority(program_id, authority, &seeds)?;
+        if source_account.owner != *authority.key {
+            return Err(MetamaskError::InvalidAuthority.into());
+        }
+        // The approval goes to the signed spender only
+        Self::check_authority(program_id, delegate, &[&eth_token[..20], &spender[..20], &[spender_nonce]])?;
+
+        let signers = &[&seeds[..]];
+        Self::invoke_approve(token_program, source, delegate, authority, value, signers)?;
+        Self::emit_event(program_id, accounts, event::approval(eth_token, source.key, delegate.key, value))
+    }
+
+    /// Processes an [TransferFrom](enum.Instruction.html).
+    pub fn process_transfer_from(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        amount: u64,
+        nonce: u8,
+        eth_token: &[u8;20],
+        eth_acc: &[u8;20],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_program = next_account_info(account_info_iter)?;
+        let source = next_account_info(account_info_iter)?;
+        let destination = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let config = next_account_info(account_info_iter)?;
+
+        Self::check_not_paused(program_id, config)?;
+        let (info, source_account) = Self::check_mint_info(program_id, token_program, mint_info, source, eth_token)?;
+
+        // Spender authority acts as delegate, SPL token decreases delegated amount
+        let seeds = [&eth_token[..20], &eth_acc[..20], &[nonce]];
+        Self::check_authority(program_id, authority, &seeds)?;
+        if source_account.delegate != COption::Some(*authority.key) || source_account.delegated_amount < amount {
+            return Err(MetamaskError::InsufficientAllowance.into());
+        }
+
+        let signers = &[&seeds[..]];
+        Self::invoke_transfer(
+            token_program, source, destination, authority, &info,
+            account_info_iter.as_slice(), amount, signers,
+        )?;
+        Self::emit_event(program_id, accounts, event::transfer(eth_token, source.key, destination.key, amount))
     }
 
     /// Processes an [TransferLamports](enum.Instruction.html).
     pub fn process_transfer_lamports(
         accounts: &[AccountInfo],
+        program_id: &Pubkey,
         amount: u64,
         nonce: u8,
         eth_acc: &[u8;20],
@@ -202,6 +857,9 @@ impl Processor {
         let source = next_account_info(account_info_iter)?;
         let destination = next_account_info(account_info_iter)?;
         let system_id = next_account_info(account_info_iter)?;
+        let config = next_account_info(account_info_iter)?;
+
+        Self::check_not_paused(program_id, config)?;
 
         let seeds = [&eth_acc[..20], "lamports".as_ref(), &[nonce]];
         let signers = &[&seeds[..]];
@@ -217,6 +875,155 @@ impl Processor {
         )
     }
 
+    /// Processes an [InitializeConfig](enum.Instruction.html).
+    pub fn process_initialize_config(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        admin: &Pubkey,
+        recovery: &Pubkey,
+        nonce: u8,
+        chain_id: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_info = next_account_info(account_info_iter)?;
+        let system_id = next_account_info(account_info_iter)?;
+        let rent_id = next_account_info(account_info_iter)?;
+        let user = next_account_info(account_info_iter)?;
+
+        // Only canonical address is accepted so there is single config
+        let (expected, expected_nonce) = find_config_address(program_id);
+        if *config_info.key != expected || nonce != expected_nonce {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let rent = &Rent::from_account_info(rent_id)?;
+
+        let seeds = [CONFIG_SEED, &[nonce]];
+        let signers = &[&seeds[..]];
+
+        let ix = solana_program::system_instruction::create_account(
+            user.key,
+            config_info.key,
+            rent.minimum_balance(Config::LEN),
+            Config::LEN as u64,
+            program_id,);
+        invoke_signed(&ix, &[config_info.clone(), system_id.clone(), user.clone()], signers)?;
+        info!("Create account done");
+
+        let config = Config {
+            admin: *admin,
+            recovery: *recovery,
+            paused: false,
+            nonce,
+            operator: Pubkey::default(),
+            fee_basis_points: 0,
+            chain_id,
+        };
+        Config::pack(config, &mut config_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes [Pause and Unpause](enum.Instruction.html).
+    pub fn process_set_paused(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        paused: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_info = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+
+        let mut config = Self::load_config_by_admin(program_id, config_info, admin)?;
+        config.paused = paused;
+        Config::pack(config, &mut config_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes a [SetFee](enum.Instruction.html).
+    pub fn process_set_fee(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        operator: &Pubkey,
+        fee_basis_points: u16,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_info = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+
+        if fee_basis_points > Config::MAX_FEE_BASIS_POINTS {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut config = Self::load_config_by_admin(program_id, config_info, admin)?;
+        config.operator = *operator;
+        config.fee_basis_points = fee_basis_points;
+        Config::pack(config, &mut config_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes a [RescueTokens](enum.Instruction.html).
+    pub fn process_rescue_tokens(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        amount: u64,
+        nonce: u8,
+        eth_token: &[u8;20],
+        eth_acc: &[u8;20],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let token_program = next_account_info(account_info_iter)?;
+        let config_info = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let source = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let destination = next_account_info(account_info_iter)?;
+
+        let config = Self::load_config_by_admin(program_id, config_info, admin)?;
+
+        if !token_2022::is_token_program(token_program.key)
+                || source.owner != token_program.key || destination.owner != token_program.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let source_account = Self::unpack_token_account(source)?;
+        let destination_account = Self::unpack_token_account(destination)?;
+        if destination_account.owner != config.recovery {
+            return Err(MetamaskError::InvalidAuthority.into());
+        }
+
+        let seeds = [&eth_token[..20], &eth_acc[..20], &[nonce]];
+        Self::check_authority(program_id, authority, &seeds)?;
+        if source_account.owner != *authority.key {
+            return Err(MetamaskError::InvalidAuthority.into());
+        }
+
+        // Authority of registered balance is its balance info account
+        if authority.owner == program_id && authority.data_len() == BalanceInfo::LEN {
+            let balance = BalanceInfo::unpack_unchecked(&authority.data.borrow())?;
+            if balance.account == *source.key {
+                return Err(MetamaskError::BalanceRegistered.into());
+            }
+        }
+
+        let remaining_accounts = account_info_iter.as_slice();
+        let decimals = match remaining_accounts.first() {
+            Some(mint) if *token_program.key == token_2022::id() => Self::mint_decimals(mint)?,
+            _ => 0,
+        };
+        let info = MintInfo {
+            mint: source_account.mint,
+            decimals,
+            eth_token: *eth_token,
+        };
+
+        let signers = &[&seeds[..]];
+        Self::invoke_transfer(
+            token_program, source, destination, authority, &info,
+            remaining_accounts, amount, signers,
+        )?;
+        Self::emit_event(program_id, accounts, event::transfer(eth_token, source.key, destination.key, amount))
+    }
+
     /// Processes an [Instruction](enum.Instruction.html).
     pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
         let instruction = MetamaskInstruction::unpack(input)?;
@@ -240,11 +1047,70 @@ impl Processor {
                 info!(&hex::encode(&eth_token));
                 info!(&hex::encode(&eth_acc));
                 Self::process_transfer(
-                    accounts, amount, nonce, &eth_token, &eth_acc,
+                    accounts, &program_id, amount, nonce, &eth_token, &eth_acc,
                 )
             }
             MetamaskInstruction::TransferLamports {amount, nonce, eth_acc} => {
-                Self::process_transfer_lamports(accounts, amount, nonce, &eth_acc)
+                Self::process_transfer_lamports(accounts, &program_id, amount, nonce, &eth_acc)
+            }
+            MetamaskInstruction::InitializeMint {eth_token, nonce,} => {
+                info!("Instruction: Initialize mint");
+                Self::process_initialize_mint(accounts, &program_id, &eth_token, nonce,)
+            }
+            MetamaskInstruction::Approve {amount, nonce, eth_token, eth_acc,} => {
+                info!("Instruction: Approve");
+                Self::process_approve(accounts, &program_id, amount, nonce, &eth_token, &eth_acc,)
+            }
+            MetamaskInstruction::TransferFrom {amount, nonce, eth_token, eth_acc,} => {
+                info!("Instruction: TransferFrom");
+                Self::process_transfer_from(accounts, &program_id, amount, nonce, &eth_token, &eth_acc,)
+            }
+            MetamaskInstruction::InitializeConfig {admin, recovery, nonce, chain_id,} => {
+                info!("Instruction: Initialize config");
+                Self::process_initialize_config(accounts, &program_id, &admin, &recovery, nonce, chain_id,)
+            }
+            MetamaskInstruction::Pause => {
+                info!("Instruction: Pause");
+                Self::process_set_paused(accounts, &program_id, true)
+            }
+            MetamaskInstruction::Unpause => {
+                info!("Instruction: Unpause");
+                Self::process_set_paused(accounts, &program_id, false)
+            }
+            MetamaskInstruction::RescueTokens {amount, nonce, eth_token, eth_acc,} => {
+                info!("Instruction: Rescue tokens");
+                Self::process_rescue_tokens(accounts, &program_id, amount, nonce, &eth_token, &eth_acc,)
+            }
+            MetamaskInstruction::SetFee {operator, fee_basis_points,} => {
+                info!("Instruction: Set fee");
+                Self::process_set_fee(accounts, &program_id, &operator, fee_basis_points,)
+            }
+            MetamaskInstruction::TransferWithFee {amount, nonce, eth_token, eth_acc,} => {
+                info!("Instruction: Transfer with fee");
+                Self::process_transfer_with_fee(accounts, &program_id, amount, nonce, &eth_token, &eth_acc,)
+            }
+            MetamaskInstruction::TransferMultisig {amount, eth_token, signer_count,} => {
+                info!("Instruction: Transfer multisig");
+                Self::process_transfer_multisig(accounts, &program_id, amount, &eth_token, signer_count,)
+            }
+            MetamaskInstruction::TransferToWallet {amount, nonce, eth_token, eth_acc,} => {
+                info!("Instruction: Transfer to wallet");
+                Self::process_transfer_to_wallet(accounts, &program_id, amount, nonce, &eth_token, &eth_acc,)
+            }
+            MetamaskInstruction::OnEvent {..} => {
+                Ok(())
+            }
+            MetamaskInstruction::TransferWithMemo {amount, nonce, eth_token, eth_acc, memo, reference_count,} => {
+                info!("Instruction: Transfer with memo");
+                Self::process_transfer_with_memo(
+                    accounts, &program_id, amount, nonce, &eth_token, &eth_acc, &memo, reference_count,
+                )
+            }
+            MetamaskInstruction::Permit {nonce, spender_nonce, eth_token, spender, value, deadline, owner, message, ..} => {
+                info!("Instruction: Permit");
+                Self::process_permit(
+                    accounts, &program_id, nonce, spender_nonce, &eth_token, &spender, value, deadline, &owner, &message.0,
+                )
             }
         }
     }
@@ -261,6 +1127,14 @@ impl PrintProgramError for MetamaskError {
             MetamaskError::BalanceAlreadyRegistered => info!("Error: Same balance is already registered"),
             MetamaskError::TokenNotRegistered => info!("Error: Token is not registered"),
             MetamaskError::InvalidInstruction => info!("Error: InvalidInstruction"),
+            MetamaskError::InvalidPermit => info!("Error: Invalid permit"),
+            MetamaskError::PermitExpired => info!("Error: Permit expired"),
+            MetamaskError::UnknownMint => info!("Error: Unknown mint"),
+            MetamaskError::InsufficientAllowance => info!("Error: Insufficient allowance"),
+            MetamaskError::InvalidAuthority => info!("Error: Invalid authority"),
+            MetamaskError::RegistryAlreadyExists => info!("Error: Registry already exists"),
+            MetamaskError::Paused => info!("Error: Program is paused"),
+            MetamaskError::BalanceRegistered => info!("Error: Token account is registered balance"),
         }
     }
 }
@@ -107,4 +107,145 @@ impl Pack for BalanceInfo {
     }
 }
 
+/// MintInfo data. Registry record of SPL mint, created at address derived from
+/// create_program_address([mint, 'mint'], program_id).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MintInfo {
+    pub mint: Pubkey,
+    pub decimals: u8,
+    pub eth_token: [u8;20],
+}
+impl Sealed for MintInfo {}
+impl IsInitialized for MintInfo {
+    fn is_initialized(&self) -> bool {
+        self.mint != Pubkey::new(&[0;32])
+    }
+}
+impl Pack for MintInfo {
+    const LEN: usize = 53;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 53];
+        let (mint, decimals, eth_token) = array_refs![src, 32, 1, 20];
+        Ok(MintInfo {
+            mint: Pubkey::new_from_array(*mint),
+            decimals: decimals[0],
+            eth_token: *eth_token,
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 53];
+        let (mint, decimals, eth_token) = mut_array_refs![dst, 32, 1, 20];
+        mint.copy_from_slice(self.mint.as_ref());
+        decimals[0] = self.decimals;
+        eth_token.copy_from_slice(self.eth_token.as_ref());
+    }
+}
+
+/// Seed of the config account address
+pub const CONFIG_SEED: &[u8] = b"config";
 
+/// Address of config account and its canonical nonce
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// Config data. Program settings, created at address derived from
+/// create_program_address(['config'], program_id) with canonical nonce.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Config {
+    pub admin: Pubkey,
+    pub recovery: Pubkey,
+    pub paused: bool,
+    pub nonce: u8,
+    /// Owner of token accounts which receive transfer fees
+    pub operator: Pubkey,
+    /// Transfer fee in basis points of transferred amount
+    pub fee_basis_points: u16,
+    /// Chain id of the permit domain, same as chain id of evm_loader
+    pub chain_id: u64,
+}
+impl Sealed for Config {}
+impl IsInitialized for Config {
+    fn is_initialized(&self) -> bool {
+        self.admin != Pubkey::new(&[0;32])
+    }
+}
+impl Pack for Config {
+    const LEN: usize = 108;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 108];
+        let (admin, recovery, paused, nonce, operator, fee_basis_points, chain_id) =
+            array_refs![src, 32, 32, 1, 1, 32, 2, 8];
+        let paused = match paused {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Config {
+            admin: Pubkey::new_from_array(*admin),
+            recovery: Pubkey::new_from_array(*recovery),
+            paused,
+            nonce: nonce[0],
+            operator: Pubkey::new_from_array(*operator),
+            fee_basis_points: u16::from_le_bytes(*fee_basis_points),
+            chain_id: u64::from_le_bytes(*chain_id),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 108];
+        let (admin, recovery, paused, nonce, operator, fee_basis_points, chain_id) =
+            mut_array_refs![dst, 32, 32, 1, 1, 32, 2, 8];
+        admin.copy_from_slice(self.admin.as_ref());
+        recovery.copy_from_slice(self.recovery.as_ref());
+        paused[0] = self.paused as u8;
+        nonce[0] = self.nonce;
+        operator.copy_from_slice(self.operator.as_ref());
+        *fee_basis_points = self.fee_basis_points.to_le_bytes();
+        *chain_id = self.chain_id.to_le_bytes();
+    }
+}
+
+impl Config {
+    /// Maximum fee: whole transferred amount
+    pub const MAX_FEE_BASIS_POINTS: u16 = 10_000;
+
+    /// Fee deducted from transferred amount
+    pub fn transfer_fee(&self, amount: u64) -> u64 {
+        (u128::from(amount) * u128::from(self.fee_basis_points) / u128::from(Self::MAX_FEE_BASIS_POINTS)) as u64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_config_packing() {
+        let config = Config {
+            admin: Pubkey::new(&[1; 32]),
+            recovery: Pubkey::new(&[2; 32]),
+            paused: true,
+            nonce: 254,
+            operator: Pubkey::new(&[3; 32]),
+            fee_basis_points: 30,
+            chain_id: 111,
+        };
+        let mut data = [0_u8; Config::LEN];
+        Config::pack(config, &mut data).unwrap();
+        assert_eq!(Config::unpack(&data).unwrap(), config);
+
+        data[64] = 2;
+        assert_eq!(Config::unpack(&data), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_transfer_fee() {
+        let mut config = Config { fee_basis_points: 30, ..Config::default() };
+        assert_eq!(config.transfer_fee(1_000_000), 3_000);
+        assert_eq!(config.transfer_fee(10), 0);
+        config.fee_basis_points = Config::MAX_FEE_BASIS_POINTS;
+        assert_eq!(config.transfer_fee(u64::MAX), u64::MAX);
+    }
+}
@@ -0,0 +1,159 @@
+//! SPL Token-2022 support: program id and extensions which affect transfers
+
+use arrayref::{array_ref, array_refs};
+use solana_program::{
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+
+solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAs6EPsYjNXiD3kG2xS8zN9Hc");
+
+/// Extension type of transfer fee config of mint
+pub const TRANSFER_FEE_CONFIG_EXTENSION: u16 = 1;
+/// Extension type of transfer hook of mint
+pub const TRANSFER_HOOK_EXTENSION: u16 = 14;
+
+const MAX_FEE_BASIS_POINTS: u128 = 10_000;
+
+/// Check that program is one of the supported token programs
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || *program_id == id()
+}
+
+/// Find extension data in the account data of Token-2022 mint or account.
+/// Extensions are stored after base account data (padded to the size of token account)
+/// and account type byte as list of (type: u16, length: u16, value).
+pub fn get_extension(data: &[u8], extension_type: u16) -> Option<&[u8]> {
+    let mut offset = spl_token::state::Account::LEN + 1;
+    while offset + 4 <= data.len() {
+        let (ext_type, ext_len) = array_refs![array_ref![data, offset, 4], 2, 2];
+        let ext_type = u16::from_le_bytes(*ext_type);
+        let ext_len = u16::from_le_bytes(*ext_len) as usize;
+        offset += 4;
+        if ext_type == 0 || offset + ext_len > data.len() {
+            return None;
+        }
+        if ext_type == extension_type {
+            return Some(&data[offset..offset + ext_len]);
+        }
+        offset += ext_len;
+    }
+    None
+}
+
+/// Transfer fee which takes effect from epoch
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransferFee {
+    pub epoch: u64,
+    pub maximum_fee: u64,
+    pub transfer_fee_basis_points: u16,
+}
+impl TransferFee {
+    fn unpack(src: &[u8; 18]) -> Self {
+        let (epoch, maximum_fee, basis_points) = array_refs![src, 8, 8, 2];
+        TransferFee {
+            epoch: u64::from_le_bytes(*epoch),
+            maximum_fee: u64::from_le_bytes(*maximum_fee),
+            transfer_fee_basis_points: u16::from_le_bytes(*basis_points),
+        }
+    }
+
+    /// Fee withheld from transferred amount (rounded up, limited by maximum fee)
+    pub fn calculate_fee(&self, amount: u64) -> u64 {
+        let basis_points = u128::from(self.transfer_fee_basis_points);
+        if basis_points == 0 || amount == 0 {
+            return 0;
+        }
+        let fee = (u128::from(amount) * basis_points + MAX_FEE_BASIS_POINTS - 1) / MAX_FEE_BASIS_POINTS;
+        std::cmp::min(fee, u128::from(self.maximum_fee)) as u64
+    }
+}
+
+/// Transfer fee of the mint effective in epoch
+pub fn transfer_fee(mint_data: &[u8], epoch: u64) -> Option<TransferFee> {
+    let config = get_extension(mint_data, TRANSFER_FEE_CONFIG_EXTENSION)?;
+    if config.len() < 108 {
+        return None;
+    }
+    // config and withdraw authorities, withheld amount, older fee, newer fee
+    let (_, _, _, older, newer) = array_refs![array_ref![config, 0, 108], 32, 32, 8, 18, 18];
+    let newer = TransferFee::unpack(newer);
+    if epoch >= newer.epoch {
+        Some(newer)
+    } else {
+        Some(TransferFee::unpack(older))
+    }
+}
+
+/// Amount which destination receives when amount is transferred
+pub fn expected_received_amount(mint_data: &[u8], amount: u64, epoch: u64) -> u64 {
+    let fee = transfer_fee(mint_data, epoch).map_or(0, |fee| fee.calculate_fee(amount));
+    amount.saturating_sub(fee)
+}
+
+/// Program called by Token-2022 on each transfer of the mint
+pub fn transfer_hook_program_id(mint_data: &[u8]) -> Option<Pubkey> {
+    let hook = get_extension(mint_data, TRANSFER_HOOK_EXTENSION)?;
+    if hook.len() < 64 {
+        return None;
+    }
+    let program_id = Pubkey::new_from_array(*array_ref![hook, 32, 32]);
+    if program_id == Pubkey::default() {
+        None
+    } else {
+        Some(program_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mint_with_extension(extension_type: u16, value: &[u8]) -> Vec<u8> {
+        let mut data = vec![0_u8; spl_token::state::Account::LEN];
+        data.push(1); // AccountType::Mint
+        data.extend_from_slice(&extension_type.to_le_bytes());
+        data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        data.extend_from_slice(value);
+        data
+    }
+
+    fn fee_bytes(epoch: u64, maximum_fee: u64, basis_points: u16) -> Vec<u8> {
+        let mut data = epoch.to_le_bytes().to_vec();
+        data.extend_from_slice(&maximum_fee.to_le_bytes());
+        data.extend_from_slice(&basis_points.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_calculate_fee() {
+        let fee = TransferFee { epoch: 0, maximum_fee: 5_000, transfer_fee_basis_points: 100 };
+        assert_eq!(fee.calculate_fee(0), 0);
+        assert_eq!(fee.calculate_fee(1), 1);
+        assert_eq!(fee.calculate_fee(10_000), 100);
+        assert_eq!(fee.calculate_fee(1_000_000), 5_000);
+    }
+
+    #[test]
+    fn test_expected_received_amount() {
+        let mut config = vec![0_u8; 72];
+        config.extend(fee_bytes(0, u64::MAX, 50));
+        config.extend(fee_bytes(10, u64::MAX, 200));
+        let mint = mint_with_extension(TRANSFER_FEE_CONFIG_EXTENSION, &config);
+
+        assert_eq!(expected_received_amount(&mint, 10_000, 9), 9_950);
+        assert_eq!(expected_received_amount(&mint, 10_000, 10), 9_800);
+        assert_eq!(expected_received_amount(&[0_u8; 82], 10_000, 10), 10_000);
+    }
+
+    #[test]
+    fn test_transfer_hook_program_id() {
+        let hook_program = Pubkey::new_unique();
+        let mut hook = vec![0_u8; 32];
+        hook.extend_from_slice(hook_program.as_ref());
+        let mint = mint_with_extension(TRANSFER_HOOK_EXTENSION, &hook);
+
+        assert_eq!(transfer_hook_program_id(&mint), Some(hook_program));
+        assert_eq!(transfer_hook_program_id(&mint_with_extension(TRANSFER_HOOK_EXTENSION, &[0_u8; 64])), None);
+    }
+}
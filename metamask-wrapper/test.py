@@ -32,6 +32,7 @@ token_program = 'TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA'
 metamask_program = '4oLBsQAa3jwkJZoStAektoxq1mkwiFS8WVSjVhTzwM3w'
 wrapper_program = 'HB7yN5ZLPi1cLUAZs6QF4y6ZdRN1K4YhGzyRgewF23rD'
 system_id = '11111111111111111111111111111111'
+chain_id = 111
 
 CREATE_ACCOUNT_LAYOUT = cStruct(
     "instruction" / Int32ul,
@@ -243,6 +244,9 @@ class SolanaTests(unittest.TestCase):
         print('Assign token to:', eth_token, self.wrapper.program_address([bytes(eth_token)]))
         trx = Transaction().add(
             self.wrapper.initializeToken(token.pubkey, eth_token, self.acc.public_key()))
+        trx.add(self.wrapper.initializeMint(token.pubkey, eth_token, self.acc.public_key()))
+        if http_client.get_account_info(self.wrapper.configAddress())['result']['value'] is None:
+            trx.add(self.wrapper.initializeConfig(self.acc.public_key(), self.acc.public_key(), self.acc.public_key(), chain_id))
         http_client.send_transaction(trx, self.acc, opts=TxOpts(skip_confirmation=True))
 
         balances = []
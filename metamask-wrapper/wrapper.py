@@ -10,11 +10,18 @@ from construct import Bytes, Int8ul, Int32ul, Int64ul, Pass  # type: ignore
 from construct import Struct as cStruct
 import subprocess
 from eth_keys import keys as eth_keys
+from sha3 import keccak_256
 import random
 
 system_id = '11111111111111111111111111111111'
 rent_id = 'SysvarRent111111111111111111111111111111111'
 token_id = 'TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA'
+memo_id = 'MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr'
+associated_token_id = 'ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL'
+sysvar_instructions_id = 'Sysvar1nstructions1111111111111111111111111'
+secp256k1_id = 'KeccakSecp256k11111111111111111111111111111'
+
+PERMIT_SIGNED_DATA_OFFSET = 59
 
 def create_program_address(seeds, programId):
     seeds_str = ' '.join([s.hex() for s in seeds])
@@ -26,6 +33,18 @@ def create_program_address(seeds, programId):
     (account, nonce) = result.decode('utf8').split('  ')
     return account, int(nonce)
 
+def permit_message(chain_id, eth_token, owner, spender, value, nonce, deadline):
+    word = lambda v: v.to_bytes(32, 'big')
+    address = lambda a: bytes(12) + bytes(a)
+    domain = keccak_256(
+        keccak_256(b'EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)').digest() +
+        keccak_256(b'Metamask Wrapper').digest() + keccak_256(b'1').digest() +
+        word(chain_id) + address(eth_token)).digest()
+    struct = keccak_256(
+        keccak_256(b'Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)').digest() +
+        address(owner) + address(spender) + word(value) + word(nonce) + word(deadline)).digest()
+    return b'\x19\x01' + domain + struct
+
 class EthereumAddress:
     def __init__(self, data, private=None):
         if isinstance(data, str):
@@ -69,6 +88,33 @@ INITIALIZE_BALANCE_LAYOUT = cStruct(
     "nonce" / Int8ul,
 )
 
+PERMIT_LAYOUT = cStruct(
+    "instruction" / Int8ul,
+    "nonce" / Int8ul,
+    "spender_nonce" / Int8ul,
+    "eth_token" / Bytes(20),
+    "spender" / Bytes(20),
+    "value" / Int64ul,
+    "deadline" / Int64ul,
+    "owner" / Bytes(20),
+    "signature" / Bytes(65),
+    "message" / Bytes(66),
+)
+
+INITIALIZE_CONFIG_LAYOUT = cStruct(
+    "instruction" / Int8ul,
+    "admin" / PUBLIC_KEY_LAYOUT,
+    "recovery" / PUBLIC_KEY_LAYOUT,
+    "nonce" / Int8ul,
+    "chain_id" / Int64ul,
+)
+
+INITIALIZE_MINT_LAYOUT = cStruct(
+    "instruction" / Int8ul,
+    "eth_token" / Bytes(20),
+    "nonce" / Int8ul,
+)
+
 ACCOUNT_INFO_LAYOUT = cStruct(
     "eth_acc" / Bytes(20),
     "trx_count" / Int32ul,
@@ -85,6 +131,22 @@ BALANCE_INFO_LAYOUT = cStruct(
     "eth_acc" / Bytes(20),
 )
 
+CONFIG_LAYOUT = cStruct(
+    "admin" / PUBLIC_KEY_LAYOUT,
+    "recovery" / PUBLIC_KEY_LAYOUT,
+    "paused" / Int8ul,
+    "nonce" / Int8ul,
+    "operator" / PUBLIC_KEY_LAYOUT,
+    "fee_basis_points" / Bytes(2),
+    "chain_id" / Int64ul,
+)
+
+MINT_INFO_LAYOUT = cStruct(
+    "mint" / PUBLIC_KEY_LAYOUT,
+    "decimals" / Int8ul,
+    "eth_token" / Bytes(20),
+)
+
 TRANSFER_LAYOUT = cStruct(
     "instruction" / Int8ul,
     "amount" / Int64ul,
@@ -128,6 +190,16 @@ class BalanceInfo(NamedTuple):
         cont = BALANCE_INFO_LAYOUT.parse(data)
         return BalanceInfo(PublicKey(cont.account), cont.eth_token.hex(), cont.eth_acc.hex())
 
+class MintInfo(NamedTuple):
+    mint: PublicKey
+    decimals: int
+    eth_token: bytes
+
+    @staticmethod
+    def frombytes(data):
+        cont = MINT_INFO_LAYOUT.parse(data)
+        return MintInfo(PublicKey(cont.mint), cont.decimals, cont.eth_token)
+
 
 class WrapperProgram():
     def __init__(self, client, program):
@@ -137,6 +209,10 @@ class WrapperProgram():
     def program_address(self, seeds):
         return create_program_address(seeds, self.program)
 
+    def configAddress(self):
+        (config, nonce) = create_program_address(['config'.encode('ascii')], self.program)
+        return config
+
     def _getAccountData(self, account, expected_length, owner=None):
         info = self.client.get_account_info(account)['result']['value']
         if info is None:
@@ -166,6 +242,15 @@ class WrapperProgram():
         data = self._getAccountData(account_info, BALANCE_INFO_LAYOUT.sizeof())
         return BalanceInfo.frombytes(data)
 
+    def getMintInfo(self, mint):
+        (mint_info, nonce) = create_program_address([bytes(PublicKey(mint)), 'mint'.encode('ascii')], self.program)
+        data = self._getAccountData(mint_info, MINT_INFO_LAYOUT.sizeof())
+        return MintInfo.frombytes(data)
+
+    def getChainId(self):
+        data = self._getAccountData(self.configAddress(), CONFIG_LAYOUT.sizeof())
+        return CONFIG_LAYOUT.parse(data).chain_id
+
     def getLamports(self, eth_acc):
         (account, nonce) = create_program_address([bytes(eth_acc), 'lamports'.encode('ascii')], self.program)
         return int(self.client.get_balance(account)['result']['value'])
@@ -177,6 +262,8 @@ class WrapperProgram():
     def transfer(self, eth_token, eth_acc, source, destination, amount):
         print('--- transfer:', eth_token, eth_acc, source, destination, amount)
         (authority, nonceAuthority) = create_program_address([bytes(eth_token), bytes(eth_acc)], self.program)
+        mint = self.getTokenInfo(eth_token).token
+        (mint_info, nonceMint) = create_program_address([bytes(mint), 'mint'.encode('ascii')], self.program)
         data = TRANSFER_LAYOUT.build(dict(
             instruction=3,
             amount=amount,
@@ -188,9 +275,94 @@ class WrapperProgram():
                 AccountMeta(pubkey=token_id, is_signer=False, is_writable=False),
                 AccountMeta(pubkey=source, is_signer=False, is_writable=True),
                 AccountMeta(pubkey=destination, is_signer=False, is_writable=True),
-                AccountMeta(pubkey=authority, is_signer=False, is_writable=False)])
+                AccountMeta(pubkey=authority, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=mint_info, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=self.configAddress(), is_signer=False, is_writable=False)])
 
 
+    def approve(self, eth_token, eth_acc, source, spender, amount):
+        print('--- approve:', eth_token, eth_acc, source, spender, amount)
+        (authority, nonceAuthority) = create_program_address([bytes(eth_token), bytes(eth_acc)], self.program)
+        (delegate, nonceDelegate) = create_program_address([bytes(eth_token), bytes(spender)], self.program)
+        mint = self.getTokenInfo(eth_token).token
+        (mint_info, nonceMint) = create_program_address([bytes(mint), 'mint'.encode('ascii')], self.program)
+        data = TRANSFER_LAYOUT.build(dict(
+            instruction=6,
+            amount=amount,
+            nonce=nonceAuthority,
+            eth_token=bytes(eth_token),
+            eth_acc=bytes(eth_acc),
+        ))
+        return TransactionInstruction(program_id=self.program, data=data, keys=[
+                AccountMeta(pubkey=token_id, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=source, is_signer=False, is_writable=True),
+                AccountMeta(pubkey=delegate, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=authority, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=mint_info, is_signer=False, is_writable=False)])
+
+    def transferFrom(self, eth_token, spender, source, destination, amount):
+        print('--- transferFrom:', eth_token, spender, source, destination, amount)
+        (delegate, nonceDelegate) = create_program_address([bytes(eth_token), bytes(spender)], self.program)
+        mint = self.getTokenInfo(eth_token).token
+        (mint_info, nonceMint) = create_program_address([bytes(mint), 'mint'.encode('ascii')], self.program)
+        data = TRANSFER_LAYOUT.build(dict(
+            instruction=7,
+            amount=amount,
+            nonce=nonceDelegate,
+            eth_token=bytes(eth_token),
+            eth_acc=bytes(spender),
+        ))
+        return TransactionInstruction(program_id=self.program, data=data, keys=[
+                AccountMeta(pubkey=token_id, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=source, is_signer=False, is_writable=True),
+                AccountMeta(pubkey=destination, is_signer=False, is_writable=True),
+                AccountMeta(pubkey=delegate, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=mint_info, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=self.configAddress(), is_signer=False, is_writable=False)])
+
+    def permit(self, eth_token, owner, source, spender, value, deadline, signer_index):
+        """Returns secp256k1 and permit instructions, permit must be at signer_index+1 in transaction"""
+        print('--- permit:', eth_token, owner, source, spender, value, deadline)
+        (authority, nonceAuthority) = create_program_address([bytes(eth_token), bytes(owner)], self.program)
+        (delegate, nonceDelegate) = create_program_address([bytes(eth_token), bytes(spender)], self.program)
+        (account_info, nonceAccount) = create_program_address([bytes(owner)], self.program)
+        mint = self.getTokenInfo(eth_token).token
+        (mint_info, nonceMint) = create_program_address([bytes(mint), 'mint'.encode('ascii')], self.program)
+
+        message = permit_message(self.getChainId(), eth_token, owner, spender, value, self.getAccountInfo(owner).trx_count, deadline)
+        signature = owner.private.sign_msg_hash(keccak_256(message).digest()).to_bytes()
+        data = PERMIT_LAYOUT.build(dict(
+            instruction=8,
+            nonce=nonceAuthority,
+            spender_nonce=nonceDelegate,
+            eth_token=bytes(eth_token),
+            spender=bytes(spender),
+            value=value,
+            deadline=deadline,
+            owner=bytes(owner),
+            signature=signature,
+            message=message,
+        ))
+
+        index = signer_index + 1
+        offset = PERMIT_SIGNED_DATA_OFFSET
+        check_data = bytes([1]) + \
+            (offset+20).to_bytes(2, 'little') + bytes([index]) + \
+            offset.to_bytes(2, 'little') + bytes([index]) + \
+            (offset+20+65).to_bytes(2, 'little') + len(message).to_bytes(2, 'little') + bytes([index])
+        return [
+            TransactionInstruction(program_id=secp256k1_id, data=check_data, keys=[]),
+            TransactionInstruction(program_id=self.program, data=data, keys=[
+                AccountMeta(pubkey=token_id, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=source, is_signer=False, is_writable=True),
+                AccountMeta(pubkey=delegate, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=authority, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=mint_info, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=account_info, is_signer=False, is_writable=True),
+                AccountMeta(pubkey=sysvar_instructions_id, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=self.configAddress(), is_signer=False, is_writable=False)]),
+        ]
+
     def transferLamports(self, eth_acc, destination, amount):
         (source, nonce) = create_program_address([bytes(eth_acc), 'lamports'.encode('ascii')], self.program)
         if isinstance(destination, EthereumAddress):
@@ -205,7 +377,8 @@ class WrapperProgram():
         return TransactionInstruction(program_id=self.program, data=data, keys=[
                 AccountMeta(pubkey=source, is_signer=False, is_writable=True),
                 AccountMeta(pubkey=destination, is_signer=False, is_writable=True),
-                AccountMeta(pubkey=system_id, is_signer=False, is_writable=False)])
+                AccountMeta(pubkey=system_id, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=self.configAddress(), is_signer=False, is_writable=False)])
 
 
     def initializeAccount(self, eth_acc, signer_key):
@@ -253,3 +426,91 @@ class WrapperProgram():
                 AccountMeta(pubkey=rent_id, is_signer=False, is_writable=False),
                 AccountMeta(pubkey=signer_key, is_signer=True, is_writable=True),
             ])
+
+    def initializeMint(self, mint, eth_token, signer_key):
+        (mint_info, nonce) = create_program_address([bytes(PublicKey(mint)), 'mint'.encode('ascii')], self.program)
+        data = INITIALIZE_MINT_LAYOUT.build(dict(
+            instruction=5,
+            eth_token=bytes(eth_token),
+            nonce=nonce,
+        ))
+        return TransactionInstruction(program_id=self.program, data=data, keys=[
+                AccountMeta(pubkey=mint_info, is_signer=False, is_writable=True),
+                AccountMeta(pubkey=mint, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=system_id, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=rent_id, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=signer_key, is_signer=True, is_writable=True),
+            ])
+
+    def initializeConfig(self, admin, recovery, signer_key, chain_id):
+        (config, nonce) = create_program_address(['config'.encode('ascii')], self.program)
+        data = INITIALIZE_CONFIG_LAYOUT.build(dict(
+            instruction=9,
+            admin=bytes(PublicKey(admin)),
+            recovery=bytes(PublicKey(recovery)),
+            nonce=nonce,
+            chain_id=chain_id,
+        ))
+        return TransactionInstruction(program_id=self.program, data=data, keys=[
+                AccountMeta(pubkey=config, is_signer=False, is_writable=True),
+                AccountMeta(pubkey=system_id, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=rent_id, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=signer_key, is_signer=True, is_writable=True),
+            ])
+
+    def setFee(self, operator, fee_basis_points, admin):
+        data = bytes([13]) + bytes(PublicKey(operator)) + fee_basis_points.to_bytes(2, 'little')
+        return TransactionInstruction(program_id=self.program, data=data, keys=[
+                AccountMeta(pubkey=self.configAddress(), is_signer=False, is_writable=True),
+                AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+            ])
+
+    def transferWithMemo(self, eth_token, eth_acc, source, destination, amount, memo, references=[]):
+        print('--- transferWithMemo:', eth_token, eth_acc, source, destination, amount, memo, references)
+        instruction = self.transfer(eth_token, eth_acc, source, destination, amount)
+        memo = memo.encode('utf8')
+        data = bytes([15]) + instruction.data[1:] + len(memo).to_bytes(4, 'little') + memo + bytes([len(references)])
+        return TransactionInstruction(program_id=self.program, data=data,
+                keys=instruction.keys + [AccountMeta(pubkey=memo_id, is_signer=False, is_writable=False)] +
+                    [AccountMeta(pubkey=reference, is_signer=False, is_writable=False) for reference in references])
+
+    def transferMultisig(self, eth_token, source, destination, multisig, signers, amount):
+        print('--- transferMultisig:', eth_token, source, destination, multisig, signers, amount)
+        mint = self.getTokenInfo(eth_token).token
+        (mint_info, nonceMint) = create_program_address([bytes(mint), 'mint'.encode('ascii')], self.program)
+        data = bytes([16]) + amount.to_bytes(8, 'little') + bytes(eth_token) + bytes([len(signers)])
+        return TransactionInstruction(program_id=self.program, data=data, keys=[
+                AccountMeta(pubkey=token_id, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=source, is_signer=False, is_writable=True),
+                AccountMeta(pubkey=destination, is_signer=False, is_writable=True),
+                AccountMeta(pubkey=multisig, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=mint_info, is_signer=False, is_writable=False),
+                AccountMeta(pubkey=self.configAddress(), is_signer=False, is_writable=False)] +
+            [AccountMeta(pubkey=signer, is_signer=True, is_writable=False) for signer in signers])
+
+    def transferWithFee(self, eth_token, eth_acc, source, destination, fee_account, amount):
+        print('--- transferWithFee:', eth_token, eth_acc, source, destination, fee_account, amount)
+        instruction = self.transfer(eth_token, eth_acc, source, destination, amount)
+        return TransactionInstruction(program_id=self.program, data=bytes([14]) + instruction.data[1:],
+                keys=instruction.keys + [AccountMeta(pubkey=fee_account, is_signer=False, is_writable=True)])
+
+    def transferToWallet(self, eth_token, eth_acc, source, wallet, payer, amount):
+        print('--- transferToWallet:', eth_token, eth_acc, source, wallet, payer, amount)
+        mint = self.getTokenInfo(eth_token).token
+        (destination, nonceDestination) = create_program_address(
+                [bytes(PublicKey(wallet)), bytes(PublicKey(token_id)), bytes(PublicKey(mint))], associated_token_id)
+        instruction = self.transfer(eth_token, eth_acc, source, destination, amount)
+        return TransactionInstruction(program_id=self.program, data=bytes([17]) + instruction.data[1:],
+                keys=instruction.keys + [
+                    AccountMeta(pubkey=payer, is_signer=True, is_writable=True),
+                    AccountMeta(pubkey=wallet, is_signer=False, is_writable=False),
+                    AccountMeta(pubkey=mint, is_signer=False, is_writable=False),
+                    AccountMeta(pubkey=system_id, is_signer=False, is_writable=False),
+                    AccountMeta(pubkey=associated_token_id, is_signer=False, is_writable=False),
+                    AccountMeta(pubkey=rent_id, is_signer=False, is_writable=False)])
+
+    def setPaused(self, paused, admin):
+        return TransactionInstruction(program_id=self.program, data=bytes([10 if paused else 11]), keys=[
+                AccountMeta(pubkey=self.configAddress(), is_signer=False, is_writable=True),
+                AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+            ])